@@ -0,0 +1,234 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use tachyon::{
+    channel::{Channel, ChannelConfig},
+    connection::Identity,
+    fragmentation::Fragmentation,
+    header::Header,
+    nack::Nack,
+    network_address::NetworkAddress,
+    send_buffer_manager::SendBufferManager,
+    sequence::Sequence,
+    tachyon_socket::TachyonSocket,
+    Tachyon, TachyonConfig,
+};
+
+// counts heap allocations so we can report allocations per iteration alongside timings
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn report_allocations(name: &str, iterations: usize, mut f: impl FnMut()) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..iterations {
+        f();
+    }
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    println!("{0}: {1:.2} allocations per iteration", name, (after - before) as f64 / iterations as f64);
+}
+
+// In memory transport.  Datagrams are taken straight out of the sender's send buffers and fed to the receiving channel
+// the same way Tachyon::receive_from_socket does, so no sockets or syscalls end up in the measurements.
+struct MemoryTransport {
+    sender: Channel,
+    receiver: Channel,
+    // unbound, send_to is a no-op
+    socket: TachyonSocket,
+    datagrams: VecDeque<u16>,
+    receive_buffer: Vec<u8>,
+}
+
+impl MemoryTransport {
+    fn create() -> Self {
+        let address = NetworkAddress::default();
+        return MemoryTransport {
+            sender: Channel::create(1, address, ChannelConfig::default_ordered()),
+            receiver: Channel::create(1, address, ChannelConfig::default_ordered()),
+            socket: TachyonSocket::create(),
+            datagrams: VecDeque::new(),
+            receive_buffer: vec![0; 4096],
+        };
+    }
+
+    fn send(&mut self, data: &[u8]) {
+        let result = self.sender.send_reliable(NetworkAddress::default(), data, data.len(), &self.socket);
+        self.datagrams.push_back(result.header.sequence);
+    }
+
+    fn deliver(&mut self) -> u32 {
+        let mut received = 0;
+        while let Some(sequence) = self.datagrams.pop_front() {
+            if let Some(send_buffer) = self.sender.send_buffers.get_send_buffer(sequence) {
+                let header = Header::read(send_buffer.byte_buffer.get());
+                self.receiver.receiver.receive_packet(header.sequence, send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+            }
+            loop {
+                let res = self.receiver.receive_published(&mut self.receive_buffer);
+                if res.0 == 0 {
+                    break;
+                }
+                received += 1;
+            }
+        }
+        return received;
+    }
+}
+
+fn bench_reliable_send(c: &mut Criterion) {
+    let mut transport = MemoryTransport::create();
+    let data: Vec<u8> = vec![3; 384];
+
+    c.bench_function("reliable_send_384", |b| {
+        b.iter(|| {
+            transport.send(black_box(&data));
+            transport.datagrams.clear();
+        })
+    });
+
+    report_allocations("reliable_send_384", 10000, || {
+        transport.send(&data);
+        transport.datagrams.clear();
+    });
+}
+
+fn bench_receive_drain(c: &mut Criterion) {
+    let mut transport = MemoryTransport::create();
+    let data: Vec<u8> = vec![3; 384];
+
+    c.bench_function("receive_drain_64x384", |b| {
+        b.iter(|| {
+            for _ in 0..64 {
+                transport.send(&data);
+            }
+            black_box(transport.deliver());
+        })
+    });
+
+    report_allocations("receive_drain_64x384", 1000, || {
+        for _ in 0..64 {
+            transport.send(&data);
+        }
+        transport.deliver();
+    });
+}
+
+fn bench_fragmentation(c: &mut Criterion) {
+    let data: Vec<u8> = vec![3; 16 * 1024];
+    let mut frag = Fragmentation::default();
+    let mut sender = SendBufferManager::default();
+
+    c.bench_function("fragment_create_16k", |b| {
+        b.iter(|| {
+            black_box(frag.create_fragments(&mut sender, 1, &data, data.len()));
+        })
+    });
+
+    let assemble = |frag: &mut Fragmentation, sender: &mut SendBufferManager| {
+        let created = frag.create_fragments(sender, 1, &data, data.len());
+        let mut header = Header::default();
+        for sequence in &created {
+            let send_buffer = sender.get_send_buffer(*sequence).unwrap();
+            frag.receive_fragment(send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+            header = Header::read_fragmented(send_buffer.byte_buffer.get());
+        }
+        return frag.assemble(header).unwrap().len();
+    };
+
+    c.bench_function("fragment_create_assemble_16k", |b| {
+        b.iter(|| {
+            black_box(assemble(&mut frag, &mut sender));
+        })
+    });
+
+    report_allocations("fragment_create_assemble_16k", 1000, || {
+        assemble(&mut frag, &mut sender);
+    });
+}
+
+fn bench_nack_serialization(c: &mut Criterion) {
+    let mut nacks: Vec<Nack> = Vec::new();
+    let mut start = 33;
+    for _ in 0..16 {
+        let mut nack = Nack::default();
+        nack.start_sequence = start;
+        let mut seq = Sequence::previous_sequence(start);
+        for _ in 0..32 {
+            nack.set_flagged(seq);
+            seq = Sequence::previous_sequence(seq);
+        }
+        nacks.push(nack);
+        start += 33;
+    }
+    let mut data: Vec<u8> = vec![0; 4096];
+    let mut sequences: Vec<u16> = Vec::with_capacity(1024);
+
+    c.bench_function("nack_write_varint_16", |b| {
+        b.iter(|| {
+            black_box(Nack::write_varint(&nacks, &mut data, 4));
+        })
+    });
+
+    c.bench_function("nack_read_varint_16", |b| {
+        b.iter(|| {
+            sequences.clear();
+            Nack::read_varint(&mut sequences, &data, 4);
+            black_box(sequences.len());
+        })
+    });
+
+    report_allocations("nack_write_read_varint_16", 10000, || {
+        Nack::write_varint(&nacks, &mut data, 4);
+        sequences.clear();
+        Nack::read_varint(&mut sequences, &data, 4);
+    });
+}
+
+// A login storm, connections made back to back on a server with many configured channels
+fn bench_connect_burst(c: &mut Criterion) {
+    let create = || {
+        let mut tachyon = Tachyon::create(TachyonConfig::default());
+        for channel_id in 3..35 {
+            tachyon.configure_channel(channel_id, ChannelConfig::default_ordered());
+        }
+        return tachyon;
+    };
+    let connect = |tachyon: &mut Tachyon| {
+        for port in 0..256 {
+            tachyon.create_connection(NetworkAddress::localhost(10000 + port), Identity::default());
+        }
+    };
+
+    c.bench_function("connect_256x34_channels", |b| {
+        b.iter_batched(create, |mut tachyon| {
+            connect(&mut tachyon);
+            black_box(tachyon);
+        }, BatchSize::LargeInput)
+    });
+
+    report_allocations("connect_256x34_channels", 10, || {
+        let mut tachyon = create();
+        connect(&mut tachyon);
+    });
+}
+
+criterion_group!(benches, bench_reliable_send, bench_receive_drain, bench_fragmentation, bench_nack_serialization, bench_connect_burst);
+criterion_main!(benches);
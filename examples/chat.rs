@@ -0,0 +1,148 @@
+// A chat room where everyone is an identity.
+//
+//   cargo run --example chat -- server [port]
+//   cargo run --example chat -- client <id> [address]
+//
+// The server takes ids 1 to MAX_USERS, each with session id SESSION_BASE + id standing in for the login token a real
+// application would hand out.  Lines typed into a client go to every linked client, prefixed with the sender's id.
+use std::{
+    env,
+    io::{self, BufRead},
+    net::SocketAddr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use tachyon::{
+    channel::ChannelConfig,
+    network_address::NetworkAddress,
+    pool::SendTarget,
+    Tachyon, TachyonConfig,
+};
+
+const CHAT_CHANNEL: u16 = 3;
+const MAX_USERS: u32 = 100;
+const SESSION_BASE: u32 = 1000;
+const FRAME: Duration = Duration::from_millis(10);
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("server") => {
+            let port: u32 = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(8010);
+            run_server(NetworkAddress::localhost(port));
+        }
+        Some("client") => {
+            let id: u32 = match args.get(2).and_then(|arg| arg.parse().ok()) {
+                Some(id) if id > 0 && id <= MAX_USERS => id,
+                _ => {
+                    eprintln!("client needs an id from 1 to {}", MAX_USERS);
+                    return;
+                }
+            };
+            let address: SocketAddr = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or_else(|| "127.0.0.1:8010".parse().unwrap());
+            run_client(id, NetworkAddress::from_socket_addr(address));
+        }
+        _ => {
+            eprintln!("usage: chat server [port] | chat client <id> [address]");
+        }
+    }
+}
+
+fn create() -> Tachyon {
+    let mut config = TachyonConfig::default();
+    config.use_identity = 1;
+    let mut tachyon = Tachyon::create(config);
+    // ordered, and long lines are fragmented like anything else
+    tachyon.configure_channel(CHAT_CHANNEL, ChannelConfig::default_ordered());
+    return tachyon;
+}
+
+fn run_server(address: NetworkAddress) {
+    let mut server = create();
+    for id in 1..=MAX_USERS {
+        server.set_identity(id, SESSION_BASE + id);
+    }
+    if !server.bind(address) {
+        eprintln!("bind {} failed", address.to_socket_addr());
+        return;
+    }
+    println!("chat server on {}", address.to_socket_addr());
+
+    let mut receive_buffer: Vec<u8> = vec![0; 64 * 1024];
+    loop {
+        loop {
+            let result = server.receive_loop(&mut receive_buffer);
+            if result.length == 0 || result.error > 0 {
+                break;
+            }
+            // only linked identities get this far on an identity server
+            let text = String::from_utf8_lossy(&receive_buffer[0..result.length as usize]).to_string();
+            let line = format!("[{}] {}", result.identity_id, text);
+            println!("{}", line);
+
+            for linked in server.get_linked_identities() {
+                let target = SendTarget { identity_id: linked.connection.identity.id, address: NetworkAddress::default() };
+                server.send_to_target(CHAT_CHANNEL, target, line.as_bytes(), line.len());
+            }
+        }
+        server.update();
+        thread::sleep(FRAME);
+    }
+}
+
+fn run_client(id: u32, address: NetworkAddress) {
+    let mut client = create();
+    client.set_client_identity(id, SESSION_BASE + id);
+    if !client.connect(address) {
+        eprintln!("connect to {} failed", address.to_socket_addr());
+        return;
+    }
+
+    let (lines_sender, lines) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if lines_sender.send(line).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+
+    let mut receive_buffer: Vec<u8> = vec![0; 64 * 1024];
+    let mut linked = false;
+    let target = SendTarget { identity_id: 0, address: NetworkAddress::default() };
+    loop {
+        // update() sends the link request until the server links us
+        client.update();
+        if client.identity.is_linked() != linked {
+            linked = client.identity.is_linked();
+            println!("{}", if linked { "linked, type away" } else { "not linked" });
+        }
+
+        loop {
+            let result = client.receive_loop(&mut receive_buffer);
+            if result.length == 0 || result.error > 0 {
+                break;
+            }
+            println!("{}", String::from_utf8_lossy(&receive_buffer[0..result.length as usize]));
+        }
+
+        // lines typed before the link goes through wait in the channel
+        while linked {
+            match lines.try_recv() {
+                Ok(line) => {
+                    client.send_to_target(CHAT_CHANNEL, target, line.as_bytes(), line.len());
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return,
+            }
+        }
+        thread::sleep(FRAME);
+    }
+}
@@ -0,0 +1,61 @@
+// Echoes every message back to whoever sent it, on the channel it came in on.
+//
+//   cargo run --release --example echo_server -- [port] [servers]
+//
+// A Pool of servers on successive ports from port, 8001 and 4 by default, received in parallel.  stress_client is the
+// other end.
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+
+use tachyon::{
+    channel::ChannelConfig,
+    network_address::NetworkAddress,
+    pool::{Pool, SendTarget},
+    TachyonConfig,
+};
+
+// ordered, stress_client sends its fragmented messages here.  Both ends configure it the same way.
+const LARGE_CHANNEL: u16 = 3;
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let port: u32 = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(8001);
+    let count: u32 = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(4);
+
+    let mut pool = Pool::create(count as u8, 1024 * 1024, 1024 * 1024);
+    let servers = pool.create_servers_in_range(TachyonConfig::default(), NetworkAddress::localhost(0), port, count);
+    if servers.is_empty() {
+        eprintln!("no ports free from {}", port);
+        return;
+    }
+    for server in &servers {
+        if let Some(tachyon) = pool.get_server(server.id) {
+            tachyon.configure_channel(LARGE_CHANNEL, ChannelConfig::default_ordered());
+        }
+        println!("server {} listening on {}", server.id, server.address.to_socket_addr());
+    }
+
+    let mut echoed: u64 = 0;
+    let mut reported_at = Instant::now();
+    loop {
+        pool.receive_blocking();
+        // clients heard from for the first time this receive, so the sends below find their server
+        pool.build_connection_maps();
+        for message in pool.drain_published(usize::MAX) {
+            let target = SendTarget { identity_id: 0, address: message.address };
+            pool.send_to_target(message.channel, target, &message.data, message.data.len() as i32);
+            echoed += 1;
+        }
+        pool.update_servers();
+
+        if reported_at.elapsed() >= REPORT_INTERVAL {
+            let connections: usize = pool.servers.values().map(|server| server.connections.len()).sum();
+            println!("connections:{} echoed:{}", connections, echoed);
+            reported_at = Instant::now();
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,114 @@
+// Runs clients against echo_server and reports how much came back and how fast.
+//
+//   cargo run --release --example stress_client -- [address] [servers] [clients] [messages] [size]
+//
+// 127.0.0.1:8001, 4 servers, 8 clients sending 10000 messages each of 64 bytes by default.  Clients are spread over
+// the servers' successive ports.  Every 100th message is LARGE_SIZE bytes on the ordered channel, which fragments it.
+use std::{
+    env,
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use tachyon::{
+    channel::ChannelConfig,
+    network_address::NetworkAddress,
+    pool::SendTarget,
+    Tachyon, TachyonConfig,
+};
+
+// the same as echo_server's
+const LARGE_CHANNEL: u16 = 3;
+const SMALL_CHANNEL: u16 = 2;
+const LARGE_SIZE: usize = 4000;
+const LARGE_EVERY: u32 = 100;
+// sent per client per frame, well under the 1024 send buffers kept for resends
+const BATCH: u32 = 64;
+// give up when nothing comes back for this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct StressClient {
+    tachyon: Tachyon,
+    sent: u32,
+    received: u32,
+    mismatched: u32,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let address: SocketAddr = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or_else(|| "127.0.0.1:8001".parse().unwrap());
+    let servers: u32 = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(4).max(1);
+    let client_count: u32 = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or(8);
+    let messages: u32 = args.get(4).and_then(|arg| arg.parse().ok()).unwrap_or(10000);
+    let size: usize = args.get(5).and_then(|arg| arg.parse().ok()).unwrap_or(64).max(1);
+
+    let mut clients: Vec<StressClient> = Vec::new();
+    for index in 0..client_count {
+        let mut server_address = NetworkAddress::from_socket_addr(address);
+        server_address.port += index % servers;
+        let mut tachyon = Tachyon::create(TachyonConfig::default());
+        tachyon.configure_channel(LARGE_CHANNEL, ChannelConfig::default_ordered());
+        if !tachyon.connect(server_address) {
+            eprintln!("connect to {} failed", server_address.to_socket_addr());
+            return;
+        }
+        clients.push(StressClient { tachyon, sent: 0, received: 0, mismatched: 0 });
+    }
+
+    let mut send_buffer: Vec<u8> = vec![0; LARGE_SIZE.max(size)];
+    let mut receive_buffer: Vec<u8> = vec![0; LARGE_SIZE.max(size)];
+    let target = SendTarget { identity_id: 0, address: NetworkAddress::default() };
+    let started = Instant::now();
+    let mut heard_at = Instant::now();
+
+    loop {
+        let mut done = true;
+        for (index, client) in clients.iter_mut().enumerate() {
+            // every message starts with the client's index, so echoes that got crossed show up
+            send_buffer[0] = index as u8;
+            for _ in 0..BATCH {
+                if client.sent == messages {
+                    break;
+                }
+                client.sent += 1;
+                if client.sent % LARGE_EVERY == 0 {
+                    client.tachyon.send_to_target(LARGE_CHANNEL, target, &send_buffer, LARGE_SIZE);
+                } else {
+                    client.tachyon.send_to_target(SMALL_CHANNEL, target, &send_buffer, size);
+                }
+            }
+
+            loop {
+                let result = client.tachyon.receive_loop(&mut receive_buffer);
+                if result.length == 0 || result.error > 0 {
+                    break;
+                }
+                client.received += 1;
+                if receive_buffer[0] != index as u8 {
+                    client.mismatched += 1;
+                }
+                heard_at = Instant::now();
+            }
+            client.tachyon.update();
+            if client.received < messages {
+                done = false;
+            }
+        }
+
+        if done || heard_at.elapsed() > IDLE_TIMEOUT {
+            break;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let sent: u64 = clients.iter().map(|client| client.sent as u64).sum();
+    let received: u64 = clients.iter().map(|client| client.received as u64).sum();
+    let mismatched: u64 = clients.iter().map(|client| client.mismatched as u64).sum();
+    println!("sent:{} echoed:{} mismatched:{} in {:.2}s, {:.0} round trips a second", sent, received, mismatched, elapsed, received as f64 / elapsed);
+    for (index, client) in clients.iter_mut().enumerate() {
+        let stats = client.tachyon.get_combined_stats().channel_stats;
+        println!("client {} received:{} nacks_sent:{} resent:{} fragments_assembled:{}", index, client.received, stats.nacks_sent, stats.resent, stats.fragments_assembled);
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use super::network_address::NetworkAddress;
+
+pub const ADDRESS_LIST_ALLOW: u8 = 1;
+pub const ADDRESS_LIST_DENY: u8 = 2;
+
+// An ip and the number of leading bits that have to match, 32 for a single address.  Ports are ignored.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AddressRange {
+    ip: u32,
+    prefix: u8,
+}
+
+impl AddressRange {
+    // None for prefixes over 32, bits past the prefix are cleared
+    pub fn create(address: NetworkAddress, prefix: u8) -> Option<Self> {
+        if prefix > 32 {
+            return None;
+        }
+        let ip = AddressRange::ip_of(address) & AddressRange::mask(prefix);
+        return Some(AddressRange { ip, prefix });
+    }
+
+    pub fn single(address: NetworkAddress) -> Self {
+        return AddressRange { ip: AddressRange::ip_of(address), prefix: 32 };
+    }
+
+    // "10.1.2.3" or "10.0.0.0/8"
+    pub fn parse(value: &str) -> Option<Self> {
+        let (ip, prefix) = match value.split_once('/') {
+            Some((ip, prefix)) => (ip, prefix.parse::<u8>().ok()?),
+            None => (value, 32),
+        };
+        let ip: Ipv4Addr = ip.parse().ok()?;
+        let octets = ip.octets();
+        let address = NetworkAddress {
+            a: octets[0] as u16,
+            b: octets[1] as u16,
+            c: octets[2] as u16,
+            d: octets[3] as u16,
+            port: 0,
+        };
+        return AddressRange::create(address, prefix);
+    }
+
+    pub fn contains(&self, address: NetworkAddress) -> bool {
+        return AddressRange::ip_of(address) & AddressRange::mask(self.prefix) == self.ip;
+    }
+
+    pub fn prefix(&self) -> u8 {
+        return self.prefix;
+    }
+
+    fn ip_of(address: NetworkAddress) -> u32 {
+        return (address.a as u32 & 0xff) << 24 | (address.b as u32 & 0xff) << 16 | (address.c as u32 & 0xff) << 8 | (address.d as u32 & 0xff);
+    }
+
+    fn mask(prefix: u8) -> u32 {
+        if prefix == 0 {
+            return 0;
+        }
+        return u32::MAX << (32 - prefix as u32);
+    }
+}
+
+impl std::fmt::Display for AddressRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{0}/{1}", Ipv4Addr::from(self.ip), self.prefix)
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct AddressFilterStats {
+    // matched the deny list
+    pub denied: u64,
+    // the allow list isn't empty and they're not on it
+    pub not_allowed: u64,
+}
+
+#[derive(Default)]
+struct AddressLists {
+    allow: Vec<AddressRange>,
+    deny: Vec<AddressRange>,
+}
+
+// Allow and deny lists checked on every datagram before any connection state exists for its address.  Deny always
+// wins, and once anything is on the allow list only addresses on it get through.
+//
+// Clones share the lists and counters, a Pool hands one to each of its servers so changes reach servers that are busy
+// receiving on other threads.  Each clone keeps its own copy of the lists and only takes the lock to refresh it after
+// a change.
+pub struct AddressFilter {
+    lists: Arc<RwLock<Arc<AddressLists>>>,
+    version: Arc<AtomicU64>,
+    denied: Arc<AtomicU64>,
+    not_allowed: Arc<AtomicU64>,
+    local: Arc<AddressLists>,
+    local_version: u64,
+}
+
+impl Clone for AddressFilter {
+    fn clone(&self) -> Self {
+        let filter = AddressFilter {
+            lists: self.lists.clone(),
+            version: self.version.clone(),
+            denied: self.denied.clone(),
+            not_allowed: self.not_allowed.clone(),
+            local: self.local.clone(),
+            local_version: self.local_version,
+        };
+        return filter;
+    }
+}
+
+impl AddressFilter {
+    pub fn default() -> Self {
+        let filter = AddressFilter {
+            lists: Arc::new(RwLock::new(Arc::new(AddressLists::default()))),
+            version: Arc::new(AtomicU64::new(0)),
+            denied: Arc::new(AtomicU64::new(0)),
+            not_allowed: Arc::new(AtomicU64::new(0)),
+            local: Arc::new(AddressLists::default()),
+            local_version: 0,
+        };
+        return filter;
+    }
+
+    // false if it's already on the list
+    pub fn allow(&self, range: AddressRange) -> bool {
+        return self.change(|lists| AddressFilter::add_to(&mut lists.allow, range));
+    }
+
+    pub fn remove_allowed(&self, range: AddressRange) -> bool {
+        return self.change(|lists| AddressFilter::remove_from(&mut lists.allow, range));
+    }
+
+    pub fn deny(&self, range: AddressRange) -> bool {
+        return self.change(|lists| AddressFilter::add_to(&mut lists.deny, range));
+    }
+
+    pub fn remove_denied(&self, range: AddressRange) -> bool {
+        return self.change(|lists| AddressFilter::remove_from(&mut lists.deny, range));
+    }
+
+    // ADDRESS_LIST_ALLOW or ADDRESS_LIST_DENY, false for any other list
+    pub fn add(&self, list: u8, range: AddressRange) -> bool {
+        match list {
+            ADDRESS_LIST_ALLOW => return self.allow(range),
+            ADDRESS_LIST_DENY => return self.deny(range),
+            _ => return false,
+        }
+    }
+
+    pub fn remove(&self, list: u8, range: AddressRange) -> bool {
+        match list {
+            ADDRESS_LIST_ALLOW => return self.remove_allowed(range),
+            ADDRESS_LIST_DENY => return self.remove_denied(range),
+            _ => return false,
+        }
+    }
+
+    pub fn clear(&self) {
+        self.change(|lists| {
+            lists.allow.clear();
+            lists.deny.clear();
+            return true;
+        });
+    }
+
+    pub fn allowed(&self) -> Vec<AddressRange> {
+        return self.current().allow.clone();
+    }
+
+    pub fn denied(&self) -> Vec<AddressRange> {
+        return self.current().deny.clone();
+    }
+
+    // whether datagrams from the address would get through, without counting anything
+    pub fn is_allowed(&self, address: NetworkAddress) -> bool {
+        return AddressFilter::check(&self.current(), address) == 0;
+    }
+
+    pub fn stats(&self) -> AddressFilterStats {
+        let stats = AddressFilterStats {
+            denied: self.denied.load(Ordering::Relaxed),
+            not_allowed: self.not_allowed.load(Ordering::Relaxed),
+        };
+        return stats;
+    }
+
+    // run on every datagram, false drops it
+    pub fn accept(&mut self, address: NetworkAddress) -> bool {
+        return self.filter(address) == 0;
+    }
+
+    // accept that returns the ADDRESS_LIST_x that rejected the address, 0 if it didn't
+    pub fn filter(&mut self, address: NetworkAddress) -> u8 {
+        let version = self.version.load(Ordering::Acquire);
+        if version != self.local_version {
+            self.local = self.current();
+            self.local_version = version;
+        }
+
+        let rejected_by = AddressFilter::check(&self.local, address);
+        match rejected_by {
+            0 => {}
+            ADDRESS_LIST_DENY => {
+                self.denied.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {
+                self.not_allowed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        return rejected_by;
+    }
+
+    // 0 if accepted, otherwise the list that rejected it
+    fn check(lists: &AddressLists, address: NetworkAddress) -> u8 {
+        if lists.deny.iter().any(|range| range.contains(address)) {
+            return ADDRESS_LIST_DENY;
+        }
+        if !lists.allow.is_empty() && !lists.allow.iter().any(|range| range.contains(address)) {
+            return ADDRESS_LIST_ALLOW;
+        }
+        return 0;
+    }
+
+    fn current(&self) -> Arc<AddressLists> {
+        match self.lists.read() {
+            Ok(lists) => return lists.clone(),
+            Err(poisoned) => return poisoned.into_inner().clone(),
+        }
+    }
+
+    fn change<F: FnOnce(&mut AddressLists) -> bool>(&self, op: F) -> bool {
+        let mut current = match self.lists.write() {
+            Ok(lists) => lists,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut lists = AddressLists {
+            allow: current.allow.clone(),
+            deny: current.deny.clone(),
+        };
+        if !op(&mut lists) {
+            return false;
+        }
+        *current = Arc::new(lists);
+        self.version.fetch_add(1, Ordering::Release);
+        return true;
+    }
+
+    fn add_to(list: &mut Vec<AddressRange>, range: AddressRange) -> bool {
+        if list.contains(&range) {
+            return false;
+        }
+        list.push(range);
+        return true;
+    }
+
+    fn remove_from(list: &mut Vec<AddressRange>, range: AddressRange) -> bool {
+        let len = list.len();
+        list.retain(|r| *r != range);
+        return list.len() != len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTest};
+
+    use super::{AddressFilter, AddressRange};
+
+    fn address(a: u16, b: u16, c: u16, d: u16) -> NetworkAddress {
+        return NetworkAddress { a, b, c, d, port: 1000 };
+    }
+
+    #[test]
+    fn test_address_range() {
+        let range = AddressRange::parse("10.20.0.0/16").unwrap();
+        assert!(range.contains(address(10, 20, 1, 2)));
+        assert!(!range.contains(address(10, 21, 1, 2)));
+        assert_eq!(range, AddressRange::parse("10.20.99.1/16").unwrap());
+        assert_eq!("10.20.0.0/16", range.to_string());
+
+        let single = AddressRange::parse("10.20.1.2").unwrap();
+        assert_eq!(32, single.prefix());
+        assert!(single.contains(address(10, 20, 1, 2)));
+        assert!(!single.contains(address(10, 20, 1, 3)));
+        assert!(AddressRange::parse("0.0.0.0/0").unwrap().contains(address(1, 2, 3, 4)));
+
+        assert!(AddressRange::parse("10.20.0.0/33").is_none());
+        assert!(AddressRange::parse("10.20.0").is_none());
+    }
+
+    #[test]
+    fn test_lists() {
+        let mut filter = AddressFilter::default();
+        let shared = filter.clone();
+        assert!(filter.accept(address(10, 0, 0, 1)));
+
+        // clones see changes and count into the same stats
+        assert!(shared.allow(AddressRange::parse("10.0.0.0/8").unwrap()));
+        assert!(!shared.allow(AddressRange::parse("10.0.0.0/8").unwrap()));
+        assert!(shared.deny(AddressRange::parse("10.0.0.9").unwrap()));
+        assert!(filter.accept(address(10, 0, 0, 1)));
+        assert!(!filter.accept(address(10, 0, 0, 9)));
+        assert!(!filter.accept(address(11, 0, 0, 1)));
+        assert_eq!(1, shared.stats().denied);
+        assert_eq!(1, shared.stats().not_allowed);
+        assert!(!shared.is_allowed(address(10, 0, 0, 9)));
+        assert_eq!(1, filter.denied().len());
+
+        assert!(filter.remove_denied(AddressRange::parse("10.0.0.9").unwrap()));
+        assert!(!filter.remove_denied(AddressRange::parse("10.0.0.9").unwrap()));
+        assert!(filter.accept(address(10, 0, 0, 9)));
+        filter.clear();
+        assert!(filter.accept(address(11, 0, 0, 1)));
+    }
+
+    #[test]
+    #[serial]
+    fn test_filtered_receive() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        let localhost = AddressRange::single(test.address);
+        test.server.address_filter.deny(localhost);
+
+        test.client_send_unreliable(32);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(1, test.server.address_filter.stats().denied);
+        assert_eq!(0, test.server.connections.len());
+
+        test.server.address_filter.remove_denied(localhost);
+        test.server.address_filter.allow(AddressRange::parse("10.0.0.0/8").unwrap());
+        test.client_send_unreliable(32);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(1, test.server.address_filter.stats().not_allowed);
+
+        test.server.address_filter.allow(localhost);
+        test.client_send_unreliable(32);
+        assert_eq!(32, test.server_receive().length);
+        assert_eq!(1, test.server.connections.len());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::VecDeque;
+use alloc::{collections::VecDeque, vec, vec::Vec};
 
 pub const BYTE_BUFFER_SIZE_DEFAULT: usize = 1240;
 const POOL_SIZE_DEFAULT: usize = 512;
@@ -29,11 +29,16 @@ impl ByteBuffer {
     pub fn get_mut(&mut self) -> &mut [u8] {
         return &mut self.data;
     }
+
+    // the whole buffer, not cut to length
+    pub fn into_vec(self) -> Vec<u8> {
+        return self.data;
+    }
 }
 
-impl<Idx> std::ops::Index<Idx> for ByteBuffer
+impl<Idx> core::ops::Index<Idx> for ByteBuffer
 where
-    Idx: std::slice::SliceIndex<[u8]>,
+    Idx: core::slice::SliceIndex<[u8]>,
 {
     type Output = Idx::Output;
 
@@ -42,9 +47,9 @@ where
     }
 }
 
-impl<Idx> std::ops::IndexMut<Idx> for ByteBuffer
+impl<Idx> core::ops::IndexMut<Idx> for ByteBuffer
 where
-    Idx: std::slice::SliceIndex<[u8]>
+    Idx: core::slice::SliceIndex<[u8]>
 {
     fn index_mut(&mut self, index: Idx) -> &mut Self::Output {
         &mut self.data[index]
@@ -127,7 +132,7 @@ impl ByteBufferPool {
 #[cfg(test)]
 mod tests {
 
-    use crate::tachyon::byte_buffer_pool::{ByteBuffer, POOL_SIZE_DEFAULT, BYTE_BUFFER_SIZE_DEFAULT};
+    use crate::byte_buffer_pool::{ByteBuffer, POOL_SIZE_DEFAULT, BYTE_BUFFER_SIZE_DEFAULT};
 
     use super::ByteBufferPool;
 
@@ -0,0 +1,331 @@
+use std::{
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{
+    connection::Identity,
+    middleware::{PacketAction, PacketMiddleware},
+    network_address::NetworkAddress,
+    receive_result::TachyonReceiveResult,
+    transport::DatagramQueue,
+    Tachyon,
+};
+
+const CAPTURE_MAGIC: [u8; 4] = *b"TCAP";
+const CAPTURE_VERSION: u8 = 1;
+
+pub const CAPTURE_SENT: u8 = 0;
+pub const CAPTURE_RECEIVED: u8 = 1;
+
+// direction(1) delta micros(4) address(4) port(2) length(2)
+const RECORD_HEADER_SIZE: usize = 13;
+
+// Capture format, little endian:
+// file header: magic "TCAP", version u8, is_server u8
+// records: direction u8, micros since previous record u32, ipv4 address 4 x u8, port u16, length u16, datagram
+pub struct CaptureRecord {
+    pub direction: u8,
+    pub micros: u32,
+    pub address: NetworkAddress,
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        header[0] = self.direction;
+        header[1..5].copy_from_slice(&self.micros.to_le_bytes());
+        header[5] = self.address.a as u8;
+        header[6] = self.address.b as u8;
+        header[7] = self.address.c as u8;
+        header[8] = self.address.d as u8;
+        header[9..11].copy_from_slice(&(self.address.port as u16).to_le_bytes());
+        header[11..13].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
+        writer.write_all(&header)?;
+        writer.write_all(&self.data)?;
+        return Ok(());
+    }
+
+    // None at a clean end of capture
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Option<CaptureRecord>> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        match reader.read_exact(&mut header[0..1]) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        reader.read_exact(&mut header[1..])?;
+
+        let mut address = NetworkAddress::default();
+        address.a = header[5] as u16;
+        address.b = header[6] as u16;
+        address.c = header[7] as u16;
+        address.d = header[8] as u16;
+        address.port = u16::from_le_bytes([header[9], header[10]]) as u32;
+        let length = u16::from_le_bytes([header[11], header[12]]) as usize;
+
+        let mut data = vec![0; length];
+        reader.read_exact(&mut data)?;
+
+        let record = CaptureRecord {
+            direction: header[0],
+            micros: u32::from_le_bytes([header[1], header[2], header[3], header[4]]),
+            address,
+            data,
+        };
+        return Ok(Some(record));
+    }
+}
+
+pub struct CaptureHeader {
+    pub is_server: bool,
+}
+
+impl CaptureHeader {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&[CAPTURE_VERSION, self.is_server as u8])?;
+        return Ok(());
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<CaptureHeader> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != CAPTURE_MAGIC || header[4] != CAPTURE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a tachyon capture"));
+        }
+        return Ok(CaptureHeader { is_server: header[5] == 1 });
+    }
+}
+
+// In memory capture target, clones share the same buffer so a capture can be pulled out while the recorder is still installed.
+#[derive(Clone, Default)]
+pub struct CaptureBuffer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureBuffer {
+    pub fn to_vec(&self) -> Vec<u8> {
+        return self.buffer.lock().unwrap().clone();
+    }
+}
+
+impl Write for CaptureBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+// Middleware that records every datagram.  Sends are recorded before the wrapped middleware runs and receives after it,
+// so the capture holds plain tachyon packets that replay without the wrapped middleware installed.
+pub struct PacketRecorder<W: Write + Send> {
+    writer: W,
+    inner: Option<Box<dyn PacketMiddleware>>,
+    last_record: Instant,
+    pub write_errors: u64,
+}
+
+impl<W: Write + Send> PacketRecorder<W> {
+    pub fn create(mut writer: W, is_server: bool, inner: Option<Box<dyn PacketMiddleware>>) -> Self {
+        let mut write_errors = 0;
+        let header = CaptureHeader { is_server };
+        if header.write(&mut writer).is_err() {
+            write_errors += 1;
+        }
+
+        let recorder = PacketRecorder {
+            writer,
+            inner,
+            last_record: Instant::now(),
+            write_errors,
+        };
+        return recorder;
+    }
+
+    fn record(&mut self, direction: u8, address: NetworkAddress, data: &[u8]) {
+        let now = Instant::now();
+        let micros = std::cmp::min(now.duration_since(self.last_record).as_micros(), u32::MAX as u128) as u32;
+        self.last_record = now;
+
+        let record = CaptureRecord {
+            direction,
+            micros,
+            address,
+            data: data.to_vec(),
+        };
+        if record.write(&mut self.writer).is_err() {
+            self.write_errors += 1;
+        }
+    }
+}
+
+impl<W: Write + Send> PacketMiddleware for PacketRecorder<W> {
+    fn on_send(&mut self, data: &mut [u8], length: usize, address: NetworkAddress) -> PacketAction {
+        self.record(CAPTURE_SENT, address, &data[0..length]);
+        match &mut self.inner {
+            Some(inner) => {
+                return inner.on_send(data, length, address);
+            }
+            None => {
+                return PacketAction::Keep;
+            }
+        }
+    }
+
+    fn on_receive(&mut self, data: &mut [u8], length: usize, address: NetworkAddress) -> PacketAction {
+        let mut received_len = length;
+        let mut action = PacketAction::Keep;
+        if let Some(inner) = &mut self.inner {
+            action = inner.on_receive(data, length, address);
+            match action {
+                PacketAction::Keep => {}
+                PacketAction::Drop => {
+                    return PacketAction::Drop;
+                }
+                PacketAction::Modify(modified_len) => {
+                    received_len = std::cmp::min(modified_len, data.len());
+                }
+            }
+        }
+        self.record(CAPTURE_RECEIVED, address, &data[0..received_len]);
+        return action;
+    }
+}
+
+// Feeds the received side of a capture back through a Tachyon instance.  The instance should be created with the same
+// config and channels as the one that was recorded, it does not need to be bound.
+pub struct Replayer {
+    pub header: CaptureHeader,
+    pub records: Vec<CaptureRecord>,
+    // sleep between datagrams as recorded, otherwise replay as fast as possible
+    pub realtime: bool,
+}
+
+impl Replayer {
+    pub fn create<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let header = CaptureHeader::read(reader)?;
+        let mut records: Vec<CaptureRecord> = Vec::new();
+        while let Some(record) = CaptureRecord::read(reader)? {
+            records.push(record);
+        }
+
+        let replayer = Replayer {
+            header,
+            records,
+            realtime: false,
+        };
+        return Ok(replayer);
+    }
+
+    // calls on_receive for every message tachyon publishes, returns the number of messages
+    pub fn replay(&self, tachyon: &mut Tachyon, receive_buffer: &mut [u8], mut on_receive: impl FnMut(&TachyonReceiveResult, &[u8])) -> u32 {
+        tachyon.socket.is_server = self.header.is_server;
+        if !self.header.is_server && !tachyon.connections.contains_key(&NetworkAddress::default()) {
+            tachyon.create_connection(NetworkAddress::default(), Identity::default());
+        }
+        // anything tachyon sends in response (nacks) ends up in the queue and is discarded
+        let queue = DatagramQueue::default();
+        let previous_transport = tachyon.socket.transport.replace(Box::new(queue.clone()));
+
+        let mut count = 0;
+        for record in &self.records {
+            if self.realtime {
+                std::thread::sleep(Duration::from_micros(record.micros as u64));
+            }
+            if record.direction != CAPTURE_RECEIVED {
+                continue;
+            }
+
+            queue.push_incoming(record.address, &record.data);
+            loop {
+                let result = tachyon.receive_loop(receive_buffer);
+                if result.length == 0 {
+                    break;
+                }
+                on_receive(&result, &receive_buffer[0..result.length as usize]);
+                count += 1;
+            }
+        }
+
+        tachyon.socket.transport = previous_transport;
+        return count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon, TachyonConfig};
+
+    use super::*;
+
+    #[test]
+    fn test_record_roundtrip() {
+        let mut buffer: Vec<u8> = Vec::new();
+        CaptureHeader { is_server: true }.write(&mut buffer).unwrap();
+        let record = CaptureRecord {
+            direction: CAPTURE_RECEIVED,
+            micros: 1234,
+            address: NetworkAddress::localhost(8265),
+            data: vec![1, 2, 3],
+        };
+        record.write(&mut buffer).unwrap();
+        assert_eq!(6 + RECORD_HEADER_SIZE + 3, buffer.len());
+
+        let replayer = Replayer::create(&mut buffer.as_slice()).unwrap();
+        assert!(replayer.header.is_server);
+        assert_eq!(1, replayer.records.len());
+        assert_eq!(1234, replayer.records[0].micros);
+        assert!(NetworkAddress::localhost(8265) == replayer.records[0].address);
+        assert_eq!(vec![1, 2, 3], replayer.records[0].data);
+
+        assert!(Replayer::create(&mut &buffer[1..]).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_capture_replay() {
+        let mut test = TachyonTest::default();
+        let capture = CaptureBuffer::default();
+        test.connect();
+        test.server.start_capture(capture.clone());
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            test.client_send_reliable(1, 8);
+        }
+        test.send_buffer[0] = 9;
+        test.client_send_unreliable(4);
+
+        let mut received: Vec<u8> = Vec::new();
+        for _ in 0..10 {
+            let res = test.server_receive();
+            if res.length > 0 {
+                received.push(test.receive_buffer[0]);
+            }
+        }
+        assert_eq!(vec![0, 1, 2, 9], received);
+
+        let replayer = Replayer::create(&mut capture.to_vec().as_slice()).unwrap();
+        let mut replayed: Vec<u8> = Vec::new();
+        let mut tachyon = Tachyon::create(TachyonConfig::default());
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let count = replayer.replay(&mut tachyon, &mut receive_buffer, |_, data| {
+            replayed.push(data[0]);
+        });
+        assert_eq!(4, count);
+        assert_eq!(received, replayed);
+    }
+}
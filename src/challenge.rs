@@ -0,0 +1,235 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use super::{
+    connection::Identity,
+    connection_state::ConnectionState,
+    header::{
+        ChallengeHeader, MESSAGE_TYPE_CHALLENGE, MESSAGE_TYPE_CHALLENGE_RESPONSE, MESSAGE_TYPE_CONNECT_ACCEPTED,
+        MESSAGE_TYPE_CONNECT_REQUEST, TACHYON_CHALLENGE_HEADER_SIZE,
+    },
+    network_address::NetworkAddress,
+    security::SECURITY_CHALLENGE_FAILED,
+    Tachyon,
+};
+
+const CHALLENGE_SEND_INTERVAL: u128 = 300;
+// a cookie is good for one to two of these
+const COOKIE_PERIOD: u64 = 5000;
+
+// Stateless connection challenge for servers running without identities, enabled with TachyonConfig.use_challenge.
+//
+// Without it the first datagram from an address creates a connection and its channels, so spoofed source addresses
+// can make a server allocate as much as they like.  With it the server only answers a connect request with a cookie,
+// a keyed hash of the source address and the current time period, and keeps nothing.  The client has to echo the
+// cookie back from that same address before the server creates the connection.  Anything else from an address
+// without a connection is dropped.  Challenge messages are all the same size, so the server is no use for amplification.
+pub struct Challenge {
+    secret: RandomState,
+    // client, the cookie to echo back, 0 until the server sends one
+    pub cookie: u64,
+    // client, set once the server has accepted the connection
+    pub accepted: bool,
+    last_request: Instant,
+}
+
+impl Challenge {
+    pub fn default() -> Self {
+        let challenge = Challenge {
+            secret: RandomState::new(),
+            cookie: 0,
+            accepted: false,
+            last_request: Instant::now() - Duration::new(100, 0),
+        };
+        return challenge;
+    }
+
+    pub fn create_cookie(&self, address: NetworkAddress, period: u64) -> u64 {
+        let mut hasher = self.secret.build_hasher();
+        address.hash(&mut hasher);
+        period.hash(&mut hasher);
+        return hasher.finish().max(1);
+    }
+
+    // accepts cookies from the current and previous period, so one created just before a period ends still works
+    pub fn is_valid_cookie(&self, address: NetworkAddress, cookie: u64, since_start: u64) -> bool {
+        let period = since_start / COOKIE_PERIOD;
+        if cookie == self.create_cookie(address, period) {
+            return true;
+        }
+        return period > 0 && cookie == self.create_cookie(address, period - 1);
+    }
+}
+
+impl Tachyon {
+    // server, runs on every datagram when use_challenge is set.  True if it's from a connected address and should be
+    // processed as usual.
+    pub fn server_challenge_receive(&mut self, address: NetworkAddress, received_len: usize) -> bool {
+        let header = match ChallengeHeader::try_read(&self.receive_ring.received()[0..received_len]) {
+            Some(header) => header,
+            None => return self.update_challenged_connection(address),
+        };
+        let since_start = self.time_since_start();
+        match header.message_type {
+            MESSAGE_TYPE_CONNECT_REQUEST => {
+                let cookie = self.challenge.create_cookie(address, since_start / COOKIE_PERIOD);
+                self.send_challenge_message(MESSAGE_TYPE_CHALLENGE, cookie, address);
+                return false;
+            }
+            MESSAGE_TYPE_CHALLENGE_RESPONSE => {
+                if self.connections.contains_key(&address) {
+                    // our accept was lost
+                    self.send_challenge_message(MESSAGE_TYPE_CONNECT_ACCEPTED, 0, address);
+                } else if self.challenge.is_valid_cookie(address, header.cookie, since_start) {
+                    self.create_connection(address, Identity::default());
+                    self.send_challenge_message(MESSAGE_TYPE_CONNECT_ACCEPTED, 0, address);
+                } else {
+                    // expired or from before a restart, start over with a fresh one
+                    let cookie = self.challenge.create_cookie(address, since_start / COOKIE_PERIOD);
+                    self.send_challenge_message(MESSAGE_TYPE_CHALLENGE, cookie, address);
+                    self.stats.challenges_failed += 1;
+                    self.fire_security_event(SECURITY_CHALLENGE_FAILED, address, 0);
+                }
+                return false;
+            }
+            _ => {
+                return self.update_challenged_connection(address);
+            }
+        }
+    }
+
+    fn update_challenged_connection(&mut self, address: NetworkAddress) -> bool {
+        let since_start = self.time_since_start();
+        if let Some(conn) = self.connections.get_mut(&address) {
+            conn.received_at = since_start;
+            self.connection_heard(address);
+            return true;
+        }
+        self.stats.unconnected_dropped += 1;
+        return false;
+    }
+
+    // client, true if the datagram was a challenge message and has been handled
+    pub fn client_challenge_receive(&mut self, received_len: usize) -> bool {
+        let header = match ChallengeHeader::try_read(&self.receive_ring.received()[0..received_len]) {
+            Some(header) => header,
+            None => return false,
+        };
+        match header.message_type {
+            MESSAGE_TYPE_CHALLENGE => {
+                self.challenge.cookie = header.cookie;
+                if !self.challenge.accepted {
+                    self.send_challenge_message(MESSAGE_TYPE_CHALLENGE_RESPONSE, header.cookie, NetworkAddress::default());
+                    self.challenge.last_request = self.now();
+                }
+                return true;
+            }
+            MESSAGE_TYPE_CONNECT_ACCEPTED => {
+                self.challenge.accepted = true;
+                self.set_connection_state(NetworkAddress::default(), ConnectionState::Established);
+                return true;
+            }
+            _ => {
+                return false;
+            }
+        }
+    }
+
+    pub fn client_challenge_update(&mut self, now: Instant) {
+        if !self.is_challenged() || self.socket.is_server || !self.socket.is_open() {
+            return;
+        }
+
+        if self.challenge.accepted {
+            return;
+        }
+
+        let since_last = now.duration_since(self.challenge.last_request);
+        if since_last.as_millis() > CHALLENGE_SEND_INTERVAL {
+            self.challenge.last_request = now;
+            if self.challenge.cookie == 0 {
+                self.send_challenge_message(MESSAGE_TYPE_CONNECT_REQUEST, 0, NetworkAddress::default());
+            } else {
+                self.send_challenge_message(MESSAGE_TYPE_CHALLENGE_RESPONSE, self.challenge.cookie, NetworkAddress::default());
+            }
+        }
+    }
+
+    // identities already gate connection creation on the server, the challenge is only used without them
+    pub fn is_challenged(&self) -> bool {
+        return self.config.use_challenge == 1 && self.config.use_identity == 0;
+    }
+
+    fn send_challenge_message(&self, message_type: u8, cookie: u64, address: NetworkAddress) {
+        let header = ChallengeHeader { message_type, cookie };
+        let mut send_buffer: Vec<u8> = vec![0; TACHYON_CHALLENGE_HEADER_SIZE];
+        header.write(&mut send_buffer);
+        self.socket.send_to(address, &send_buffer, send_buffer.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::{
+        channel::ChannelConfig, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon, TachyonConfig,
+        SEND_ERROR_NOT_CONNECTED,
+    };
+
+    use super::Challenge;
+
+    #[test]
+    fn test_cookie() {
+        let challenge = Challenge::default();
+        let address = NetworkAddress::localhost(100);
+        let cookie = challenge.create_cookie(address, 2);
+        assert!(challenge.is_valid_cookie(address, cookie, 10000));
+        assert!(challenge.is_valid_cookie(address, cookie, 15000));
+        assert!(!challenge.is_valid_cookie(address, cookie, 20000));
+        assert!(!challenge.is_valid_cookie(NetworkAddress::localhost(101), cookie, 10000));
+
+        // another server instance doesn't accept it
+        assert!(!Challenge::default().is_valid_cookie(address, cookie, 10000));
+    }
+
+    #[test]
+    #[serial]
+    fn test_challenge() {
+        let mut test = TachyonTest::default();
+        test.server.config.use_challenge = 1;
+        test.client.config.use_challenge = 1;
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.connect();
+
+        // a client that skips the challenge gets nothing allocated
+        let mut spoofer = Tachyon::create(TachyonConfig::default());
+        spoofer.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(spoofer.connect(test.address));
+        assert_eq!(0, spoofer.send_reliable(3, NetworkAddress::default(), &[1; 8], 8).error);
+        assert_eq!(0, spoofer.send_unreliable(NetworkAddress::default(), &[1; 8], 8).error);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(0, test.server.connections.len());
+        assert_eq!(0, test.server.unreliable_channels.len());
+        assert_eq!(2, test.server.stats.unconnected_dropped);
+
+        assert_eq!(SEND_ERROR_NOT_CONNECTED, test.client_send_reliable(3, 8).error);
+        test.client.step(Duration::from_millis(0));
+        test.server_receive();
+        assert_eq!(0, test.server.connections.len());
+        test.client_receive();
+        test.server_receive();
+        assert_eq!(1, test.server.connections.len());
+        test.client_receive();
+        assert!(test.client.challenge.accepted);
+
+        assert_eq!(0, test.client_send_reliable(3, 8).error);
+        assert_eq!(8, test.server_receive().length);
+    }
+}
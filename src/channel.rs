@@ -1,23 +1,45 @@
+use std::{io::IoSlice, sync::Arc, time::Instant};
 
 use rustc_hash::{FxHashMap};
 
 use super::{
-    fragmentation::Fragmentation,
+    byte_buffer_pool::{ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT},
+    channel_epoch::ChannelEpoch,
+    clock::{system_clock, Clock},
+    fragmentation::{FragmentReceived, Fragmentation, MAX_ASSEMBLED_SIZE_MAX},
     header::{
-        Header, MESSAGE_TYPE_FRAGMENT, MESSAGE_TYPE_NONE, MESSAGE_TYPE_NACK,
+        Header, MESSAGE_TYPE_FRAGMENT, MESSAGE_TYPE_NONE, MESSAGE_TYPE_NACK, MESSAGE_TYPE_NACK_FORMAT, MESSAGE_TYPE_NACK_RANGES,
         TACHYON_FRAGMENTED_HEADER_SIZE, TACHYON_HEADER_SIZE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_RELIABLE, TACHYON_NACKED_HEADER_SIZE
     },
-    int_buffer::IntBuffer,
-    nack::Nack,
+    int_buffer::{BufferTooSmall, IntBuffer},
+    latency::{LatencyTracker, TIMESTAMP_EXTENSION_SIZE},
+    nack::{Nack, NACK_FORMAT_RANGES},
     network_address::NetworkAddress,
-    receiver::Receiver,
-    send_buffer_manager::SendBufferManager,
-    tachyon_socket::TachyonSocket, SEND_ERROR_UNKNOWN, TachyonSendResult
+    outgoing_queue::OutgoingQueue,
+    pipeline::Pipeline,
+    receive_target::ReceiveTarget,
+    receiver::{PublishOrder, ReceiveWindowConfig, Receiver, PUBLISH_ORDER_ARRIVAL},
+    send_buffer_manager::{SendBufferManager, SEND_BUFFER_SIZE, SEND_WINDOW_SIZE_MAX},
+    sequence::SEQUENCE_WINDOW_MAX,
+    sequence_trace::*,
+    tachyon_socket::TachyonSocket, SEND_ERROR_LENGTH, SEND_ERROR_UNKNOWN, TachyonSendResult
 };
 
-pub static mut NONE_SEND_DATA: &'static mut [u8] = &mut [0; TACHYON_HEADER_SIZE];
 const NACK_REDUNDANCY_DEFAULT: u32 = 1;
-pub const RECEIVE_WINDOW_SIZE_DEFAULT: u32 = 512;
+pub use super::receiver::RECEIVE_WINDOW_SIZE_DEFAULT;
+pub const SLIM_RECEIVE_WINDOW_SIZE: u32 = 64;
+pub const SLIM_SEND_WINDOW_SIZE: u32 = 128;
+pub const SLIM_POOL_SIZE: u32 = 32;
+// nacks go out in groups of 32 sequences, a smaller window never nacks anything
+pub const RECEIVE_WINDOW_SIZE_MIN: u32 = 32;
+// the receiver keeps twice the window in slots
+pub const RECEIVE_WINDOW_SIZE_MAX: u32 = SEQUENCE_WINDOW_MAX / 2;
+// adaptive nack redundancy goes up a step when the connection's loss is at least this, down a step when it's below
+// the lower one, see ChannelConfig.nack_redundancy_max
+pub const NACK_REDUNDANCY_RAISE_LOSS_PERCENT: f32 = 5.0;
+pub const NACK_REDUNDANCY_LOWER_LOSS_PERCENT: f32 = 1.0;
+// 0 unreliable, 1 and 2 the built in ordered/unordered channels
+pub const RESERVED_CHANNEL_MAX: u8 = 2;
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -39,6 +61,27 @@ pub struct ChannelStats {
     pub nones_received: u64,
     pub nones_accepted: u64,
     pub skipped_sequences: u64,
+    pub ordered_gaps_skipped: u64,
+    pub rtt: u64,
+    pub latency_p50: u64,
+    pub latency_p95: u64,
+    pub latency_p99: u64,
+    pub pipeline_dropped: u64,
+    pub queue_expired: u64,
+    pub abandoned: u64,
+    pub oversized_groups: u64,
+    pub invalid_fragments: u64,
+    pub epoch_resets: u64,
+    // the nack redundancy the channel is using now, combined stats report the highest
+    pub nack_redundancy: u64,
+    // messages given up on after ChannelConfig.max_resends
+    pub resends_exhausted: u64,
+    // nacks for messages sent with SEND_FLAG_EXPENDABLE, answered with a none
+    pub expendable_nacked: u64,
+    // bytes of nack messages sent, and how many fewer that was for nacks sent as ranges than the varint format would be
+    pub nack_bytes_sent: u64,
+    pub nack_bytes_saved: u64,
+    pub nack_range_messages: u64,
 }
 
 impl ChannelStats {
@@ -59,6 +102,71 @@ impl ChannelStats {
         self.nones_received += other.nones_received;
         self.nones_accepted += other.nones_accepted;
         self.skipped_sequences += other.skipped_sequences;
+        self.ordered_gaps_skipped += other.ordered_gaps_skipped;
+        self.pipeline_dropped += other.pipeline_dropped;
+        self.queue_expired += other.queue_expired;
+        self.abandoned += other.abandoned;
+        self.oversized_groups += other.oversized_groups;
+        self.invalid_fragments += other.invalid_fragments;
+        self.epoch_resets += other.epoch_resets;
+        self.resends_exhausted += other.resends_exhausted;
+        self.expendable_nacked += other.expendable_nacked;
+        self.nack_bytes_sent += other.nack_bytes_sent;
+        self.nack_bytes_saved += other.nack_bytes_saved;
+        self.nack_range_messages += other.nack_range_messages;
+
+        // latency isn't additive, combined stats report the worst channel
+        self.rtt = std::cmp::max(self.rtt, other.rtt);
+        self.latency_p50 = std::cmp::max(self.latency_p50, other.latency_p50);
+        self.latency_p95 = std::cmp::max(self.latency_p95, other.latency_p95);
+        self.latency_p99 = std::cmp::max(self.latency_p99, other.latency_p99);
+        self.nack_redundancy = std::cmp::max(self.nack_redundancy, other.nack_redundancy);
+    }
+
+    // a copy to diff a later snapshot against
+    pub fn snapshot(&self) -> ChannelStats {
+        return *self;
+    }
+
+    // What changed since earlier, for per interval reporting.  Counters that went backwards (stats were reset in
+    // between) come out as 0, rtt and latency are the current values.
+    pub fn diff(&self, earlier: &ChannelStats) -> ChannelStats {
+        let stats = ChannelStats {
+            sent: self.sent.saturating_sub(earlier.sent),
+            received: self.received.saturating_sub(earlier.received),
+            bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            fragments_sent: self.fragments_sent.saturating_sub(earlier.fragments_sent),
+            fragments_received: self.fragments_received.saturating_sub(earlier.fragments_received),
+            fragments_assembled: self.fragments_assembled.saturating_sub(earlier.fragments_assembled),
+            published: self.published.saturating_sub(earlier.published),
+            published_consumed: self.published_consumed.saturating_sub(earlier.published_consumed),
+            nacks_sent: self.nacks_sent.saturating_sub(earlier.nacks_sent),
+            nacks_received: self.nacks_received.saturating_sub(earlier.nacks_received),
+            resent: self.resent.saturating_sub(earlier.resent),
+            nones_sent: self.nones_sent.saturating_sub(earlier.nones_sent),
+            nones_received: self.nones_received.saturating_sub(earlier.nones_received),
+            nones_accepted: self.nones_accepted.saturating_sub(earlier.nones_accepted),
+            skipped_sequences: self.skipped_sequences.saturating_sub(earlier.skipped_sequences),
+            ordered_gaps_skipped: self.ordered_gaps_skipped.saturating_sub(earlier.ordered_gaps_skipped),
+            rtt: self.rtt,
+            latency_p50: self.latency_p50,
+            latency_p95: self.latency_p95,
+            latency_p99: self.latency_p99,
+            pipeline_dropped: self.pipeline_dropped.saturating_sub(earlier.pipeline_dropped),
+            queue_expired: self.queue_expired.saturating_sub(earlier.queue_expired),
+            abandoned: self.abandoned.saturating_sub(earlier.abandoned),
+            oversized_groups: self.oversized_groups.saturating_sub(earlier.oversized_groups),
+            invalid_fragments: self.invalid_fragments.saturating_sub(earlier.invalid_fragments),
+            epoch_resets: self.epoch_resets.saturating_sub(earlier.epoch_resets),
+            nack_redundancy: self.nack_redundancy,
+            resends_exhausted: self.resends_exhausted.saturating_sub(earlier.resends_exhausted),
+            expendable_nacked: self.expendable_nacked.saturating_sub(earlier.expendable_nacked),
+            nack_bytes_sent: self.nack_bytes_sent.saturating_sub(earlier.nack_bytes_sent),
+            nack_bytes_saved: self.nack_bytes_saved.saturating_sub(earlier.nack_bytes_saved),
+            nack_range_messages: self.nack_range_messages.saturating_sub(earlier.nack_range_messages),
+        };
+        return stats;
     }
 }
 
@@ -69,7 +177,9 @@ impl std::fmt::Display for ChannelStats {
             "sent:{} received:{},kb_sent:{} kb_received:{}
 fragments_sent:{} fragments_received:{} fragments_assembled:{},
 published: {} published_consumed:{} nacks_sent:{} nacks_received:{} resent:{}
-nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
+nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{} ordered_gaps_skipped:{} pipeline_dropped:{} queue_expired:{} abandoned:{}
+oversized_groups:{} invalid_fragments:{} epoch_resets:{} rtt:{} latency_p50:{} latency_p95:{} latency_p99:{} nack_redundancy:{} resends_exhausted:{} expendable_nacked:{}
+nack_bytes_sent:{} nack_bytes_saved:{} nack_range_messages:{}\n\n",
             self.sent,
             self.received,
             self.bytes_sent / 1024,
@@ -85,7 +195,24 @@ nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
             self.nones_sent,
             self.nones_received,
             self.nones_accepted,
-            self.skipped_sequences
+            self.skipped_sequences,
+            self.ordered_gaps_skipped,
+            self.pipeline_dropped,
+            self.queue_expired,
+            self.abandoned,
+            self.oversized_groups,
+            self.invalid_fragments,
+            self.epoch_resets,
+            self.rtt,
+            self.latency_p50,
+            self.latency_p95,
+            self.latency_p99,
+            self.nack_redundancy,
+            self.resends_exhausted,
+            self.expendable_nacked,
+            self.nack_bytes_sent,
+            self.nack_bytes_saved,
+            self.nack_range_messages
         )
     }
 }
@@ -96,7 +223,37 @@ nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
 pub struct ChannelConfig {
     pub receive_window_size: u32,
     pub nack_redundancy: u32,
-    pub ordered: u32
+    pub ordered: u32,
+    pub timestamped: u32,
+    // millis an ordered channel waits on a missing sequence before skipping it, 0 waits forever
+    pub ordered_gap_timeout: u32,
+    // skip the fragmentation stage, large messages go out as a single datagram
+    pub unfragmented: u32,
+    // fail sends with SEND_ERROR_WINDOW_FULL instead of overwriting send buffers that could still be nacked
+    pub window_full_error: u32,
+    // reliable messages kept for resending, rounded up to a power of two.  0 is the full 1024.
+    pub send_window_size: u32,
+    // byte buffers each of the send/receive pools hold on to, 0 for the defaults
+    pub pool_size: u32,
+    // fragmented messages that would assemble to more than this are discarded, 0 is MAX_ASSEMBLED_SIZE_DEFAULT.  At most
+    // MAX_ASSEMBLED_SIZE_MAX.
+    pub max_assembled_size: u32,
+    // the application's own label for the channel, never sent.  Copied into receive results and channel displays.
+    pub tag: u64,
+    // PUBLISH_ORDER_x, the order an unordered channel hands out received messages that are waiting to be taken.  Local,
+    // the ends can differ.  Ignored on ordered channels.
+    pub publish_order: u32,
+    // above nack_redundancy makes redundancy adaptive between the two, raised under loss and lowered on a clean
+    // connection as update() measures it.  0 keeps nack_redundancy fixed.
+    pub nack_redundancy_max: u32,
+    // Partial reliability, a message nacked again after this many resends is given up on.  The receiver gets a none and
+    // moves past it, it's counted in ChannelStats.resends_exhausted.  0 resends for as long as the message is kept.
+    pub max_resends: u32,
+    // keep the last this many sequence events for Tachyon::get_sequence_trace, see sequence_trace.rs.  0 is off.
+    pub trace_events: u32,
+    // offer nack format v2 to the other end, see nack.rs.  Nacks go out as ranges when the other end has it on too and
+    // they come out smaller.
+    pub nack_ranges: u32
 }
 
 impl ChannelConfig {
@@ -105,7 +262,20 @@ impl ChannelConfig {
         let config = ChannelConfig {
             ordered: 1,
             receive_window_size: RECEIVE_WINDOW_SIZE_DEFAULT,
-            nack_redundancy: NACK_REDUNDANCY_DEFAULT
+            nack_redundancy: NACK_REDUNDANCY_DEFAULT,
+            timestamped: 0,
+            ordered_gap_timeout: 0,
+            unfragmented: 0,
+            window_full_error: 0,
+            send_window_size: 0,
+            pool_size: 0,
+            max_assembled_size: 0,
+            tag: 0,
+            publish_order: PUBLISH_ORDER_ARRIVAL,
+            nack_redundancy_max: 0,
+            max_resends: 0,
+            trace_events: 0,
+            nack_ranges: 0
         };
         return config;
     }
@@ -114,19 +284,164 @@ impl ChannelConfig {
         let config = ChannelConfig {
             ordered: 0,
             receive_window_size: RECEIVE_WINDOW_SIZE_DEFAULT,
-            nack_redundancy: NACK_REDUNDANCY_DEFAULT
+            nack_redundancy: NACK_REDUNDANCY_DEFAULT,
+            timestamped: 0,
+            ordered_gap_timeout: 0,
+            unfragmented: 0,
+            window_full_error: 0,
+            send_window_size: 0,
+            pool_size: 0,
+            max_assembled_size: 0,
+            tag: 0,
+            publish_order: PUBLISH_ORDER_ARRIVAL,
+            nack_redundancy_max: 0,
+            max_resends: 0,
+            trace_events: 0,
+            nack_ranges: 0
         };
         return config;
     }
 
+    // For servers holding many mostly idle connections.  Much smaller windows and pools, at the cost of less headroom
+    // for bursts and for recovering loss on high latency links.
+    pub fn slim_ordered() -> Self {
+        let mut config = ChannelConfig::default_ordered();
+        config.make_slim();
+        return config;
+    }
+
+    pub fn slim_unordered() -> Self {
+        let mut config = ChannelConfig::default_unordered();
+        config.make_slim();
+        return config;
+    }
+
+    fn make_slim(&mut self) {
+        self.receive_window_size = SLIM_RECEIVE_WINDOW_SIZE;
+        self.send_window_size = SLIM_SEND_WINDOW_SIZE;
+        self.pool_size = SLIM_POOL_SIZE;
+    }
+
     pub fn is_ordered(&self) -> bool {
         return self.ordered == 1;
     }
+
+    pub fn receive_window_config(&self) -> ReceiveWindowConfig {
+        let config = ReceiveWindowConfig {
+            is_ordered: self.is_ordered(),
+            window_size: self.receive_window_size,
+            ordered_gap_timeout: self.ordered_gap_timeout,
+            publish_order: match self.is_ordered() {
+                true => PublishOrder::Arrival,
+                false => PublishOrder::from_config(self.publish_order).unwrap_or(PublishOrder::Arrival),
+            },
+        };
+        return config;
+    }
+
+    pub fn is_timestamped(&self) -> bool {
+        return self.timestamped == 1;
+    }
+
+    pub fn is_unfragmented(&self) -> bool {
+        return self.unfragmented == 1;
+    }
+
+    pub fn is_window_full_error(&self) -> bool {
+        return self.window_full_error == 1;
+    }
+
+    pub fn validate(&self, channel_id: u16) -> Result<(), ChannelConfigError> {
+        if channel_id <= RESERVED_CHANNEL_MAX as u16 {
+            return Err(ChannelConfigError::ReservedId);
+        }
+        if self.receive_window_size < RECEIVE_WINDOW_SIZE_MIN {
+            return Err(ChannelConfigError::WindowTooSmall);
+        }
+        if self.receive_window_size > RECEIVE_WINDOW_SIZE_MAX {
+            return Err(ChannelConfigError::WindowTooLarge);
+        }
+        if self.send_window_size > SEND_WINDOW_SIZE_MAX {
+            return Err(ChannelConfigError::SendWindowTooLarge);
+        }
+        let flags = [self.ordered, self.timestamped, self.unfragmented, self.window_full_error];
+        if flags.iter().any(|flag| *flag > 1) {
+            return Err(ChannelConfigError::InvalidFlag);
+        }
+        if PublishOrder::from_config(self.publish_order).is_none() {
+            return Err(ChannelConfigError::InvalidPublishOrder);
+        }
+        if self.max_assembled_size as usize > MAX_ASSEMBLED_SIZE_MAX {
+            return Err(ChannelConfigError::AssembledSizeTooLarge);
+        }
+        return Ok(());
+    }
+
+    // Settings both ends of a channel have to agree on.  Window and pool sizes are local and can differ.
+    pub fn check_compatible(&self, other: &ChannelConfig) -> Result<(), ChannelConfigError> {
+        if self.ordered != other.ordered || self.timestamped != other.timestamped || self.unfragmented != other.unfragmented {
+            return Err(ChannelConfigError::Mismatch);
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelConfigError {
+    ReservedId = 1,
+    WindowTooSmall = 2,
+    WindowTooLarge = 3,
+    SendWindowTooLarge = 4,
+    InvalidFlag = 5,
+    Mismatch = 6,
+    IdOutOfRange = 7,
+    InvalidPublishOrder = 8,
+    AssembledSizeTooLarge = 9,
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "channel:{} tag:{} address:{} {}", self.id, self.config.tag, self.address, self.stats)
+    }
+}
+
+impl std::fmt::Display for ChannelConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = match self {
+            ChannelConfigError::ReservedId => "channel ids 0-2 are reserved",
+            ChannelConfigError::WindowTooSmall => "receive_window_size is below RECEIVE_WINDOW_SIZE_MIN",
+            ChannelConfigError::WindowTooLarge => "receive_window_size is above RECEIVE_WINDOW_SIZE_MAX",
+            ChannelConfigError::SendWindowTooLarge => "send_window_size is above SEND_WINDOW_SIZE_MAX",
+            ChannelConfigError::InvalidFlag => "flags must be 0 or 1",
+            ChannelConfigError::Mismatch => "ordered, timestamped and unfragmented must match on both ends",
+            ChannelConfigError::IdOutOfRange => "channel ids above 255 need TachyonConfig.wide_channels",
+            ChannelConfigError::InvalidPublishOrder => "publish_order must be one of PUBLISH_ORDER_x",
+            ChannelConfigError::AssembledSizeTooLarge => "max_assembled_size is above MAX_ASSEMBLED_SIZE_MAX",
+        };
+        write!(f, "{}", message)
+    }
 }
 
+impl std::error::Error for ChannelConfigError {}
+
+// a reliable message past its ttl or out of resends was nacked and not resent
+pub type AbandonedCallback = unsafe extern "C" fn(channel_id: u16, address: NetworkAddress, sequence: u16);
+
+// an ordered channel gave up waiting on sequences first through last, see ChannelConfig.ordered_gap_timeout
+pub type GapSkippedCallback = unsafe extern "C" fn(channel_id: u16, address: NetworkAddress, first: u16, last: u16);
+
+// value is the group's length
+pub const ANOMALY_FRAGMENT_GROUP_TOO_LARGE: u8 = 1;
+// a fragment that disagrees with its group or couldn't have come from create_fragments, value is its sequence
+pub const ANOMALY_FRAGMENT_INVALID: u8 = 2;
+
+// the peer sent something no well behaved peer would, it has been dropped
+pub type AnomalyCallback = unsafe extern "C" fn(anomaly: u8, channel_id: u16, address: NetworkAddress, value: u32);
+
 pub struct Channel {
-    pub id: u8,
+    pub id: u16,
     pub address: NetworkAddress,
+    pub config: ChannelConfig,
     pub frag: Fragmentation,
     pub send_buffers: SendBufferManager,
     pub receiver: Receiver,
@@ -134,60 +449,224 @@ pub struct Channel {
     nack_send_data: Vec<u8>,
     nacked_sequences: Vec<u16>,
     nacked_sequence_map: FxHashMap<u16, NetworkAddress>,
+    nack_ranges: Vec<(u16, u16)>,
+    // the other end reads nack format v2
+    pub remote_nack_ranges: bool,
     pub resend_rewrite_buffer: Vec<u8>,
-    pub nack_redundancy: u32
+    pub nack_redundancy: u32,
+    pub latency: Option<LatencyTracker>,
+    pub clock: Arc<dyn Clock>,
+    pub unfragmented: bool,
+    pub window_full_error: bool,
+    pub pipeline: Pipeline,
+    pub outgoing: OutgoingQueue,
+    pub abandoned_callback: Option<AbandonedCallback>,
+    pub gap_skipped_callback: Option<GapSkippedCallback>,
+    pub anomaly_callback: Option<AnomalyCallback>,
+    // set when TachyonConfig.use_channel_epochs is, see channel_epoch.rs
+    pub epoch: Option<ChannelEpoch>,
+    // set when ChannelConfig.trace_events is
+    pub trace: Option<SequenceTrace>
 }
 
 impl Channel {
-    pub fn create(id: u8, address: NetworkAddress, config: ChannelConfig) -> Self {
-        let channel = Channel {
+    pub fn create(id: u16, address: NetworkAddress, config: ChannelConfig) -> Self {
+        let mut channel = Channel {
             id,
             address,
+            config,
             frag: Fragmentation::default(),
-            send_buffers: SendBufferManager::default(),
-            receiver: Receiver::create(config.is_ordered(), config.receive_window_size),
+            send_buffers: Channel::create_send_buffers(&config),
+            receiver: Receiver::create(config.receive_window_config()),
             stats: ChannelStats::default(),
-            nack_send_data: vec![0; 512],
+            nack_send_data: Vec::new(),
             nacked_sequences: Vec::new(),
             nacked_sequence_map: FxHashMap::default(),
-            resend_rewrite_buffer: vec![0;2048],
-            nack_redundancy: config.nack_redundancy
+            nack_ranges: Vec::new(),
+            remote_nack_ranges: false,
+            resend_rewrite_buffer: Vec::new(),
+            nack_redundancy: config.nack_redundancy,
+            latency: None,
+            clock: system_clock(),
+            unfragmented: config.is_unfragmented(),
+            window_full_error: config.is_window_full_error(),
+            pipeline: Pipeline::default(),
+            outgoing: OutgoingQueue::default(),
+            abandoned_callback: None,
+            gap_skipped_callback: None,
+            anomaly_callback: None,
+            epoch: None,
+            trace: None
         };
+        if config.trace_events > 0 {
+            channel.trace = Some(SequenceTrace::create(config.trace_events as usize));
+        }
+        if config.is_timestamped() {
+            channel.latency = Some(LatencyTracker::create());
+        }
+        if config.max_assembled_size > 0 {
+            channel.frag.max_assembled_size = config.max_assembled_size as usize;
+        }
+        if config.pool_size > 0 {
+            channel.receiver.buffer_pool = ByteBufferPool::create(BYTE_BUFFER_SIZE_DEFAULT, config.pool_size as usize);
+        }
         return channel;
     }
 
-    fn create_none(sequence: u16, channel_id: u8) {
+    fn create_send_buffers(config: &ChannelConfig) -> SendBufferManager {
+        if config.send_window_size == 0 && config.pool_size == 0 {
+            return SendBufferManager::default();
+        }
+        let window_size = match config.send_window_size {
+            0 => SEND_BUFFER_SIZE,
+            size => size.min(SEND_WINDOW_SIZE_MAX) as u16,
+        };
+        let pool_size = match config.pool_size {
+            0 => window_size as usize,
+            size => size as usize,
+        };
+        return SendBufferManager::create(window_size, pool_size);
+    }
+
+    // Back to the sequence state of a new channel, for when the other end restarted.  Messages not yet taken with
+    // receive are dropped along with what we were keeping for resends.  Stats, the pipeline, queued sends and the epoch stay.
+    pub fn reset_sequences(&mut self) {
+        let fresh = Channel::create(self.id, self.address, self.config);
+        self.frag = fresh.frag;
+        self.send_buffers = fresh.send_buffers;
+        self.receiver = fresh.receiver;
+        self.nacked_sequences.clear();
+        self.nacked_sequence_map.clear();
+        // it could be another version now
+        self.remote_nack_ranges = false;
+        self.set_clock(self.clock.clone());
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.frag.clock = clock.clone();
+        self.send_buffers.clock = clock.clone();
+        self.clock = clock;
+    }
+
+    // on the stack, channels of different Pool servers send nones from different threads at once
+    fn create_none(sequence: u16, channel_id: u16) -> [u8; TACHYON_HEADER_SIZE] {
+        let mut data = [0; TACHYON_HEADER_SIZE];
         let mut header = Header::default();
         header.message_type = MESSAGE_TYPE_NONE;
         header.sequence = sequence;
         header.channel = channel_id;
-        header.write(unsafe { &mut NONE_SEND_DATA });
+        header.write(&mut data);
+        return data;
     }
 
     pub fn is_ordered(&self) -> bool {
-        return self.receiver.is_ordered;
+        return self.receiver.is_ordered();
+    }
+
+    pub fn is_timestamped(&self) -> bool {
+        return self.latency.is_some();
+    }
+
+    // size of the timestamp extension that follows the reliable header
+    pub fn timestamp_size(&self) -> usize {
+        if self.is_timestamped() {
+            return TIMESTAMP_EXTENSION_SIZE;
+        } else {
+            return 0;
+        }
+    }
+
+    // zeroes the counters, rtt and latency keep tracking
+    pub fn reset_stats(&mut self) {
+        self.stats = ChannelStats::default();
+        self.receiver.reset_stats();
+        self.update_stats();
     }
 
     pub fn update_stats(&mut self) {
-        self.stats.skipped_sequences = self.receiver.skipped_sequences;
+        self.stats.nack_redundancy = self.nack_redundancy as u64;
+        self.stats.skipped_sequences = self.receiver.skipped_sequences();
+        self.stats.ordered_gaps_skipped = self.receiver.ordered_gaps_skipped();
+
+        if let Some(latency) = &self.latency {
+            let (p50, p95, p99) = latency.percentiles();
+            self.stats.rtt = latency.rtt as u64;
+            self.stats.latency_p50 = p50 as u64;
+            self.stats.latency_p95 = p95 as u64;
+            self.stats.latency_p99 = p99 as u64;
+        }
+    }
+
+    // one step towards more redundancy under loss or less on a clean connection, within nack_redundancy and
+    // nack_redundancy_max
+    pub fn adapt_nack_redundancy(&mut self, loss_percent: f32) {
+        let min = self.config.nack_redundancy;
+        let max = self.config.nack_redundancy_max;
+        if max <= min {
+            return;
+        }
+        if loss_percent >= NACK_REDUNDANCY_RAISE_LOSS_PERCENT {
+            self.nack_redundancy = (self.nack_redundancy + 1).min(max);
+        } else if loss_percent < NACK_REDUNDANCY_LOWER_LOSS_PERCENT {
+            self.nack_redundancy = self.nack_redundancy.saturating_sub(1).max(min);
+        }
+    }
+
+    // called when a reliable message is accepted, header_size being the size of the reliable header it arrived with
+    pub fn process_timestamp(&mut self, receive_buffer: &[u8], header_size: usize) {
+        if let Some(latency) = &mut self.latency {
+            latency.read(receive_buffer, header_size);
+        }
     }
 
     pub fn receive_published(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress) {
+        match self.receive_published_into(receive_buffer) {
+            Ok(res) => return res,
+            Err(_) => return (0, self.address),
+        }
+    }
+
+    // Err with the message length when the target can't hold it, the message is dropped
+    pub fn receive_published_into<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T) -> Result<(u32, NetworkAddress), usize> {
         for _ in 0..1000 {
-            let res = self.receive_published_internal(receive_buffer);
+            let res = self.receive_published_internal(target)?;
             if res.0 > 0 {
-                return (res.0, res.1);
+                if self.pipeline.is_empty() {
+                    return Ok((res.0, res.1));
+                }
+                let received = match target.reserve(res.0 as usize) {
+                    Some(received) => received,
+                    None => return Err(res.0 as usize),
+                };
+                match self.pipeline.process_receive(received) {
+                    Some(data) => {
+                        let length = data.len();
+                        match target.reserve(length) {
+                            Some(dest) => dest.copy_from_slice(data),
+                            None => return Err(length),
+                        }
+                        return Ok((length as u32, res.1));
+                    }
+                    None => {
+                        self.stats.pipeline_dropped += 1;
+                        continue;
+                    }
+                }
             }
             if !res.2 {
                 break;
             }
         }
 
-        return (0,self.address);
+        return Ok((0, self.address));
     }
 
-    // returns message length, address, should retry (queue not empty)
-    fn receive_published_internal(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, bool) {
+    fn receive_published_internal<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T) -> Result<(u32, NetworkAddress, bool), usize> {
+        if self.trace.is_some() {
+            if let Some(sequence) = self.receiver.peek_published_sequence() {
+                self.trace(TRACE_PUBLISHED, sequence);
+            }
+        }
         match self.receiver.take_published() {
             Some(byte_buffer) => {
                 let buffer_len = byte_buffer.length;
@@ -197,7 +676,7 @@ impl Channel {
 
                 if message_type == MESSAGE_TYPE_NONE {
                     self.receiver.return_buffer(byte_buffer);
-                    return (0, self.address, true);
+                    return Ok((0, self.address, true));
                 }
 
                 if message_type == MESSAGE_TYPE_FRAGMENT {
@@ -205,15 +684,22 @@ impl Channel {
                     match self.frag.assemble(header) {
                         Ok(res) => {
                             let assembled_len = res.len();
-                            receive_buffer[0..assembled_len].copy_from_slice(&res[..]);
+                            self.stats.published_consumed += 1;
+                            if target.keeps_buffers() {
+                                target.keep_buffer(res, 0);
+                            } else {
+                                match target.reserve(assembled_len) {
+                                    Some(dest) => dest.copy_from_slice(&res[..]),
+                                    None => return Err(assembled_len),
+                                }
+                            }
                             self.stats.received += 1;
                             self.stats.fragments_assembled += header.fragment_count as u64;
-                            self.stats.published_consumed += 1;
-                            return (assembled_len as u32, self.address, true);
+                            return Ok((assembled_len as u32, self.address, true));
                         }
                         Err(_) => {
                             self.receiver.return_buffer(byte_buffer);
-                            return (0, self.address, true);
+                            return Ok((0, self.address, true));
                         }
                     }
                 }
@@ -225,20 +711,34 @@ impl Channel {
                     header_size = TACHYON_HEADER_SIZE;
                 } else {
                     // should not be possible
-                    return (0, self.address, true);
+                    return Ok((0, self.address, true));
                 }
+                let header_size = header_size + self.timestamp_size();
 
-                receive_buffer[0..buffer_len - header_size].copy_from_slice(&byte_buffer.get()[header_size..buffer_len]);
-                self.receiver.return_buffer(byte_buffer);
-
+                let body_len = buffer_len - header_size;
                 self.stats.published_consumed += 1;
-                return ((buffer_len - header_size) as u32, self.address, true);
+                if target.keeps_buffers() {
+                    // the pool makes a new one to replace it
+                    target.keep_buffer(byte_buffer.into_vec(), header_size);
+                    return Ok((body_len as u32, self.address, true));
+                }
+                let copied = match target.reserve(body_len) {
+                    Some(dest) => {
+                        dest.copy_from_slice(&byte_buffer.get()[header_size..buffer_len]);
+                        true
+                    }
+                    None => false,
+                };
+                self.receiver.return_buffer(byte_buffer);
+                if !copied {
+                    return Err(body_len);
+                }
+                return Ok((body_len as u32, self.address, true));
             }
             None => {
-                return (0, self.address, false);
+                return Ok((0, self.address, false));
             }
         }
-        
     }
 
     pub fn process_none_message(&mut self, sequence: u16, receive_buffer: &mut [u8], received_len: usize) {
@@ -246,80 +746,155 @@ impl Channel {
         if self.receiver.receive_packet(sequence, receive_buffer, received_len)
         {
             self.stats.nones_accepted += 1;
+            self.trace(TRACE_NONE_RECEIVED, sequence);
         }
     }
 
     pub fn process_fragment_message(&mut self, sequence: u16, receive_buffer: &mut [u8], received_len: usize) {
-        let received_frag_res = self.frag.receive_fragment(receive_buffer, received_len);
-        if received_frag_res.0 {
-            if self.receiver.receive_packet(sequence,receive_buffer,TACHYON_FRAGMENTED_HEADER_SIZE) {
-                self.stats.fragments_received += 1;
+        match self.frag.receive_fragment(receive_buffer, received_len) {
+            FragmentReceived::Stored { .. } => {
+                if self.receiver.receive_packet(sequence,receive_buffer,TACHYON_FRAGMENTED_HEADER_SIZE) {
+                    self.stats.fragments_received += 1;
+                    self.trace(TRACE_RECEIVED, sequence);
+                } else {
+                    self.trace(TRACE_REJECTED, sequence);
+                }
+            }
+            FragmentReceived::Rejected { group_length, first } => {
+                if first {
+                    self.stats.oversized_groups += 1;
+                    self.fire_anomaly(ANOMALY_FRAGMENT_GROUP_TOO_LARGE, group_length as u32);
+                }
+                // still received so the sender isn't nacked for it forever, there's no group for it to assemble into
+                self.receiver.receive_packet(sequence, receive_buffer, TACHYON_FRAGMENTED_HEADER_SIZE);
+            }
+            FragmentReceived::Invalid => {
+                self.stats.invalid_fragments += 1;
+                self.fire_anomaly(ANOMALY_FRAGMENT_INVALID, sequence as u32);
             }
         }
     }
 
-    // separate nack message, varint encoded
-    pub fn process_nack_message(&mut self, address: NetworkAddress, receive_buffer: &mut [u8]) {
+    pub fn trace(&mut self, event: u8, sequence: u16) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(event, sequence, self.clock.now());
+        }
+    }
+
+    fn fire_anomaly(&self, anomaly: u8, value: u32) {
+        if let Some(callback) = self.anomaly_callback {
+            unsafe {
+                callback(anomaly, self.id, self.address, value);
+            }
+        }
+    }
+
+    // separate nack message, varint encoded.  A byte after the nacks offers nack format v2.
+    pub fn process_nack_message(&mut self, address: NetworkAddress, receive_buffer: &mut [u8], received_len: usize, socket: &TachyonSocket) {
+        self.nacked_sequences.clear();
+        let position = match Nack::read_varint(&mut self.nacked_sequences, &receive_buffer[0..received_len], TACHYON_HEADER_SIZE) {
+            Some(position) => position,
+            None => return,
+        };
+        self.copy_nacked_to_map(address);
+
+        if self.config.nack_ranges == 1 && received_len > position && receive_buffer[position] == NACK_FORMAT_RANGES {
+            self.remote_nack_ranges = true;
+            let mut header = Header::default();
+            header.message_type = MESSAGE_TYPE_NACK_FORMAT;
+            header.channel = self.id;
+            header.sequence = NACK_FORMAT_RANGES as u16;
+            let mut reply = [0; TACHYON_HEADER_SIZE];
+            header.write(&mut reply);
+            socket.send_on_channel(self.id, address, &reply, TACHYON_HEADER_SIZE);
+        }
+    }
+
+    // separate nack message, nack format v2
+    pub fn process_nack_ranges_message(&mut self, address: NetworkAddress, receive_buffer: &mut [u8], received_len: usize) {
+        if self.config.nack_ranges == 1 {
+            self.remote_nack_ranges = true;
+        }
         self.nacked_sequences.clear();
-        Nack::read_varint(&mut self.nacked_sequences, &receive_buffer[..],TACHYON_HEADER_SIZE);
+        if Nack::read_ranges(&mut self.nack_ranges, &receive_buffer[0..received_len], TACHYON_HEADER_SIZE).is_none() {
+            return;
+        }
+        Nack::get_range_nacked(&self.nack_ranges, &mut self.nacked_sequences);
         self.copy_nacked_to_map(address);
     }
 
-    // nack that is in a reliable message
-    pub fn process_single_nack(&mut self, address: NetworkAddress, receive_buffer: &mut [u8]) {
+    pub fn process_nack_format_message(&mut self, sequence: u16) {
+        if self.config.nack_ranges == 1 && sequence == NACK_FORMAT_RANGES as u16 {
+            self.remote_nack_ranges = true;
+        }
+    }
+
+    // nack that is in a reliable message, receive_buffer cut to the datagram's length
+    pub fn process_single_nack(&mut self, address: NetworkAddress, receive_buffer: &[u8]) {
         self.nacked_sequences.clear();
-        Nack::read_single(&mut self.nacked_sequences, &receive_buffer[..],TACHYON_HEADER_SIZE);
+        Nack::read_single(&mut self.nacked_sequences, receive_buffer, TACHYON_HEADER_SIZE);
         self.copy_nacked_to_map(address);
     }
 
 
-    pub fn send_reliable(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+    pub fn send_reliable(&mut self, address: NetworkAddress, data: &[u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+        return self.send_reliable_vectored(address, &[IoSlice::new(&data[0..body_len])], socket);
+    }
+
+    // body is the slices concatenated, copied straight into the send buffer
+    pub fn send_reliable_vectored(&mut self, address: NetworkAddress, slices: &[IoSlice], socket: &TachyonSocket) -> TachyonSendResult {
         let mut result = TachyonSendResult::default();
+        let body_len: usize = slices.iter().map(|slice| slice.len()).sum();
+
+        let mut header = Header::default();
+        header.channel = self.id;
+        header.message_type = MESSAGE_TYPE_RELIABLE;
 
         // Optionally include nacks in outgoing messages, up to nack_redundancy times for each nack
         let mut nack_option: Option<Nack> = None;
-        let mut header_len = TACHYON_HEADER_SIZE;
-       
         if self.nack_redundancy > 0 {
-            if let Some(mut nack) = self.receiver.nack_queue.pop_front() {
-                if nack.sent_count < self.nack_redundancy  {
-                    nack.sent_count += 1;
-                    nack_option = Some(nack);
-                    header_len = TACHYON_NACKED_HEADER_SIZE;
-                }
-                self.receiver.nack_queue.push_back(nack);
+            nack_option = self.receiver.next_redundant_nack(self.nack_redundancy);
+            if let Some(nack) = nack_option {
+                header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
+                header.start_sequence = nack.start_sequence;
+                header.flags = nack.flags;
             }
         }
 
-        let send_buffer_len = body_len + header_len;
+        let header_len = header.reliable_size();
+        let timestamp_len = self.timestamp_size();
+        let send_buffer_len = body_len + header_len + timestamp_len;
+        let body_start = header_len + timestamp_len;
 
         match self.send_buffers.create_send_buffer(send_buffer_len) {
             Some(send_buffer) => {
                 let sequence = send_buffer.sequence;
-                send_buffer.byte_buffer.get_mut()[header_len..body_len + header_len].copy_from_slice(&data[0..body_len]);
+                let mut offset = body_start;
+                for slice in slices {
+                    send_buffer.byte_buffer.get_mut()[offset..offset + slice.len()].copy_from_slice(slice);
+                    offset += slice.len();
+                }
 
-                let mut header = Header::default();
-                header.channel = self.id;
-                header.sequence = sequence;
+                if let Some(latency) = &self.latency {
+                    latency.write(send_buffer.byte_buffer.get_mut(), header_len);
+                }
 
+                header.sequence = sequence;
+                if header.try_write_reliable(send_buffer.byte_buffer.get_mut()).is_err() {
+                    result.error = SEND_ERROR_LENGTH;
+                    return result;
+                }
                 if let Some(nack) = nack_option {
-                    header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
-                    header.start_sequence = nack.start_sequence;
-                    header.flags = nack.flags;
-
                     self.stats.nacks_sent += nack.nacked_count as u64;
-                } else {
-                    header.message_type = MESSAGE_TYPE_RELIABLE;
                 }
-                
-                header.write(&mut send_buffer.byte_buffer.get_mut());
 
-                let sent_len = socket.send_to(address, &send_buffer.byte_buffer.get(), send_buffer_len);
+                let sent_len = socket.send_on_channel(self.id, address, &send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
                 result.sent_len = sent_len as u32;
                 result.header = header;
 
                 self.stats.bytes_sent += sent_len as u64;
                 self.stats.sent += 1;
+                self.trace(TRACE_SENT, sequence);
 
                 return result;
             }
@@ -331,13 +906,27 @@ impl Channel {
     }
 
     pub fn update(&mut self,socket: &TachyonSocket) {
+        self.update_at(socket, self.clock.now());
+    }
+
+    pub fn update_at(&mut self, socket: &TachyonSocket, now: Instant) {
+        self.epoch_update(socket, now);
         self.send_nacks(socket);
         self.resend_nacked(socket);
 
-        // this takes way too long if there are a lot of frag groups, disabling until I find a better solution
-        //self.frag.expire_groups();
+        self.frag.expire_groups_at(now);
 
-        self.receiver.publish();
+        self.publish_at(now);
+    }
+
+    pub fn publish_at(&mut self, now: Instant) {
+        if let Some((first, last)) = self.receiver.publish_at(now) {
+            if let Some(callback) = self.gap_skipped_callback {
+                unsafe {
+                    callback(self.id, self.address, first, last);
+                }
+            }
+        }
     }
 
     fn copy_nacked_to_map(&mut self, address: NetworkAddress) {
@@ -349,14 +938,41 @@ impl Channel {
 
     // Resend messages for nacks sent to us. We accumulate these into a hashmap of unique sequence/address pairs
     // and then do the resends all at once when update() is run.
-    fn resend_nacked(&mut self, socket: &TachyonSocket) {
+    pub fn resend_nacked(&mut self, socket: &TachyonSocket) {
 
         if self.nacked_sequence_map.len() == 0 {
             return;
         }
 
+        // abandoned messages get a none like anything else no longer in the window, so the receiver moves past them
+        let now = self.clock.now();
+        let max_resends = self.config.max_resends;
         for (sequence, address) in &self.nacked_sequence_map {
             self.stats.nacks_received += 1;
+            if let Some(trace) = &mut self.trace {
+                trace.record(TRACE_NACK_RECEIVED, *sequence, now);
+            }
+            let (expendable, expired, exhausted) = match self.send_buffers.get_send_buffer(*sequence) {
+                Some(send_buffer) => (send_buffer.expendable, send_buffer.is_abandoned(now), max_resends > 0 && send_buffer.resends >= max_resends),
+                None => (false, false, false),
+            };
+            if expendable {
+                self.send_buffers.remove(*sequence);
+                self.stats.expendable_nacked += 1;
+            } else if expired || exhausted {
+                self.send_buffers.remove(*sequence);
+                if expired {
+                    self.stats.abandoned += 1;
+                } else {
+                    self.stats.resends_exhausted += 1;
+                }
+                if let Some(callback) = self.abandoned_callback {
+                    unsafe {
+                        callback(self.id, *address, *sequence);
+                    }
+                }
+            }
+
             match self.send_buffers.get_send_buffer(*sequence) {
                 Some(send_buffer) => {
 
@@ -366,19 +982,33 @@ impl Channel {
 
                     // rewrite to MESSAGE_TYPE_RELIABLE.
                     if message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
-                        let send_len = Channel::rewrite_reliable_nack_to_reliable(&mut self.resend_rewrite_buffer,&send_buffer.byte_buffer.get());
-                        
-                        socket.send_to(*address, &self.resend_rewrite_buffer, send_len);
+                        let nacked = &send_buffer.byte_buffer.get()[..send_buffer.byte_buffer.length];
+                        let send_len = match Channel::rewrite_reliable_nack_to_reliable(&mut self.resend_rewrite_buffer, nacked) {
+                            Ok(send_len) => send_len,
+                            // grows to the largest message resent so far
+                            Err(too_small) => {
+                                self.resend_rewrite_buffer.resize(too_small.required, 0);
+                                Channel::rewrite_reliable_nack_to_reliable(&mut self.resend_rewrite_buffer, nacked).unwrap_or(0)
+                            }
+                        };
+                        socket.send_on_channel(self.id, *address, &self.resend_rewrite_buffer, send_len);
                     } else {
-                        socket.send_to(*address, &send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+                        socket.send_on_channel(self.id, *address, &send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+                    }
+                    send_buffer.resends += 1;
+                    if let Some(trace) = &mut self.trace {
+                        trace.record(TRACE_RESENT, *sequence, now);
                     }
                     
                     self.stats.resent += 1;
                 }
                 None => {
-                    Channel::create_none(*sequence, self.id);
-                    let _sent_len = socket.send_to(*address,unsafe { &NONE_SEND_DATA },TACHYON_HEADER_SIZE);
+                    let none = Channel::create_none(*sequence, self.id);
+                    let _sent_len = socket.send_on_channel(self.id, *address, &none, TACHYON_HEADER_SIZE);
                     self.stats.nones_sent += 1;
+                    if let Some(trace) = &mut self.trace {
+                        trace.record(TRACE_NONE_SENT, *sequence, now);
+                    }
                 }
             }
         }
@@ -386,35 +1016,84 @@ impl Channel {
     }
 
     // Send nacks for sequences we are missing
-    fn send_nacks(&mut self, socket: &TachyonSocket) {
+    pub fn send_nacks(&mut self, socket: &TachyonSocket) {
         
         let nack_count = self.receiver.create_nacks();
-        if self.receiver.nack_list.len() == 0 {
+        if self.receiver.nack_list().is_empty() {
             return;
         }
 
-        let mut header = Header::default();
-        header.message_type = MESSAGE_TYPE_NACK;
-        header.channel = self.id;
-        header.write(&mut self.nack_send_data);
+        // ranges only when they come out smaller
+        let varint_size = TACHYON_HEADER_SIZE + Nack::varint_size(self.receiver.nack_list());
+        let mut ranges_size = usize::MAX;
+        if self.remote_nack_ranges {
+            Nack::to_ranges(self.receiver.nack_list(), &mut self.nack_ranges);
+            ranges_size = TACHYON_HEADER_SIZE + Nack::ranges_size(&self.nack_ranges);
+        }
+
+        let use_ranges = ranges_size < varint_size;
+        if use_ranges {
+            self.stats.nack_bytes_saved += (varint_size - ranges_size) as u64;
+            self.stats.nack_range_messages += 1;
+        }
+        let offer_ranges = !use_ranges && self.config.nack_ranges == 1 && !self.remote_nack_ranges;
 
-        let position = Nack::write_varint(&self.receiver.nack_list, &mut self.nack_send_data, TACHYON_HEADER_SIZE as u64);
-        socket.send_to(self.address, &self.nack_send_data, position as usize);
+        // large windows nack more, nack_send_data grows to the largest nack message so far
+        let position = loop {
+            match self.write_nack_message(use_ranges, offer_ranges) {
+                Ok(position) => break position,
+                Err(too_small) => self.nack_send_data.resize(too_small.required, 0),
+            }
+        };
+        socket.send_on_channel(self.id, self.address, &self.nack_send_data, position);
+        self.stats.nack_bytes_sent += position as u64;
+
+        if let Some(trace) = &mut self.trace {
+            let now = self.clock.now();
+            let mut sequences: Vec<u16> = Vec::new();
+            for nack in self.receiver.nack_list() {
+                nack.get_nacked(&mut sequences);
+            }
+            for sequence in sequences {
+                trace.record(TRACE_NACK_SENT, sequence, now);
+            }
+        }
 
         self.stats.nacks_sent += nack_count as u64;
     }
 
-    pub fn rewrite_reliable_nack_to_reliable(rewrite_buffer: &mut [u8], send_buffer: &[u8]) -> usize {
+    // the nack message the nacks from the receiver's last create_nacks make, returns its length
+    fn write_nack_message(&mut self, use_ranges: bool, offer_ranges: bool) -> Result<usize, BufferTooSmall> {
+        let mut header = Header::default();
+        header.channel = self.id;
+        if use_ranges {
+            header.message_type = MESSAGE_TYPE_NACK_RANGES;
+            header.try_write(&mut self.nack_send_data)?;
+            return Nack::try_write_ranges(&self.nack_ranges, &mut self.nack_send_data, TACHYON_HEADER_SIZE);
+        }
+
+        header.message_type = MESSAGE_TYPE_NACK;
+        header.try_write(&mut self.nack_send_data)?;
+        let position = Nack::try_write_varint(self.receiver.nack_list(), &mut self.nack_send_data, TACHYON_HEADER_SIZE)?;
+        if offer_ranges {
+            BufferTooSmall::check(position + 1, &self.nack_send_data)?;
+            self.nack_send_data[position] = NACK_FORMAT_RANGES;
+            return Ok(position + 1);
+        }
+        return Ok(position);
+    }
+
+    // returns the length written to rewrite_buffer, or how long it needed to be
+    pub fn rewrite_reliable_nack_to_reliable(rewrite_buffer: &mut [u8], send_buffer: &[u8]) -> Result<usize, BufferTooSmall> {
         let mut header = Header::read(send_buffer);
         let src_body = TACHYON_NACKED_HEADER_SIZE..send_buffer.len();
-        let src_body_len = src_body.len();
-        let dest = TACHYON_HEADER_SIZE..(TACHYON_HEADER_SIZE + src_body_len);
-        rewrite_buffer[dest].copy_from_slice(&send_buffer[src_body]);
+        let send_len = TACHYON_HEADER_SIZE + src_body.len();
+        BufferTooSmall::check(send_len, rewrite_buffer)?;
+        rewrite_buffer[TACHYON_HEADER_SIZE..send_len].copy_from_slice(&send_buffer[src_body]);
 
         header.message_type = MESSAGE_TYPE_RELIABLE;
-        header.write(rewrite_buffer);
-
-        return TACHYON_HEADER_SIZE + src_body_len;
+        header.try_write(rewrite_buffer)?;
+        return Ok(send_len);
     }
 }
 
@@ -422,10 +1101,168 @@ impl Channel {
 #[cfg(test)]
 mod tests {
 
-    use crate::tachyon::{header::{Header, MESSAGE_TYPE_RELIABLE_WITH_NACK,  MESSAGE_TYPE_RELIABLE}, network_address::NetworkAddress, channel::ChannelConfig};
+    use std::{thread, time::Duration};
+
+    use crate::{header::{Header, MESSAGE_TYPE_NONE, MESSAGE_TYPE_RELIABLE_WITH_NACK,  MESSAGE_TYPE_RELIABLE}, network_address::NetworkAddress, channel::ChannelConfig};
+    use crate::tachyon_test::TachyonTest;
 
     use super::Channel;
 
+    #[test]
+    fn test_create_none() {
+        let handles: Vec<_> = (1..5u16).map(|channel_id| {
+            thread::spawn(move || {
+                for sequence in 0..1000u16 {
+                    let header = Header::read(&Channel::create_none(sequence, channel_id));
+                    assert_eq!((MESSAGE_TYPE_NONE, channel_id, sequence), (header.message_type, header.channel, header.sequence));
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_adapt_nack_redundancy() {
+        let mut config = ChannelConfig::default_ordered();
+        let mut channel = Channel::create(3, NetworkAddress::default(), config);
+        channel.adapt_nack_redundancy(50.0);
+        assert_eq!(1, channel.nack_redundancy);
+
+        config.nack_redundancy_max = 3;
+        let mut channel = Channel::create(3, NetworkAddress::default(), config);
+        for _ in 0..4 {
+            channel.adapt_nack_redundancy(10.0);
+        }
+        assert_eq!(3, channel.nack_redundancy);
+        // between the thresholds it holds
+        channel.adapt_nack_redundancy(2.0);
+        assert_eq!(3, channel.nack_redundancy);
+        for _ in 0..4 {
+            channel.adapt_nack_redundancy(0.0);
+        }
+        assert_eq!(1, channel.nack_redundancy);
+        channel.update_stats();
+        assert_eq!(1, channel.stats.nack_redundancy);
+    }
+
+    // a burst lost on the way to the client, the client's nacks, then everything received
+    fn lose_burst(test: &mut TachyonTest, network: &crate::loopback::LoopbackNetwork, count: usize) {
+        let client_address = NetworkAddress::localhost(49152);
+        for _ in 0..count {
+            test.server_send_reliable(3, 8);
+            assert!(network.drop_next(client_address));
+        }
+        test.server_send_reliable(3, 8);
+        assert_eq!(0, test.client_receive().length);
+        test.client.step(Duration::from_millis(10));
+        test.server_receive();
+        test.server.step(Duration::from_millis(10));
+        // resends come newest first, a receive call can run out of budget before the oldest gets through
+        let mut received = 0;
+        loop {
+            if test.client_receive().length > 0 {
+                received += 1;
+            } else if network.pending(client_address) == 0 {
+                break;
+            }
+        }
+        assert_eq!(count + 1, received);
+    }
+
+    #[test]
+    fn test_nack_ranges() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.nack_ranges = 1;
+        test.server.configure_channel(3, config);
+        test.client.configure_channel(3, config);
+        let network = test.connect_loopback();
+        test.client_send_reliable(3, 8);
+        test.server_receive();
+
+        // the first nack offers ranges, the server says it reads them
+        lose_burst(&mut test, &network, 40);
+        let address = test.remote_client();
+        assert!(test.server.get_channel(address, 3).unwrap().remote_nack_ranges);
+        assert!(test.client.get_channel(NetworkAddress::default(), 3).unwrap().remote_nack_ranges);
+        assert_eq!(0, test.client.get_channel(NetworkAddress::default(), 3).unwrap().stats.nack_range_messages);
+
+        lose_burst(&mut test, &network, 40);
+        let stats = test.client.get_channel(NetworkAddress::default(), 3).unwrap().stats;
+        assert_eq!(1, stats.nack_range_messages);
+        assert!(stats.nack_bytes_saved > 0);
+        assert_eq!(80, test.server.get_channel(address, 3).unwrap().stats.resent);
+
+        // only one end offering keeps to the varint format
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, config);
+        let network = test.connect_loopback();
+        test.client_send_reliable(3, 8);
+        test.server_receive();
+        lose_burst(&mut test, &network, 40);
+        lose_burst(&mut test, &network, 40);
+        let channel = test.client.get_channel(NetworkAddress::default(), 3).unwrap();
+        assert!(!channel.remote_nack_ranges);
+        assert_eq!(0, channel.stats.nack_range_messages);
+    }
+
+    #[test]
+    fn test_large_window() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.receive_window_size = 4096;
+        config.send_window_size = 8192;
+        assert!(test.server.configure_channel(3, config));
+        assert!(test.client.configure_channel(3, config));
+        let network = test.connect_loopback();
+        test.client_send_reliable(3, 8);
+        test.server_receive();
+
+        // far more than the default window lost in one go
+        lose_burst(&mut test, &network, 3000);
+        let address = test.remote_client();
+        assert_eq!(3000, test.server.get_channel(address, 3).unwrap().stats.resent);
+        assert_eq!(3000, test.client.get_channel(NetworkAddress::default(), 3).unwrap().stats.nacks_sent);
+    }
+
+    #[test]
+    fn test_reliable_carries_nack() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        let network = test.connect_loopback();
+        test.client_send_reliable(3, 8);
+        test.server_receive();
+
+        let client_address = NetworkAddress::localhost(49152);
+        test.server_send_reliable(3, 8);
+        assert!(network.drop_next(client_address));
+        test.server_send_reliable(3, 8);
+        // ordered, held until the lost one comes
+        assert_eq!(0, test.client_receive().length);
+
+        // lose the nack message, the next reliable message carries the nack
+        test.client.step(Duration::from_millis(10));
+        assert_eq!(1, network.pending(test.address));
+        assert!(network.drop_next(test.address));
+        let result = test.client_send_reliable(3, 8);
+        assert_eq!(MESSAGE_TYPE_RELIABLE_WITH_NACK, result.header.message_type);
+
+        let channel = test.client.get_channel(NetworkAddress::default(), 3).unwrap();
+        let send_buffer = channel.send_buffers.get_send_buffer(result.header.sequence).unwrap();
+        let header = Header::read_nacked(send_buffer.byte_buffer.get());
+        assert_eq!(result.header.start_sequence, header.start_sequence);
+        assert_eq!(result.header.flags, header.flags);
+
+        assert_eq!(8, test.server_receive().length);
+        test.server.step(Duration::from_millis(10));
+        let address = test.remote_client();
+        assert_eq!(1, test.server.get_channel(address, 3).unwrap().stats.resent);
+        assert_eq!(8, test.client_receive().length);
+    }
 
     #[test]
     fn test_rewrite_nack_to_reliable() {
@@ -438,10 +1275,16 @@ mod tests {
         header.sequence = 200;
         header.start_sequence = 12345;
         header.flags = 99;
-        header.write(&mut send_buffer);
+        header.write_nacked(&mut send_buffer);
         send_buffer[10] = 3;
         send_buffer[1199] = 7;
-        let send_len = Channel::rewrite_reliable_nack_to_reliable(&mut channel.resend_rewrite_buffer,&send_buffer);
+
+        // starts empty, the error says how far to grow it
+        let too_small = Channel::rewrite_reliable_nack_to_reliable(&mut channel.resend_rewrite_buffer, &send_buffer).unwrap_err();
+        assert_eq!(1200 - 6, too_small.required);
+        channel.resend_rewrite_buffer.resize(too_small.required, 0);
+
+        let send_len = Channel::rewrite_reliable_nack_to_reliable(&mut channel.resend_rewrite_buffer, &send_buffer).unwrap();
         assert_eq!(1200 - 6, send_len);
         assert_eq!(3, channel.resend_rewrite_buffer[4]);
         assert_eq!(7, channel.resend_rewrite_buffer[1199 - 6]);
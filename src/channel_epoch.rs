@@ -0,0 +1,169 @@
+use std::time::Instant;
+
+use super::{
+    channel::Channel,
+    header::{ChannelEpochHeader, MESSAGE_TYPE_CHANNEL_EPOCH, MESSAGE_TYPE_CHANNEL_EPOCH_ACK, TACHYON_CHANNEL_EPOCH_HEADER_SIZE},
+    tachyon_socket::TachyonSocket,
+};
+
+const EPOCH_SEND_INTERVAL: u128 = 300;
+
+// Per channel epoch handshake, enabled with TachyonConfig.use_channel_epochs.
+//
+// Sequences live only as long as the process, so when one end restarts it starts over at 0 while the other end's
+// receiver is somewhere far ahead and drops everything as old until sequences catch up.  With epochs each channel
+// picks a random epoch when it's created and sends it until the other end acks it.  An end that sees the other's
+// epoch change knows it restarted and resets its own sequences to match.  Until its epoch is acked a channel's sends
+// fail with SEND_ERROR_NOT_CONNECTED, and until it knows the other end's epoch it drops everything but epoch messages,
+// which keeps whatever the other end sent to our previous incarnation out of a fresh receiver.
+pub struct ChannelEpoch {
+    pub local: u32,
+    // 0 until the other end's first epoch message
+    pub remote: u32,
+    // the other end has our current epoch
+    pub acked: bool,
+    last_sent: Option<Instant>,
+}
+
+impl ChannelEpoch {
+    pub fn create() -> Self {
+        let epoch = ChannelEpoch {
+            local: rand::random::<u32>().max(1),
+            remote: 0,
+            acked: false,
+            last_sent: None,
+        };
+        return epoch;
+    }
+}
+
+impl Channel {
+    // true if the channel can send, always without epochs
+    pub fn is_epoch_acked(&self) -> bool {
+        match &self.epoch {
+            Some(epoch) => return epoch.acked,
+            None => return true,
+        }
+    }
+
+    // true if the channel accepts messages other than epoch messages, always without epochs
+    pub fn has_remote_epoch(&self) -> bool {
+        match &self.epoch {
+            Some(epoch) => return epoch.remote != 0,
+            None => return true,
+        }
+    }
+
+    pub fn process_epoch_message(&mut self, socket: &TachyonSocket, receive_buffer: &[u8], received_len: usize) {
+        let header = match ChannelEpochHeader::try_read(&receive_buffer[0..received_len]) {
+            Some(header) => header,
+            None => return,
+        };
+        let epoch = match &mut self.epoch {
+            Some(epoch) => epoch,
+            None => return,
+        };
+        if header.epoch == 0 {
+            return;
+        }
+
+        let restarted = epoch.remote != 0 && epoch.remote != header.epoch;
+        epoch.remote = header.epoch;
+        if header.ack_epoch == epoch.local {
+            epoch.acked = true;
+        }
+        if restarted {
+            // our epoch stays, the other end is fresh and takes it as is
+            self.reset_sequences();
+            self.stats.epoch_resets += 1;
+        }
+
+        if header.message_type == MESSAGE_TYPE_CHANNEL_EPOCH {
+            self.send_epoch_message(socket, MESSAGE_TYPE_CHANNEL_EPOCH_ACK);
+        }
+    }
+
+    // sends our epoch every EPOCH_SEND_INTERVAL until it's acked
+    pub fn epoch_update(&mut self, socket: &TachyonSocket, now: Instant) {
+        let epoch = match &mut self.epoch {
+            Some(epoch) => epoch,
+            None => return,
+        };
+        if epoch.acked {
+            return;
+        }
+        if let Some(last_sent) = epoch.last_sent {
+            if now.duration_since(last_sent).as_millis() <= EPOCH_SEND_INTERVAL {
+                return;
+            }
+        }
+        epoch.last_sent = Some(now);
+        self.send_epoch_message(socket, MESSAGE_TYPE_CHANNEL_EPOCH);
+    }
+
+    fn send_epoch_message(&self, socket: &TachyonSocket, message_type: u8) {
+        if let Some(epoch) = &self.epoch {
+            let header = ChannelEpochHeader { message_type, channel: self.id, epoch: epoch.local, ack_epoch: epoch.remote };
+            let mut send_buffer: Vec<u8> = vec![0; TACHYON_CHANNEL_EPOCH_HEADER_SIZE];
+            header.write(&mut send_buffer);
+            socket.send_on_channel(self.id, self.address, &send_buffer, send_buffer.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::{tachyon_test::TachyonTest, Tachyon, SEND_ERROR_NOT_CONNECTED};
+
+    fn handshake(test: &mut TachyonTest) {
+        test.client.step(Duration::from_millis(0));
+        test.server_receive();
+        test.client_receive();
+        test.server.step(Duration::from_millis(0));
+        test.client_receive();
+        test.server_receive();
+    }
+
+    #[test]
+    #[serial]
+    fn test_server_restart() {
+        let mut test = TachyonTest::default();
+        test.server.config.use_channel_epochs = 1;
+        test.client.config.use_channel_epochs = 1;
+        test.connect();
+
+        assert_eq!(SEND_ERROR_NOT_CONNECTED, test.client_send_reliable(1, 8).error);
+        handshake(&mut test);
+        assert!(test.client.get_channel(test.client_address, 1).unwrap().is_epoch_acked());
+        let remote = test.remote_client();
+        assert!(test.server.get_channel(remote, 1).unwrap().is_epoch_acked());
+
+        for _ in 0..3 {
+            assert_eq!(0, test.client_send_reliable(1, 8).error);
+            assert_eq!(8, test.server_receive().length);
+            assert_eq!(0, test.server_send_reliable(1, 8).error);
+            assert_eq!(8, test.client_receive().length);
+        }
+
+        // the new server's sequences start over, the client's channel would drop them as old
+        test.server = Tachyon::create(test.server.config);
+        assert!(test.server.bind(test.address));
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(SEND_ERROR_NOT_CONNECTED, test.server_send_reliable(1, 8).error);
+
+        test.server.step(Duration::from_millis(0));
+        test.client_receive();
+        test.server_receive();
+        assert_eq!(1, test.client.get_channel(test.client_address, 1).unwrap().stats.epoch_resets);
+
+        assert_eq!(0, test.server_send_reliable(1, 8).error);
+        assert_eq!(8, test.client_receive().length);
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(8, test.server_receive().length);
+    }
+}
@@ -0,0 +1,100 @@
+use super::channel::{ChannelConfig, ChannelConfigError, RESERVED_CHANNEL_MAX};
+
+// Channel ids and the ranges they fall in.  0 is the unreliable channel, 1 and 2 the built in ordered/unordered
+// channels, configure_channel takes the application range above that.
+//
+// Headers carry a u8 channel.  Ids past WIRE_CHANNEL_MAX are wide channels, only configurable with
+// TachyonConfig.wide_channels, their messages go behind a wide channel header carrying the high byte (see header.rs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub struct ChannelId(pub u16);
+
+pub const WIRE_CHANNEL_MAX: u16 = u8::MAX as u16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelIdRange {
+    Unreliable,
+    Builtin,
+    Application,
+    // above WIRE_CHANNEL_MAX, needs TachyonConfig.wide_channels
+    Wide,
+}
+
+impl ChannelId {
+    pub const UNRELIABLE: ChannelId = ChannelId(0);
+    pub const ORDERED: ChannelId = ChannelId(1);
+    pub const UNORDERED: ChannelId = ChannelId(2);
+    pub const APPLICATION_MIN: ChannelId = ChannelId(RESERVED_CHANNEL_MAX as u16 + 1);
+
+    pub fn range(&self) -> ChannelIdRange {
+        if self.0 == 0 {
+            return ChannelIdRange::Unreliable;
+        }
+        if self.0 <= RESERVED_CHANNEL_MAX as u16 {
+            return ChannelIdRange::Builtin;
+        }
+        if self.0 <= WIRE_CHANNEL_MAX {
+            return ChannelIdRange::Application;
+        }
+        return ChannelIdRange::Wide;
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        return self.0 <= RESERVED_CHANNEL_MAX as u16;
+    }
+
+    pub fn is_wide(&self) -> bool {
+        return self.0 > WIRE_CHANNEL_MAX;
+    }
+
+    // Ok with the id if configure_channel would take it, wide_channels is TachyonConfig.wide_channels
+    pub fn check_configurable(&self, wide_channels: bool) -> Result<u16, ChannelConfigError> {
+        match self.range() {
+            ChannelIdRange::Application => return Ok(self.0),
+            ChannelIdRange::Wide if wide_channels => return Ok(self.0),
+            ChannelIdRange::Wide => return Err(ChannelConfigError::IdOutOfRange),
+            _ => return Err(ChannelConfigError::ReservedId),
+        }
+    }
+}
+
+impl From<u8> for ChannelId {
+    fn from(id: u8) -> Self {
+        return ChannelId(id as u16);
+    }
+}
+
+impl std::fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// a configured channel as listed by Tachyon::configured_channels
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ConfiguredChannel {
+    pub id: ChannelId,
+    pub config: ChannelConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelId, ChannelIdRange};
+    use crate::channel::ChannelConfigError;
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(ChannelIdRange::Unreliable, ChannelId::UNRELIABLE.range());
+        assert_eq!(ChannelIdRange::Builtin, ChannelId::UNORDERED.range());
+        assert_eq!(ChannelIdRange::Application, ChannelId(255).range());
+        assert_eq!(ChannelIdRange::Wide, ChannelId(256).range());
+        assert!(!ChannelId(255).is_wide());
+
+        assert_eq!(Err(ChannelConfigError::ReservedId), ChannelId(1).check_configurable(true));
+        assert_eq!(Ok(3), ChannelId::APPLICATION_MIN.check_configurable(false));
+        assert_eq!(Err(ChannelConfigError::IdOutOfRange), ChannelId(300).check_configurable(false));
+        assert_eq!(Ok(300), ChannelId(300).check_configurable(true));
+        assert_eq!(ChannelId(7), ChannelId::from(7u8));
+    }
+}
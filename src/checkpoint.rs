@@ -0,0 +1,355 @@
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use super::{
+    byte_buffer_pool::{ByteBuffer, ByteBufferPool},
+    channel::Channel,
+    connection::{Connection, Identity},
+    connection_state::ConnectionState,
+    network_address::NetworkAddress,
+    send_buffer_manager::SendBuffer,
+    Tachyon,
+};
+
+const STATE_MAGIC: [u8; 4] = *b"TSTA";
+// version 1 had u8 channel ids, still loaded
+const STATE_VERSION: u8 = 2;
+
+// Connection state checkpoint, little endian:
+// header: magic "TSTA", version u8
+// identities: count u32, then id u32, session_id u32
+// connections: count u32, then address, identity id u32, session_id u32, linked u32
+// channels: count u32, then address, channel id u16,
+//   send: current_sequence u16, buffer count u32, then sequence u16, age millis u32, length u32, data
+//   receive: current_sequence u16, last_sequence u16,
+//     received count u32, then slot u16
+//     buffered count u32, then slot u16, length u32, data
+//     published count u32, then length u32, data
+// address is a, b, c, d u16 and port u32.
+//
+// Fragment groups being reassembled, send ttls, unreliable channel sequences and stats are not saved.  Partial fragment groups
+// just get dropped, the same as when they expire.
+impl Tachyon {
+    pub fn save_state<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let now = self.now();
+        writer.write_all(&STATE_MAGIC)?;
+        writer.write_all(&[STATE_VERSION])?;
+
+        write_u32(writer, self.identities.len() as u32)?;
+        for (id, session_id) in &self.identities {
+            write_u32(writer, *id)?;
+            write_u32(writer, *session_id)?;
+        }
+
+        write_u32(writer, self.connections.len() as u32)?;
+        for connection in self.connections.values() {
+            write_address(writer, connection.address)?;
+            write_u32(writer, connection.identity.id)?;
+            write_u32(writer, connection.identity.session_id)?;
+            write_u32(writer, connection.identity.linked)?;
+        }
+
+        write_u32(writer, self.channels.len() as u32)?;
+        for ((address, channel_id), channel) in &self.channels {
+            write_address(writer, *address)?;
+            write_u16(writer, *channel_id)?;
+
+            let send_buffers = &channel.send_buffers;
+            write_u16(writer, send_buffers.current_sequence)?;
+            let pending: Vec<&SendBuffer> = send_buffers.buffers.values.iter().flatten().collect();
+            write_u32(writer, pending.len() as u32)?;
+            for send_buffer in pending {
+                let age = now.saturating_duration_since(send_buffer.created_at).as_millis();
+                write_u16(writer, send_buffer.sequence)?;
+                write_u32(writer, std::cmp::min(age, u32::MAX as u128) as u32)?;
+                write_bytes(writer, &send_buffer.byte_buffer.get()[0..send_buffer.byte_buffer.length])?;
+            }
+
+            let receiver = &channel.receiver;
+            write_u16(writer, receiver.current_sequence())?;
+            write_u16(writer, receiver.last_sequence())?;
+
+            let received: Vec<usize> = (0..receiver.received.values.len()).filter(|index| receiver.received.values[*index].is_some()).collect();
+            write_u32(writer, received.len() as u32)?;
+            for index in received {
+                write_u16(writer, index as u16)?;
+            }
+
+            let buffered: Vec<usize> = (0..receiver.buffered.values.len()).filter(|index| receiver.buffered.values[*index].is_some()).collect();
+            write_u32(writer, buffered.len() as u32)?;
+            for index in buffered {
+                if let Some(byte_buffer) = receiver.buffered.get_at_index(index) {
+                    write_u16(writer, index as u16)?;
+                    write_bytes(writer, &byte_buffer.get()[0..byte_buffer.length])?;
+                }
+            }
+
+            write_u32(writer, receiver.published.len() as u32)?;
+            for byte_buffer in &receiver.published {
+                write_bytes(writer, &byte_buffer.get()[0..byte_buffer.length])?;
+            }
+        }
+        return Ok(());
+    }
+
+    // Restores into a bound instance with the same channel configuration as the one that saved.  Connections are recreated
+    // as if just heard from, channels that aren't configured here are skipped.  Returns the number of connections restored.
+    pub fn load_state<R: Read>(&mut self, reader: &mut R) -> io::Result<u32> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        let version = header[4];
+        if header[0..4] != STATE_MAGIC || version == 0 || version > STATE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a tachyon state checkpoint"));
+        }
+        let now = self.now();
+
+        let identity_count = read_u32(reader)?;
+        for _ in 0..identity_count {
+            let id = read_u32(reader)?;
+            let session_id = read_u32(reader)?;
+            self.identities.insert(id, session_id);
+        }
+
+        let connection_count = read_u32(reader)?;
+        for _ in 0..connection_count {
+            let address = read_address(reader)?;
+            let identity = Identity {
+                id: read_u32(reader)?,
+                session_id: read_u32(reader)?,
+                linked: read_u32(reader)?,
+            };
+
+            let mut connection = Connection::create(address, self.id);
+            connection.identity = identity;
+            connection.received_at = self.time_since_start();
+            connection.state = ConnectionState::Established;
+            self.insert_connection(connection);
+            if identity.is_linked() {
+                self.identity_to_address_map.insert(identity.id, address);
+            }
+            if self.config.use_channel_epochs == 1 {
+                self.create_configured_channels(address);
+            }
+        }
+        self.identity_addresses.publish(&self.identity_to_address_map);
+
+        let channel_count = read_u32(reader)?;
+        for _ in 0..channel_count {
+            let address = read_address(reader)?;
+            let channel_id = if version == 1 {
+                let mut channel_id = [0u8; 1];
+                reader.read_exact(&mut channel_id)?;
+                channel_id[0] as u16
+            } else {
+                read_u16(reader)?
+            };
+
+            // read it all regardless, the reader has to move past channels we skip
+            let mut restored = ChannelState::read(reader)?;
+            if let Some(channel) = self.channel_mut(address, channel_id) {
+                restored.apply(channel, now);
+            }
+        }
+
+        return Ok(connection_count);
+    }
+}
+
+struct ChannelState {
+    send_sequence: u16,
+    send_buffers: Vec<(u16, u32, Vec<u8>)>,
+    current_sequence: u16,
+    last_sequence: u16,
+    received: Vec<u16>,
+    buffered: Vec<(u16, Vec<u8>)>,
+    published: Vec<Vec<u8>>,
+}
+
+impl ChannelState {
+    fn read<R: Read>(reader: &mut R) -> io::Result<ChannelState> {
+        let send_sequence = read_u16(reader)?;
+        let mut send_buffers = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let sequence = read_u16(reader)?;
+            let age = read_u32(reader)?;
+            send_buffers.push((sequence, age, read_bytes(reader)?));
+        }
+
+        let current_sequence = read_u16(reader)?;
+        let last_sequence = read_u16(reader)?;
+        let mut received = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            received.push(read_u16(reader)?);
+        }
+        let mut buffered = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let index = read_u16(reader)?;
+            buffered.push((index, read_bytes(reader)?));
+        }
+        let mut published = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            published.push(read_bytes(reader)?);
+        }
+
+        let state = ChannelState {
+            send_sequence,
+            send_buffers,
+            current_sequence,
+            last_sequence,
+            received,
+            buffered,
+            published,
+        };
+        return Ok(state);
+    }
+
+    fn apply(&mut self, channel: &mut Channel, now: std::time::Instant) {
+        let send_buffers = &mut channel.send_buffers;
+        send_buffers.current_sequence = self.send_sequence;
+        for (sequence, age, data) in self.send_buffers.drain(..) {
+            let send_buffer = SendBuffer {
+                sequence,
+                byte_buffer: ChannelState::to_byte_buffer(&mut send_buffers.buffer_pool, &data),
+                created_at: now.checked_sub(Duration::from_millis(age as u64)).unwrap_or(now),
+                expires_at: None,
+                resends: 0,
+                expendable: false,
+            };
+            send_buffers.buffers.insert(sequence, send_buffer);
+        }
+
+        let receiver = &mut channel.receiver;
+        receiver.restore_sequences(self.current_sequence, self.last_sequence);
+        if self.received.len() > 0 || self.buffered.len() > 0 {
+            receiver.received.allocate();
+            receiver.buffered.allocate();
+        }
+        for index in &self.received {
+            if let Some(slot) = receiver.received.values.get_mut(*index as usize) {
+                *slot = Some(true);
+            }
+        }
+        for (index, data) in &self.buffered {
+            let byte_buffer = ChannelState::to_byte_buffer(&mut receiver.buffer_pool, data);
+            if let Some(slot) = receiver.buffered.values.get_mut(*index as usize) {
+                *slot = Some(byte_buffer);
+            }
+        }
+        for data in &self.published {
+            let byte_buffer = ChannelState::to_byte_buffer(&mut receiver.buffer_pool, data);
+            receiver.restore_published(byte_buffer);
+        }
+    }
+
+    fn to_byte_buffer(pool: &mut ByteBufferPool, data: &[u8]) -> ByteBuffer {
+        let mut byte_buffer = pool.get_buffer(data.len());
+        byte_buffer.get_mut()[0..data.len()].copy_from_slice(data);
+        return byte_buffer;
+    }
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    return writer.write_all(&value.to_le_bytes());
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    return writer.write_all(&value.to_le_bytes());
+}
+
+fn write_bytes<W: Write>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    write_u32(writer, data.len() as u32)?;
+    return writer.write_all(data);
+}
+
+fn write_address<W: Write>(writer: &mut W, address: NetworkAddress) -> io::Result<()> {
+    for part in [address.a, address.b, address.c, address.d] {
+        write_u16(writer, part)?;
+    }
+    return write_u32(writer, address.port);
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    return Ok(u16::from_le_bytes(bytes));
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    return Ok(u32::from_le_bytes(bytes));
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let length = read_u32(reader)? as usize;
+    let mut data: Vec<u8> = vec![0; length];
+    reader.read_exact(&mut data)?;
+    return Ok(data);
+}
+
+fn read_address<R: Read>(reader: &mut R) -> io::Result<NetworkAddress> {
+    let address = NetworkAddress {
+        a: read_u16(reader)?,
+        b: read_u16(reader)?,
+        c: read_u16(reader)?,
+        d: read_u16(reader)?,
+        port: read_u32(reader)?,
+    };
+    return Ok(address);
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{channel::ChannelConfig, tachyon_test::TachyonTest, Tachyon};
+
+    #[test]
+    #[serial]
+    fn test_save_load() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.set_identity(5, 50);
+        test.connect();
+
+        test.client_send_reliable(3, 8);
+        assert_eq!(8, test.server_receive().length);
+        test.client_send_reliable(3, 8);
+        assert_eq!(8, test.server_receive().length);
+        test.server_send_reliable(3, 4);
+
+        let mut state: Vec<u8> = Vec::new();
+        test.server.save_state(&mut state).unwrap();
+
+        // restart the server on the same port
+        test.server = Tachyon::create(test.config);
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(test.server.bind(test.address));
+        assert_eq!(1, test.server.load_state(&mut &state[..]).unwrap());
+        assert_eq!(Some(&50), test.server.identities.get(&5));
+        assert_eq!(1, test.server.connections.len());
+
+        // sequences carry on where they left off, a fresh ordered receiver would hold this waiting on 1 and 2
+        test.client_send_reliable(3, 16);
+        let res = test.server_receive();
+        assert_eq!(16, res.length);
+        assert_eq!(3, res.channel);
+
+        let client_address = test.server.connections.keys().next().copied().unwrap();
+        let channel = test.server.get_channel(client_address, 3).unwrap();
+        assert_eq!(1, channel.send_buffers.current_sequence);
+        assert!(channel.send_buffers.buffers.is_some(1));
+        assert_eq!(3, channel.receiver.current_sequence());
+
+        let mut server = Tachyon::create(test.config);
+        assert!(server.load_state(&mut &b"TCAP\x01"[..]).is_err());
+        // version 1, u8 channel ids, nothing in it
+        let empty: Vec<u8> = [&b"TSTA\x01"[..], &[0; 12]].concat();
+        assert_eq!(0, server.load_state(&mut &empty[..]).unwrap());
+        let newer: Vec<u8> = [&b"TSTA\x03"[..], &[0; 12]].concat();
+        assert!(server.load_state(&mut &newer[..]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+// CRC32C (Castagnoli), what TachyonConfig.use_checksum puts in front of every datagram.  Table driven, a byte at a time.
+
+const CRC32C_POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ CRC32C_POLYNOMIAL;
+            } else {
+                crc >>= 1;
+            }
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    return table;
+}
+
+static CRC32C_TABLE: [u32; 256] = make_table();
+
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = CRC32C_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    return !crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc32c;
+
+    #[test]
+    fn test_crc32c() {
+        // the standard check value
+        assert_eq!(0xE306_9283, crc32c(b"123456789"));
+        assert_eq!(0, crc32c(&[]));
+        assert_ne!(crc32c(&[1, 2, 3]), crc32c(&[1, 2, 4]));
+    }
+}
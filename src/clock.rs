@@ -0,0 +1,78 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::time::{Duration, Instant};
+
+// Time source for every timeout in the protocol (fragment and send buffer expiry, ordered gaps, identity retry, quality).
+// Tachyon shares one clock with all of its channels, see Tachyon::set_clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[cfg(feature = "std")]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        return Instant::now();
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn system_clock() -> Arc<dyn Clock> {
+    return Arc::new(SystemClock);
+}
+
+// What send buffers and fragmentation start with.  Without std there is no system time, it's a MockClock at the start
+// until the application hands in its own.
+pub fn default_clock() -> Arc<dyn Clock> {
+    #[cfg(feature = "std")]
+    return system_clock();
+    #[cfg(not(feature = "std"))]
+    return Arc::new(MockClock::create(Instant::from_start(Duration::ZERO)));
+}
+
+// Only moves when advanced.  Shared through an Arc so tests can keep a handle while Tachyon uses it.
+pub struct MockClock {
+    start: Instant,
+    elapsed_micros: AtomicU64,
+}
+
+impl MockClock {
+    pub fn create(start: Instant) -> Self {
+        let clock = MockClock {
+            start,
+            elapsed_micros: AtomicU64::new(0),
+        };
+        return clock;
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.elapsed_micros.fetch_add(dt.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        return self.start + Duration::from_micros(self.elapsed_micros.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Clock, MockClock};
+
+    #[test]
+    fn test_mock_clock() {
+        let start = Instant::now();
+        let clock = MockClock::create(start);
+        assert!(start == clock.now());
+
+        clock.advance(Duration::from_millis(1500));
+        clock.advance(Duration::from_micros(5));
+        assert_eq!(1_500_005, clock.now().duration_since(start).as_micros());
+    }
+}
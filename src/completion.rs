@@ -0,0 +1,82 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+// Counts down the workers of a Pool::receive, the main thread waits on it in finish_receive.
+pub struct Completion {
+    remaining: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Completion {
+    pub fn create(count: usize) -> Self {
+        let completion = Completion {
+            remaining: Mutex::new(count),
+            done: Condvar::new(),
+        };
+        return completion;
+    }
+
+    // called by each worker when it's done
+    pub fn complete(&self) {
+        let mut remaining = self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+        *remaining = remaining.saturating_sub(1);
+        if *remaining == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        return *self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+    }
+
+    pub fn try_wait(&self) -> bool {
+        return self.remaining() == 0;
+    }
+
+    pub fn wait(&self) {
+        let mut remaining = self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+        while *remaining > 0 {
+            remaining = self.done.wait(remaining).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    // false if there are still workers running after timeout
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut remaining = self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+        while *remaining > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            remaining = self.done.wait_timeout(remaining, deadline - now).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::Completion;
+
+    #[test]
+    fn test_completion() {
+        let completion = Arc::new(Completion::create(2));
+        assert!(!completion.try_wait());
+        assert!(!completion.wait_timeout(Duration::from_millis(1)));
+
+        completion.complete();
+        assert_eq!(1, completion.remaining());
+
+        let worker = completion.clone();
+        let handle = thread::spawn(move || worker.complete());
+        assert!(completion.wait_timeout(Duration::from_secs(5)));
+        assert!(completion.try_wait());
+        completion.wait();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use super::{address_filter::AddressRange, network_address::NetworkAddress, security::SECURITY_CONNECT_THROTTLED, Tachyon};
+
+const DEFAULT_PREFIX_BITS: u8 = 24;
+const THROTTLE_WINDOW_MILLIS: u128 = 1000;
+
+// Limits on the connections a server without identities or challenges creates for new addresses, where any datagram
+// from an address it doesn't know makes one.  Datagrams over a limit are dropped before the connection exists.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct ConnectLimitConfig {
+    // 0 for no limit.  Both allow bursts of up to one second's worth.
+    pub per_second: u32,
+    // per range of addresses sharing their first prefix_bits bits
+    pub per_prefix_per_second: u32,
+    // 0 for 24, over 32 is taken as 32
+    pub prefix_bits: u32,
+}
+
+impl ConnectLimitConfig {
+    pub fn is_enabled(&self) -> bool {
+        return self.per_second > 0 || self.per_prefix_per_second > 0;
+    }
+
+    pub fn prefix(&self) -> u8 {
+        if self.prefix_bits == 0 {
+            return DEFAULT_PREFIX_BITS;
+        }
+        return self.prefix_bits.min(32) as u8;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ConnectBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl ConnectBucket {
+    fn create(per_second: u32, now: Instant) -> Self {
+        return ConnectBucket { tokens: per_second as f64, refilled_at: now };
+    }
+
+    fn refill(&mut self, per_second: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second as f64).min(per_second as f64);
+        self.refilled_at = now;
+    }
+}
+
+// The global bucket and one per prefix that made a connection in the last second.  A prefix bucket that's been left
+// alone that long is full again, so it's forgotten.
+#[derive(Default)]
+pub struct ConnectLimits {
+    global: Option<ConnectBucket>,
+    prefixes: FxHashMap<AddressRange, ConnectBucket>,
+    window_start: Option<Instant>,
+}
+
+impl ConnectLimits {
+    pub fn prefix_count(&self) -> usize {
+        return self.prefixes.len();
+    }
+}
+
+impl Tachyon {
+    // false if a connection for the address would go over TachyonConfig.connect_limit, the datagram is dropped then
+    pub fn connect_limit_allows(&mut self, address: NetworkAddress) -> bool {
+        let config = self.config.connect_limit;
+        if !config.is_enabled() {
+            return true;
+        }
+
+        let now = self.now();
+        let limits = &mut self.connect_limits;
+        let mut global = limits.global.unwrap_or_else(|| ConnectBucket::create(config.per_second, now));
+        global.refill(config.per_second, now);
+        let range = AddressRange::create(address, config.prefix()).unwrap();
+        let mut prefix = limits.prefixes.get(&range).copied().unwrap_or_else(|| ConnectBucket::create(config.per_prefix_per_second, now));
+        prefix.refill(config.per_prefix_per_second, now);
+
+        let global_allows = config.per_second == 0 || global.tokens >= 1.0;
+        let prefix_allows = config.per_prefix_per_second == 0 || prefix.tokens >= 1.0;
+        if global_allows && prefix_allows {
+            global.tokens -= 1.0;
+            prefix.tokens -= 1.0;
+            limits.global = Some(global);
+            if config.per_prefix_per_second > 0 {
+                limits.prefixes.insert(range, prefix);
+            }
+            return true;
+        }
+        limits.global = Some(global);
+
+        self.stats.connections_throttled += 1;
+        // one event a second, a flood would fill the queue otherwise
+        let first_throttle = match limits.window_start {
+            Some(start) => now.saturating_duration_since(start).as_millis() >= THROTTLE_WINDOW_MILLIS,
+            None => true,
+        };
+        if first_throttle {
+            limits.window_start = Some(now);
+            let value = if prefix_allows { 0 } else { config.prefix() as u32 };
+            self.fire_security_event(SECURITY_CONNECT_THROTTLED, address, value);
+        }
+        return false;
+    }
+
+    // forgets prefix buckets that are full again, run from update
+    pub fn connect_limit_update(&mut self, now: Instant) {
+        if self.connect_limits.prefixes.is_empty() {
+            return;
+        }
+        self.connect_limits.prefixes.retain(|_, bucket| now.saturating_duration_since(bucket.refilled_at) < Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{network_address::NetworkAddress, security::SECURITY_CONNECT_THROTTLED, tachyon_test::TachyonTest};
+
+    fn address(c: u16, d: u16) -> NetworkAddress {
+        return NetworkAddress { a: 10, b: 0, c, d, port: 5000 };
+    }
+
+    #[test]
+    fn test_connect_limit() {
+        let mut test = TachyonTest::default();
+        test.server.config.connect_limit.per_second = 4;
+        test.server.config.connect_limit.per_prefix_per_second = 2;
+        test.server.set_security_event_capacity(8);
+        test.server.step(Duration::from_millis(0));
+
+        // two from one /24, the third is over its limit
+        assert!(test.server.on_receive_connection_update(address(1, 1)));
+        assert!(test.server.on_receive_connection_update(address(1, 2)));
+        assert!(!test.server.on_receive_connection_update(address(1, 3)));
+        assert_eq!(2, test.server.connections.len());
+        let event = test.server.take_security_event().unwrap();
+        assert_eq!(SECURITY_CONNECT_THROTTLED, event.kind);
+        assert_eq!(24, event.value);
+
+        // known addresses aren't limited
+        assert!(test.server.on_receive_connection_update(address(1, 1)));
+
+        // two from another, then the global limit
+        assert!(test.server.on_receive_connection_update(address(2, 1)));
+        assert!(test.server.on_receive_connection_update(address(2, 2)));
+        assert!(!test.server.on_receive_connection_update(address(3, 1)));
+        assert_eq!(4, test.server.connections.len());
+        assert_eq!(2, test.server.stats.connections_throttled);
+        // within the same second, no second event
+        assert!(test.server.take_security_event().is_none());
+
+        // half a second refills half of each
+        test.server.step(Duration::from_millis(500));
+        assert!(test.server.on_receive_connection_update(address(3, 1)));
+        assert!(test.server.on_receive_connection_update(address(1, 3)));
+        assert!(!test.server.on_receive_connection_update(address(4, 1)));
+        assert_eq!(3, test.server.stats.connections_throttled);
+
+        // full buckets are forgotten, and the next second gets an event again
+        test.server.step(Duration::from_millis(1000));
+        assert_eq!(0, test.server.connect_limits.prefix_count());
+        for c in 5..9 {
+            assert!(test.server.on_receive_connection_update(address(c, 1)));
+        }
+        assert!(!test.server.on_receive_connection_update(address(9, 1)));
+        assert_eq!(0, test.server.take_security_event().unwrap().value);
+    }
+}
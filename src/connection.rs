@@ -1,4 +1,4 @@
-use super::network_address::NetworkAddress;
+use super::{connection_state::ConnectionState, network_address::NetworkAddress, AddressStats};
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -8,6 +8,10 @@ pub struct Connection {
     pub tachyon_id: u16,
     pub received_at: u64,
     pub since_last_received: u64,
+    pub handle: ConnectionHandle,
+    // the application's, see Tachyon::set_connection_user_data
+    pub user_data: u64,
+    pub state: ConnectionState,
 }
 
 impl Connection {
@@ -18,11 +22,51 @@ impl Connection {
             tachyon_id,
             received_at: 0,
             since_last_received: 0,
+            handle: ConnectionHandle::default(),
+            user_data: 0,
+            state: ConnectionState::Connecting,
         };
         return conn;
     }
 }
 
+// a server's linked identity with its connection and what its channels add up to, see Tachyon::get_linked_identities
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct LinkedIdentity {
+    pub connection: Connection,
+    pub stats: AddressStats,
+}
+
+// why a server denied a link request
+pub const LINK_DENIED_UNKNOWN_ID: u8 = 1;
+pub const LINK_DENIED_BAD_SESSION: u8 = 2;
+// TachyonConfig.max_linked_identities reached, clients keep retrying
+pub const LINK_DENIED_SERVER_FULL: u8 = 3;
+pub const LINK_DENIED_BANNED: u8 = 4;
+
+// why a server sent a client IDENTITY_UNLINKED, in the ConnectionHeader's session_id
+pub const UNLINK_REASON_REQUESTED: u8 = 0;
+// the server has no linked connection for the client's address, it likely restarted
+pub const UNLINK_REASON_NOT_LINKED: u8 = 1;
+
+// The last denial a client got.  It stops sending link requests for the denied identity unless the reason is
+// LINK_DENIED_SERVER_FULL, setting another id or session starts them again.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct LinkDenial {
+    pub id: u32,
+    pub session_id: u32,
+    pub reason: u8,
+}
+
+impl LinkDenial {
+    pub fn blocks(&self, identity: &Identity) -> bool {
+        return self.reason != 0 && self.reason != LINK_DENIED_SERVER_FULL && self.id == identity.id && self.session_id == identity.session_id;
+    }
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default)]
@@ -45,3 +89,97 @@ impl Identity {
         self.linked = linked;
     }
 }
+
+// Stable reference to a connection for as long as it exists.  Sending through a handle resolves the address with an
+// index instead of the address/identity hash lookups.  Once the connection is removed the handle stays invalid, even if
+// a new connection from the same address gets the same slot.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct ConnectionHandle {
+    pub index: u32,
+    // 0 is never issued
+    pub generation: u32,
+}
+
+impl ConnectionHandle {
+    pub fn is_valid(&self) -> bool {
+        return self.generation > 0;
+    }
+}
+
+struct HandleSlot {
+    address: NetworkAddress,
+    generation: u32,
+    live: bool,
+}
+
+#[derive(Default)]
+pub struct ConnectionHandles {
+    slots: Vec<HandleSlot>,
+    free: Vec<u32>,
+}
+
+impl ConnectionHandles {
+    pub fn allocate(&mut self, address: NetworkAddress) -> ConnectionHandle {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.address = address;
+            slot.generation = slot.generation.wrapping_add(1).max(1);
+            slot.live = true;
+            return ConnectionHandle { index, generation: slot.generation };
+        }
+
+        let index = self.slots.len() as u32;
+        self.slots.push(HandleSlot { address, generation: 1, live: true });
+        return ConnectionHandle { index, generation: 1 };
+    }
+
+    pub fn release(&mut self, handle: ConnectionHandle) {
+        if self.resolve(handle).is_some() {
+            self.slots[handle.index as usize].live = false;
+            self.free.push(handle.index);
+        }
+    }
+
+    pub fn resolve(&self, handle: ConnectionHandle) -> Option<NetworkAddress> {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.live && slot.generation == handle.generation => {
+                return Some(slot.address);
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.slots.len() - self.free.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network_address::NetworkAddress;
+
+    use super::ConnectionHandles;
+
+    #[test]
+    fn test_handles() {
+        let mut handles = ConnectionHandles::default();
+        let first = handles.allocate(NetworkAddress::localhost(100));
+        let second = handles.allocate(NetworkAddress::localhost(200));
+        assert!(handles.resolve(first) == Some(NetworkAddress::localhost(100)));
+
+        handles.release(first);
+        assert!(handles.resolve(first).is_none());
+        handles.release(first);
+        assert_eq!(1, handles.len());
+
+        // slot is reused, the old handle stays invalid
+        let third = handles.allocate(NetworkAddress::localhost(100));
+        assert_eq!(first.index, third.index);
+        assert!(handles.resolve(first).is_none());
+        assert!(handles.resolve(third) == Some(NetworkAddress::localhost(100)));
+        assert!(handles.resolve(second) == Some(NetworkAddress::localhost(200)));
+    }
+}
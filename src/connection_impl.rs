@@ -1,27 +1,65 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use super::connection::{Connection, Identity};
+use super::connection_map::ConnectionChange;
+use super::connection_state::ConnectionState;
+use super::connection::{
+    Connection, ConnectionHandle, Identity, LinkedIdentity, LINK_DENIED_BAD_SESSION, LINK_DENIED_BANNED, LINK_DENIED_SERVER_FULL,
+    LINK_DENIED_UNKNOWN_ID, UNLINK_REASON_NOT_LINKED, UNLINK_REASON_REQUESTED,
+};
 use super::header::{
-    ConnectionHeader, MESSAGE_TYPE_IDENTITY_LINKED, MESSAGE_TYPE_IDENTITY_UNLINKED,
-    MESSAGE_TYPE_LINK_IDENTITY, MESSAGE_TYPE_UNLINK_IDENTITY,
+    ConnectionHeader, LINK_PAYLOAD_MAX, MESSAGE_TYPE_IDENTITY_DENIED, MESSAGE_TYPE_IDENTITY_LINKED,
+    MESSAGE_TYPE_IDENTITY_UNLINKED, MESSAGE_TYPE_LINK_IDENTITY, MESSAGE_TYPE_UNLINK_IDENTITY,
 };
+use super::link_payload::LinkPayloads;
 use super::network_address::NetworkAddress;
-use super::Tachyon;
+use super::security::SECURITY_NEW_ADDRESS;
+use super::{Tachyon, SEND_ERROR_IDENTITY, SEND_ERROR_NOT_CONNECTED};
 
 const IDENTITY_SEND_INTERVAL: u128 = 300;
+// replies to traffic from addresses that aren't linked, capped so spoofed traffic can't make a server send at will
+const NOT_LINKED_REPLIES_PER_SECOND: u32 = 64;
 
 pub const CONNECTION_ADDED_EVENT: u8 = 1;
 pub const CONNECTION_REMOVED_EVENT: u8 = 2;
+// an address went over its receive rate limit often enough to be muted, see rate_limit.rs
+pub const CONNECTION_MUTED_EVENT: u8 = 3;
+pub const CONNECTION_UNMUTED_EVENT: u8 = 4;
+// client reconnects, see reconnect.rs.  Failed is when it gives up after ReconnectConfig.max_attempts.
+pub const CONNECTION_RECONNECTING_EVENT: u8 = 5;
+pub const CONNECTION_RECONNECTED_EVENT: u8 = 6;
+pub const CONNECTION_RECONNECT_FAILED_EVENT: u8 = 7;
+// the connection's state changed, the event's connection carries the new one, see connection_state.rs
+pub const CONNECTION_STATE_CHANGED_EVENT: u8 = 8;
 
 pub const LINK_IDENTITY_EVENT: u8 = 1;
 pub const UNLINK_IDENTITY_EVENT: u8 = 2;
 pub const IDENTITY_LINKED_EVENT: u8 = 3;
 pub const IDENTITY_UNLINKED_EVENT: u8 = 4;
+// a client's link request was denied, the event's identity.session_id is the LINK_DENIED_x reason
+pub const IDENTITY_DENIED_EVENT: u8 = 5;
+// a client linked again after the server said it wasn't linked, fires right after IDENTITY_LINKED_EVENT
+pub const IDENTITY_RELINKED_EVENT: u8 = 6;
+
+#[derive(Default)]
+pub struct NotLinkedReplies {
+    window_start: Option<Instant>,
+    count: u32,
+}
 
 pub type ConnectionEventCallback = unsafe extern "C" fn(action: u8, connection: Connection);
 pub type IdentityEventCallback = unsafe extern "C" fn(action: u8, connection: Connection);
 
 impl Tachyon {
+    // The identity a client links as, sent to the server on update() until it's linked.  Needs
+    // TachyonConfig.use_identity, a different identity than the one linked starts linking over.
+    pub fn set_client_identity(&mut self, id: u32, session_id: u32) {
+        if self.identity.id != id || self.identity.session_id != session_id {
+            self.identity.linked = 0;
+        }
+        self.identity.id = id;
+        self.identity.session_id = session_id;
+    }
+
     // setting identity removes any associated connection
     pub fn set_identity(&mut self, id: u32, session_id: u32) {
         self.remove_connection_by_identity(id);
@@ -33,25 +71,144 @@ impl Tachyon {
         }
     }
 
+    // Removes any connection the identity has, and denies its link requests with LINK_DENIED_BANNED until unbanned.
+    // false if it was already banned.
+    pub fn ban_identity(&mut self, id: u32) -> bool {
+        if !self.banned_identities.insert(id) {
+            return false;
+        }
+        self.remove_connection_by_identity(id);
+        if self.identity_to_address_map.remove(&id).is_some() {
+            self.identity_addresses.publish(&self.identity_to_address_map);
+        }
+        return true;
+    }
+
+    pub fn unban_identity(&mut self, id: u32) -> bool {
+        return self.banned_identities.remove(&id);
+    }
+
     pub fn create_connection(&mut self, address: NetworkAddress, identity: Identity) {
         let mut conn = Connection::create(address, self.id);
         conn.identity = identity;
         conn.received_at = self.time_since_start();
-        self.connections.insert(address, conn);
-        self.create_configured_channels(address);
+        conn.state = self.initial_connection_state(&identity);
+        self.insert_connection(conn);
+        if self.config.use_channel_epochs == 1 {
+            self.create_configured_channels(address);
+        }
         self.fire_connection_event(CONNECTION_ADDED_EVENT, address);
+        if self.socket.is_server {
+            self.fire_security_event(SECURITY_NEW_ADDRESS, address, 0);
+        }
     }
 
-    fn remove_connection(&mut self, address: NetworkAddress) {
-        self.connections.remove(&address);
-        self.remove_configured_channels(address);
+    // gives the connection a fresh handle, invalidating the one it replaces if any
+    pub fn insert_connection(&mut self, mut conn: Connection) {
+        conn.handle = self.connection_handles.allocate(conn.address);
+        if self.socket.is_server {
+            self.socket.set_scramble_session(conn.address, conn.identity.session_id);
+        }
+        if let Some(changes) = &self.connection_changes {
+            changes.publish(ConnectionChange::Added(conn));
+        }
+        if let Some(replaced) = self.connections.insert(conn.address, conn) {
+            self.connection_handles.release(replaced.handle);
+        }
+    }
+
+    pub(crate) fn remove_connection(&mut self, address: NetworkAddress) {
+        if let Some(conn) = self.connections.get_mut(&address) {
+            conn.state = ConnectionState::Closed;
+        }
         self.fire_connection_event(CONNECTION_REMOVED_EVENT, address);
+        if let Some(conn) = self.connections.remove(&address) {
+            self.connection_handles.release(conn.handle);
+            if let Some(changes) = &self.connection_changes {
+                changes.publish(ConnectionChange::Removed(conn));
+            }
+        }
+        self.remove_configured_channels(address);
+        self.topics.remove_address(address);
+        self.remove_received_link_payload(address);
+        if self.socket.is_server {
+            self.socket.set_scramble_session(address, 0);
+        }
+    }
+
+    // A Pool sharing one port among its servers saw the address's connection come up on another server, the kernel moved
+    // the client there.  Drops the stale one here without telling the client.  False if there was none.
+    pub fn release_moved_connection(&mut self, address: NetworkAddress) -> bool {
+        let id = match self.connections.get(&address) {
+            Some(conn) => conn.identity.id,
+            None => return false,
+        };
+        if id > 0 && self.identity_to_address_map.get(&id) == Some(&address) {
+            self.identity_to_address_map.remove(&id);
+            self.identity_addresses.publish(&self.identity_to_address_map);
+        }
+        self.remove_connection(address);
+        return true;
+    }
+
+    // default (invalid) handle if there is no connection for the address
+    pub fn get_connection_handle(&self, address: NetworkAddress) -> ConnectionHandle {
+        match self.connections.get(&address) {
+            Some(conn) => return conn.handle,
+            None => return ConnectionHandle::default(),
+        }
+    }
+
+    pub fn get_connection_by_handle(&self, handle: ConnectionHandle) -> Option<&Connection> {
+        let address = self.connection_handles.resolve(handle)?;
+        return self.connections.get(&address);
     }
 
     pub fn get_connection(&self, address: NetworkAddress) -> Option<&Connection> {
         return self.connections.get(&address);
     }
 
+    // Application state for the connection, a key or pointer into its own storage.  It comes back in receive results and
+    // connection events and goes away with the connection, a relink from another address starts at 0 again.  Not kept
+    // by checkpoints.  False if there is no connection for the address.
+    pub fn set_connection_user_data(&mut self, address: NetworkAddress, user_data: u64) -> bool {
+        match self.connections.get_mut(&address) {
+            Some(conn) => {
+                conn.user_data = user_data;
+                return true;
+            }
+            None => return false,
+        }
+    }
+
+    pub fn get_connection_user_data(&self, address: NetworkAddress) -> Option<u64> {
+        return self.connections.get(&address).map(|conn| conn.user_data);
+    }
+
+    pub fn is_identity_linked(&self, id: u32) -> bool {
+        return self.identity_to_address_map.contains_key(&id);
+    }
+
+    // every linked identity, ordered by id
+    pub fn get_linked_identities(&mut self) -> Vec<LinkedIdentity> {
+        let mut ids: Vec<u32> = self.identity_to_address_map.keys().copied().collect();
+        ids.sort_unstable();
+
+        let since_start = self.time_since_start();
+        let mut list: Vec<LinkedIdentity> = Vec::with_capacity(ids.len());
+        for id in ids {
+            let address = self.identity_to_address_map[&id];
+            let mut connection = match self.connections.get(&address) {
+                Some(conn) => *conn,
+                None => continue,
+            };
+            connection.since_last_received = since_start - connection.received_at;
+            let stats = self.get_address_stats(address).unwrap_or_default();
+            list.push(LinkedIdentity { connection, stats });
+        }
+        return list;
+    }
+
     pub fn get_connection_by_identity(&self, id: u32) -> Option<&Connection> {
         if let Some(address) = self.identity_to_address_map.get(&id) {
             return self.get_connection(*address);
@@ -80,6 +237,9 @@ impl Tachyon {
         if let Some(callback) = self.identity_event_callback {
             let mut conn = Connection::create(address, self.id);
             conn.identity = Identity {id, session_id, linked: 0 };
+            if let Some(existing) = self.connections.get(&address) {
+                conn.user_data = existing.user_data;
+            }
             if event_id == IDENTITY_LINKED_EVENT {
                conn.identity.linked = 1; 
             }
@@ -89,32 +249,43 @@ impl Tachyon {
         }
     }
 
+    // removed events fire while the connection still exists, so both carry its handle
     pub fn fire_connection_event(&self, event_id: u8, address: NetworkAddress) {
         if let Some(callback) = self.connection_event_callback {
-            let conn = Connection::create(address, self.id);
+            let conn = match self.connections.get(&address) {
+                Some(conn) => *conn,
+                None => Connection::create(address, self.id),
+            };
             unsafe {
                 callback(event_id, conn);
             }
         }
     }
 
-    // run when use_identity is not set
-    pub fn on_receive_connection_update(&mut self, address: NetworkAddress) {
+    // run when use_identity is not set, false if TachyonConfig.connect_limit turned a new address away
+    pub fn on_receive_connection_update(&mut self, address: NetworkAddress) -> bool {
         let since_start = self.time_since_start();
         if let Some(conn) = self.connections.get_mut(&address) {
             conn.received_at = since_start;
+            self.connection_heard(address);
         } else {
+            if !self.connect_limit_allows(address) {
+                return false;
+            }
             self.create_connection(address, Identity::default());
         }
+        return true;
     }
 
     pub fn validate_and_update_linked_connection(&mut self, address: NetworkAddress) -> bool {
         let since_start = self.time_since_start();
         if let Some(conn) = self.connections.get_mut(&address) {
-            if conn.identity.id == 0 {
+            // peers talk without linking
+            if conn.identity.id == 0 && !self.peers.contains(&address) {
                 return false;
             }
             conn.received_at = since_start;
+            self.connection_heard(address);
             return true;
         }
         return false;
@@ -146,17 +317,35 @@ impl Tachyon {
     
 
     pub fn try_link_identity(&mut self, address: NetworkAddress, id: u32, session_id: u32) -> bool {
+        return self.link_identity(address, id, session_id) == 0;
+    }
+
+    // 0 when linked, otherwise the LINK_DENIED_x reason
+    pub fn link_identity(&mut self, address: NetworkAddress, id: u32, session_id: u32) -> u8 {
+        if self.banned_identities.contains(&id) {
+            return LINK_DENIED_BANNED;
+        }
         if let Some(current_session_id) = self.identities.get(&id) {
             if session_id != *current_session_id {
-                return false;
+                return LINK_DENIED_BAD_SESSION;
             }
 
             let identity = self.get_connection_identity(address);
             if identity.id == id && identity.session_id == *current_session_id {
-                return true;
+                return 0;
+            }
+
+            // relinking from another address replaces that connection, it doesn't add one
+            let max = self.config.max_linked_identities as usize;
+            if max > 0 && !self.identity_to_address_map.contains_key(&id) && self.connections.len() >= max {
+                return LINK_DENIED_SERVER_FULL;
             }
 
             self.remove_connection_by_identity(id);
+            // another identity's connection on the address goes too, the link starts with fresh channels
+            if self.connections.contains_key(&address) {
+                self.remove_connection(address);
+            }
             let identity = Identity {
                 id: id,
                 session_id: session_id,
@@ -164,10 +353,11 @@ impl Tachyon {
             };
             self.create_connection(address, identity);
             self.identity_to_address_map.insert(id, address);
-            self.send_identity_linked(address);
-            return true;
+            self.identity_addresses.publish(&self.identity_to_address_map);
+            self.send_identity_linked(address, id);
+            return 0;
         }
-        return false;
+        return LINK_DENIED_UNKNOWN_ID;
     }
 
     pub fn try_unlink_identity(&mut self, address: NetworkAddress, id: u32, session_id: u32) -> bool {
@@ -178,6 +368,7 @@ impl Tachyon {
 
             self.remove_connection_by_identity(id);
             self.identity_to_address_map.remove(&id);
+            self.identity_addresses.publish(&self.identity_to_address_map);
             self.send_identity_unlinked(address);
             return true;
         }
@@ -185,12 +376,12 @@ impl Tachyon {
         return false;
     }
 
-    pub fn client_identity_update(&mut self) {
+    pub fn client_identity_update(&mut self, now: Instant) {
         if self.config.use_identity == 0 {
             return;
         }
 
-        if self.socket.socket.is_none() {
+        if !self.socket.is_open() {
             return;
         }
 
@@ -206,9 +397,13 @@ impl Tachyon {
             return;
         }
 
-        let since_last = Instant::now() - self.last_identity_link_request;
+        if self.link_denial.blocks(&self.identity) {
+            return;
+        }
+
+        let since_last = now.duration_since(self.last_identity_link_request);
         if since_last.as_millis() > IDENTITY_SEND_INTERVAL {
-            self.last_identity_link_request = Instant::now();
+            self.last_identity_link_request = now;
             self.send_link_identity(self.identity.id, self.identity.session_id);
         }
     }
@@ -219,35 +414,104 @@ impl Tachyon {
         } else {
             if self.config.use_identity == 1 {
                 return self.identity.linked == 1;
+            } else if self.is_challenged() {
+                return self.challenge.accepted;
             } else {
                 return true;
             }
         }
     }
 
+    // the send error when can_send is false
+    pub fn send_blocked_error(&self) -> u32 {
+        if self.is_challenged() {
+            return SEND_ERROR_NOT_CONNECTED;
+        }
+        return SEND_ERROR_IDENTITY;
+    }
+
+    // padded to the largest payload, so a server's denied reply always fits, see set_link_denied_payload
     pub fn send_link_identity(&self, id: u32, session_id: u32) {
-        self.send_identity_message(MESSAGE_TYPE_LINK_IDENTITY, id, session_id,  NetworkAddress::default());
+        let size = LinkPayloads::message_size(LINK_PAYLOAD_MAX);
+        self.send_identity_message(MESSAGE_TYPE_LINK_IDENTITY, id, session_id, NetworkAddress::default(), self.link_payloads.request(), size);
     }
 
     pub fn send_unlink_identity(&self, id: u32, session_id: u32) {
-        self.send_identity_message(MESSAGE_TYPE_UNLINK_IDENTITY, id, session_id, NetworkAddress::default());
+        self.send_identity_message(MESSAGE_TYPE_UNLINK_IDENTITY, id, session_id, NetworkAddress::default(), &[], 0);
     }
 
-    pub fn send_identity_linked(&self, address: NetworkAddress) {
-        self.send_identity_message(MESSAGE_TYPE_IDENTITY_LINKED, 0, 0, address);
+    pub fn send_identity_linked(&self, address: NetworkAddress, id: u32) {
+        self.send_identity_message(MESSAGE_TYPE_IDENTITY_LINKED, 0, 0, address, self.link_payloads.reply(id), 0);
     }
 
     pub fn send_identity_unlinked(&self, address: NetworkAddress) {
-        self.send_identity_message(MESSAGE_TYPE_IDENTITY_UNLINKED, 0, 0, address);
+        self.send_identity_message(MESSAGE_TYPE_IDENTITY_UNLINKED, 0, UNLINK_REASON_REQUESTED as u32, address, &[], 0);
+    }
+
+    // Tells a client sending us traffic that it isn't linked, so one that thinks it is links again.  Like other replies
+    // it's never larger than what it answers.
+    pub fn send_not_linked(&mut self, address: NetworkAddress, request_len: usize) {
+        if LinkPayloads::message_size(0) > request_len {
+            return;
+        }
+        let now = self.now();
+        let replies = &mut self.not_linked_replies;
+        match replies.window_start {
+            Some(start) if now.saturating_duration_since(start).as_millis() < 1000 => {
+                if replies.count >= NOT_LINKED_REPLIES_PER_SECOND {
+                    return;
+                }
+            }
+            _ => {
+                replies.window_start = Some(now);
+                replies.count = 0;
+            }
+        }
+        replies.count += 1;
+        self.send_identity_message(MESSAGE_TYPE_IDENTITY_UNLINKED, 0, UNLINK_REASON_NOT_LINKED as u32, address, &[], 0);
+    }
+
+    // a linked client was told by the server it isn't, true until it's linked again
+    pub fn is_relinking(&self) -> bool {
+        return self.relink_pending;
     }
 
-    fn send_identity_message(&self, message_type: u8, id: u32, session_id: u32, address: NetworkAddress) {
+    // the link request goes out on the next update instead of waiting out the interval
+    pub fn start_relink(&mut self) {
+        self.relink_pending = true;
+        self.link_on_next_update();
+    }
+
+    pub(crate) fn link_on_next_update(&mut self) {
+        self.last_identity_link_request = self.now() - Duration::from_millis(IDENTITY_SEND_INTERVAL as u64 + 1);
+    }
+
+    pub fn finish_relink(&mut self) {
+        self.relink_pending = false;
+        self.stats.relinks += 1;
+    }
+
+    // nothing when the reply would be larger than the request, the denied payload is left off if that's what makes it
+    pub fn send_identity_denied(&self, address: NetworkAddress, id: u32, reason: u8, request_len: usize) {
+        let mut payload = self.link_payloads.denied();
+        if LinkPayloads::message_size(payload.len()) > request_len {
+            payload = &[];
+        }
+        if LinkPayloads::message_size(0) > request_len {
+            return;
+        }
+        self.send_identity_message(MESSAGE_TYPE_IDENTITY_DENIED, id, reason as u32, address, payload, 0);
+    }
+
+    fn send_identity_message(&self, message_type: u8, id: u32, session_id: u32, address: NetworkAddress, payload: &[u8], min_size: usize) {
         let mut header = ConnectionHeader::default();
         header.message_type = message_type;
         header.id = id;
         header.session_id = session_id;
-        let mut send_buffer: Vec<u8> = vec![0; 12];
+        let size = std::cmp::max(LinkPayloads::message_size(payload.len()), min_size);
+        let mut send_buffer: Vec<u8> = vec![0; size];
         header.write(&mut send_buffer);
+        LinkPayloads::write(&mut send_buffer, payload);
         self.socket.send_to(address, &send_buffer, send_buffer.len());
     }
 }
@@ -257,9 +521,11 @@ mod tests {
 
     use serial_test::serial;
 
-    use crate::tachyon::{
-        connection::Identity, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon,
-        TachyonConfig,
+    use crate::{
+        connection::{Identity, LINK_DENIED_BAD_SESSION, LINK_DENIED_BANNED, LINK_DENIED_SERVER_FULL, LINK_DENIED_UNKNOWN_ID},
+        network_address::NetworkAddress,
+        tachyon_test::TachyonTest,
+        Tachyon, TachyonConfig,
     };
 
     #[test]
@@ -307,6 +573,65 @@ mod tests {
         assert_eq!(0, server.get_channel_count(address));
     }
 
+    #[test]
+    fn test_connection_user_data() {
+        let address = NetworkAddress::localhost(100);
+
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        server.set_identity(1, 10);
+        assert!(!server.set_connection_user_data(address, 7));
+
+        server.try_link_identity(address, 1, 10);
+        assert_eq!(Some(0), server.get_connection_user_data(address));
+        assert!(server.set_connection_user_data(address, 7));
+        assert_eq!(7, server.get_connection_by_identity(1).unwrap().user_data);
+
+        // gone with the connection
+        server.try_unlink_identity(address, 1, 10);
+        assert_eq!(None, server.get_connection_user_data(address));
+        server.try_link_identity(address, 1, 10);
+        assert_eq!(Some(0), server.get_connection_user_data(address));
+    }
+
+    #[test]
+    #[serial]
+    fn test_receive_user_data() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        test.client_send_reliable(1, 8);
+        assert_eq!(0, test.server_receive().user_data);
+
+        let address = test.remote_client();
+        test.server.set_connection_user_data(address, 42);
+        test.client_send_reliable(1, 8);
+        assert_eq!(42, test.server_receive().user_data);
+    }
+
+    #[test]
+    fn test_linked_identities() {
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        server.set_identity(2, 20);
+        server.set_identity(1, 10);
+        server.set_identity(3, 30);
+        server.try_link_identity(NetworkAddress::localhost(200), 2, 20);
+        server.try_link_identity(NetworkAddress::localhost(100), 1, 10);
+        assert!(server.is_identity_linked(1));
+        assert!(!server.is_identity_linked(3));
+
+        let linked = server.get_linked_identities();
+        assert_eq!(2, linked.len());
+        assert_eq!((1, 10), (linked[0].connection.identity.id, linked[0].connection.identity.session_id));
+        assert!(NetworkAddress::localhost(100) == linked[0].connection.address);
+        assert_eq!(2, linked[0].stats.channel_count);
+        assert_eq!(2, linked[1].connection.identity.id);
+
+        server.try_unlink_identity(NetworkAddress::localhost(100), 1, 10);
+        assert!(!server.is_identity_linked(1));
+        assert_eq!(1, server.get_linked_identities().len());
+    }
+
     #[test]
     fn test_validate_and_update_connection() {
         let address = NetworkAddress::localhost(100);
@@ -336,6 +661,22 @@ mod tests {
         assert!(tach.can_send());
     }
 
+    #[test]
+    fn test_set_client_identity() {
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut client = Tachyon::create(config);
+        client.set_client_identity(1, 10);
+        client.identity.linked = 1;
+
+        // the same identity stays linked, another one has to link
+        client.set_client_identity(1, 10);
+        assert!(client.identity.is_linked());
+        client.set_client_identity(1, 11);
+        assert!(!client.identity.is_linked());
+        assert_eq!(11, client.identity.session_id);
+    }
+
     #[test]
     #[serial]
     fn test_link_flow() {
@@ -359,12 +700,30 @@ mod tests {
         test.client_receive();
         assert!(test.client.identity.is_linked());
 
+        // receive results carry the sender identity
+        test.client_send_reliable(1, 4);
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(1, res.identity_id);
+        assert_eq!(11, res.session_id);
+
         // unlinked
         test.client
             .send_unlink_identity(test.client.identity.id, test.client.identity.session_id);
         test.server_receive();
         test.client_receive();
         assert!(!test.client.identity.is_linked());
+        let client_address = test.client_address;
+        assert_eq!(0, test.client.get_channel(client_address, 1).unwrap().send_buffers.current_sequence);
+
+        // linking again starts clean on both ends
+        test.client.last_identity_link_request = std::time::Instant::now() - std::time::Duration::from_secs(1);
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+        assert_eq!(0, test.client_send_reliable(1, 4).error);
+        assert_eq!(4, test.server_receive().length);
     }
 
     #[test]
@@ -388,5 +747,82 @@ mod tests {
         test.server_receive();
         test.client_receive();
         assert!(!test.client.identity.is_linked());
+        assert_eq!(LINK_DENIED_BAD_SESSION, test.client.link_denial.reason);
+        assert!(test.client.link_denial.blocks(&test.client.identity));
+
+        // a new session retries
+        test.client.identity.session_id = 10;
+        assert!(!test.client.link_denial.blocks(&test.client.identity));
+        test.client.last_identity_link_request = std::time::Instant::now() - std::time::Duration::from_secs(1);
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+    }
+
+    #[test]
+    #[serial]
+    fn test_relink_after_restart() {
+        let mut test = TachyonTest::default();
+        test.client.config.use_identity = 1;
+        test.client.identity = Identity { id: 1, session_id: 10, linked: 0 };
+        test.server.config.use_identity = 1;
+        test.server.set_identity(1, 10);
+        test.connect();
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+        for _ in 0..3 {
+            test.client_send_reliable(1, 8);
+            assert_eq!(8, test.server_receive().length);
+        }
+
+        // the new server doesn't know the client and tells it so
+        test.server = Tachyon::create(test.server.config);
+        test.server.set_identity(1, 10);
+        assert!(test.server.bind(test.address));
+        test.client_send_reliable(1, 8);
+        assert_eq!(0, test.server_receive().length);
+        test.client_receive();
+        assert!(!test.client.identity.is_linked());
+        assert!(test.client.is_relinking());
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+        assert!(!test.client.is_relinking());
+        assert_eq!(1, test.client.stats.relinks);
+
+        // sequences start over on both ends
+        test.client_send_reliable(1, 8);
+        assert_eq!(8, test.server_receive().length);
+    }
+
+    #[test]
+    fn test_link_denied_reasons() {
+        let address = NetworkAddress::localhost(100);
+        let mut config = TachyonConfig::default();
+        config.max_linked_identities = 1;
+        let mut server = Tachyon::create(config);
+        server.set_identity(1, 10);
+        server.set_identity(2, 20);
+
+        assert_eq!(LINK_DENIED_UNKNOWN_ID, server.link_identity(address, 3, 30));
+        assert_eq!(LINK_DENIED_BAD_SESSION, server.link_identity(address, 1, 11));
+        assert_eq!(0, server.link_identity(address, 1, 10));
+
+        // relinking from another address doesn't count against the limit
+        assert_eq!(LINK_DENIED_SERVER_FULL, server.link_identity(NetworkAddress::localhost(200), 2, 20));
+        assert_eq!(0, server.link_identity(NetworkAddress::localhost(200), 1, 10));
+
+        assert!(server.ban_identity(1));
+        assert!(!server.ban_identity(1));
+        assert_eq!(0, server.connections.len());
+        assert_eq!(LINK_DENIED_BANNED, server.link_identity(address, 1, 10));
+        assert!(server.unban_identity(1));
+        assert_eq!(0, server.link_identity(address, 1, 10));
     }
 }
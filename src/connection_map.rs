@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use crossbeam::queue::SegQueue;
+use rustc_hash::FxHashMap;
+
+use super::{connection::Connection, network_address::NetworkAddress};
+
+#[derive(Clone, Copy)]
+pub enum ConnectionChange {
+    Added(Connection),
+    Removed(Connection),
+}
+
+// Where the servers of a Pool publish their connection adds and removes, each server has a clone.  Servers push from
+// whatever thread is receiving for them, Pool::build_connection_maps drains it.
+#[derive(Clone, Default)]
+pub struct ConnectionChanges {
+    queue: Arc<SegQueue<ConnectionChange>>,
+}
+
+impl ConnectionChanges {
+    pub fn publish(&self, change: ConnectionChange) {
+        self.queue.push(change);
+    }
+
+    pub fn pop(&self) -> Option<ConnectionChange> {
+        return self.queue.pop();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.queue.len();
+    }
+}
+
+// Which server has the connection for an address or identity.  A Pool keeps one up to date from ConnectionChanges and
+// hands out Arcs of it, a snapshot never changes once taken.
+#[derive(Clone, Default)]
+pub struct ConnectionMaps {
+    pub by_address: FxHashMap<NetworkAddress, Connection>,
+    pub by_identity: FxHashMap<u32, Connection>,
+}
+
+impl ConnectionMaps {
+    // Removes only take out entries of the same server, so a connection that moved to another server isn't lost to a
+    // remove that was published after the add.  Returns the connection an add took the address from when it was on
+    // another server.
+    pub fn apply(&mut self, change: ConnectionChange) -> Option<Connection> {
+        match change {
+            ConnectionChange::Added(conn) => {
+                let replaced = self.by_address.insert(conn.address, conn);
+                if conn.identity.id > 0 {
+                    self.by_identity.insert(conn.identity.id, conn);
+                }
+                return replaced.filter(|replaced| replaced.tachyon_id != conn.tachyon_id);
+            }
+            ConnectionChange::Removed(conn) => {
+                if let Some(current) = self.by_address.get(&conn.address) {
+                    if current.tachyon_id == conn.tachyon_id {
+                        self.by_address.remove(&conn.address);
+                    }
+                }
+                if conn.identity.id > 0 {
+                    if let Some(current) = self.by_identity.get(&conn.identity.id) {
+                        if current.tachyon_id == conn.tachyon_id && current.address == conn.address {
+                            self.by_identity.remove(&conn.identity.id);
+                        }
+                    }
+                }
+                return None;
+            }
+        }
+    }
+
+    pub fn get_by_address(&self, address: NetworkAddress) -> Option<&Connection> {
+        return self.by_address.get(&address);
+    }
+
+    pub fn get_by_identity(&self, id: u32) -> Option<&Connection> {
+        return self.by_identity.get(&id);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.by_address.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        connection::{Connection, Identity},
+        network_address::NetworkAddress,
+    };
+
+    use super::{ConnectionChange, ConnectionMaps};
+
+    fn connection(port: u32, id: u32, server_id: u16) -> Connection {
+        let mut conn = Connection::create(NetworkAddress::localhost(port), server_id);
+        conn.identity = Identity { id, session_id: 1, linked: 0 };
+        return conn;
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut maps = ConnectionMaps::default();
+        maps.apply(ConnectionChange::Added(connection(100, 1, 1)));
+        maps.apply(ConnectionChange::Added(connection(200, 0, 1)));
+        assert_eq!(2, maps.len());
+        assert_eq!(1, maps.get_by_identity(1).unwrap().tachyon_id);
+        assert!(maps.get_by_identity(0).is_none());
+
+        // the identity moved to another server before the old server's remove arrived
+        assert!(maps.apply(ConnectionChange::Added(connection(300, 1, 2))).is_none());
+        maps.apply(ConnectionChange::Removed(connection(100, 1, 1)));
+        assert_eq!(2, maps.get_by_identity(1).unwrap().tachyon_id);
+        assert!(maps.get_by_address(NetworkAddress::localhost(100)).is_none());
+
+        maps.apply(ConnectionChange::Removed(connection(300, 1, 2)));
+        assert!(maps.get_by_identity(1).is_none());
+        assert_eq!(1, maps.len());
+
+        // same address on another server, the old server's connection is handed back
+        assert_eq!(1, maps.apply(ConnectionChange::Added(connection(200, 0, 3))).unwrap().tachyon_id);
+        assert!(maps.apply(ConnectionChange::Added(connection(200, 0, 3))).is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use super::{connection::Identity, connection_impl::CONNECTION_STATE_CHANGED_EVENT, network_address::NetworkAddress, Tachyon};
+
+// Where a connection is in its life, kept on every Connection and reported with CONNECTION_STATE_CHANGED_EVENT.
+//
+// Clients start out Connecting and servers start identity connections out Linked, they become Established on the first
+// datagram after that.  Servers without identities only create connections for traffic, those start Established.  Idle
+// and TimingOut follow TachyonConfig.idle_millis/timeout_millis of silence, and the next datagram makes the connection
+// Established again.  Nothing is removed for timing out, that's the application's call.  Closed is only seen in
+// CONNECTION_REMOVED_EVENT and by asking about an address that has no connection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ConnectionState {
+    Connecting = 1,
+    Linked = 2,
+    Established = 3,
+    Idle = 4,
+    TimingOut = 5,
+    Closed = 6,
+}
+
+impl ConnectionState {
+    // heard from recently enough to count as up
+    pub fn is_active(&self) -> bool {
+        return *self == ConnectionState::Established || *self == ConnectionState::Idle;
+    }
+}
+
+impl Tachyon {
+    pub fn get_connection_state(&self, address: NetworkAddress) -> ConnectionState {
+        match self.connections.get(&address) {
+            Some(conn) => return conn.state,
+            None => return ConnectionState::Closed,
+        }
+    }
+
+    pub(crate) fn initial_connection_state(&self, identity: &Identity) -> ConnectionState {
+        if !self.socket.is_server {
+            return ConnectionState::Connecting;
+        }
+        if identity.id > 0 {
+            return ConnectionState::Linked;
+        }
+        return ConnectionState::Established;
+    }
+
+    pub(crate) fn set_connection_state(&mut self, address: NetworkAddress, state: ConnectionState) {
+        match self.connections.get_mut(&address) {
+            Some(conn) if conn.state != state => conn.state = state,
+            _ => return,
+        }
+        self.fire_connection_event(CONNECTION_STATE_CHANGED_EVENT, address);
+    }
+
+    // every datagram from a connection, after received_at is updated
+    pub(crate) fn connection_heard(&mut self, address: NetworkAddress) {
+        let state = match self.connections.get(&address) {
+            Some(conn) => conn.state,
+            None => return,
+        };
+        if state == ConnectionState::Established || state == ConnectionState::Closed {
+            return;
+        }
+        // a client isn't up until it can send
+        if state == ConnectionState::Connecting && !self.can_send() {
+            return;
+        }
+        self.set_connection_state(address, ConnectionState::Established);
+    }
+
+    // client, every datagram from the server
+    pub(crate) fn client_connection_heard(&mut self) {
+        let since_start = self.time_since_start();
+        let address = NetworkAddress::default();
+        if let Some(conn) = self.connections.get_mut(&address) {
+            conn.received_at = since_start;
+        }
+        self.connection_heard(address);
+    }
+
+    pub(crate) fn update_connection_states(&mut self) {
+        let idle = self.config.idle_millis as u64;
+        let timeout = self.config.timeout_millis as u64;
+        if idle == 0 && timeout == 0 {
+            return;
+        }
+
+        let since_start = self.time_since_start();
+        let mut changes: Vec<(NetworkAddress, ConnectionState)> = Vec::new();
+        for conn in self.connections.values() {
+            if !conn.state.is_active() && conn.state != ConnectionState::TimingOut {
+                continue;
+            }
+            let silence = since_start.saturating_sub(conn.received_at);
+            let state = if timeout > 0 && silence > timeout {
+                ConnectionState::TimingOut
+            } else if idle > 0 && silence > idle {
+                ConnectionState::Idle
+            } else {
+                conn.state
+            };
+            if state != conn.state {
+                changes.push((conn.address, state));
+            }
+        }
+        for (address, state) in changes {
+            self.set_connection_state(address, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::{connection::Identity, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon, TachyonConfig};
+
+    use super::ConnectionState;
+
+    #[test]
+    fn test_server_link_states() {
+        let address = NetworkAddress::localhost(100);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        server.socket.is_server = true;
+        server.set_identity(1, 10);
+        assert_eq!(ConnectionState::Closed, server.get_connection_state(address));
+
+        server.try_link_identity(address, 1, 10);
+        assert_eq!(ConnectionState::Linked, server.get_connection_state(address));
+        assert!(server.validate_and_update_linked_connection(address));
+        assert_eq!(ConnectionState::Established, server.get_connection_state(address));
+
+        server.try_unlink_identity(address, 1, 10);
+        assert_eq!(ConnectionState::Closed, server.get_connection_state(address));
+    }
+
+    #[test]
+    #[serial]
+    fn test_idle_and_timing_out() {
+        let mut test = TachyonTest::default();
+        test.server.config.idle_millis = 100;
+        test.server.config.timeout_millis = 300;
+        test.client.config.idle_millis = 100;
+        test.connect();
+        assert_eq!(ConnectionState::Connecting, test.client.get_connection_state(NetworkAddress::default()));
+
+        test.client_send_reliable(1, 8);
+        test.server_receive();
+        let address = test.remote_client();
+        assert_eq!(ConnectionState::Established, test.server.get_connection_state(address));
+
+        test.server.step(Duration::from_millis(150));
+        assert_eq!(ConnectionState::Idle, test.server.get_connection_state(address));
+        test.server.step(Duration::from_millis(200));
+        assert_eq!(ConnectionState::TimingOut, test.server.get_connection_state(address));
+        assert_eq!(1, test.server.connections.len());
+
+        // heard from again
+        test.client_send_reliable(1, 8);
+        test.server_receive();
+        assert_eq!(ConnectionState::Established, test.server.get_connection_state(address));
+
+        test.server.send_reliable(1, address, &test.send_buffer, 8);
+        test.client_receive();
+        assert_eq!(ConnectionState::Established, test.client.get_connection_state(NetworkAddress::default()));
+        test.client.step(Duration::from_millis(150));
+        assert_eq!(ConnectionState::Idle, test.client.get_connection_state(NetworkAddress::default()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_client_link_states() {
+        let mut test = TachyonTest::default();
+        test.client.config.use_identity = 1;
+        test.client.identity = Identity { id: 1, session_id: 10, linked: 0 };
+        test.server.config.use_identity = 1;
+        test.server.set_identity(1, 10);
+        test.connect();
+        let server = NetworkAddress::default();
+
+        test.client.update();
+        test.server_receive();
+        assert_eq!(ConnectionState::Connecting, test.client.get_connection_state(server));
+        test.client_receive();
+        assert_eq!(ConnectionState::Linked, test.client.get_connection_state(server));
+
+        let address = test.remote_client();
+        test.server.send_reliable(1, address, &test.send_buffer, 8);
+        test.client_receive();
+        assert_eq!(ConnectionState::Established, test.client.get_connection_state(server));
+
+        test.client.send_unlink_identity(1, 10);
+        test.server_receive();
+        test.client_receive();
+        assert_eq!(ConnectionState::Connecting, test.client.get_connection_state(server));
+    }
+}
@@ -0,0 +1,112 @@
+use super::{header::*, Tachyon};
+
+// The protocol messages one receive_loop call handled on its way to the message it returned, the ones that never reach
+// the application.  Tachyon::control_counts always has the current or last call's, the observer is optional.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ControlCounts {
+    // nack messages in either format, and nack format replies
+    pub nacks: u32,
+    pub nones: u32,
+    // every fragment received, assembled messages are returned as usual
+    pub fragments: u32,
+    // link, unlink, linked, unlinked and denied
+    pub identity: u32,
+    // client side, the server answers challenges before anything is counted
+    pub challenge: u32,
+    pub channel_epoch: u32,
+    pub topic: u32,
+    pub total: u32,
+}
+
+pub type ControlObserverCallback = unsafe extern "C" fn(counts: ControlCounts);
+
+impl ControlCounts {
+    pub fn record(&mut self, message_type: u8) {
+        match message_type {
+            MESSAGE_TYPE_NACK | MESSAGE_TYPE_NACK_RANGES | MESSAGE_TYPE_NACK_FORMAT => self.nacks += 1,
+            MESSAGE_TYPE_NONE => self.nones += 1,
+            MESSAGE_TYPE_FRAGMENT => self.fragments += 1,
+            MESSAGE_TYPE_LINK_IDENTITY | MESSAGE_TYPE_UNLINK_IDENTITY | MESSAGE_TYPE_IDENTITY_LINKED | MESSAGE_TYPE_IDENTITY_UNLINKED
+            | MESSAGE_TYPE_IDENTITY_DENIED => self.identity += 1,
+            MESSAGE_TYPE_CONNECT_REQUEST | MESSAGE_TYPE_CHALLENGE | MESSAGE_TYPE_CHALLENGE_RESPONSE | MESSAGE_TYPE_CONNECT_ACCEPTED => {
+                self.challenge += 1
+            }
+            MESSAGE_TYPE_CHANNEL_EPOCH | MESSAGE_TYPE_CHANNEL_EPOCH_ACK => self.channel_epoch += 1,
+            MESSAGE_TYPE_SUBSCRIBE | MESSAGE_TYPE_UNSUBSCRIBE | MESSAGE_TYPE_TOPIC_ACK => self.topic += 1,
+            _ => return,
+        }
+        self.total += 1;
+    }
+}
+
+impl Tachyon {
+    // called at the end of every receive_loop and receive_into_vec call that handled control messages
+    pub fn set_control_observer(&mut self, callback: Option<ControlObserverCallback>) {
+        self.control_observer = callback;
+    }
+
+    pub(crate) fn report_control(&self) {
+        if self.control_counts.total == 0 {
+            return;
+        }
+        if let Some(callback) = self.control_observer {
+            unsafe {
+                callback(self.control_counts);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::{AtomicU32, Ordering}, time::Duration};
+
+    use crate::{channel::ChannelConfig, tachyon_test::TachyonTest};
+
+    use super::ControlCounts;
+
+    static OBSERVED_NACKS: AtomicU32 = AtomicU32::new(0);
+
+    unsafe extern "C" fn on_control(counts: ControlCounts) {
+        OBSERVED_NACKS.fetch_add(counts.nacks, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_control_counts() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.set_control_observer(Some(on_control));
+        let network = test.connect_loopback();
+        test.client_send_reliable(3, 8);
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(ControlCounts::default(), test.server.control_counts);
+
+        // the server nacks the lost one
+        test.client_send_reliable(3, 8);
+        assert!(network.drop_next(test.address));
+        test.client_send_reliable(3, 8);
+        assert_eq!(0, test.server_receive().length);
+        test.server.step(Duration::from_millis(10));
+        assert_eq!(0, test.client_receive().length);
+        assert_eq!(1, test.client.control_counts.nacks);
+        assert_eq!(1, test.client.control_counts.total);
+        assert_eq!(1, OBSERVED_NACKS.load(Ordering::SeqCst));
+
+        // calls that handle nothing don't reach the observer
+        assert_eq!(0, test.client_receive().length);
+        assert_eq!(ControlCounts::default(), test.client.control_counts);
+        assert_eq!(1, OBSERVED_NACKS.load(Ordering::SeqCst));
+
+        // the resend comes in with the update, a fragmented message with the call that got its last fragment
+        test.client.step(Duration::from_millis(10));
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(8, test.server_receive().length);
+        test.client_send_reliable(3, 3000);
+        assert_eq!(3000, test.server_receive().length);
+        assert_eq!(3, test.server.control_counts.fragments);
+        assert_eq!(3, test.server.control_counts.total);
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    network_address::NetworkAddress,
+    tachyon_socket::SocketReceiveResult,
+    transport::{Datagram, Transport},
+};
+
+// Outgoing side of a browser connection: a WebRTC data channel opened with ordered: false and maxRetransmits: 0, or a
+// WebTransport session's datagrams.  Implemented by the application on top of whatever WebRTC/WebTransport stack it
+// uses, signaling and session setup stay with the application too.  Both are already unreliable and unordered, which
+// is exactly what the protocol expects from UDP.
+pub trait DatagramChannel: Send {
+    fn send(&self, data: &[u8]) -> bool;
+
+    // datagrams larger than this are not sent, WebTransport datagrams are limited to the path mtu
+    fn max_datagram_size(&self) -> usize;
+}
+
+struct DataChannelState {
+    peers: FxHashMap<NetworkAddress, Box<dyn DatagramChannel>>,
+    incoming: VecDeque<Datagram>,
+    next_peer_id: u32,
+}
+
+// Transport over browser datagram channels so browser clients can talk to native Tachyon servers.  Clones share state:
+// install one clone with bind_transport/connect_transport and keep another to add peers and feed received messages in
+// from the channel's message callbacks, which may run on other threads.
+// Peers get synthetic addresses 0.0.0.0:<peer id>.  On a client the server is always the default address.
+#[derive(Clone)]
+pub struct DataChannelTransport {
+    state: Arc<Mutex<DataChannelState>>,
+}
+
+impl DataChannelTransport {
+    pub fn create() -> Self {
+        let state = DataChannelState {
+            peers: FxHashMap::default(),
+            incoming: VecDeque::new(),
+            next_peer_id: 0,
+        };
+        return DataChannelTransport {
+            state: Arc::new(Mutex::new(state)),
+        };
+    }
+
+    // server side, returns the address the peer shows up as in Tachyon
+    pub fn add_peer(&self, channel: Box<dyn DatagramChannel>) -> NetworkAddress {
+        let mut state = self.state.lock().unwrap();
+        state.next_peer_id += 1;
+        let mut address = NetworkAddress::default();
+        address.port = state.next_peer_id;
+        state.peers.insert(address, channel);
+        return address;
+    }
+
+    // client side, the one channel to the server
+    pub fn set_server(&self, channel: Box<dyn DatagramChannel>) {
+        self.state.lock().unwrap().peers.insert(NetworkAddress::default(), channel);
+    }
+
+    // on close, Tachyon's connection for the address times out/gets removed as with udp
+    pub fn remove_peer(&self, address: NetworkAddress) {
+        self.state.lock().unwrap().peers.remove(&address);
+    }
+
+    pub fn on_message(&self, address: NetworkAddress, data: &[u8]) {
+        let datagram = Datagram {
+            address,
+            data: data.to_vec(),
+        };
+        self.state.lock().unwrap().incoming.push_back(datagram);
+    }
+}
+
+impl Transport for DataChannelTransport {
+    fn send_to(&self, address: NetworkAddress, data: &[u8]) -> usize {
+        let state = self.state.lock().unwrap();
+        match state.peers.get(&address) {
+            Some(channel) => {
+                if data.len() > channel.max_datagram_size() || !channel.send(data) {
+                    return 0;
+                }
+                return data.len();
+            }
+            None => {
+                return 0;
+            }
+        }
+    }
+
+    fn receive(&mut self, data: &mut [u8]) -> SocketReceiveResult {
+        let mut state = self.state.lock().unwrap();
+        while let Some(datagram) = state.incoming.pop_front() {
+            // late messages from a closed channel
+            if !state.peers.contains_key(&datagram.address) {
+                continue;
+            }
+            if datagram.data.len() > data.len() {
+                return SocketReceiveResult::Error;
+            }
+            data[0..datagram.data.len()].copy_from_slice(&datagram.data);
+            return SocketReceiveResult::Success {
+                bytes_received: datagram.data.len(),
+                network_address: datagram.address,
+            };
+        }
+        return SocketReceiveResult::Empty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{network_address::NetworkAddress, Tachyon, TachyonConfig};
+
+    use super::{DataChannelTransport, DatagramChannel};
+
+    // stands in for the browser side, delivers straight into the other transport
+    struct LoopbackChannel {
+        remote: DataChannelTransport,
+        remote_address: NetworkAddress,
+        max_size: usize,
+    }
+
+    impl DatagramChannel for LoopbackChannel {
+        fn send(&self, data: &[u8]) -> bool {
+            self.remote.on_message(self.remote_address, data);
+            return true;
+        }
+
+        fn max_datagram_size(&self) -> usize {
+            return self.max_size;
+        }
+    }
+
+    fn connect_client(server_transport: &DataChannelTransport, max_size: usize) -> Tachyon {
+        let client_transport = DataChannelTransport::create();
+        let peer_address = server_transport.add_peer(Box::new(LoopbackChannel {
+            remote: client_transport.clone(),
+            remote_address: NetworkAddress::default(),
+            max_size,
+        }));
+        client_transport.set_server(Box::new(LoopbackChannel {
+            remote: server_transport.clone(),
+            remote_address: peer_address,
+            max_size,
+        }));
+
+        let mut client = Tachyon::create(TachyonConfig::default());
+        client.connect_transport(Box::new(client_transport));
+        return client;
+    }
+
+    #[test]
+    fn test_data_channel_transport() {
+        let server_transport = DataChannelTransport::create();
+        let mut server = Tachyon::create(TachyonConfig::default());
+        server.bind_transport(Box::new(server_transport.clone()));
+
+        let mut client1 = connect_client(&server_transport, 16 * 1024);
+        let mut client2 = connect_client(&server_transport, 1200);
+
+        let mut send_buffer: Vec<u8> = vec![0; 4096];
+        let mut receive_buffer: Vec<u8> = vec![0; 8192];
+
+        send_buffer[0] = 1;
+        assert_eq!(0, client1.send_reliable(1, NetworkAddress::default(), &send_buffer, 64).error);
+        send_buffer[0] = 2;
+        assert_eq!(0, client2.send_unreliable(NetworkAddress::default(), &send_buffer, 64).error);
+
+        let res = server.receive_loop(&mut receive_buffer);
+        assert_eq!(64, res.length);
+        assert_eq!(1, receive_buffer[0]);
+        assert_eq!(1, res.address.port);
+        let res = server.receive_loop(&mut receive_buffer);
+        assert_eq!(2, receive_buffer[0]);
+        assert_eq!(2, res.address.port);
+
+        // reply to the second client only
+        let address = res.address;
+        send_buffer[0] = 3;
+        assert_eq!(0, server.send_reliable(1, address, &send_buffer, 64).error);
+        let res = client2.receive_loop(&mut receive_buffer);
+        assert_eq!(64, res.length);
+        assert_eq!(3, receive_buffer[0]);
+        assert_eq!(0, client1.receive_loop(&mut receive_buffer).length);
+
+        // too large for the channel
+        let res = client2.send_unreliable(NetworkAddress::default(), &send_buffer, 2000);
+        assert_eq!(0, res.sent_len);
+
+        server_transport.remove_peer(address);
+        assert_eq!(0, server.send_unreliable(address, &send_buffer, 8).sent_len);
+    }
+}
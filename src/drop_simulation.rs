@@ -0,0 +1,168 @@
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use super::header::{channel_message_start, MESSAGE_TYPE_RELIABLE};
+
+// what TachyonSocket always seeded with, seed 0 keeps it so existing tests lose the same datagrams
+pub const DROP_SEED_DEFAULT: u64 = 32634;
+
+// Simulated loss on top of TachyonConfig.drop_packet_chance, which stays the inbound rate.  drop_reliable_only applies
+// to both directions.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct DropSimulationConfig {
+    // 0 for DROP_SEED_DEFAULT.  Each direction gets its own generator from it, so changing one rate doesn't change
+    // which datagrams the other drops.
+    pub seed: u64,
+    // percent of datagrams dropped on send, before anything reaches the socket
+    pub outbound_chance: u64,
+    // after a random drop the next this many datagrams in the same direction are dropped too, 0 for single drops.
+    // Models the bursts real links lose in.
+    pub burst_length: u32,
+}
+
+impl DropSimulationConfig {
+    pub fn seed(&self) -> u64 {
+        if self.seed == 0 {
+            return DROP_SEED_DEFAULT;
+        }
+        return self.seed;
+    }
+}
+
+// One direction's drops
+pub struct DropSimulator {
+    rng: StdRng,
+    pub chance: u64,
+    pub reliable_only: bool,
+    pub burst_length: u32,
+    burst_left: u32,
+    pub dropped: u64,
+}
+
+impl DropSimulator {
+    pub fn create(seed: u64) -> Self {
+        let simulator = DropSimulator {
+            rng: SeedableRng::seed_from_u64(seed),
+            chance: 0,
+            reliable_only: false,
+            burst_length: 0,
+            burst_left: 0,
+            dropped: 0,
+        };
+        return simulator;
+    }
+
+    // Data is the datagram as the protocol sees it, from the tachyon header on.  Nothing is drawn for datagrams that
+    // can't be dropped, so what else goes by doesn't change which reliable ones are.
+    pub fn should_drop(&mut self, data: &[u8]) -> bool {
+        if self.chance == 0 || data.is_empty() {
+            return false;
+        }
+        if self.reliable_only && data[channel_message_start(data)] != MESSAGE_TYPE_RELIABLE {
+            return false;
+        }
+
+        if self.burst_left > 0 {
+            self.burst_left -= 1;
+            self.dropped += 1;
+            return true;
+        }
+
+        let r = self.rng.gen_range(1..100);
+        if r <= self.chance {
+            self.burst_left = self.burst_length;
+            self.dropped += 1;
+            return true;
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::header::{MESSAGE_TYPE_NACK, MESSAGE_TYPE_NONE, MESSAGE_TYPE_RELIABLE, MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_WIDE_CHANNEL};
+
+    use super::{DropSimulationConfig, DropSimulator, DROP_SEED_DEFAULT};
+
+    fn drops(simulator: &mut DropSimulator, count: usize) -> Vec<bool> {
+        return (0..count).map(|_| simulator.should_drop(&[MESSAGE_TYPE_RELIABLE])).collect();
+    }
+
+    #[test]
+    fn test_seed() {
+        assert_eq!(DROP_SEED_DEFAULT, DropSimulationConfig::default().seed());
+
+        let mut first = DropSimulator::create(7);
+        let mut second = DropSimulator::create(7);
+        let mut other = DropSimulator::create(8);
+        first.chance = 20;
+        second.chance = 20;
+        other.chance = 20;
+        let pattern = drops(&mut first, 1000);
+        assert_eq!(pattern, drops(&mut second, 1000));
+        assert_ne!(pattern, drops(&mut other, 1000));
+
+        let dropped = pattern.iter().filter(|dropped| **dropped).count();
+        assert!(dropped > 120 && dropped < 280);
+        assert_eq!(dropped as u64, first.dropped);
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut simulator = DropSimulator::create(DROP_SEED_DEFAULT);
+        simulator.chance = 5;
+        simulator.burst_length = 3;
+        let pattern = drops(&mut simulator, 2000);
+
+        // every run of drops that ended is at least a random drop and its burst
+        let mut run = 0;
+        for dropped in pattern.iter() {
+            if *dropped {
+                run += 1;
+            } else {
+                assert!(run == 0 || run >= 4);
+                run = 0;
+            }
+        }
+        assert!(simulator.dropped > 0);
+    }
+
+    #[test]
+    fn test_reliable_only() {
+        let mut simulator = DropSimulator::create(DROP_SEED_DEFAULT);
+        simulator.chance = 99;
+        simulator.reliable_only = true;
+        simulator.burst_length = 2;
+        for _ in 0..100 {
+            assert!(!simulator.should_drop(&[MESSAGE_TYPE_UNRELIABLE]));
+        }
+        assert!(simulator.should_drop(&[MESSAGE_TYPE_RELIABLE]));
+        // a burst skips what it can't drop
+        assert!(!simulator.should_drop(&[MESSAGE_TYPE_UNRELIABLE]));
+        assert!(simulator.should_drop(&[MESSAGE_TYPE_RELIABLE]));
+        assert!(simulator.should_drop(&[MESSAGE_TYPE_RELIABLE]));
+        assert!(!simulator.should_drop(&[]));
+    }
+
+    #[test]
+    fn test_other_types_dont_draw() {
+        let mut alone = DropSimulator::create(DROP_SEED_DEFAULT);
+        let mut mixed = DropSimulator::create(DROP_SEED_DEFAULT);
+        for simulator in [&mut alone, &mut mixed] {
+            simulator.chance = 30;
+            simulator.reliable_only = true;
+        }
+
+        let mut mixed_pattern: Vec<bool> = Vec::new();
+        for _ in 0..500 {
+            for message_type in [MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_NACK, MESSAGE_TYPE_NONE] {
+                assert!(!mixed.should_drop(&[message_type, 1, 2, 3]));
+            }
+            // the type behind a wide channel header is what counts
+            assert!(!mixed.should_drop(&[MESSAGE_TYPE_WIDE_CHANNEL, 1, MESSAGE_TYPE_NONE, 1, 2, 3]));
+            mixed_pattern.push(mixed.should_drop(&[MESSAGE_TYPE_RELIABLE, 1, 2, 3]));
+        }
+        assert_eq!(drops(&mut alone, 500), mixed_pattern);
+    }
+}
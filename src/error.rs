@@ -0,0 +1,181 @@
+use std::{error::Error, fmt};
+
+use super::{
+    receive_result::{TachyonReceiveResult, RECEIVE_ERROR_CHANNEL, RECEIVE_ERROR_HANDLE, RECEIVE_ERROR_MESSAGE_SIZE, RECEIVE_ERROR_UNKNOWN},
+    TachyonSendResult, SEND_ERROR_CHANNEL, SEND_ERROR_FRAGMENT, SEND_ERROR_HANDLE, SEND_ERROR_IDENTITY, SEND_ERROR_LENGTH,
+    SEND_ERROR_NOT_CONNECTED, SEND_ERROR_PIPELINE, SEND_ERROR_SOCKET, SEND_ERROR_UNKNOWN, SEND_ERROR_WINDOW_FULL,
+};
+
+// The SEND_ERROR_x codes as an error type, for callers that want a Result instead of checking result.error.  The
+// result structs stay as they are for the ffi, to_result converts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TachyonSendError {
+    Socket,
+    Channel { channel_id: u16 },
+    Fragment,
+    Unknown,
+    Length,
+    Identity,
+    Pipeline,
+    WindowFull { channel_id: u16 },
+    Handle,
+    NotConnected,
+}
+
+impl TachyonSendError {
+    // None for 0, codes we don't know are Unknown
+    pub fn from_code(code: u32, channel_id: u16) -> Option<Self> {
+        let error = match code {
+            0 => return None,
+            SEND_ERROR_SOCKET => TachyonSendError::Socket,
+            SEND_ERROR_CHANNEL => TachyonSendError::Channel { channel_id },
+            SEND_ERROR_FRAGMENT => TachyonSendError::Fragment,
+            SEND_ERROR_LENGTH => TachyonSendError::Length,
+            SEND_ERROR_IDENTITY => TachyonSendError::Identity,
+            SEND_ERROR_PIPELINE => TachyonSendError::Pipeline,
+            SEND_ERROR_WINDOW_FULL => TachyonSendError::WindowFull { channel_id },
+            SEND_ERROR_HANDLE => TachyonSendError::Handle,
+            SEND_ERROR_NOT_CONNECTED => TachyonSendError::NotConnected,
+            _ => TachyonSendError::Unknown,
+        };
+        return Some(error);
+    }
+
+    pub fn code(&self) -> u32 {
+        match self {
+            TachyonSendError::Socket => return SEND_ERROR_SOCKET,
+            TachyonSendError::Channel { .. } => return SEND_ERROR_CHANNEL,
+            TachyonSendError::Fragment => return SEND_ERROR_FRAGMENT,
+            TachyonSendError::Unknown => return SEND_ERROR_UNKNOWN,
+            TachyonSendError::Length => return SEND_ERROR_LENGTH,
+            TachyonSendError::Identity => return SEND_ERROR_IDENTITY,
+            TachyonSendError::Pipeline => return SEND_ERROR_PIPELINE,
+            TachyonSendError::WindowFull { .. } => return SEND_ERROR_WINDOW_FULL,
+            TachyonSendError::Handle => return SEND_ERROR_HANDLE,
+            TachyonSendError::NotConnected => return SEND_ERROR_NOT_CONNECTED,
+        }
+    }
+}
+
+impl fmt::Display for TachyonSendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TachyonSendError::Socket => return f.write_str("socket is not open"),
+            TachyonSendError::Channel { channel_id } => return write!(f, "channel {} is not configured for the address", channel_id),
+            TachyonSendError::Fragment => return f.write_str("message could not be fragmented"),
+            TachyonSendError::Unknown => return f.write_str("unknown send error"),
+            TachyonSendError::Length => return f.write_str("message is empty or too long"),
+            TachyonSendError::Identity => return f.write_str("no connection for the identity"),
+            TachyonSendError::Pipeline => return f.write_str("pipeline dropped the message"),
+            TachyonSendError::WindowFull { channel_id } => return write!(f, "send window of channel {} is full", channel_id),
+            TachyonSendError::Handle => return f.write_str("connection handle is stale"),
+            TachyonSendError::NotConnected => return f.write_str("not connected yet"),
+        }
+    }
+}
+
+impl Error for TachyonSendError {}
+
+// RECEIVE_ERROR_x, see TachyonSendError
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TachyonReceiveError {
+    Unknown,
+    Channel { channel_id: u16 },
+    Handle,
+    MessageTooLarge { channel_id: u16 },
+}
+
+impl TachyonReceiveError {
+    pub fn from_code(code: u32, channel_id: u16) -> Option<Self> {
+        let error = match code {
+            0 => return None,
+            RECEIVE_ERROR_CHANNEL => TachyonReceiveError::Channel { channel_id },
+            RECEIVE_ERROR_HANDLE => TachyonReceiveError::Handle,
+            RECEIVE_ERROR_MESSAGE_SIZE => TachyonReceiveError::MessageTooLarge { channel_id },
+            _ => TachyonReceiveError::Unknown,
+        };
+        return Some(error);
+    }
+
+    pub fn code(&self) -> u32 {
+        match self {
+            TachyonReceiveError::Unknown => return RECEIVE_ERROR_UNKNOWN,
+            TachyonReceiveError::Channel { .. } => return RECEIVE_ERROR_CHANNEL,
+            TachyonReceiveError::Handle => return RECEIVE_ERROR_HANDLE,
+            TachyonReceiveError::MessageTooLarge { .. } => return RECEIVE_ERROR_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for TachyonReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TachyonReceiveError::Unknown => return f.write_str("unknown receive error"),
+            TachyonReceiveError::Channel { channel_id } => return write!(f, "received on channel {} which is not configured", channel_id),
+            TachyonReceiveError::Handle => return f.write_str("connection handle is stale"),
+            TachyonReceiveError::MessageTooLarge { channel_id } => return write!(f, "message on channel {} didn't fit the receive buffer", channel_id),
+        }
+    }
+}
+
+impl Error for TachyonReceiveError {}
+
+impl TachyonSendResult {
+    // sent_len, channel errors carry the channel id from header.channel
+    pub fn to_result(&self) -> Result<u32, TachyonSendError> {
+        match TachyonSendError::from_code(self.error, self.header.channel) {
+            Some(error) => return Err(error),
+            None => return Ok(self.sent_len),
+        }
+    }
+}
+
+impl TachyonReceiveResult {
+    // length, 0 when there was nothing to receive
+    pub fn to_result(&self) -> Result<u32, TachyonReceiveError> {
+        match TachyonReceiveError::from_code(self.error, self.channel) {
+            Some(error) => return Err(error),
+            None => return Ok(self.length),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTest, SEND_ERROR_WINDOW_FULL};
+
+    use super::{TachyonReceiveError, TachyonSendError};
+
+    #[test]
+    fn test_codes() {
+        for code in 1..=10 {
+            let error = TachyonSendError::from_code(code, 3).unwrap();
+            assert_eq!(code, error.code());
+            assert!(!error.to_string().is_empty());
+        }
+        assert!(TachyonSendError::from_code(0, 3).is_none());
+        assert_eq!(Some(TachyonSendError::Unknown), TachyonSendError::from_code(99, 3));
+        assert_eq!("send window of channel 3 is full", TachyonSendError::from_code(SEND_ERROR_WINDOW_FULL, 3).unwrap().to_string());
+
+        for code in 1..=4 {
+            assert_eq!(code, TachyonReceiveError::from_code(code, 3).unwrap().code());
+        }
+
+        let boxed: Box<dyn std::error::Error> = Box::new(TachyonSendError::Length);
+        assert_eq!("message is empty or too long", boxed.to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_context() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        let result = test.client.send_reliable(9, NetworkAddress::default(), &[1; 8], 8);
+        assert_eq!(Err(TachyonSendError::Channel { channel_id: 9 }), result.to_result());
+        assert_eq!(Ok(8 + crate::header::TACHYON_HEADER_SIZE as u32), test.client_send_reliable(1, 8).to_result());
+        assert_eq!(Ok(8), test.server_receive().to_result());
+    }
+}
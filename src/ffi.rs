@@ -1,7 +1,15 @@
 
-use crate::tachyon::*;
+use crate::*;
 
+use super::address_filter::{AddressFilterStats, AddressRange};
+use super::channel_id::ConfiguredChannel;
+use super::connection::LinkedIdentity;
+use super::control_observer::{ControlCounts, ControlObserverCallback};
+use super::group::{GroupSendResult, GroupStats};
 use super::pool::SendTarget;
+use super::relay::RelayStats;
+use super::security::{SecurityEvent, SecurityEventCallback};
+use super::topic::TopicSendResult;
 
 
 #[no_mangle]
@@ -37,9 +45,10 @@ pub extern "C" fn destroy_tachyon(tachyon: *mut Tachyon) {
 pub extern "C" fn bind_socket(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
     let tachyon = unsafe { &mut *tachyon_ptr };
     let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
-    match tachyon.bind(address) {
-        true => return 1,
-        false => return -1,
+    // negative SOCKET_ERROR_x on failure
+    match tachyon.try_bind(address) {
+        Ok(_) => return 1,
+        Err(error) => return -error.code(),
     }
 }
 
@@ -50,22 +59,58 @@ pub extern "C" fn connect_socket(
 ) -> i32 {
     let tachyon = unsafe { &mut *tachyon_ptr };
     let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
-    match tachyon.connect(address) {
-        true => return 1,
-        false => return -1,
+    match tachyon.try_connect(address) {
+        Ok(_) => return 1,
+        Err(error) => return -error.code(),
     }
 }
 
+// the last failed send's SOCKET_ERROR_x and os error code, 0 if there wasn't one.  Clears it.
+#[no_mangle]
+pub extern "C" fn take_socket_error(tachyon_ptr: *mut Tachyon, os_error: *mut i32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let error = tachyon.take_socket_error();
+    if !os_error.is_null() {
+        unsafe { *os_error = error.map(|e| e.raw_os_error()).unwrap_or(0) };
+    }
+    return error.map(|e| e.code()).unwrap_or(0);
+}
+
 #[no_mangle]
 pub extern "C" fn configure_channel(tachyon_ptr: *mut Tachyon, channel_id: u8, config_ptr: *const ChannelConfig) -> i32 {
     let tachyon = unsafe { &mut *tachyon_ptr };
     let channel_config = unsafe { &*config_ptr };
-    let res = tachyon.configure_channel(channel_id, *channel_config);
-    if res {
-        return 1;
-    } else {
-        return -1;
+    // negative ChannelConfigError on failure
+    match tachyon.try_configure_channel(channel_id as u16, *channel_config) {
+        Ok(_) => return 1,
+        Err(error) => return -(error as i32),
+    }
+}
+
+// Fills up to max configured channels by id, returns how many there are.  Call with max 0 to size the array.
+#[no_mangle]
+pub extern "C" fn tachyon_get_configured_channels(tachyon_ptr: *mut Tachyon, channels_ptr: *mut ConfiguredChannel, max: u32) -> i32 {
+    let tachyon = unsafe { &*tachyon_ptr };
+    let channels = tachyon.configured_channels();
+    let count = channels.len().min(max as usize);
+    if count > 0 {
+        let dst = unsafe { std::slice::from_raw_parts_mut(channels_ptr, count) };
+        dst.copy_from_slice(&channels[0..count]);
     }
+    return channels.len() as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn channel_config_digest(tachyon_ptr: *mut Tachyon) -> u32 {
+    let tachyon = unsafe { &*tachyon_ptr };
+    return tachyon.channel_config_digest();
+}
+
+#[no_mangle]
+pub extern "C" fn configure_unreliable_channel(tachyon_ptr: *mut Tachyon, config_ptr: *const UnreliableChannelConfig) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let config = unsafe { &*config_ptr };
+    tachyon.configure_unreliable_channel(*config);
 }
 
 pub fn copy_send_result(from: TachyonSendResult, to: *mut TachyonSendResult) {
@@ -80,12 +125,53 @@ pub fn copy_send_result(from: TachyonSendResult, to: *mut TachyonSendResult) {
 pub extern "C" fn send_to_target(tachyon_ptr: *mut Tachyon, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
     let tachyon = unsafe { &mut *tachyon_ptr };
     let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
-    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+
+    let result = tachyon.send_to_target(channel as u16, target, slice, length as usize);
+    copy_send_result(result, ret);
+}
 
-    let result = tachyon.send_to_target(channel, target, slice, length as usize);
+#[no_mangle]
+pub extern "C" fn send_to_handle(tachyon_ptr: *mut Tachyon, channel: u8, handle: ConnectionHandle, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+
+    let result = tachyon.send_to_handle(channel as u16, handle, slice, length as usize);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn send_reliable_ttl(tachyon_ptr: *mut Tachyon, channel: u8, naddress: *const NetworkAddress, data: *mut u8, length: i32, ttl_millis: u32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+
+    let result = tachyon.send_reliable_ttl(channel as u16, address, slice, length as usize, ttl_millis);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn send_reliable_flags(tachyon_ptr: *mut Tachyon, channel: u8, naddress: *const NetworkAddress, data: *mut u8, length: i32, flags: u32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+
+    let result = tachyon.send_reliable_flags(channel as u16, address, slice, length as usize, flags);
     copy_send_result(result, ret);
 }
 
+#[no_mangle]
+pub extern "C" fn register_abandoned_callback(tachyon_ptr: *mut Tachyon, abandoned_callback: Option<AbandonedCallback>) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_abandoned_callback(abandoned_callback);
+}
+
+#[no_mangle]
+pub extern "C" fn register_gap_skipped_callback(tachyon_ptr: *mut Tachyon, gap_skipped_callback: Option<GapSkippedCallback>) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_gap_skipped_callback(gap_skipped_callback);
+}
+
 #[no_mangle]
 pub extern "C" fn receive(tachyon_ptr: *mut Tachyon, data: *mut u8, receive_buffer_len: u32, ret: *mut TachyonReceiveResult) {
     let tachyon = unsafe { &mut *tachyon_ptr };
@@ -97,9 +183,25 @@ pub extern "C" fn receive(tachyon_ptr: *mut Tachyon, data: *mut u8, receive_buff
         (*ret).address = result.address;
         (*ret).length = result.length;
         (*ret).error = result.error;
+        (*ret).identity_id = result.identity_id;
+        (*ret).session_id = result.session_id;
     }
 }
 
+#[no_mangle]
+pub extern "C" fn tachyon_enqueue_reliable(tachyon_ptr: *mut Tachyon, channel: u8, naddress: *const NetworkAddress, data: *mut u8, length: i32, priority: u8, deadline_millis: u32) -> u32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    return tachyon.enqueue_reliable(channel as u16, address, slice, priority, deadline_millis);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_flush_outgoing(tachyon_ptr: *mut Tachyon) -> u32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    return tachyon.flush_outgoing();
+}
+
 #[no_mangle]
 pub extern "C" fn tachyon_update(tachyon_ptr: *mut Tachyon) {
     let tachyon = unsafe { &mut *tachyon_ptr };
@@ -117,6 +219,104 @@ pub extern "C" fn tachyon_get_connection(tachyon_ptr: *mut Tachyon, naddress: *c
     }
 }
 
+#[no_mangle]
+pub extern "C" fn tachyon_set_connection_user_data(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, user_data: u64) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.set_connection_user_data(address, user_data) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// a ConnectionState, Closed if there is no connection for the address
+#[no_mangle]
+pub extern "C" fn tachyon_get_connection_state(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> u8 {
+    let tachyon = unsafe { &*tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    return tachyon.get_connection_state(address) as u8;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_address_stats(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, ret: *mut AddressStats) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match tachyon.get_address_stats(address) {
+        Some(stats) => {
+            unsafe {
+                (*ret) = stats;
+            }
+            return 1;
+        }
+        None => {
+            return -1;
+        }
+    }
+}
+
+// The newest max of the channel's traced events, oldest first.  sequence -1 for all of them.  Returns how many were copied,
+// -1 if the channel doesn't exist or isn't traced.
+#[no_mangle]
+pub extern "C" fn tachyon_get_sequence_trace(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, channel: u8, sequence: i32, events_ptr: *mut SequenceEvent, max: u32) -> i32 {
+    let tachyon = unsafe { &*tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let sequence = if sequence < 0 { None } else { Some(sequence as u16) };
+    match tachyon.get_sequence_trace(address, channel as u16, sequence) {
+        Some(events) => {
+            let count = events.len().min(max as usize);
+            if count > 0 {
+                let dst = unsafe { std::slice::from_raw_parts_mut(events_ptr, count) };
+                dst.copy_from_slice(&events[events.len() - count..]);
+            }
+            return count as i32;
+        }
+        None => {
+            return -1;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_connection_quality(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, quality: *mut ConnectionQuality) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match tachyon.get_connection_quality(address) {
+        Some(connection_quality) => {
+            unsafe {
+                (*quality) = connection_quality;
+            }
+            return 1;
+        }
+        None => {
+            return -1;
+        }
+    }
+}
+
+// Fills up to max linked identities ordered by id, returns how many there are.  Call with max 0 to size the array.
+#[no_mangle]
+pub extern "C" fn tachyon_get_linked_identities(tachyon_ptr: *mut Tachyon, identities_ptr: *mut LinkedIdentity, max: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let identities = tachyon.get_linked_identities();
+    let count = identities.len().min(max as usize);
+    if count > 0 {
+        let dst = unsafe { std::slice::from_raw_parts_mut(identities_ptr, count) };
+        dst.copy_from_slice(&identities[0..count]);
+    }
+    return identities.len() as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_is_identity_linked(tachyon_ptr: *mut Tachyon, id: u32) -> i32 {
+    let tachyon = unsafe { &*tachyon_ptr };
+    if tachyon.is_identity_linked(id) {
+        return 1;
+    } else {
+        return 0;
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn tachyon_get_connection_by_identity(tachyon_ptr: *mut Tachyon, id: u32, connection: *mut Connection) {
     let tachyon = unsafe { &mut *tachyon_ptr };
@@ -140,25 +340,115 @@ pub extern "C" fn tachyon_get_config(tachyon_ptr: *mut Tachyon, config: *mut Tac
 pub extern "C" fn set_identity(tachyon_ptr: *mut Tachyon, id: u32, session_id: u32, on_self: u32) {
     let tachyon = unsafe { &mut *tachyon_ptr };
     if on_self == 1 {
-        tachyon.identity.id = id;
-        tachyon.identity.session_id = session_id;
+        tachyon.set_client_identity(id, session_id);
     } else {
         tachyon.set_identity(id, session_id);
     }
 }
 
+#[no_mangle]
+pub extern "C" fn tachyon_ban_identity(tachyon_ptr: *mut Tachyon, id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.ban_identity(id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_unban_identity(tachyon_ptr: *mut Tachyon, id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.unban_identity(id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// -1 if the client hasn't been denied
+#[no_mangle]
+pub extern "C" fn tachyon_get_link_denial(tachyon_ptr: *mut Tachyon, ret: *mut LinkDenial) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.link_denial.reason == 0 {
+        return -1;
+    }
+    unsafe {
+        (*ret) = tachyon.link_denial;
+    }
+    return 1;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_link_payload(tachyon_ptr: *mut Tachyon, data: *const u8, length: i32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    if tachyon.set_link_payload(slice) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_link_reply_payload(tachyon_ptr: *mut Tachyon, id: u32, data: *const u8, length: i32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    if tachyon.set_link_reply_payload(id, slice) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_link_denied_payload(tachyon_ptr: *mut Tachyon, data: *const u8, length: i32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    if tachyon.set_link_denied_payload(slice) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// copies up to length bytes of the address's link payload into data, returns how many or -1 if there is none
+#[no_mangle]
+pub extern "C" fn tachyon_get_link_payload(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, data: *mut u8, length: i32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match tachyon.get_link_payload(address) {
+        Some(payload) => {
+            let len = std::cmp::min(payload.len(), length as usize);
+            let slice = unsafe { std::slice::from_raw_parts_mut(data, len) };
+            slice.copy_from_slice(&payload[..len]);
+            return len as i32;
+        }
+        None => {
+            return -1;
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn get_stats(tachyon_ptr: *mut Tachyon, stats: *mut TachyonStats) {
     let tachyon = unsafe { &mut *tachyon_ptr };
     let combined = tachyon.get_combined_stats();
     unsafe {
         (*stats).channel_stats = combined.channel_stats;
+        (*stats).unreliable_stats = combined.unreliable_stats;
         (*stats).packets_dropped = combined.packets_dropped;
         (*stats).unreliable_sent = combined.unreliable_sent;
         (*stats).unreliable_received = combined.unreliable_received;
     }
 }
 
+#[no_mangle]
+pub extern "C" fn reset_stats(tachyon_ptr: *mut Tachyon) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.reset_stats();
+}
+
 
 #[no_mangle]
 pub extern "C" fn create_unreliable_sender(tachyon_ptr: *mut Tachyon) -> *mut UnreliableSender {
@@ -181,8 +471,274 @@ pub extern "C" fn destroy_unreliable_sender(sender_ptr: *mut UnreliableSender) {
 pub extern "C" fn unreliable_sender_send(sender_ptr: *mut UnreliableSender, naddress: *const NetworkAddress, data_ptr: *mut u8, length: i32, ret: *mut TachyonSendResult) {
     let sender = unsafe { &mut *sender_ptr };
     let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
-    let data = unsafe { std::slice::from_raw_parts_mut(data_ptr, length as usize) };
+    let data = unsafe { std::slice::from_raw_parts(data_ptr, length as usize) };
     let result = sender.send(address, data, length as usize);
     copy_send_result(result, ret);
 }
 
+#[no_mangle]
+pub extern "C" fn unreliable_sender_send_to_identity(sender_ptr: *mut UnreliableSender, identity_id: u32, data_ptr: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let sender = unsafe { &mut *sender_ptr };
+    let data = unsafe { std::slice::from_raw_parts(data_ptr, length as usize) };
+    let result = sender.send_to_identity(identity_id, data, length as usize);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_create_group(tachyon_ptr: *mut Tachyon, group_id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.create_group(group_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_remove_group(tachyon_ptr: *mut Tachyon, group_id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.remove_group(group_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_join_group(tachyon_ptr: *mut Tachyon, group_id: u32, identity_id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.join_group(group_id, identity_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_leave_group(tachyon_ptr: *mut Tachyon, group_id: u32, identity_id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.leave_group(group_id, identity_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_send_to_group(tachyon_ptr: *mut Tachyon, channel: u8, group_id: u32, data: *mut u8, length: i32, ret: *mut GroupSendResult) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    match tachyon.send_to_group(channel as u16, group_id, slice, length as usize) {
+        Some(result) => {
+            unsafe { *ret = result };
+            return 1;
+        }
+        None => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_group_stats(tachyon_ptr: *mut Tachyon, group_id: u32, stats: *mut GroupStats) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    match tachyon.get_group_stats(group_id) {
+        Some(group_stats) => {
+            unsafe { *stats = group_stats };
+            return 1;
+        }
+        None => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_subscribe(tachyon_ptr: *mut Tachyon, topic: u32) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.subscribe(topic);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_unsubscribe(tachyon_ptr: *mut Tachyon, topic: u32) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.unsubscribe(topic);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_is_subscribed(tachyon_ptr: *mut Tachyon, topic: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.is_subscribed(topic) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_publish_to_topic(tachyon_ptr: *mut Tachyon, channel: u8, topic: u32, data: *mut u8, length: i32, ret: *mut TopicSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    let result = tachyon.publish_to_topic(channel as u16, topic, slice, length as usize);
+    unsafe { *ret = result };
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_is_muted(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.is_muted(address) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_unmute(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.unmute(address) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_add_peer(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.add_peer(address) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_remove_peer(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.remove_peer(address) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// list is ADDRESS_LIST_ALLOW or ADDRESS_LIST_DENY, prefix 32 for just the one address
+#[no_mangle]
+pub extern "C" fn tachyon_address_filter_add(tachyon_ptr: *mut Tachyon, list: u8, naddress: *const NetworkAddress, prefix: u8) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match AddressRange::create(address, prefix) {
+        Some(range) if tachyon.address_filter.add(list, range) => return 1,
+        _ => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_address_filter_remove(tachyon_ptr: *mut Tachyon, list: u8, naddress: *const NetworkAddress, prefix: u8) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match AddressRange::create(address, prefix) {
+        Some(range) if tachyon.address_filter.remove(list, range) => return 1,
+        _ => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_address_filter_is_allowed(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.address_filter.is_allowed(address) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_address_filter_stats(tachyon_ptr: *mut Tachyon, ret: *mut AddressFilterStats) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let stats = tachyon.address_filter.stats();
+    unsafe { *ret = stats };
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_security_event_callback(tachyon_ptr: *mut Tachyon, callback: Option<SecurityEventCallback>) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_security_event_callback(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_security_event_capacity(tachyon_ptr: *mut Tachyon, capacity: u32) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_security_event_capacity(capacity as usize);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_control_observer(tachyon_ptr: *mut Tachyon, callback: Option<ControlObserverCallback>) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_control_observer(callback);
+}
+
+// the last receive call's
+#[no_mangle]
+pub extern "C" fn tachyon_get_control_counts(tachyon_ptr: *mut Tachyon, ret: *mut ControlCounts) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    unsafe { *ret = tachyon.control_counts };
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_enable_relay(tachyon_ptr: *mut Tachyon, channel: u8) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.enable_relay(channel) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_set_relay_route(tachyon_ptr: *mut Tachyon, identity_id: u32, naddress: *const NetworkAddress) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    tachyon.set_relay_route(identity_id, address);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_remove_relay_route(tachyon_ptr: *mut Tachyon, identity_id: u32) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.remove_relay_route(identity_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_send_relayed(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, identity_id: u32, channel: u8, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+
+    let result = tachyon.send_relayed(address, identity_id, channel, slice);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_relay_stats(tachyon_ptr: *mut Tachyon, ret: *mut RelayStats) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    unsafe { *ret = tachyon.relay.stats };
+}
+
+// 1 with the oldest queued event in ret, -1 if there are none
+#[no_mangle]
+pub extern "C" fn tachyon_take_security_event(tachyon_ptr: *mut Tachyon, ret: *mut SecurityEvent) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    match tachyon.take_security_event() {
+        Some(event) => {
+            unsafe { *ret = event };
+            return 1;
+        }
+        None => return -1,
+    }
+}
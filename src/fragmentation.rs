@@ -1,80 +1,153 @@
-use std::time::Instant;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use alloc::{vec, vec::Vec};
+use core::cmp::Reverse;
+use core::ops::Deref;
 
+use super::clock::*;
 use super::header::*;
 use super::send_buffer_manager::*;
 use super::sequence::*;
-use rustc_hash::FxHashMap;
+use super::time::{Duration, Instant};
+
+// keyed by fragment group.  There's no hasher to build a HashMap on without std.
+#[cfg(feature = "std")]
+pub type GroupMap<V> = rustc_hash::FxHashMap<u16, V>;
+#[cfg(not(feature = "std"))]
+pub type GroupMap<V> = alloc::collections::BTreeMap<u16, V>;
 
 const GROUP_EXPIRE: u128 = 5000;
 const FRAG_SIZE: usize = 1200;
+pub const MAX_ASSEMBLED_SIZE_DEFAULT: usize = 1024 * 1024;
+// fragment counts are 16 bit
+pub const MAX_ASSEMBLED_SIZE_MAX: usize = u16::MAX as usize * FRAG_SIZE;
+
+pub enum FragmentReceived {
+    // complete once every fragment of the group is in
+    Stored { complete: bool },
+    // the group would assemble to more than max_assembled_size.  first is set for the first fragment of it we see.
+    Rejected { group_length: usize, first: bool },
+    // bigger than we ever send or outside its group's sequences
+    Invalid,
+}
+
+impl FragmentReceived {
+    pub fn is_complete(&self) -> bool {
+        return matches!(self, FragmentReceived::Stored { complete: true });
+    }
+}
+
+// what the group's first fragment said about it, every other fragment has to agree
+pub struct FragmentGroup {
+    pub start_sequence: u16,
+    pub fragment_count: u16,
+    pub fragments: GroupMap<Vec<u8>>,
+}
+
+impl FragmentGroup {
+    fn matches(&self, header: &Header) -> bool {
+        return self.start_sequence == header.fragment_start_sequence && self.fragment_count == header.fragment_count;
+    }
+}
+
 pub struct Fragmentation {
     pub next_group: u16,
-    pub received: FxHashMap<u16, FxHashMap<u16, Vec<u8>>>,
-    pub received_at: FxHashMap<u16, Instant>,
+    // groups with more fragments than this takes are discarded before anything is stored
+    pub max_assembled_size: usize,
+    // discarded groups, so each is only reported once.  Expire like received groups.
+    pub rejected_groups: GroupMap<Instant>,
+    pub received: GroupMap<FragmentGroup>,
+    pub received_at: GroupMap<Instant>,
+    // groups by first receive time, oldest on top.  Entries for groups that were assembled or restarted are left in
+    // and skipped when they come up, received_at is the source of truth.
+    pub expire_queue: BinaryHeap<Reverse<(Instant, u16)>>,
+    pub clock: Arc<dyn Clock>,
 }
 
 impl Fragmentation {
     pub fn default() -> Self {
         let default = Fragmentation {
             next_group: 1,
-            received: FxHashMap::default(),
-            received_at: FxHashMap::default(),
+            max_assembled_size: MAX_ASSEMBLED_SIZE_DEFAULT,
+            rejected_groups: GroupMap::default(),
+            received: GroupMap::default(),
+            received_at: GroupMap::default(),
+            expire_queue: BinaryHeap::new(),
+            clock: default_clock(),
         };
         return default;
     }
 
     pub fn expire_groups(&mut self) {
-        let mut expired: Vec<u16> = Vec::new();
-        for (group, time) in &self.received_at {
-            if time.elapsed().as_millis() > GROUP_EXPIRE {
-                self.received.remove(group);
-                expired.push(*group);
+        self.expire_groups_at(self.clock.now());
+    }
+
+    // only looks at groups that are actually expired, so it's cheap enough to run every update
+    pub fn expire_groups_at(&mut self, now: Instant) {
+        let expire = Duration::from_millis(GROUP_EXPIRE as u64);
+        while let Some(Reverse((time, group))) = self.expire_queue.peek().copied() {
+            if now.saturating_duration_since(time) <= expire {
+                break;
+            }
+            self.expire_queue.pop();
+            if self.received_at.get(&group) == Some(&time) {
+                self.received_at.remove(&group);
+                self.received.remove(&group);
             }
         }
-        for group in expired {
-            self.received_at.remove(&group);
+        if !self.rejected_groups.is_empty() {
+            self.rejected_groups.retain(|_, time| now.saturating_duration_since(*time) <= expire);
         }
     }
 
+    fn track_group(&mut self, group: u16, time: Instant) {
+        self.received_at.insert(group, time);
+        self.expire_queue.push(Reverse((time, group)));
+    }
+
     pub fn should_fragment(length: usize) -> bool {
         return length >= FRAG_SIZE;
     }
 
+    pub fn fragment_count(length: usize) -> u16 {
+        return ((length + FRAG_SIZE - 1) / FRAG_SIZE) as u16;
+    }
+
     fn get_next_group(&mut self) -> u16 {
         self.next_group += 1;
-        if self.next_group >= std::u16::MAX - 1 {
+        if self.next_group >= u16::MAX - 1 {
             self.next_group = 1;
         }
         return self.next_group;
     }
 
-    fn get_group_length(map: &FxHashMap<u16, Vec<u8>>) -> usize {
+    fn get_group_length(group: &FragmentGroup) -> usize {
         let mut length = 0;
-        for (_, value) in map {
+        for value in group.fragments.values() {
             length += value.len() - TACHYON_FRAGMENTED_HEADER_SIZE;
         }
         return length;
     }
 
     pub fn assemble(&mut self, header: Header) -> Result<Vec<u8>, ()> {
-        let map = match self.received.get_mut(&header.fragment_group) {
+        let group = match self.received.get(&header.fragment_group) {
             Some(v) => v,
             None => {
                 return Err(());
             }
         };
-        if map.len() != header.fragment_count as usize {
+        if !group.matches(&header) || group.fragments.len() != header.fragment_count as usize {
             return Err(());
         }
 
-        let body_length = Fragmentation::get_group_length(map);
+        let body_length = Fragmentation::get_group_length(group);
 
         let mut buffer: Vec<u8> = vec![0; body_length];
         let mut offset = 0;
 
         let mut seq = header.fragment_start_sequence;
         for _ in 0..header.fragment_count {
-            match map.get_mut(&seq) {
+            match group.fragments.get(&seq) {
                 Some(fragment) => {
                     let frag_body_len = fragment.len() - TACHYON_FRAGMENTED_HEADER_SIZE;
                     let src = &fragment[TACHYON_FRAGMENTED_HEADER_SIZE..fragment.len()];
@@ -85,6 +158,7 @@ impl Fragmentation {
                 }
                 None => {
                     self.received.remove(&header.fragment_group);
+                    self.received_at.remove(&header.fragment_group);
                     return Err(());
                 }
             }
@@ -92,43 +166,103 @@ impl Fragmentation {
         }
 
         self.received.remove(&header.fragment_group);
+        self.received_at.remove(&header.fragment_group);
         return Ok(buffer);
     }
 
-    pub fn receive_fragment(&mut self, data: &[u8], length: usize) -> (bool, bool) {
-        let header = Header::read_fragmented(data);
-        if !self.received.contains_key(&header.fragment_group) {
-            self.received.insert(header.fragment_group, FxHashMap::default());
-            self.received_at.insert(header.fragment_group, Instant::now());
+    pub fn receive_fragment(&mut self, data: &[u8], length: usize) -> FragmentReceived {
+        let header = match Header::try_read_fragmented(&data[0..length]) {
+            Some(header) => header,
+            None => return FragmentReceived::Invalid,
+        };
+        if !Fragmentation::is_valid_fragment(&header, length) {
+            return FragmentReceived::Invalid;
         }
-        if let Some(map) = self.received.get_mut(&header.fragment_group) {
-            let slice = &data[0..length as usize];
 
-            let mut fragment: Vec<u8> = vec![0; length];
-            fragment[..].copy_from_slice(slice);
-            if !map.contains_key(&header.sequence) {
-                map.insert(header.sequence, fragment);
-            }
+        if self.rejected_groups.contains_key(&header.fragment_group) {
+            return FragmentReceived::Rejected {
+                group_length: header.fragment_count as usize * FRAG_SIZE,
+                first: false,
+            };
+        }
+        if header.fragment_count > Fragmentation::fragment_count(self.max_assembled_size) {
+            self.rejected_groups.insert(header.fragment_group, self.clock.now());
+            return FragmentReceived::Rejected {
+                group_length: header.fragment_count as usize * FRAG_SIZE,
+                first: true,
+            };
+        }
 
-            return (true, map.len() == header.fragment_count as usize);
+        if !self.received.contains_key(&header.fragment_group) {
+            let group = FragmentGroup {
+                start_sequence: header.fragment_start_sequence,
+                fragment_count: header.fragment_count,
+                fragments: GroupMap::default(),
+            };
+            self.received.insert(header.fragment_group, group);
+            self.track_group(header.fragment_group, self.clock.now());
+        }
+        let group = match self.received.get_mut(&header.fragment_group) {
+            Some(group) => group,
+            None => {
+                return FragmentReceived::Invalid;
+            }
+        };
+        if !group.matches(&header) {
+            return FragmentReceived::Invalid;
+        }
+        // first one wins, a resend carries the same bytes
+        if !group.fragments.contains_key(&header.sequence) {
+            group.fragments.insert(header.sequence, data[0..length].to_vec());
         }
+        return FragmentReceived::Stored {
+            complete: group.fragments.len() == header.fragment_count as usize,
+        };
+    }
 
-        return (false, false);
+    // Fragments the way create_fragments makes them.  Every one but the last carries exactly FRAG_SIZE and all of them
+    // are within fragment_count sequences of the start, so a group can never assemble to more than its count says.
+    fn is_valid_fragment(header: &Header, length: usize) -> bool {
+        if length <= TACHYON_FRAGMENTED_HEADER_SIZE || header.fragment_count == 0 {
+            return false;
+        }
+        let body_len = length - TACHYON_FRAGMENTED_HEADER_SIZE;
+        if header.sequence as u32 >= SEQUENCE_SPACE || header.fragment_start_sequence as u32 >= SEQUENCE_SPACE {
+            return false;
+        }
+        // sequences go from 65534 back to 0, a group can straddle that
+        let offset = Sequence::ahead_in(header.sequence as u32, header.fragment_start_sequence as u32, SEQUENCE_SPACE) as u16;
+        if offset >= header.fragment_count {
+            return false;
+        }
+        if offset == header.fragment_count - 1 {
+            return body_len <= FRAG_SIZE;
+        }
+        return body_len == FRAG_SIZE;
     }
 
-    pub fn create_fragments(&mut self, sender: &mut SendBufferManager, channel: u8, data: &[u8], length: usize) -> Vec<u16> {
-        let slice = &data[0..length];
+    pub fn create_fragments(&mut self, sender: &mut SendBufferManager, channel: u16, data: &[u8], length: usize) -> Vec<u16> {
+        return self.create_fragments_vectored(sender, channel, &[&data[0..length]]);
+    }
 
-        let chunks = slice.chunks(FRAG_SIZE as usize);
-        let fragment_count = chunks.len() as u16;
+    // Fragments are written straight into the send buffers, walking the slices as we go.  Those same buffers are what
+    // gets resent on a nack, so the message body is only ever copied once on the send side.  Slices are IoSlices from
+    // Tachyon, anything that derefs to bytes works.
+    pub fn create_fragments_vectored<S: Deref<Target = [u8]>>(&mut self, sender: &mut SendBufferManager, channel: u16, slices: &[S]) -> Vec<u16> {
+        let length: usize = slices.iter().map(|slice| slice.len()).sum();
         let mut fragments: Vec<u16> = Vec::new();
+        if length > MAX_ASSEMBLED_SIZE_MAX {
+            return fragments;
+        }
+        let fragment_count = Fragmentation::fragment_count(length);
         let group = self.get_next_group();
 
         let mut start_sequence = 0;
-        let mut index = 0;
+        let mut slice_index = 0;
+        let mut slice_offset = 0;
 
-        for chunk in chunks {
-            let chunk_len = chunk.len();
+        for index in 0..fragment_count as usize {
+            let chunk_len = core::cmp::min(FRAG_SIZE, length - index * FRAG_SIZE);
             let fragment_len = chunk_len + TACHYON_FRAGMENTED_HEADER_SIZE;
 
             match sender.create_send_buffer(fragment_len) {
@@ -141,10 +275,21 @@ impl Fragmentation {
                     let fragment_header = Header::create_fragmented(sequence, channel, group, start_sequence, fragment_count);
                     fragment_header.write_fragmented(&mut send_buffer.byte_buffer.get_mut());
 
-                    send_buffer.byte_buffer.get_mut()[TACHYON_FRAGMENTED_HEADER_SIZE..chunk_len + TACHYON_FRAGMENTED_HEADER_SIZE].copy_from_slice(chunk);
+                    let mut written = 0;
+                    while written < chunk_len {
+                        let slice = &slices[slice_index];
+                        let copy_len = core::cmp::min(slice.len() - slice_offset, chunk_len - written);
+                        let dest = TACHYON_FRAGMENTED_HEADER_SIZE + written;
+                        send_buffer.byte_buffer.get_mut()[dest..dest + copy_len].copy_from_slice(&slice[slice_offset..slice_offset + copy_len]);
+
+                        written += copy_len;
+                        slice_offset += copy_len;
+                        if slice_offset == slice.len() {
+                            slice_index += 1;
+                            slice_offset = 0;
+                        }
+                    }
                     fragments.push(sequence);
-
-                    index += 1;
                 }
                 None => {
                     fragments.clear();
@@ -159,23 +304,50 @@ impl Fragmentation {
 
 #[cfg(test)]
 mod tests {
+    use std::io::IoSlice;
     use std::time::Duration;
 
-    use crate::tachyon::fragmentation::*;
+    use crate::fragmentation::*;
 
     #[test]
     fn test_expire() {
         let mut frag = Fragmentation::default();
-        frag.received_at.insert(1, Instant::now());
+        frag.track_group(1, Instant::now());
         frag.expire_groups();
         assert!(frag.received_at.contains_key(&1));
         assert_eq!(1, frag.received_at.len());
 
         let now = Instant::now() - Duration::new(6, 0);
-        frag.received_at.insert(2, now);
+        frag.track_group(2, now);
         frag.expire_groups();
         assert!(!frag.received_at.contains_key(&2));
         assert_eq!(1, frag.received_at.len());
+        assert_eq!(1, frag.expire_queue.len());
+    }
+
+    #[test]
+    fn test_expire_many() {
+        let mut frag = Fragmentation::default();
+        let start = Instant::now();
+        for group in 0..10000 {
+            frag.track_group(group, start + Duration::from_millis(group as u64));
+        }
+
+        // an assembled group leaves a stale queue entry behind
+        frag.received_at.remove(&5);
+        // restarted group, only the newer time counts
+        frag.track_group(6, start + Duration::from_millis(20000));
+
+        // groups 0-98 are expired, minus the two above
+        frag.expire_groups_at(start + Duration::from_millis(5000 + 99));
+        assert_eq!(10000 - 1 - 97, frag.received_at.len());
+        assert!(frag.received_at.contains_key(&6));
+
+        frag.expire_groups_at(start + Duration::from_millis(20000));
+        assert_eq!(1, frag.received_at.len());
+        frag.expire_groups_at(start + Duration::from_millis(30000));
+        assert_eq!(0, frag.received_at.len());
+        assert_eq!(0, frag.expire_queue.len());
     }
 
     #[test]
@@ -204,6 +376,35 @@ mod tests {
         assert_eq!(2, header.sequence);
     }
 
+    #[test]
+    fn test_create_vectored() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+
+        let first: Vec<u8> = vec![1; 1000];
+        let second: Vec<u8> = vec![2; 1000];
+        let third: Vec<u8> = vec![3; 500];
+        let slices = [IoSlice::new(&first), IoSlice::new(&[]), IoSlice::new(&second), IoSlice::new(&third)];
+        let created = frag.create_fragments_vectored(&mut sender, 1, &slices);
+        assert_eq!(3, created.len());
+
+        for seq in &created {
+            let send_buffer = sender.get_send_buffer(*seq).unwrap();
+            frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+        }
+        let send_buffer = sender.get_send_buffer(created[2]).unwrap();
+        assert_eq!(100 + TACHYON_FRAGMENTED_HEADER_SIZE, send_buffer.byte_buffer.length);
+
+        let header = Header::read_fragmented(&send_buffer.byte_buffer.get());
+        let assembled = frag.assemble(header).unwrap();
+        assert_eq!(2500, assembled.len());
+        assert_eq!(1, assembled[999]);
+        assert_eq!(2, assembled[1000]);
+        assert_eq!(2, assembled[1999]);
+        assert_eq!(3, assembled[2000]);
+        assert_eq!(3, assembled[2499]);
+    }
+
     #[test]
     fn test_receive() {
         let mut frag = Fragmentation::default();
@@ -213,15 +414,15 @@ mod tests {
         let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
         let send_buffer = sender.get_send_buffer(created[0]).unwrap();
         let complete = frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
-        assert!(!complete.1);
+        assert!(!complete.is_complete());
 
         let send_buffer = sender.get_send_buffer(created[1]).unwrap();
         let complete = frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
-        assert!(!complete.1);
+        assert!(!complete.is_complete());
 
         let send_buffer = sender.get_send_buffer(created[2]).unwrap();
         let complete = frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
-        assert!(complete.1);
+        assert!(complete.is_complete());
 
         let header = Header::read_fragmented(&send_buffer.byte_buffer.get());
         let assembled = frag.assemble(header);
@@ -233,4 +434,145 @@ mod tests {
             assert_eq!(3, assembled_data[i]);
         }
     }
+
+    #[test]
+    fn test_receive_across_wrap() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+        sender.current_sequence = 65532;
+
+        let data: Vec<u8> = vec![3; 2500];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        assert_eq!(vec![65533, 65534, 0], created);
+        for sequence in &created {
+            let send_buffer = sender.get_send_buffer(*sequence).unwrap();
+            assert!(matches!(frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length), FragmentReceived::Stored { .. }));
+        }
+        let send_buffer = sender.get_send_buffer(0).unwrap();
+        let header = Header::read_fragmented(&send_buffer.byte_buffer.get());
+        assert_eq!(vec![3; 2500], frag.assemble(header).unwrap());
+
+        // one past the end of the group is still outside it
+        let mut forged = send_buffer.byte_buffer.get()[0..send_buffer.byte_buffer.length].to_vec();
+        let mut header = Header::read_fragmented(&forged);
+        header.sequence = 1;
+        header.write_fragmented(&mut forged);
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Invalid));
+    }
+
+    #[test]
+    fn test_max_assembled_size() {
+        let mut frag = Fragmentation::default();
+        frag.max_assembled_size = 2400;
+        let mut sender = SendBufferManager::default();
+
+        // exactly the max is fine
+        let data: Vec<u8> = vec![3; 2400];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        for sequence in &created {
+            let send_buffer = sender.get_send_buffer(*sequence).unwrap();
+            assert!(matches!(frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length), FragmentReceived::Stored { .. }));
+        }
+
+        let data: Vec<u8> = vec![3; 2401];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        let send_buffer = sender.get_send_buffer(created[0]).unwrap();
+        match frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length) {
+            FragmentReceived::Rejected { group_length, first } => {
+                assert_eq!(3600, group_length);
+                assert!(first);
+            }
+            _ => panic!("not rejected"),
+        }
+        let send_buffer = sender.get_send_buffer(created[1]).unwrap();
+        assert!(matches!(frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length), FragmentReceived::Rejected { first: false, .. }));
+        assert_eq!(1, frag.received.len());
+        assert_eq!(1, frag.rejected_groups.len());
+
+        // a fragment outside its group
+        let mut forged = send_buffer.byte_buffer.get()[0..send_buffer.byte_buffer.length].to_vec();
+        let mut header = Header::read_fragmented(&forged);
+        header.sequence = header.fragment_start_sequence.wrapping_add(header.fragment_count);
+        header.write_fragmented(&mut forged);
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Invalid));
+
+        frag.expire_groups_at(Instant::now() + Duration::from_millis(GROUP_EXPIRE as u64 + 1));
+        assert_eq!(0, frag.rejected_groups.len());
+    }
+
+    fn forge(fragment: &[u8], edit: impl Fn(&mut Header)) -> Vec<u8> {
+        let mut forged = fragment.to_vec();
+        let mut header = Header::read_fragmented(&forged);
+        edit(&mut header);
+        header.write_fragmented(&mut forged);
+        return forged;
+    }
+
+    #[test]
+    fn test_adversarial() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+        let data: Vec<u8> = vec![3; 2500];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        let fragments: Vec<Vec<u8>> = created.iter().map(|sequence| {
+            let send_buffer = sender.get_send_buffer(*sequence).unwrap();
+            send_buffer.byte_buffer.get()[0..send_buffer.byte_buffer.length].to_vec()
+        }).collect();
+
+        assert!(matches!(frag.receive_fragment(&fragments[0], fragments[0].len()), FragmentReceived::Stored { complete: false }));
+
+        // disagrees with the group on count or start
+        let forged = forge(&fragments[1], |header| header.fragment_count = 4);
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Invalid));
+        let forged = forge(&fragments[1], |header| {
+            header.fragment_start_sequence = header.sequence;
+            header.fragment_count = 2;
+        });
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Invalid));
+
+        // shorter than FRAG_SIZE before the last, longer than FRAG_SIZE anywhere, no fragments at all
+        assert!(matches!(frag.receive_fragment(&fragments[1], fragments[1].len() - 1), FragmentReceived::Invalid));
+        let mut long = fragments[2].clone();
+        long.resize(TACHYON_FRAGMENTED_HEADER_SIZE + FRAG_SIZE + 1, 3);
+        assert!(matches!(frag.receive_fragment(&long, long.len()), FragmentReceived::Invalid));
+        let forged = forge(&fragments[1], |header| header.fragment_count = 0);
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Invalid));
+
+        // a duplicate with different bytes doesn't replace the first
+        let mut duplicate = fragments[0].clone();
+        duplicate[TACHYON_FRAGMENTED_HEADER_SIZE] = 9;
+        frag.receive_fragment(&duplicate, duplicate.len());
+        assert_eq!(1, frag.received[&frag.next_group].fragments.len());
+
+        frag.receive_fragment(&fragments[1], fragments[1].len());
+        assert!(frag.receive_fragment(&fragments[2], fragments[2].len()).is_complete());
+
+        // assembling with a header that disagrees leaves the group alone
+        let header = Header::read_fragmented(&fragments[2]);
+        let mut wrong = header;
+        wrong.fragment_start_sequence = wrong.fragment_start_sequence.wrapping_add(1);
+        assert!(frag.assemble(wrong).is_err());
+        let assembled = frag.assemble(header).unwrap();
+        assert_eq!(data, assembled);
+    }
+
+    #[test]
+    fn test_rejected_stays_rejected() {
+        let mut frag = Fragmentation::default();
+        frag.max_assembled_size = 1200;
+        let mut sender = SendBufferManager::default();
+        let data: Vec<u8> = vec![3; 2400];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        let send_buffer = sender.get_send_buffer(created[1]).unwrap();
+        let second = send_buffer.byte_buffer.get()[0..send_buffer.byte_buffer.length].to_vec();
+        assert!(matches!(frag.receive_fragment(&second, second.len()), FragmentReceived::Rejected { first: true, .. }));
+
+        // the same group claiming to be small now
+        let forged = forge(&second, |header| {
+            header.fragment_start_sequence = header.sequence;
+            header.fragment_count = 1;
+        });
+        assert!(matches!(frag.receive_fragment(&forged, forged.len()), FragmentReceived::Rejected { first: false, .. }));
+        assert_eq!(0, frag.received.len());
+    }
 }
@@ -0,0 +1,183 @@
+use rustc_hash::FxHashMap;
+
+use super::{
+    pool::{Pool, PublishedMessage, SendTarget},
+    relay::{RelayHeader, RELAY_KIND_FROM},
+    TachyonSendResult,
+};
+
+// Where messages from an identity go when a Pool is a gateway, one public socket fanning out to internal servers.
+// Called from whichever thread publishes received messages, see Pool::set_route_resolver.
+pub trait RouteResolver: Send {
+    // None leaves the message published for the application
+    fn resolve(&mut self, identity_id: u32, channel_id: u16) -> Option<SendTarget>;
+}
+
+// The plain identity to target table, for when routes don't need anything smarter
+#[derive(Default)]
+pub struct RoutingTable {
+    routes: FxHashMap<u32, SendTarget>,
+}
+
+impl RoutingTable {
+    pub fn insert(&mut self, identity_id: u32, target: SendTarget) {
+        self.routes.insert(identity_id, target);
+    }
+
+    // false if the identity had no route
+    pub fn remove(&mut self, identity_id: u32) -> bool {
+        return self.routes.remove(&identity_id).is_some();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.routes.len();
+    }
+}
+
+impl RouteResolver for RoutingTable {
+    fn resolve(&mut self, identity_id: u32, _channel_id: u16) -> Option<SendTarget> {
+        return self.routes.get(&identity_id).copied();
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct GatewayStats {
+    // sent on to the target the resolver gave
+    pub routed: u64,
+    // resolved, but the send failed, they're dropped
+    pub send_failed: u64,
+}
+
+#[derive(Default)]
+pub struct Gateway {
+    resolver: Option<Box<dyn RouteResolver>>,
+    pub stats: GatewayStats,
+}
+
+impl Pool {
+    // Received messages from linked identities go through the resolver as they're published.  The ones it resolves are
+    // sent on to the target from whichever server has it, in a RELAY_KIND_FROM relay header carrying the channel they
+    // came in on and the sender's identity, session and address.  The server sending them needs relay enabled, the
+    // target needs relay enabled on the same channel and the server as a peer, see relay.rs.  None stops routing.
+    pub fn set_route_resolver(&mut self, resolver: Option<Box<dyn RouteResolver>>) {
+        self.gateway.resolver = resolver;
+    }
+
+    pub fn gateway_stats(&self) -> GatewayStats {
+        return self.gateway.stats;
+    }
+
+    // false if the message was routed and isn't published
+    pub(crate) fn route_received(&mut self, message: &PublishedMessage) -> bool {
+        if message.identity_id == 0 {
+            return true;
+        }
+        let target = match &mut self.gateway.resolver {
+            Some(resolver) => resolver.resolve(message.identity_id, message.channel),
+            None => return true,
+        };
+        let target = match target {
+            Some(target) => target,
+            None => return true,
+        };
+
+        let result = match self.target_server(target) {
+            Some((address, server)) => {
+                let header = RelayHeader {
+                    kind: RELAY_KIND_FROM,
+                    channel: message.channel as u8,
+                    identity_id: message.identity_id,
+                    session_id: message.session_id,
+                    address: message.address,
+                };
+                server.send_relay_header(address, header, &message.data[..])
+            }
+            None => TachyonSendResult::default(),
+        };
+        if result.error == 0 && result.sent_len > 0 {
+            self.gateway.stats.routed += 1;
+        } else {
+            self.gateway.stats.send_failed += 1;
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        channel::ChannelConfig, connection::Identity, loopback::LoopbackNetwork, network_address::NetworkAddress, pool::{Pool, SendTarget}, Tachyon,
+        TachyonConfig,
+    };
+
+    use super::RoutingTable;
+
+    #[test]
+    fn test_gateway() {
+        let network = LoopbackNetwork::default();
+        let gateway_address = NetworkAddress::localhost(8400);
+        let internal_address = NetworkAddress::localhost(8401);
+        let mut pool = Pool::create(1, 1024, 1024);
+
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut gateway = Tachyon::create(config);
+        gateway.id = 1;
+        gateway.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(gateway.enable_relay(3));
+        gateway.connection_changes = Some(pool.connection_changes.clone());
+        assert!(gateway.bind_loopback(&network, gateway_address));
+        gateway.set_identity(5, 50);
+        gateway.set_identity(6, 60);
+        assert!(gateway.add_peer(internal_address));
+
+        let mut internal = Tachyon::create(TachyonConfig::default());
+        internal.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(internal.enable_relay(3));
+        assert!(internal.bind_loopback(&network, internal_address));
+        assert!(internal.add_peer(gateway_address));
+
+        let mut clients: Vec<Tachyon> = Vec::new();
+        for id in [5, 6] {
+            let mut client = Tachyon::create(config);
+            client.identity = Identity { id, session_id: id * 10, linked: 0 };
+            client.connect_loopback(&network, gateway_address);
+            client.update();
+            clients.push(client);
+        }
+        pool.servers.insert(1, gateway);
+        pool.receive_blocking();
+        pool.build_connection_maps();
+
+        let mut routes = RoutingTable::default();
+        routes.insert(5, SendTarget { identity_id: 0, address: internal_address });
+        pool.set_route_resolver(Some(Box::new(routes)));
+
+        let target = SendTarget { identity_id: 0, address: NetworkAddress::default() };
+        let mut buffer: Vec<u8> = vec![0; 1024];
+        for client in clients.iter_mut() {
+            client.receive_loop(&mut buffer);
+            assert!(client.identity.is_linked());
+            let data = [client.identity.id as u8; 4];
+            assert_eq!(0, client.send_to_target(1, target, &data, 4).error);
+        }
+        pool.receive_blocking();
+
+        // 5 went on to the internal server, 6 has no route and is published as usual
+        assert_eq!(1, pool.gateway_stats().routed);
+        let published = pool.take_published().unwrap();
+        assert_eq!(6, published.identity_id);
+        assert!(pool.take_published().is_none());
+
+        let result = internal.receive_loop(&mut buffer);
+        assert_eq!(4, result.length);
+        assert_eq!(1, result.channel);
+        assert_eq!(&[5, 5, 5, 5], &buffer[0..4]);
+        // the internal server sees who really sent it
+        assert_eq!((5, 50), (result.identity_id, result.session_id));
+        assert!(NetworkAddress::localhost(49152) == result.address);
+        assert!(gateway_address == result.relayed_by);
+    }
+}
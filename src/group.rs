@@ -0,0 +1,203 @@
+use std::io::IoSlice;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{Tachyon, SEND_ERROR_NOT_CONNECTED};
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct GroupStats {
+    pub sends: u64,
+    pub delivered: u64,
+    pub failed: u64,
+    // members without a linked connection at the time of a send
+    pub offline: u64,
+    pub bytes_sent: u64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct GroupSendResult {
+    pub sent: u32,
+    pub failed: u32,
+    pub offline: u32,
+    pub bytes_sent: u32,
+}
+
+// A set of identities that can be sent to as one.  Membership is by identity, so it outlives reconnects, members are
+// sent to at whatever address they're linked from when the send happens.
+#[derive(Default)]
+pub struct Group {
+    pub members: FxHashSet<u32>,
+    pub stats: GroupStats,
+}
+
+#[derive(Default)]
+pub struct Groups {
+    groups: FxHashMap<u32, Group>,
+}
+
+impl Groups {
+    pub fn get(&self, group_id: u32) -> Option<&Group> {
+        return self.groups.get(&group_id);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.groups.len();
+    }
+}
+
+impl Tachyon {
+    // false if the group already exists
+    pub fn create_group(&mut self, group_id: u32) -> bool {
+        if self.groups.groups.contains_key(&group_id) {
+            return false;
+        }
+        self.groups.groups.insert(group_id, Group::default());
+        return true;
+    }
+
+    pub fn remove_group(&mut self, group_id: u32) -> bool {
+        return self.groups.groups.remove(&group_id).is_some();
+    }
+
+    // false if there's no such group or the identity is already in it
+    pub fn join_group(&mut self, group_id: u32, identity_id: u32) -> bool {
+        match self.groups.groups.get_mut(&group_id) {
+            Some(group) => return group.members.insert(identity_id),
+            None => return false,
+        }
+    }
+
+    pub fn leave_group(&mut self, group_id: u32, identity_id: u32) -> bool {
+        match self.groups.groups.get_mut(&group_id) {
+            Some(group) => return group.members.remove(&identity_id),
+            None => return false,
+        }
+    }
+
+    // every group the identity is in
+    pub fn leave_all_groups(&mut self, identity_id: u32) {
+        for group in self.groups.groups.values_mut() {
+            group.members.remove(&identity_id);
+        }
+    }
+
+    pub fn get_group_stats(&self, group_id: u32) -> Option<GroupStats> {
+        return self.groups.get(group_id).map(|group| group.stats);
+    }
+
+    // Reliable send of one message to every member of the group.  The message is built once by the caller and sent from
+    // the same slice to each member, sends to members that fail don't stop the rest.  None if there's no such group.
+    pub fn send_to_group(&mut self, channel_id: u16, group_id: u32, data: &[u8], length: usize) -> Option<GroupSendResult> {
+        let group = self.groups.groups.get(&group_id)?;
+        let mut result = GroupSendResult::default();
+        if length > data.len() {
+            result.failed = group.members.len() as u32;
+            return Some(result);
+        }
+
+        let members: Vec<u32> = group.members.iter().copied().collect();
+        let slices = [IoSlice::new(&data[0..length])];
+        for identity_id in members {
+            let address = match self.identity_to_address_map.get(&identity_id) {
+                Some(address) => *address,
+                None => {
+                    result.offline += 1;
+                    continue;
+                }
+            };
+
+            let send_result = self.send_vectored(channel_id, address, &slices);
+            if send_result.error == 0 {
+                result.sent += 1;
+                result.bytes_sent += send_result.sent_len;
+            } else if send_result.error == SEND_ERROR_NOT_CONNECTED {
+                result.offline += 1;
+            } else {
+                result.failed += 1;
+            }
+        }
+
+        if let Some(group) = self.groups.groups.get_mut(&group_id) {
+            group.stats.sends += 1;
+            group.stats.delivered += result.sent as u64;
+            group.stats.failed += result.failed as u64;
+            group.stats.offline += result.offline as u64;
+            group.stats.bytes_sent += result.bytes_sent as u64;
+        }
+        return Some(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTestClient, Tachyon, TachyonConfig};
+
+    #[test]
+    #[serial]
+    fn test_group_send() {
+        let address = NetworkAddress::localhost(8196);
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut server = Tachyon::create(config);
+        assert!(server.bind(address));
+
+        let mut clients: Vec<TachyonTestClient> = Vec::new();
+        for id in 1..3 {
+            let mut client = TachyonTestClient::create(address);
+            client.client.config.use_identity = 1;
+            client.client.identity.id = id;
+            client.client.identity.session_id = 10;
+            client.connect();
+            server.set_identity(id, 10);
+            clients.push(client);
+        }
+
+        // link both, the server answers each link request
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        for client in &mut clients {
+            client.client.update();
+        }
+        server.receive_loop(&mut receive_buffer);
+        for client in &mut clients {
+            client.client_receive();
+            assert!(client.client.identity.is_linked());
+        }
+
+        assert!(server.create_group(5));
+        assert!(!server.create_group(5));
+        assert!(server.join_group(5, 1));
+        assert!(server.join_group(5, 2));
+        assert!(server.join_group(5, 3));
+        assert!(!server.join_group(5, 3));
+        assert!(!server.join_group(6, 1));
+
+        let result = server.send_to_group(1, 5, &[7; 32], 32).unwrap();
+        assert_eq!(2, result.sent);
+        assert_eq!(1, result.offline);
+        assert_eq!(0, result.failed);
+        for client in &mut clients {
+            assert_eq!(32, client.client_receive().length);
+        }
+
+        assert!(server.leave_group(5, 2));
+        let result = server.send_to_group(2, 5, &[7; 32], 32).unwrap();
+        assert_eq!(1, result.sent);
+        assert_eq!(32, clients[0].client_receive().length);
+        assert_eq!(0, clients[1].client_receive().length);
+
+        let stats = server.get_group_stats(5).unwrap();
+        assert_eq!(2, stats.sends);
+        assert_eq!(3, stats.delivered);
+        assert_eq!(2, stats.offline);
+
+        assert!(server.send_to_group(1, 6, &[7; 32], 32).is_none());
+        assert!(server.remove_group(5));
+        assert!(server.get_group_stats(5).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::{Arc, Mutex};
+
+use rustc_hash::FxHashMap;
+
+use crate::*;
+
+use super::{ffi, pool::SendTarget, receive_result::RECEIVE_ERROR_HANDLE};
+
+// Handles instead of raw pointers for hosts that can lose track of what they created, like a Unity domain reload.  A
+// stale or made up handle is rejected instead of dereferenced, and destroy_all cleans up whatever a previous load of the
+// host left behind since the native library stays loaded across reloads.  Handles are never reused.
+// The functions mirror ffi.rs, failing with -1 or SEND_ERROR_HANDLE/RECEIVE_ERROR_HANDLE on an invalid handle.
+pub struct HandleTable<T> {
+    next_handle: u64,
+    entries: FxHashMap<u64, Arc<Mutex<T>>>,
+}
+
+impl<T> HandleTable<T> {
+    pub fn default() -> Self {
+        return HandleTable {
+            next_handle: 0,
+            entries: FxHashMap::default(),
+        };
+    }
+
+    // 0 is never a valid handle
+    pub fn insert(&mut self, value: T) -> u64 {
+        self.next_handle += 1;
+        self.entries.insert(self.next_handle, Arc::new(Mutex::new(value)));
+        return self.next_handle;
+    }
+
+    pub fn get(&self, handle: u64) -> Option<Arc<Mutex<T>>> {
+        return self.entries.get(&handle).cloned();
+    }
+
+    // a call in progress on another thread keeps its instance alive until it returns
+    pub fn remove(&mut self, handle: u64) -> bool {
+        return self.entries.remove(&handle).is_some();
+    }
+
+    pub fn clear(&mut self) -> u32 {
+        let count = self.entries.len() as u32;
+        self.entries.clear();
+        return count;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+}
+
+static TACHYON_HANDLES: Mutex<Option<HandleTable<Tachyon>>> = Mutex::new(None);
+
+fn with_handles<R>(f: impl FnOnce(&mut HandleTable<Tachyon>) -> R) -> R {
+    let mut handles = TACHYON_HANDLES.lock().unwrap();
+    return f(handles.get_or_insert_with(HandleTable::default));
+}
+
+// the table lock is only held for the lookup, so calls on different instances don't serialize
+fn with_tachyon<R>(handle: u64, f: impl FnOnce(*mut Tachyon) -> R) -> Option<R> {
+    let entry = with_handles(|handles| handles.get(handle))?;
+    let mut tachyon = entry.lock().unwrap();
+    return Some(f(&mut *tachyon as *mut Tachyon));
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_create(config_ptr: *const TachyonConfig) -> u64 {
+    let config: TachyonConfig = unsafe { std::ptr::read(config_ptr as *const _) };
+    let tachyon = Tachyon::create(config);
+    return with_handles(|handles| handles.insert(tachyon));
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_destroy(handle: u64) -> i32 {
+    if with_handles(|handles| handles.remove(handle)) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// returns how many instances were destroyed
+#[no_mangle]
+pub extern "C" fn tachyon_handle_destroy_all() -> u32 {
+    return with_handles(|handles| handles.clear());
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_is_valid(handle: u64) -> i32 {
+    if with_handles(|handles| handles.get(handle).is_some()) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_bind(handle: u64, naddress: *const NetworkAddress) -> i32 {
+    return with_tachyon(handle, |tachyon| ffi::bind_socket(tachyon, naddress)).unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_connect(handle: u64, naddress: *const NetworkAddress) -> i32 {
+    return with_tachyon(handle, |tachyon| ffi::connect_socket(tachyon, naddress)).unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_configure_channel(handle: u64, channel_id: u8, config_ptr: *const ChannelConfig) -> i32 {
+    return with_tachyon(handle, |tachyon| ffi::configure_channel(tachyon, channel_id, config_ptr)).unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_send_to_target(handle: u64, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    if with_tachyon(handle, |tachyon| ffi::send_to_target(tachyon, channel, target_ptr, data, length, ret)).is_none() {
+        let mut result = TachyonSendResult::default();
+        result.error = SEND_ERROR_HANDLE;
+        ffi::copy_send_result(result, ret);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_receive(handle: u64, data: *mut u8, receive_buffer_len: u32, ret: *mut TachyonReceiveResult) {
+    if with_tachyon(handle, |tachyon| ffi::receive(tachyon, data, receive_buffer_len, ret)).is_none() {
+        let mut result = TachyonReceiveResult::default();
+        result.error = RECEIVE_ERROR_HANDLE;
+        unsafe {
+            (*ret) = result;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_update(handle: u64) -> i32 {
+    return with_tachyon(handle, |tachyon| {
+        ffi::tachyon_update(tachyon);
+        return 1;
+    })
+    .unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_set_identity(handle: u64, id: u32, session_id: u32, on_self: u32) -> i32 {
+    return with_tachyon(handle, |tachyon| {
+        ffi::set_identity(tachyon, id, session_id, on_self);
+        return 1;
+    })
+    .unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_get_stats(handle: u64, stats: *mut TachyonStats) -> i32 {
+    return with_tachyon(handle, |tachyon| {
+        ffi::get_stats(tachyon, stats);
+        return 1;
+    })
+    .unwrap_or(-1);
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_handle_get_connection_quality(handle: u64, naddress: *const NetworkAddress, quality: *mut ConnectionQuality) -> i32 {
+    return with_tachyon(handle, |tachyon| ffi::tachyon_get_connection_quality(tachyon, naddress, quality)).unwrap_or(-1);
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, receive_result::TachyonReceiveResult};
+
+    use super::*;
+
+    #[test]
+    #[serial]
+    fn test_handles() {
+        tachyon_handle_destroy_all();
+        let config = TachyonConfig::default();
+        let server = tachyon_handle_create(&config);
+        let client = tachyon_handle_create(&config);
+        assert!(server > 0 && client > server);
+
+        let address = NetworkAddress::localhost(8191);
+        assert_eq!(1, tachyon_handle_bind(server, &address));
+        assert_eq!(1, tachyon_handle_connect(client, &address));
+
+        let mut data: Vec<u8> = vec![7; 64];
+        let target = SendTarget::default();
+        let mut send_result = TachyonSendResult::default();
+        tachyon_handle_send_to_target(client, 0, &target, data.as_mut_ptr(), 16, &mut send_result);
+        assert_eq!(0, send_result.error);
+
+        let mut receive_result = TachyonReceiveResult::default();
+        tachyon_handle_receive(server, data.as_mut_ptr(), 64, &mut receive_result);
+        assert_eq!(16, receive_result.length);
+
+        // stale handle after destroy
+        assert_eq!(1, tachyon_handle_destroy(client));
+        assert_eq!(-1, tachyon_handle_is_valid(client));
+        assert_eq!(-1, tachyon_handle_destroy(client));
+        assert_eq!(-1, tachyon_handle_update(client));
+        tachyon_handle_send_to_target(client, 0, &target, data.as_mut_ptr(), 16, &mut send_result);
+        assert_eq!(SEND_ERROR_HANDLE, send_result.error);
+        tachyon_handle_receive(client, data.as_mut_ptr(), 64, &mut receive_result);
+        assert_eq!(RECEIVE_ERROR_HANDLE, receive_result.error);
+
+        // never handed out
+        assert_eq!(-1, tachyon_handle_update(0));
+        assert_eq!(-1, tachyon_handle_update(client + 100));
+
+        // what a host reload does on startup, new handles keep counting up
+        assert_eq!(1, tachyon_handle_destroy_all());
+        assert_eq!(-1, tachyon_handle_is_valid(server));
+        assert!(tachyon_handle_create(&config) > client);
+        assert_eq!(1, tachyon_handle_destroy_all());
+    }
+}
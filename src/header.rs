@@ -1,4 +1,4 @@
-use super::int_buffer::IntBuffer;
+use super::int_buffer::{BufferTooSmall, IntBuffer};
 
 pub const MESSAGE_TYPE_UNRELIABLE: u8 = 0;
 pub const MESSAGE_TYPE_RELIABLE: u8 = 1;
@@ -11,11 +11,80 @@ pub const MESSAGE_TYPE_LINK_IDENTITY: u8 = 6;
 pub const MESSAGE_TYPE_UNLINK_IDENTITY: u8 = 7;
 
 pub const MESSAGE_TYPE_IDENTITY_LINKED: u8 = 8;
+// ConnectionHeader.session_id is the UNLINK_REASON_x
 pub const MESSAGE_TYPE_IDENTITY_UNLINKED: u8 = 9;
+// a server's reply to a failed link request, ConnectionHeader.id is the identity and session_id the LINK_DENIED_x reason
+pub const MESSAGE_TYPE_IDENTITY_DENIED: u8 = 20;
 
+pub const MESSAGE_TYPE_UNRELIABLE_SEQUENCED: u8 = 10;
+
+pub const MESSAGE_TYPE_CONNECT_REQUEST: u8 = 11;
+pub const MESSAGE_TYPE_CHALLENGE: u8 = 12;
+pub const MESSAGE_TYPE_CHALLENGE_RESPONSE: u8 = 13;
+pub const MESSAGE_TYPE_CONNECT_ACCEPTED: u8 = 14;
+
+pub const MESSAGE_TYPE_CHANNEL_EPOCH: u8 = 15;
+pub const MESSAGE_TYPE_CHANNEL_EPOCH_ACK: u8 = 16;
+
+pub const MESSAGE_TYPE_SUBSCRIBE: u8 = 17;
+pub const MESSAGE_TYPE_UNSUBSCRIBE: u8 = 18;
+pub const MESSAGE_TYPE_TOPIC_ACK: u8 = 19;
+
+// nack format v2, see nack.rs.  Only sent to a channel that said it reads them with MESSAGE_TYPE_NACK_FORMAT.
+pub const MESSAGE_TYPE_NACK_RANGES: u8 = 21;
+// Header.sequence is the nack format the channel reads, the reply to a nack offering it
+pub const MESSAGE_TYPE_NACK_FORMAT: u8 = 22;
+
+// In front of a channel message on a channel above 255, with TachyonConfig.wide_channels.  The second byte is the high
+// byte of the channel id, what follows is the datagram as it is on any other channel, its header carrying the low byte.
+pub const MESSAGE_TYPE_WIDE_CHANNEL: u8 = 23;
+
+// With TachyonConfig.use_protocol_magic every datagram starts with the magic and version, ahead of the header.  Bump
+// the version on wire format changes.
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"TCHY";
+pub const PROTOCOL_VERSION: u8 = 1;
+pub const PROTOCOL_PREFIX_SIZE: usize = 5;
+// With TachyonConfig.use_checksum a CRC32C of the rest of the datagram follows, little endian
+pub const CHECKSUM_SIZE: usize = 4;
+
+pub const TACHYON_UNRELIABLE_HEADER_SIZE: usize = 1;
+pub const TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE: usize = 3;
 pub const TACHYON_HEADER_SIZE: usize = 4;
 pub const TACHYON_NACKED_HEADER_SIZE: usize = 10;
 pub const TACHYON_FRAGMENTED_HEADER_SIZE: usize = 10;
+// every challenge message is this size, so a reply is never larger than the request that caused it
+pub const TACHYON_CHALLENGE_HEADER_SIZE: usize = 9;
+pub const TACHYON_CHANNEL_EPOCH_HEADER_SIZE: usize = 10;
+pub const TACHYON_TOPIC_HEADER_SIZE: usize = 6;
+pub const TACHYON_CONNECTION_HEADER_SIZE: usize = 9;
+pub const TACHYON_WIDE_CHANNEL_HEADER_SIZE: usize = 2;
+// largest application payload an identity message carries, see link_payload.rs
+pub const LINK_PAYLOAD_MAX: usize = 64;
+
+// the wide channel header for a channel, None when the channel fits the header's byte
+pub fn wide_channel_header(channel: u16) -> Option<[u8; TACHYON_WIDE_CHANNEL_HEADER_SIZE]> {
+    if channel <= u8::MAX as u16 {
+        return None;
+    }
+    return Some([MESSAGE_TYPE_WIDE_CHANNEL, (channel >> 8) as u8]);
+}
+
+// where the channel message starts in a datagram, past the wide channel header if there is one
+pub fn channel_message_start(data: &[u8]) -> usize {
+    if data.len() > TACHYON_WIDE_CHANNEL_HEADER_SIZE && data[0] == MESSAGE_TYPE_WIDE_CHANNEL {
+        return TACHYON_WIDE_CHANNEL_HEADER_SIZE;
+    }
+    return 0;
+}
+
+// the message types sent on a channel, the ones that can follow a wide channel header
+pub fn is_channel_message(message_type: u8) -> bool {
+    match message_type {
+        MESSAGE_TYPE_RELIABLE | MESSAGE_TYPE_RELIABLE_WITH_NACK | MESSAGE_TYPE_FRAGMENT | MESSAGE_TYPE_NONE | MESSAGE_TYPE_NACK
+        | MESSAGE_TYPE_NACK_RANGES | MESSAGE_TYPE_NACK_FORMAT | MESSAGE_TYPE_CHANNEL_EPOCH | MESSAGE_TYPE_CHANNEL_EPOCH_ACK => return true,
+        _ => return false,
+    }
+}
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -38,6 +107,14 @@ impl ConnectionHeader {
         return header;
     }
 
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_CONNECTION_HEADER_SIZE {
+            return None;
+        }
+        return Some(ConnectionHeader::read(buffer));
+    }
+
     pub fn write(&self, buffer: &mut [u8]) {
         let mut writer = IntBuffer { index: 0 };
 
@@ -47,12 +124,133 @@ impl ConnectionHeader {
     }
 }
 
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct ChallengeHeader {
+    pub message_type: u8,
+    pub cookie: u64,
+}
+
+impl ChallengeHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = ChallengeHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        let high = reader.read_u32(buffer) as u64;
+        let low = reader.read_u32(buffer) as u64;
+        header.cookie = (high << 32) | low;
+
+        return header;
+    }
+
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_CHALLENGE_HEADER_SIZE {
+            return None;
+        }
+        return Some(ChallengeHeader::read(buffer));
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u32((self.cookie >> 32) as u32, buffer);
+        writer.write_u32(self.cookie as u32, buffer);
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct ChannelEpochHeader {
+    pub message_type: u8,
+    // only the low byte is written, the high byte goes in the wide channel header
+    pub channel: u16,
+    pub epoch: u32,
+    // the epoch the sender has for us, 0 if it has none yet
+    pub ack_epoch: u32,
+}
+
+impl ChannelEpochHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = ChannelEpochHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.channel = reader.read_u8(buffer) as u16;
+        header.epoch = reader.read_u32(buffer);
+        header.ack_epoch = reader.read_u32(buffer);
+
+        return header;
+    }
+
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_CHANNEL_EPOCH_HEADER_SIZE {
+            return None;
+        }
+        return Some(ChannelEpochHeader::read(buffer));
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u8(self.channel as u8, buffer);
+        writer.write_u32(self.epoch, buffer);
+        writer.write_u32(self.ack_epoch, buffer);
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct TopicHeader {
+    pub message_type: u8,
+    pub topic: u32,
+    // acks, 1 if the server has the subscription
+    pub subscribed: u8,
+}
+
+impl TopicHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = TopicHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.topic = reader.read_u32(buffer);
+        header.subscribed = reader.read_u8(buffer);
+
+        return header;
+    }
+
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_TOPIC_HEADER_SIZE {
+            return None;
+        }
+        return Some(TopicHeader::read(buffer));
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u32(self.topic, buffer);
+        writer.write_u8(self.subscribed, buffer);
+    }
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default)]
 pub struct Header {
     pub message_type: u8,
-    pub channel: u8,
+    // only the low byte is written, the high byte goes in the wide channel header
+    pub channel: u16,
     pub sequence: u16,
 
     // fragment - optional
@@ -73,22 +271,53 @@ impl Header {
         writer.write_u8(self.message_type as u8, buffer);
     }
 
+    pub fn write_unreliable_sequenced(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u16(self.sequence, buffer);
+    }
+
+    pub fn read_unreliable_sequenced(buffer: &[u8]) -> Self {
+        let mut header = Header::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.sequence = reader.read_u16(buffer);
+
+        return header;
+    }
+
     pub fn write_nacked(&self, buffer: &mut [u8]) {
         let mut writer = IntBuffer { index: 0 };
 
         writer.write_u8(self.message_type, buffer);
-        writer.write_u8(self.channel, buffer);
+        writer.write_u8(self.channel as u8, buffer);
         writer.write_u16(self.sequence, buffer);
 
         writer.write_u16(self.start_sequence, buffer);
         writer.write_u32(self.flags, buffer);
     }
 
+    pub fn read_nacked(buffer: &[u8]) -> Self {
+        let mut header = Header::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.channel = reader.read_u8(buffer) as u16;
+        header.sequence = reader.read_u16(buffer);
+
+        header.start_sequence = reader.read_u16(buffer);
+        header.flags = reader.read_u32(buffer);
+
+        return header;
+    }
+
     pub fn write(&self, buffer: &mut [u8]) {
         let mut writer = IntBuffer { index: 0 };
 
         writer.write_u8(self.message_type, buffer);
-        writer.write_u8(self.channel, buffer);
+        writer.write_u8(self.channel as u8, buffer);
         writer.write_u16(self.sequence, buffer);
     }
   
@@ -97,7 +326,7 @@ impl Header {
         let mut reader = IntBuffer { index: 0 };
 
         header.message_type = reader.read_u8(buffer);
-        header.channel = reader.read_u8(buffer);
+        header.channel = reader.read_u8(buffer) as u16;
         header.sequence = reader.read_u16(buffer);
 
         return header;
@@ -108,7 +337,7 @@ impl Header {
         let mut writer = IntBuffer { index: 0 };
 
         writer.write_u8(self.message_type, buffer);
-        writer.write_u8(self.channel, buffer);
+        writer.write_u8(self.channel as u8, buffer);
         writer.write_u16(self.sequence, buffer);
 
         writer.write_u16(self.fragment_group, buffer);
@@ -121,7 +350,7 @@ impl Header {
         let mut reader = IntBuffer { index: 0 };
 
         header.message_type = reader.read_u8(buffer);
-        header.channel = reader.read_u8(buffer);
+        header.channel = reader.read_u8(buffer) as u16;
         header.sequence = reader.read_u16(buffer);
 
         header.fragment_group = reader.read_u16(buffer);
@@ -131,7 +360,82 @@ impl Header {
         return header;
     }
 
-    pub fn create_fragmented(sequence: u16, channel: u8, group: u16, start: u16, count: u16) -> Self {
+    // The reads above, None when buffer is shorter than the header.  buffer is the received datagram, not the buffer it
+    // was received into.
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_HEADER_SIZE {
+            return None;
+        }
+        return Some(Header::read(buffer));
+    }
+
+    // The first look at a datagram, only message_type is set when it's shorter than the reliable header like the 1 byte
+    // unreliable header can be.  None when it's empty.
+    pub fn try_read_type(buffer: &[u8]) -> Option<Self> {
+        if let Some(header) = Header::try_read(buffer) {
+            return Some(header);
+        }
+        let mut header = Header::default();
+        header.message_type = *buffer.first()?;
+        return Some(header);
+    }
+
+    pub fn try_read_unreliable_sequenced(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE {
+            return None;
+        }
+        return Some(Header::read_unreliable_sequenced(buffer));
+    }
+
+    pub fn try_read_nacked(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_NACKED_HEADER_SIZE {
+            return None;
+        }
+        return Some(Header::read_nacked(buffer));
+    }
+
+    pub fn try_read_fragmented(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < TACHYON_FRAGMENTED_HEADER_SIZE {
+            return None;
+        }
+        return Some(Header::read_fragmented(buffer));
+    }
+
+    // The writes above, or how long the buffer needed to be.  Ok is the header length.
+    pub fn try_write(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        BufferTooSmall::check(TACHYON_HEADER_SIZE, buffer)?;
+        self.write(buffer);
+        return Ok(TACHYON_HEADER_SIZE);
+    }
+
+    pub fn try_write_nacked(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        BufferTooSmall::check(TACHYON_NACKED_HEADER_SIZE, buffer)?;
+        self.write_nacked(buffer);
+        return Ok(TACHYON_NACKED_HEADER_SIZE);
+    }
+
+    pub fn try_write_fragmented(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        BufferTooSmall::check(TACHYON_FRAGMENTED_HEADER_SIZE, buffer)?;
+        self.write_fragmented(buffer);
+        return Ok(TACHYON_FRAGMENTED_HEADER_SIZE);
+    }
+
+    // what a reliable message of this type starts with, only RELIABLE_WITH_NACK carries the nack
+    pub fn reliable_size(&self) -> usize {
+        if self.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
+            return TACHYON_NACKED_HEADER_SIZE;
+        }
+        return TACHYON_HEADER_SIZE;
+    }
+
+    pub fn try_write_reliable(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        if self.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
+            return self.try_write_nacked(buffer);
+        }
+        return self.try_write(buffer);
+    }
+
+    pub fn create_fragmented(sequence: u16, channel: u16, group: u16, start: u16, count: u16) -> Self {
         let mut header = Header::default();
         header.message_type = MESSAGE_TYPE_FRAGMENT;
         header.sequence = sequence;
@@ -143,3 +447,109 @@ impl Header {
         return header;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::header::{
+        channel_message_start, is_channel_message, wide_channel_header, ConnectionHeader, Header, MESSAGE_TYPE_LINK_IDENTITY,
+        MESSAGE_TYPE_RELIABLE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_WIDE_CHANNEL,
+        TACHYON_CONNECTION_HEADER_SIZE, TACHYON_HEADER_SIZE, TACHYON_NACKED_HEADER_SIZE, TACHYON_WIDE_CHANNEL_HEADER_SIZE,
+    };
+
+    #[test]
+    fn test_try_write() {
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE;
+        header.channel = 3;
+        header.sequence = 200;
+
+        let mut buffer: Vec<u8> = vec![0; TACHYON_HEADER_SIZE - 1];
+        let too_small = header.try_write(&mut buffer).unwrap_err();
+        assert_eq!(TACHYON_HEADER_SIZE, too_small.required);
+        assert_eq!(TACHYON_HEADER_SIZE - 1, too_small.length);
+        assert_eq!(vec![0; TACHYON_HEADER_SIZE - 1], buffer);
+
+        buffer.resize(too_small.required, 0);
+        assert_eq!(Ok(TACHYON_HEADER_SIZE), header.try_write(&mut buffer));
+        assert_eq!(200, Header::read(&buffer).sequence);
+    }
+
+    #[test]
+    fn test_try_write_reliable() {
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
+        header.channel = 3;
+        header.sequence = 200;
+        header.start_sequence = 190;
+        header.flags = 5;
+        assert_eq!(TACHYON_NACKED_HEADER_SIZE, header.reliable_size());
+
+        let mut buffer: Vec<u8> = vec![0; TACHYON_HEADER_SIZE];
+        assert_eq!(TACHYON_NACKED_HEADER_SIZE, header.try_write_reliable(&mut buffer).unwrap_err().required);
+        buffer.resize(header.reliable_size(), 0);
+        assert_eq!(Ok(TACHYON_NACKED_HEADER_SIZE), header.try_write_reliable(&mut buffer));
+
+        let read = Header::read_nacked(&buffer);
+        assert_eq!(190, read.start_sequence);
+        assert_eq!(5, read.flags);
+
+        header.message_type = MESSAGE_TYPE_RELIABLE;
+        assert_eq!(TACHYON_HEADER_SIZE, header.reliable_size());
+        assert_eq!(Ok(TACHYON_HEADER_SIZE), header.try_write_reliable(&mut buffer));
+    }
+    #[test]
+    fn test_try_read() {
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
+        header.channel = 3;
+        header.sequence = 200;
+        header.start_sequence = 190;
+        header.flags = 5;
+        let mut buffer: Vec<u8> = vec![0; TACHYON_NACKED_HEADER_SIZE];
+        header.write_nacked(&mut buffer);
+
+        assert_eq!(190, Header::try_read_nacked(&buffer).unwrap().start_sequence);
+        assert!(Header::try_read_nacked(&buffer[0..TACHYON_NACKED_HEADER_SIZE - 1]).is_none());
+        assert_eq!(200, Header::try_read(&buffer[0..TACHYON_HEADER_SIZE]).unwrap().sequence);
+        assert!(Header::try_read(&buffer[0..TACHYON_HEADER_SIZE - 1]).is_none());
+
+        // the first look only has the type when the rest isn't there
+        assert_eq!(200, Header::try_read_type(&buffer).unwrap().sequence);
+        let unreliable = Header::try_read_type(&[MESSAGE_TYPE_UNRELIABLE, 7]).unwrap();
+        assert_eq!(MESSAGE_TYPE_UNRELIABLE, unreliable.message_type);
+        assert_eq!(0, unreliable.channel);
+        assert!(Header::try_read_type(&[]).is_none());
+
+        let connection_header = ConnectionHeader { message_type: MESSAGE_TYPE_LINK_IDENTITY, id: 4, session_id: 9 };
+        let mut buffer: Vec<u8> = vec![0; TACHYON_CONNECTION_HEADER_SIZE];
+        connection_header.write(&mut buffer);
+        assert_eq!(9, ConnectionHeader::try_read(&buffer).unwrap().session_id);
+        assert!(ConnectionHeader::try_read(&buffer[0..TACHYON_CONNECTION_HEADER_SIZE - 1]).is_none());
+    }
+
+    #[test]
+    fn test_wide_channel() {
+        assert!(wide_channel_header(255).is_none());
+        let wide = wide_channel_header(300).unwrap();
+        assert_eq!([MESSAGE_TYPE_WIDE_CHANNEL, 1], wide);
+
+        // the low byte goes in the header behind it
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE;
+        header.channel = 300;
+        header.sequence = 200;
+        let mut datagram: Vec<u8> = vec![0; TACHYON_WIDE_CHANNEL_HEADER_SIZE + TACHYON_HEADER_SIZE];
+        datagram[0..TACHYON_WIDE_CHANNEL_HEADER_SIZE].copy_from_slice(&wide);
+        header.write(&mut datagram[TACHYON_WIDE_CHANNEL_HEADER_SIZE..]);
+
+        let start = channel_message_start(&datagram);
+        assert_eq!(TACHYON_WIDE_CHANNEL_HEADER_SIZE, start);
+        assert!(is_channel_message(datagram[start]));
+        let read = Header::read(&datagram[start..]);
+        assert_eq!(300, read.channel | (datagram[1] as u16) << 8);
+        assert_eq!(200, read.sequence);
+
+        assert_eq!(0, channel_message_start(&datagram[start..]));
+        assert!(!is_channel_message(MESSAGE_TYPE_UNRELIABLE));
+    }
+}
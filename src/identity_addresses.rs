@@ -0,0 +1,70 @@
+use std::sync::{Arc, RwLock};
+
+use rustc_hash::FxHashMap;
+
+use super::network_address::NetworkAddress;
+
+// Read only copy of Tachyon.identity_to_address_map for other threads, so UnreliableSenders can send to an identity.
+// Tachyon publishes a new map whenever an identity is linked or unlinked.  A reader only holds the lock long enough to
+// clone the current Arc, lookups run against that snapshot and never block the owning thread.
+#[derive(Clone)]
+pub struct IdentityAddresses {
+    current: Arc<RwLock<Arc<FxHashMap<u32, NetworkAddress>>>>,
+}
+
+impl IdentityAddresses {
+    pub fn default() -> Self {
+        let addresses = IdentityAddresses {
+            current: Arc::new(RwLock::new(Arc::new(FxHashMap::default()))),
+        };
+        return addresses;
+    }
+
+    pub fn publish(&self, map: &FxHashMap<u32, NetworkAddress>) {
+        let snapshot = Arc::new(map.clone());
+        match self.current.write() {
+            Ok(mut current) => *current = snapshot,
+            Err(poisoned) => *poisoned.into_inner() = snapshot,
+        }
+    }
+
+    // hold on to this for a batch of lookups
+    pub fn snapshot(&self) -> Arc<FxHashMap<u32, NetworkAddress>> {
+        match self.current.read() {
+            Ok(current) => return current.clone(),
+            Err(poisoned) => return poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<NetworkAddress> {
+        return self.snapshot().get(&id).copied();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashMap;
+
+    use crate::network_address::NetworkAddress;
+
+    use super::IdentityAddresses;
+
+    #[test]
+    fn test_snapshot() {
+        let addresses = IdentityAddresses::default();
+        let shared = addresses.clone();
+        assert!(shared.get(1).is_none());
+
+        let mut map = FxHashMap::default();
+        map.insert(1, NetworkAddress::localhost(100));
+        addresses.publish(&map);
+        let snapshot = shared.snapshot();
+        assert!(shared.get(1).unwrap() == NetworkAddress::localhost(100));
+
+        // taken snapshots don't change
+        map.clear();
+        addresses.publish(&map);
+        assert!(shared.get(1).is_none());
+        assert_eq!(1, snapshot.len());
+    }
+}
@@ -1,11 +1,39 @@
-use std::ops::Range;
+use core::ops::Range;
 
 use super::network_address::NetworkAddress;
 
+// what write_address writes
+pub const NETWORK_ADDRESS_SIZE: usize = 12;
+
 pub struct IntBuffer {
     pub index: usize,
 }
 
+// A checked write that didn't fit.  required is how long the buffer has to be, counted from its start.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BufferTooSmall {
+    pub required: usize,
+    pub length: usize,
+}
+
+impl BufferTooSmall {
+    pub fn check(required: usize, buffer: &[u8]) -> Result<(), BufferTooSmall> {
+        if buffer.len() < required {
+            return Err(BufferTooSmall { required, length: buffer.len() });
+        }
+        return Ok(());
+    }
+}
+
+impl core::fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "buffer of {} bytes needed {}", self.length, self.required)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
 impl IntBuffer {
 
     pub fn write_address(&mut self, address: NetworkAddress, data: &mut [u8]) {
@@ -70,6 +98,51 @@ impl IntBuffer {
         return value;
     }
 
+    // The try_ reads return None when data ends first and leave index where it was, anything read off the network goes
+    // through these.  The reads above panic past the end of data and read whatever is there past the end of a datagram,
+    // they're for buffers this side wrote itself.
+    pub fn try_read_u8(&mut self, data: &[u8]) -> Option<u8> {
+        let value = *data.get(self.index)?;
+        self.index += 1;
+        return Some(value);
+    }
+
+    pub fn try_read_u16(&mut self, data: &[u8]) -> Option<u16> {
+        let bytes = data.get(self.index..self.index + 2)?;
+        self.index += 2;
+        return Some(u16::from_le_bytes([bytes[0], bytes[1]]));
+    }
+
+    pub fn try_read_u32(&mut self, data: &[u8]) -> Option<u32> {
+        let bytes = data.get(self.index..self.index + 4)?;
+        self.index += 4;
+        return Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+
+    pub fn try_read_address(&mut self, data: &[u8]) -> Option<NetworkAddress> {
+        data.get(self.index..self.index + NETWORK_ADDRESS_SIZE)?;
+        return Some(self.read_address(data));
+    }
+
+    // the writes above, Err and nothing written when data is too short
+    pub fn try_write_u8(&mut self, v: u8, data: &mut [u8]) -> Result<(), BufferTooSmall> {
+        BufferTooSmall::check(self.index + 1, data)?;
+        self.write_u8(v, data);
+        return Ok(());
+    }
+
+    pub fn try_write_u16(&mut self, v: u16, data: &mut [u8]) -> Result<(), BufferTooSmall> {
+        BufferTooSmall::check(self.index + 2, data)?;
+        self.write_u16(v, data);
+        return Ok(());
+    }
+
+    pub fn try_write_u32(&mut self, v: u32, data: &mut [u8]) -> Result<(), BufferTooSmall> {
+        BufferTooSmall::check(self.index + 4, data)?;
+        self.write_u32(v, data);
+        return Ok(());
+    }
+
     pub fn u4_to_u8(v1: u8, v2: u8) -> u8 {
         return v1 | v2 << 4;
     }
@@ -113,7 +186,7 @@ impl LengthPrefixed {
 
 #[cfg(test)]
 mod tests {
-    use crate::tachyon::int_buffer::IntBuffer;
+    use crate::{int_buffer::{IntBuffer, NETWORK_ADDRESS_SIZE}, network_address::NetworkAddress};
 
     #[test]
     fn test_4bit() {
@@ -144,4 +217,40 @@ mod tests {
         assert_eq!(1, buffer.read_u32(&bytes));
         return;
     }
+
+    #[test]
+    fn test_try_read_write() {
+        let mut bytes: Vec<u8> = vec![0; 7];
+        let mut buffer = IntBuffer { index: 0 };
+        assert_eq!(Ok(()), buffer.try_write_u32(234, &mut bytes));
+        assert_eq!(Ok(()), buffer.try_write_u16(44, &mut bytes));
+        let too_small = buffer.try_write_u16(1, &mut bytes).unwrap_err();
+        assert_eq!(8, too_small.required);
+        assert_eq!(6, buffer.index);
+        assert_eq!(Ok(()), buffer.try_write_u8(99, &mut bytes));
+
+        buffer.index = 0;
+        assert_eq!(Some(234), buffer.try_read_u32(&bytes));
+        assert_eq!(Some(44), buffer.try_read_u16(&bytes));
+        assert_eq!(None, buffer.try_read_u16(&bytes));
+        assert_eq!(6, buffer.index);
+        assert_eq!(Some(99), buffer.try_read_u8(&bytes));
+        assert_eq!(None, buffer.try_read_u8(&bytes));
+        assert_eq!(None, buffer.try_read_u32(&[]));
+    }
+
+    #[test]
+    fn test_try_read_address() {
+        let address = NetworkAddress::localhost(8010);
+        let mut bytes: Vec<u8> = vec![0; NETWORK_ADDRESS_SIZE];
+        let mut buffer = IntBuffer { index: 0 };
+        buffer.write_address(address, &mut bytes);
+        assert_eq!(NETWORK_ADDRESS_SIZE, buffer.index);
+
+        buffer.index = 0;
+        assert!(buffer.try_read_address(&bytes) == Some(address));
+        buffer.index = 1;
+        assert!(buffer.try_read_address(&bytes).is_none());
+        assert_eq!(1, buffer.index);
+    }
 }
@@ -0,0 +1,174 @@
+use std::time::Instant;
+
+use super::int_buffer::IntBuffer;
+
+// sent_at + echo, written directly after the reliable header on timestamped channels
+pub const TIMESTAMP_EXTENSION_SIZE: usize = 8;
+const LATENCY_SAMPLE_COUNT: usize = 256;
+
+// Tracks one way delay for a timestamped channel.
+// Every timestamped message carries the time it was sent (millis since the sender started) and an echo of the last remote
+// timestamp we received, advanced by how long we held it. When the echo comes back we have an rtt, and the sample with
+// the lowest rtt gives us the best estimate of the offset between the two clocks.
+// Until we have an rtt, delays are relative to the fastest message seen so far, which is still good enough to show jitter.
+pub struct LatencyTracker {
+    start_time: Instant,
+    remote_sent_at: u32,
+    remote_received_at: u32,
+    has_remote: bool,
+    pub rtt: u32,
+    min_rtt: u32,
+    clock_offset: i64,
+    has_clock_offset: bool,
+    min_raw_delay: i64,
+    samples: Vec<u32>,
+    sample_index: usize,
+}
+
+impl LatencyTracker {
+    pub fn create() -> Self {
+        let tracker = LatencyTracker {
+            start_time: Instant::now(),
+            remote_sent_at: 0,
+            remote_received_at: 0,
+            has_remote: false,
+            rtt: 0,
+            min_rtt: u32::MAX,
+            clock_offset: 0,
+            has_clock_offset: false,
+            min_raw_delay: i64::MAX,
+            samples: Vec::with_capacity(LATENCY_SAMPLE_COUNT),
+            sample_index: 0,
+        };
+        return tracker;
+    }
+
+    pub fn now(&self) -> u32 {
+        return self.start_time.elapsed().as_millis() as u32;
+    }
+
+    pub fn write(&self, buffer: &mut [u8], position: usize) {
+        let now = self.now();
+        let mut echo = 0;
+        if self.has_remote {
+            echo = self.remote_sent_at.wrapping_add(now.wrapping_sub(self.remote_received_at));
+        }
+
+        let mut writer = IntBuffer { index: position };
+        writer.write_u32(now, buffer);
+        writer.write_u32(echo, buffer);
+    }
+
+    // nothing when buffer ends before the timestamp does
+    pub fn read(&mut self, buffer: &[u8], position: usize) {
+        let now = self.now();
+        let mut reader = IntBuffer { index: position };
+        let sent_at = reader.try_read_u32(buffer);
+        let echo = reader.try_read_u32(buffer);
+        if let (Some(sent_at), Some(echo)) = (sent_at, echo) {
+            self.on_timestamp(now, sent_at, echo);
+        }
+    }
+
+    pub fn on_timestamp(&mut self, now: u32, sent_at: u32, echo: u32) {
+        if echo > 0 {
+            let rtt = now.wrapping_sub(echo);
+            self.rtt = rtt;
+            if rtt <= self.min_rtt {
+                self.min_rtt = rtt;
+                self.clock_offset = sent_at as i64 + (rtt / 2) as i64 - now as i64;
+                self.has_clock_offset = true;
+            }
+        }
+
+        self.remote_sent_at = sent_at;
+        self.remote_received_at = now;
+        self.has_remote = true;
+
+        let raw_delay = now as i64 - sent_at as i64;
+        let delay: i64;
+        if self.has_clock_offset {
+            delay = raw_delay + self.clock_offset;
+        } else {
+            if raw_delay < self.min_raw_delay {
+                self.min_raw_delay = raw_delay;
+            }
+            delay = raw_delay - self.min_raw_delay;
+        }
+        self.add_sample(std::cmp::max(delay, 0) as u32);
+    }
+
+    fn add_sample(&mut self, delay: u32) {
+        if self.samples.len() < LATENCY_SAMPLE_COUNT {
+            self.samples.push(delay);
+        } else {
+            self.samples[self.sample_index] = delay;
+        }
+        self.sample_index = (self.sample_index + 1) % LATENCY_SAMPLE_COUNT;
+    }
+
+    // returns (p50, p95, p99)
+    pub fn percentiles(&self) -> (u32, u32, u32) {
+        if self.samples.len() == 0 {
+            return (0, 0, 0);
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        return (
+            LatencyTracker::percentile(&sorted, 50),
+            LatencyTracker::percentile(&sorted, 95),
+            LatencyTracker::percentile(&sorted, 99),
+        );
+    }
+
+    // nearest rank
+    pub fn percentile(sorted: &[u32], percent: usize) -> u32 {
+        let rank = (sorted.len() * percent + 99) / 100;
+        return sorted[std::cmp::max(rank, 1) - 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatencyTracker, TIMESTAMP_EXTENSION_SIZE};
+
+    #[test]
+    fn test_relative_delay_without_rtt() {
+        let mut tracker = LatencyTracker::create();
+        tracker.on_timestamp(1000, 900, 0);
+        tracker.on_timestamp(1010, 905, 0);
+        tracker.on_timestamp(1020, 920, 0);
+
+        // fastest message is the baseline
+        let (p50, _p95, p99) = tracker.percentiles();
+        assert_eq!(0, p50);
+        assert_eq!(5, p99);
+    }
+
+    #[test]
+    fn test_rtt_clock_offset() {
+        let mut tracker = LatencyTracker::create();
+        // remote clock is 5000 ahead, one way delay is 10
+        tracker.on_timestamp(100, 5090, 80);
+        assert_eq!(20, tracker.rtt);
+
+        tracker.on_timestamp(200, 5190, 0);
+        tracker.on_timestamp(300, 5280, 0);
+        let (p50, _p95, p99) = tracker.percentiles();
+        assert_eq!(10, p50);
+        assert_eq!(20, p99);
+    }
+
+    #[test]
+    fn test_write_read() {
+        let mut buffer: Vec<u8> = vec![0; 16];
+        let sender = LatencyTracker::create();
+        sender.write(&mut buffer, 4);
+
+        let mut receiver = LatencyTracker::create();
+        receiver.read(&buffer, 4);
+        assert!(receiver.has_remote);
+        assert_eq!(1, receiver.samples.len());
+        assert_eq!(8, TIMESTAMP_EXTENSION_SIZE);
+    }
+}
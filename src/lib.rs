@@ -1,53 +1,215 @@
+// The protocol core (headers, sequences, nacks, fragmentation, the receive window and send buffers) builds on core and
+// alloc.  Sockets, the system clock and Tachyon itself need the std feature, which is on by default.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod address_filter;
+#[cfg(feature = "std")]
+pub mod challenge;
+#[cfg(feature = "std")]
 pub mod channel;
+#[cfg(feature = "std")]
+pub mod channel_id;
+#[cfg(feature = "std")]
+pub mod channel_epoch;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub mod checksum;
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod completion;
+#[cfg(feature = "std")]
+pub mod connect_limit;
+#[cfg(feature = "std")]
 pub mod connection;
+#[cfg(feature = "std")]
+pub mod connection_map;
+#[cfg(feature = "std")]
+pub mod connection_state;
+#[cfg(feature = "std")]
+pub mod control_observer;
+#[cfg(feature = "std")]
+pub mod data_channel_transport;
+#[cfg(feature = "std")]
+pub mod drop_simulation;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
 pub mod ffi;
 pub mod fragmentation;
+#[cfg(feature = "std")]
+pub mod gateway;
+#[cfg(feature = "std")]
+pub mod group;
+#[cfg(feature = "std")]
+pub mod handle_ffi;
 pub mod header;
+#[cfg(feature = "std")]
+pub mod identity_addresses;
 pub mod int_buffer;
+#[cfg(feature = "std")]
+pub mod latency;
+#[cfg(feature = "std")]
+pub mod link_payload;
+#[cfg(feature = "std")]
+pub mod loopback;
+#[cfg(feature = "std")]
+pub mod middleware;
 pub mod nack;
+#[cfg(feature = "std")]
+pub mod outgoing_queue;
+#[cfg(feature = "std")]
+pub mod out_buffer;
 pub mod network_address;
+#[cfg(feature = "std")]
+pub mod peers;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
 pub mod pool;
+#[cfg(feature = "std")]
 pub mod pool_ffi;
+#[cfg(feature = "std")]
+pub mod pool_load_generator;
+#[cfg(feature = "std")]
+pub mod quality;
+#[cfg(feature = "std")]
+pub mod rate_limit;
+#[cfg(feature = "std")]
 pub mod receive_result;
+#[cfg(feature = "std")]
+pub mod receive_ring;
+#[cfg(feature = "std")]
+pub mod receive_target;
 pub mod receiver;
+#[cfg(feature = "std")]
+pub mod reconnect;
+#[cfg(feature = "std")]
+pub mod relay;
+#[cfg(feature = "std")]
+pub mod rpc;
+#[cfg(feature = "std")]
+pub mod scramble;
+#[cfg(feature = "std")]
+pub mod security;
 pub mod send_buffer_manager;
 pub mod sequence;
 pub mod sequence_buffer;
+#[cfg(feature = "std")]
+pub mod sans_io;
+#[cfg(feature = "std")]
+pub mod sequence_trace;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "std")]
+pub mod sync_tachyon;
+#[cfg(feature = "std")]
 pub mod tachyon_socket;
+pub mod time;
+#[cfg(feature = "std")]
+pub mod topic;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
 pub mod unreliable_sender;
+#[cfg(feature = "std")]
+pub mod unreliable_channel;
+#[cfg(feature = "std")]
+pub mod unreliable_sender_pool;
+#[cfg(feature = "std")]
+pub mod wire;
 pub mod byte_buffer_pool;
+#[cfg(feature = "std")]
+pub mod capture;
+#[cfg(feature = "std")]
 pub mod pool_unreliable_sender;
+#[cfg(feature = "std")]
 pub mod memory_block;
+#[cfg(all(feature = "bytes", feature = "std"))]
+pub mod receive_bytes;
 
+#[cfg(feature = "std")]
 mod connection_impl;
 
 // additional stress/scale testing
 #[cfg(test)]
 pub mod tachyon_test;
 
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
 use std::time::Duration;
+#[cfg(feature = "std")]
 use std::time::Instant;
 
-use rustc_hash::FxHashMap;
-
-use self::channel::*;
-use self::connection::*;
-use self::connection_impl::ConnectionEventCallback;
-use self::connection_impl::IDENTITY_LINKED_EVENT;
-use self::connection_impl::IDENTITY_UNLINKED_EVENT;
-use self::connection_impl::LINK_IDENTITY_EVENT;
-use self::connection_impl::IdentityEventCallback;
-use self::connection_impl::UNLINK_IDENTITY_EVENT;
-use self::fragmentation::*;
-use self::header::*;
-use self::network_address::NetworkAddress;
-use self::pool::SendTarget;
-use self::receive_result::ReceiveResult;
-use self::receive_result::TachyonReceiveResult;
-use self::receive_result::RECEIVE_ERROR_CHANNEL;
-use self::receive_result::RECEIVE_ERROR_UNKNOWN;
-use self::tachyon_socket::*;
-use self::unreliable_sender::UnreliableSender;
+#[cfg(feature = "std")]
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[cfg(feature = "std")]
+use self::{
+    address_filter::AddressFilter,
+    byte_buffer_pool::{ByteBuffer, BYTE_BUFFER_SIZE_DEFAULT},
+    capture::PacketRecorder,
+    challenge::Challenge,
+    channel::*,
+    channel_epoch::ChannelEpoch,
+    channel_id::{ChannelId, ConfiguredChannel},
+    clock::*,
+    drop_simulation::DropSimulationConfig,
+    connection::*,
+    connection_impl::ConnectionEventCallback,
+    connection_impl::IDENTITY_DENIED_EVENT,
+    connection_impl::IDENTITY_LINKED_EVENT,
+    connection_impl::IDENTITY_RELINKED_EVENT,
+    connection_impl::IDENTITY_UNLINKED_EVENT,
+    connect_limit::{ConnectLimitConfig, ConnectLimits},
+    connection_impl::NotLinkedReplies,
+    connection_impl::LINK_IDENTITY_EVENT,
+    connection_impl::IdentityEventCallback,
+    connection_impl::UNLINK_IDENTITY_EVENT,
+    fragmentation::*,
+    group::Groups,
+    relay::Relay,
+    rpc::Rpc,
+    topic::Topics,
+    connection_map::ConnectionChanges,
+    connection_state::ConnectionState,
+    control_observer::{ControlCounts, ControlObserverCallback},
+    link_payload::LinkPayloads,
+    rate_limit::{RateLimitConfig, RateLimits},
+    reconnect::{Reconnect, ReconnectConfig},
+    sequence_trace::*,
+    security::{SecurityEvents, SECURITY_ADDRESS_REJECTED, SECURITY_IDENTITY_LINK_FAILED, SECURITY_MALFORMED_PACKET},
+    header::*,
+    middleware::PacketMiddleware,
+    network_address::NetworkAddress,
+    pipeline::Pipeline,
+    pipeline::PipelineStageFactory,
+    pool::SendTarget,
+    quality::ConnectionQuality,
+    quality::QualityTracker,
+    receive_ring::ReceiveRing,
+    receive_ring::RECEIVE_RING_SLOTS,
+    receive_result::ReceiveResult,
+    receive_result::TachyonReceiveResult,
+    receive_result::RECEIVE_ERROR_CHANNEL,
+    receive_result::RECEIVE_ERROR_UNKNOWN,
+    receive_result::RECEIVE_ERROR_MESSAGE_SIZE,
+    receive_target::{GrowableBuffer, ReceiveTarget},
+    error::TachyonReceiveError,
+    tachyon_socket::*,
+    transport::Transport,
+    unreliable_channel::UnreliableChannel,
+    unreliable_channel::UnreliableChannelConfig,
+    unreliable_channel::UnreliableChannelStats,
+    unreliable_sender::UnreliableSender,
+    identity_addresses::IdentityAddresses,
+};
 
 pub const SEND_ERROR_CHANNEL: u32 = 2;
 pub const SEND_ERROR_SOCKET: u32 = 1;
@@ -55,41 +217,212 @@ pub const SEND_ERROR_FRAGMENT: u32 = 3;
 pub const SEND_ERROR_UNKNOWN: u32 = 4;
 pub const SEND_ERROR_LENGTH: u32 = 5;
 pub const SEND_ERROR_IDENTITY: u32 = 6;
+pub const SEND_ERROR_PIPELINE: u32 = 7;
+pub const SEND_ERROR_WINDOW_FULL: u32 = 8;
+pub const SEND_ERROR_HANDLE: u32 = 9;
+pub const SEND_ERROR_NOT_CONNECTED: u32 = 10;
+
+// send_reliable_flags, the message keeps its place in the channel's order but is never resent
+pub const SEND_FLAG_EXPENDABLE: u32 = 1;
+
+// TachyonConfig.auto_create_channels, the config a channel created on first receive gets
+pub const AUTO_CREATE_CHANNELS_ORDERED: u32 = 1;
+pub const AUTO_CREATE_CHANNELS_UNORDERED: u32 = 2;
 
 
+#[cfg(feature = "std")]
 const SOCKET_RECEIVE_BUFFER_LEN: usize = 1024 * 1024;
+// datagrams a single receive_loop call reads without finding a message to return
+#[cfg(feature = "std")]
+const RECEIVE_DATA_BUDGET: u32 = 100;
+#[cfg(feature = "std")]
+const RECEIVE_CONTROL_BUDGET: u32 = 1000;
 
+#[cfg(feature = "std")]
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default, Debug)]
 pub struct TachyonStats {
     pub channel_stats: ChannelStats,
+    pub unreliable_stats: UnreliableChannelStats,
     pub packets_dropped: u64,
     pub unreliable_sent: u64,
     pub unreliable_received: u64,
+    pub unconnected_dropped: u64,
+    pub challenges_failed: u64,
+    // dropped for going over the rate limit or while muted
+    pub rate_limited: u64,
+    pub mutes: u64,
+    // dropped for lacking the protocol magic or having another version
+    pub bad_magic: u64,
+    pub version_mismatched: u64,
+    pub checksum_failed: u64,
+    // a client linked again after the server said it wasn't linked, see Tachyon::is_relinking
+    pub relinks: u64,
+    // updates that came more than twice TachyonConfig.update_interval_millis after the last one, and the intervals
+    // they left without an update
+    pub late_updates: u64,
+    pub skipped_updates: u64,
+    // longest gap between updates seen, kept by diff
+    pub max_update_gap_millis: u64,
+    // messages dropped for not fitting the receive buffer, each also failed a receive with RECEIVE_ERROR_MESSAGE_SIZE
+    pub receive_too_small: u64,
+    // reconnects that got through, see reconnect.rs
+    pub reconnects: u64,
+    // sends dropped by TachyonConfig.drop_simulation, inbound drops are in packets_dropped
+    pub outbound_dropped: u64,
+    // ids TachyonConfig.auto_create_channels configured
+    pub channels_auto_created: u64,
+    // datagrams from new addresses dropped by TachyonConfig.connect_limit
+    pub connections_throttled: u64,
+}
+
+// One address's channels added up, see Tachyon::get_address_stats
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct AddressStats {
+    pub channel_count: u32,
+    pub channel_stats: ChannelStats,
+    pub unreliable_stats: UnreliableChannelStats,
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for AddressStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "channel_count:{0} channel_stats:{1} unreliable_stats:{2}\n",
+            self.channel_count,
+            self.channel_stats,
+            self.unreliable_stats
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl TachyonStats {
+    // a copy to diff a later snapshot against, take it from get_combined_stats
+    pub fn snapshot(&self) -> TachyonStats {
+        return *self;
+    }
+
+    // what changed since earlier, see ChannelStats::diff
+    pub fn diff(&self, earlier: &TachyonStats) -> TachyonStats {
+        let stats = TachyonStats {
+            channel_stats: self.channel_stats.diff(&earlier.channel_stats),
+            unreliable_stats: self.unreliable_stats.diff(&earlier.unreliable_stats),
+            packets_dropped: self.packets_dropped.saturating_sub(earlier.packets_dropped),
+            unreliable_sent: self.unreliable_sent.saturating_sub(earlier.unreliable_sent),
+            unreliable_received: self.unreliable_received.saturating_sub(earlier.unreliable_received),
+            unconnected_dropped: self.unconnected_dropped.saturating_sub(earlier.unconnected_dropped),
+            challenges_failed: self.challenges_failed.saturating_sub(earlier.challenges_failed),
+            rate_limited: self.rate_limited.saturating_sub(earlier.rate_limited),
+            mutes: self.mutes.saturating_sub(earlier.mutes),
+            bad_magic: self.bad_magic.saturating_sub(earlier.bad_magic),
+            version_mismatched: self.version_mismatched.saturating_sub(earlier.version_mismatched),
+            checksum_failed: self.checksum_failed.saturating_sub(earlier.checksum_failed),
+            relinks: self.relinks.saturating_sub(earlier.relinks),
+            late_updates: self.late_updates.saturating_sub(earlier.late_updates),
+            skipped_updates: self.skipped_updates.saturating_sub(earlier.skipped_updates),
+            max_update_gap_millis: self.max_update_gap_millis,
+            receive_too_small: self.receive_too_small.saturating_sub(earlier.receive_too_small),
+            reconnects: self.reconnects.saturating_sub(earlier.reconnects),
+            outbound_dropped: self.outbound_dropped.saturating_sub(earlier.outbound_dropped),
+            channels_auto_created: self.channels_auto_created.saturating_sub(earlier.channels_auto_created),
+            connections_throttled: self.connections_throttled.saturating_sub(earlier.connections_throttled),
+        };
+        return stats;
+    }
 }
 
+#[cfg(feature = "std")]
 impl std::fmt::Display for TachyonStats {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "channel_stats:{0} packets_dropped:{1} unreliable_sent:{2} unreliable_received:{3}\n",
+            "channel_stats:{0} unreliable_stats:{1} packets_dropped:{2} unreliable_sent:{3} unreliable_received:{4} unconnected_dropped:{5} challenges_failed:{6} rate_limited:{7} mutes:{8} bad_magic:{9} version_mismatched:{10} checksum_failed:{11} relinks:{12} late_updates:{13} skipped_updates:{14} max_update_gap_millis:{15} receive_too_small:{16} reconnects:{17} outbound_dropped:{18} channels_auto_created:{19} connections_throttled:{20}\n",
             self.channel_stats,
+            self.unreliable_stats,
             self.packets_dropped,
             self.unreliable_sent,
-            self.unreliable_received
+            self.unreliable_received,
+            self.unconnected_dropped,
+            self.challenges_failed,
+            self.rate_limited,
+            self.mutes,
+            self.bad_magic,
+            self.version_mismatched,
+            self.checksum_failed,
+            self.relinks,
+            self.late_updates,
+            self.skipped_updates,
+            self.max_update_gap_millis,
+            self.receive_too_small,
+            self.reconnects,
+            self.outbound_dropped,
+            self.channels_auto_created,
+            self.connections_throttled
         )
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Default, Clone, Copy)]
 #[repr(C)]
 pub struct TachyonConfig {
     pub use_identity: u32,
     pub drop_packet_chance: u64,
-    pub drop_reliable_only: u32
+    pub drop_reliable_only: u32,
+    // servers without identities only create connections for clients that pass a cookie challenge, see challenge.rs.
+    // Set on both ends.
+    pub use_challenge: u32,
+    // channels resynchronize sequences when the other end restarts, see channel_epoch.rs.  Set on both ends.
+    pub use_channel_epochs: u32,
+    // per address limits on what we receive, see rate_limit.rs.  Off by default.
+    pub rate_limit: RateLimitConfig,
+    // every datagram starts with PROTOCOL_MAGIC and PROTOCOL_VERSION, anything else is dropped before it's parsed.
+    // Set on both ends.
+    pub use_protocol_magic: u32,
+    // every datagram carries a CRC32C of itself, ones that don't match are dropped.  Set on both ends.
+    pub use_checksum: u32,
+    // scrambles channel and sequence of reliable messages with a mask from this and the linked session, see
+    // scramble.rs.  0 is off.  Set to the same value on both ends.
+    pub scramble_key: u64,
+    // identity servers deny links past this many connections with LINK_DENIED_SERVER_FULL, 0 for no limit
+    pub max_linked_identities: u32,
+    // how often the application calls update.  Nacks and resends only go out from update, updates more than twice this
+    // apart are counted in TachyonStats.late_updates.  0 doesn't check.
+    pub update_interval_millis: u32,
+    // servers bind with SO_REUSEPORT so several can share a port and the kernel spreads clients over them, see
+    // Pool::create_servers_on_port.  Linux only, the bind fails elsewhere.
+    pub reuse_port: u32,
+    // clients reconnect on their own after losing the server, see reconnect.rs.  Off by default.
+    pub reconnect: ReconnectConfig,
+    // connections go Idle and then TimingOut after this long without a datagram, see connection_state.rs.  0 doesn't
+    // track that state.
+    pub idle_millis: u32,
+    pub timeout_millis: u32,
+    // seed, outbound rate and burst length for simulated loss, see drop_simulation.rs.  drop_packet_chance is the
+    // inbound rate.
+    pub drop_simulation: DropSimulationConfig,
+    // A message for a channel id that isn't configured creates the channel instead of failing the receive with
+    // RECEIVE_ERROR_CHANNEL, for when one side of a rolling upgrade defines new channels first.  Only for addresses
+    // with a connection.  AUTO_CREATE_CHANNELS_ORDERED or _UNORDERED, 0 is off.
+    pub auto_create_channels: u32,
+    // how fast a server without identities or challenges creates connections for new addresses, see connect_limit.rs.
+    // Off by default.
+    pub connect_limit: ConnectLimitConfig,
+    // servers only take datagrams from addresses added with Tachyon::add_peer, see peers.rs.  No connection is created
+    // for anything else.
+    pub peers_only: u32,
+    // channel ids above 255 can be configured, their messages go behind a wide channel header (see header.rs).  Both
+    // ends need it, a receiver without it drops wide datagrams as malformed.
+    pub wide_channels: u32,
 }
 
+#[cfg(feature = "std")]
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default)]
@@ -99,26 +432,64 @@ pub struct TachyonSendResult {
     pub header: Header,
 }
 
+#[cfg(feature = "std")]
 pub struct Tachyon {
     pub id: u16,
     pub socket: TachyonSocket,
-    pub socket_receive_buffer: Vec<u8>,
+    pub receive_ring: ReceiveRing,
     pub unreliable_sender: Option<UnreliableSender>,
     pub identities: FxHashMap<u32, u32>,
+    pub banned_identities: FxHashSet<u32>,
     pub connections: FxHashMap<NetworkAddress, Connection>,
     pub identity_to_address_map: FxHashMap<u32, NetworkAddress>,
-    pub channels: FxHashMap<(NetworkAddress, u8), Channel>,
-    pub channel_config: FxHashMap<u8, ChannelConfig>,
+    // identity_to_address_map for UnreliableSenders on other threads
+    pub identity_addresses: IdentityAddresses,
+    pub channels: FxHashMap<(NetworkAddress, u16), Channel>,
+    pub channel_config: FxHashMap<u16, ChannelConfig>,
+    pub channel_stages: FxHashMap<u16, Vec<PipelineStageFactory>>,
+    pub unreliable_channels: FxHashMap<NetworkAddress, UnreliableChannel>,
+    pub unreliable_channel_config: UnreliableChannelConfig,
+    pub quality: FxHashMap<NetworkAddress, QualityTracker>,
     pub config: TachyonConfig,
     pub nack_send_data: Vec<u8>,
     pub stats: TachyonStats,
     pub start_time: Instant,
+    pub clock: Arc<dyn Clock>,
+    // installed by the first step(), the instance then runs on stepped time
+    stepped_clock: Option<Arc<MockClock>>,
+    last_update: Option<Instant>,
     pub last_identity_link_request: Instant,
     pub identity: Identity,
+    // the last link denial a client got
+    pub link_denial: LinkDenial,
+    relink_pending: bool,
+    not_linked_replies: NotLinkedReplies,
     pub identity_event_callback: Option<IdentityEventCallback>,
-    pub connection_event_callback: Option<ConnectionEventCallback>
+    pub connection_event_callback: Option<ConnectionEventCallback>,
+    pub abandoned_callback: Option<AbandonedCallback>,
+    pub gap_skipped_callback: Option<GapSkippedCallback>,
+    pub anomaly_callback: Option<AnomalyCallback>,
+    pub control_observer: Option<ControlObserverCallback>,
+    // the current or last receive_loop call's, see control_observer.rs
+    pub control_counts: ControlCounts,
+    pub challenge: Challenge,
+    pub connection_handles: ConnectionHandles,
+    pub groups: Groups,
+    pub rpc: Rpc,
+    pub relay: Relay,
+    pub topics: Topics,
+    pub rate_limits: RateLimits,
+    pub connect_limits: ConnectLimits,
+    pub peers: FxHashSet<NetworkAddress>,
+    pub reconnect: Reconnect,
+    pub address_filter: AddressFilter,
+    pub security_events: SecurityEvents,
+    pub link_payloads: LinkPayloads,
+    // set by a Pool, connection adds and removes are published to it
+    pub connection_changes: Option<ConnectionChanges>
 }
 
+#[cfg(feature = "std")]
 impl Tachyon {
     pub fn create(config: TachyonConfig) -> Self {
         return Tachyon::create_with_id(config, 0);
@@ -130,21 +501,52 @@ impl Tachyon {
         let mut tachyon = Tachyon {
             id,
             identities: FxHashMap::default(),
+            banned_identities: FxHashSet::default(),
             connections: FxHashMap::default(),
             identity_to_address_map: FxHashMap::default(),
+            identity_addresses: IdentityAddresses::default(),
             channels: FxHashMap::default(),
             channel_config: FxHashMap::default(),
+            channel_stages: FxHashMap::default(),
+            unreliable_channels: FxHashMap::default(),
+            unreliable_channel_config: UnreliableChannelConfig::default(),
+            quality: FxHashMap::default(),
             socket: socket,
-            socket_receive_buffer: vec![0;SOCKET_RECEIVE_BUFFER_LEN],
+            receive_ring: ReceiveRing::create(RECEIVE_RING_SLOTS, SOCKET_RECEIVE_BUFFER_LEN),
             unreliable_sender: None,
             config,
             nack_send_data: vec![0; 4096],
             stats: TachyonStats::default(),
             start_time: Instant::now(),
+            clock: system_clock(),
+            stepped_clock: None,
+            last_update: None,
             last_identity_link_request: Instant::now() - Duration::new(100, 0),
             identity: Identity::default(),
+            link_denial: LinkDenial::default(),
+            relink_pending: false,
+            not_linked_replies: NotLinkedReplies::default(),
             identity_event_callback: None,
-            connection_event_callback: None
+            connection_event_callback: None,
+            abandoned_callback: None,
+            gap_skipped_callback: None,
+            anomaly_callback: None,
+            control_observer: None,
+            control_counts: ControlCounts::default(),
+            challenge: Challenge::default(),
+            connection_handles: ConnectionHandles::default(),
+            groups: Groups::default(),
+            rpc: Rpc::default(),
+            relay: Relay::default(),
+            topics: Topics::default(),
+            rate_limits: RateLimits::default(),
+            connect_limits: ConnectLimits::default(),
+            peers: FxHashSet::default(),
+            reconnect: Reconnect::default(),
+            address_filter: AddressFilter::default(),
+            security_events: SecurityEvents::default(),
+            link_payloads: LinkPayloads::default(),
+            connection_changes: None
         };
 
         tachyon.channel_config.insert(1, ChannelConfig::default_ordered());
@@ -154,34 +556,78 @@ impl Tachyon {
     }
 
 
+    pub fn now(&self) -> Instant {
+        return self.clock.now();
+    }
+
+    // shared by every channel, including ones created later
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        for channel in self.channels.values_mut() {
+            channel.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+
     pub fn time_since_start(&self) -> u64 {
-        return Instant::now().duration_since(self.start_time).as_millis() as u64;
+        return self.now().duration_since(self.start_time).as_millis() as u64;
     }
 
     pub fn bind(&mut self, address: NetworkAddress) -> bool {
-        match self.socket.bind_socket(address) {
-            CreateConnectResult::Success => {
-                self.unreliable_sender = self.create_unreliable_sender();
-                return true;
-            }
-            CreateConnectResult::Error => {
-                return false;
-            }
-        }
+        return self.try_bind(address).is_ok();
+    }
+
+    pub fn try_bind(&mut self, address: NetworkAddress) -> Result<(), TachyonSocketError> {
+        self.configure_socket();
+        self.socket.bind_socket(address)?;
+        self.unreliable_sender = self.create_unreliable_sender();
+        return Ok(());
     }
 
     pub fn connect(&mut self, address: NetworkAddress) -> bool {
-        match self.socket.connect_socket(address) {
-            CreateConnectResult::Success => {
-                let local_address = NetworkAddress::default();
-                self.create_connection(local_address, Identity::default());
-                self.unreliable_sender = self.create_unreliable_sender();
-                return true;
-            }
-            CreateConnectResult::Error => {
-                return false;
-            }
-        }
+        return self.try_connect(address).is_ok();
+    }
+
+    pub fn try_connect(&mut self, address: NetworkAddress) -> Result<(), TachyonSocketError> {
+        self.configure_socket();
+        self.socket.connect_socket(address)?;
+        self.challenge.accepted = false;
+        self.challenge.cookie = 0;
+        let local_address = NetworkAddress::default();
+        self.create_connection(local_address, Identity::default());
+        self.unreliable_sender = self.create_unreliable_sender();
+        self.reconnect.server_address = Some(address);
+        self.reconnect.last_heard = Some(self.now());
+        self.reconnect.unlinked_since = None;
+        return Ok(());
+    }
+
+    // Why the last send that didn't make it onto the wire failed, cleared when read.  Send results only carry a 0
+    // sent_len for these, reliable messages are still resent later.
+    pub fn take_socket_error(&mut self) -> Option<TachyonSocketError> {
+        return self.socket.take_last_error();
+    }
+
+    // Runs over a Transport instead of a UdpSocket, transport::DatagramQueue keeps the datagrams in memory queues.
+    pub fn bind_transport(&mut self, transport: Box<dyn Transport>) {
+        self.configure_socket();
+        self.socket.bind_transport(transport);
+        self.unreliable_sender = Some(UnreliableSender::create(None));
+    }
+
+    pub fn connect_transport(&mut self, transport: Box<dyn Transport>) {
+        self.configure_socket();
+        self.socket.connect_transport(transport);
+        self.create_connection(NetworkAddress::default(), Identity::default());
+        self.unreliable_sender = Some(UnreliableSender::create(None));
+    }
+
+    // the socket level options from config, before the socket is opened
+    fn configure_socket(&mut self) {
+        self.socket.set_protocol_magic(self.config.use_protocol_magic == 1);
+        self.socket.set_checksum(self.config.use_checksum == 1);
+        self.socket.set_scramble_key(self.config.scramble_key);
+        self.socket.reuse_port = self.config.reuse_port == 1;
+        self.socket.set_drop_simulation(self.config.drop_packet_chance, self.config.drop_reliable_only == 1, self.config.drop_simulation);
     }
 
     pub fn create_unreliable_sender(&self) -> Option<UnreliableSender> {
@@ -189,12 +635,15 @@ impl Tachyon {
         if !socket.is_some() {
             return None;
         }
-        let sender = UnreliableSender::create(socket);
+        let mut sender = UnreliableSender::create(socket);
+        sender.identity_addresses = Some(self.identity_addresses.clone());
+        sender.protocol_prefix = self.config.use_protocol_magic == 1;
+        sender.checksum = self.config.use_checksum == 1;
         return Some(sender);
     }
 
-    pub fn get_channel(&mut self, address: NetworkAddress, channel_id: u8) -> Option<&mut Channel> {
-        match self.channels.get_mut(&(address, channel_id)) {
+    pub fn get_channel(&mut self, address: NetworkAddress, channel_id: u16) -> Option<&mut Channel> {
+        match self.channel_mut(address, channel_id) {
             Some(channel) => {
                 return Some(channel);
             }
@@ -204,19 +653,115 @@ impl Tachyon {
         }
     }
 
-    fn create_configured_channels(&mut self, address: NetworkAddress) {
-        for (channel_id,config) in &self.channel_config {
-            match self.channels.get_mut(&(address, *channel_id)) {
-                Some(_) => {}
-                None => {
-                    let channel = Channel::create(*channel_id, address, *config);
-                    self.channels.insert((address, *channel_id), channel);
-                }
+    pub fn get_unreliable_channel(&mut self, address: NetworkAddress) -> Option<&mut UnreliableChannel> {
+        return self.unreliable_channels.get_mut(&address);
+    }
+
+    // every reliable channel the address has, ordered by id
+    pub fn get_channels_for(&mut self, address: NetworkAddress) -> Vec<&mut Channel> {
+        let mut channels: Vec<&mut Channel> = self.channels.iter_mut()
+            .filter(|(key, _)| key.0 == address)
+            .map(|(_, channel)| channel)
+            .collect();
+        channels.sort_by_key(|channel| channel.id);
+        return channels;
+    }
+
+    // Every channel of the address back to fresh sequences, for when the other end's channels are new.  With channel
+    // epochs each also gets a new epoch, so the other end resets too if it still has the old one.
+    pub fn reset_channels(&mut self, address: NetworkAddress) {
+        let use_epochs = self.config.use_channel_epochs == 1;
+        for channel in self.get_channels_for(address) {
+            channel.reset_sequences();
+            if use_epochs {
+                channel.epoch = Some(ChannelEpoch::create());
             }
         }
     }
 
+    // get_combined_stats for a single address, None if it has no channels
+    pub fn get_address_stats(&mut self, address: NetworkAddress) -> Option<AddressStats> {
+        let mut stats = AddressStats::default();
+        for channel in self.get_channels_for(address) {
+            channel.update_stats();
+            stats.channel_stats.add_from(&channel.stats);
+        }
+        // channels not used yet have nothing to add but still count
+        stats.channel_count = self.get_channel_count(address);
+        if let Some(channel) = self.unreliable_channels.get(&address) {
+            stats.unreliable_stats = channel.stats;
+        } else if stats.channel_count == 0 {
+            return None;
+        }
+        return Some(stats);
+    }
+
+    fn create_configured_channels(&mut self, address: NetworkAddress) {
+        let channel_ids: Vec<u16> = self.channel_config.keys().copied().collect();
+        for channel_id in channel_ids {
+            self.create_channel(address, channel_id);
+        }
+    }
+
+    fn create_channel(&mut self, address: NetworkAddress, channel_id: u16) {
+        if self.channels.contains_key(&(address, channel_id)) {
+            return;
+        }
+        let config = match self.channel_config.get(&channel_id) {
+            Some(config) => *config,
+            None => return,
+        };
+        let mut channel = Channel::create(channel_id, address, config);
+        channel.set_clock(self.clock.clone());
+        channel.abandoned_callback = self.abandoned_callback;
+        channel.gap_skipped_callback = self.gap_skipped_callback;
+        channel.anomaly_callback = self.anomaly_callback;
+        if self.config.use_channel_epochs == 1 {
+            channel.epoch = Some(ChannelEpoch::create());
+        }
+        if let Some(factories) = self.channel_stages.get(&channel_id) {
+            channel.pipeline = Pipeline::create(factories);
+        }
+        self.channels.insert((address, channel_id), channel);
+    }
+
+    // Connections get their configured channels on first use, a send or receive on one or anything that looks it up,
+    // so connecting doesn't pay for channels that are never used.  Except with channel epochs, where the handshake has
+    // to run before the first send and create_connection creates them all.
+    fn create_channel_on_use(&mut self, address: NetworkAddress, channel_id: u16) {
+        if !self.channels.contains_key(&(address, channel_id)) && self.connections.contains_key(&address) {
+            self.create_channel(address, channel_id);
+        }
+    }
+
+    fn channel_mut(&mut self, address: NetworkAddress, channel_id: u16) -> Option<&mut Channel> {
+        self.create_channel_on_use(address, channel_id);
+        return self.channels.get_mut(&(address, channel_id));
+    }
+
+    // TachyonConfig.auto_create_channels.  An id that isn't configured is configured with the default ordered or
+    // unordered config, so it's there for every connection from then on and goes when they do.
+    fn auto_create_channel(&mut self, address: NetworkAddress, channel_id: u16) {
+        if !self.connections.contains_key(&address) || self.channel_config.contains_key(&channel_id) {
+            return;
+        }
+        let config = if self.config.auto_create_channels == AUTO_CREATE_CHANNELS_UNORDERED {
+            ChannelConfig::default_unordered()
+        } else {
+            ChannelConfig::default_ordered()
+        };
+        if self.try_configure_channel(channel_id, config).is_err() {
+            return;
+        }
+        self.create_channel(address, channel_id);
+        self.stats.channels_auto_created += 1;
+    }
+
+    // every configured channel counts for a connection, created yet or not
     pub fn get_channel_count(&mut self, address: NetworkAddress) -> u32 {
+        if self.connections.contains_key(&address) {
+            return self.channel_config.len() as u32;
+        }
         let mut count = 0;
         for config in &self.channel_config {
             let channel_id = *config.0;
@@ -232,73 +777,438 @@ impl Tachyon {
             let channel_id = *config.0;
             self.channels.remove(&(address, channel_id));
         }
+        self.unreliable_channels.remove(&address);
+        self.quality.remove(&address);
+    }
+
+    pub fn configure_channel(&mut self, channel_id: u16, config: ChannelConfig) -> bool {
+        return self.try_configure_channel(channel_id, config).is_ok();
+    }
+
+    pub fn try_configure_channel(&mut self, channel_id: u16, config: ChannelConfig) -> Result<(), ChannelConfigError> {
+        ChannelId(channel_id).check_configurable(self.config.wide_channels == 1)?;
+        config.validate(channel_id)?;
+        self.channel_config.insert(channel_id, config);
+        return Ok(());
+    }
+
+    pub fn try_configure_channel_id(&mut self, channel_id: ChannelId, config: ChannelConfig) -> Result<(), ChannelConfigError> {
+        return self.try_configure_channel(channel_id.0, config);
+    }
+
+    pub fn get_channel_config(&self, channel_id: ChannelId) -> Option<&ChannelConfig> {
+        return self.channel_config.get(&channel_id.0);
+    }
+
+    // ChannelConfig.tag, 0 for the unreliable channel and ids that aren't configured
+    pub fn get_channel_tag(&self, channel_id: u16) -> u64 {
+        match self.get_channel_config(ChannelId(channel_id)) {
+            Some(config) => return config.tag,
+            None => return 0,
+        }
+    }
+
+    // The channel's traced sequence events oldest first, only those of sequence if given.  None unless the channel was
+    // configured with ChannelConfig.trace_events.
+    pub fn get_sequence_trace(&self, address: NetworkAddress, channel_id: u16, sequence: Option<u16>) -> Option<Vec<SequenceEvent>> {
+        let trace = self.channels.get(&(address, channel_id))?.trace.as_ref()?;
+        match sequence {
+            Some(sequence) => return Some(trace.events_for(sequence)),
+            None => return Some(trace.events()),
+        }
+    }
+
+    // every configured channel including the built in 1 and 2, by id
+    pub fn configured_channels(&self) -> Vec<ConfiguredChannel> {
+        let mut channels: Vec<ConfiguredChannel> = self.channel_config.iter()
+            .map(|(id, config)| ConfiguredChannel { id: ChannelId(*id), config: *config })
+            .collect();
+        channels.sort_by_key(|channel| channel.id);
+        return channels;
+    }
+
+    // Covers every channel id and the settings both ends have to agree on, see ChannelConfig::check_compatible.
+    // Exchange it in the application's own handshake, a different digest means the peers were configured differently.
+    // Stable across platforms and versions with the same ChannelConfig layout.
+    pub fn channel_config_digest(&self) -> u32 {
+        let mut ids: Vec<u16> = self.channel_config.keys().copied().collect();
+        ids.sort();
+
+        // fnv-1a
+        let mut hash: u32 = 0x811c9dc5;
+        for id in ids {
+            let config = &self.channel_config[&id];
+            let mut bytes = vec![id as u8, config.is_ordered() as u8, config.is_timestamped() as u8, config.is_unfragmented() as u8];
+            // the high byte only for wide ids, so digests of channels that fit a byte are what they were
+            if ChannelId(id).is_wide() {
+                bytes.push((id >> 8) as u8);
+            }
+            for byte in bytes {
+                hash ^= byte as u32;
+                hash = hash.wrapping_mul(0x01000193);
+            }
+        }
+        return hash;
     }
 
-    pub fn configure_channel(&mut self, channel_id: u8, config: ChannelConfig) -> bool {
-        if channel_id < 3 {
+    // Appends a pipeline stage to a configured channel, see pipeline.rs.  Only applies to channels created afterwards,
+    // so add stages before binding/connecting.  Both ends need the same stages in the same order.
+    pub fn add_channel_stage(&mut self, channel_id: u16, factory: PipelineStageFactory) -> bool {
+        if !self.channel_config.contains_key(&channel_id) {
             return false;
         }
-        self.channel_config.insert(channel_id, config);
+        self.channel_stages.entry(channel_id).or_insert_with(Vec::new).push(factory);
         return true;
     }
 
+    // packet middleware runs on every datagram this instance sends and receives, see middleware.rs
+    pub fn set_middleware(&mut self, middleware: Box<dyn PacketMiddleware>) {
+        self.socket.set_middleware(middleware);
+    }
+
+    pub fn clear_middleware(&mut self) {
+        self.socket.clear_middleware();
+    }
+
+    // records every datagram to writer in the capture format, see capture.rs. Any installed middleware keeps running.
+    pub fn start_capture<W: std::io::Write + Send + 'static>(&mut self, writer: W) {
+        let inner = self.socket.middleware.take().map(|state| state.into_inner().middleware);
+        let recorder = PacketRecorder::create(writer, self.socket.is_server, inner);
+        self.socket.set_middleware(Box::new(recorder));
+    }
+
+    // channel 0
+    pub fn configure_unreliable_channel(&mut self, config: UnreliableChannelConfig) {
+        self.unreliable_channel_config = config;
+    }
+
     pub fn get_combined_stats(&mut self) -> TachyonStats {
         let mut channel_stats = ChannelStats::default();
         for channel in self.channels.values_mut() {
             channel.update_stats();
             channel_stats.add_from(&channel.stats);
         }
+        let mut unreliable_stats = UnreliableChannelStats::default();
+        for channel in self.unreliable_channels.values() {
+            unreliable_stats.add_from(&channel.stats);
+        }
         let mut stats = self.stats.clone();
         stats.channel_stats = channel_stats;
+        stats.unreliable_stats = unreliable_stats;
+        stats.outbound_dropped = self.socket.outbound_drops.borrow().dropped;
         return stats;
     }
 
+    // zeroes every counter get_combined_stats reports, on all channels
+    pub fn reset_stats(&mut self) {
+        self.stats = TachyonStats::default();
+        self.socket.outbound_drops.borrow_mut().dropped = 0;
+        for channel in self.channels.values_mut() {
+            channel.reset_stats();
+        }
+        for channel in self.unreliable_channels.values_mut() {
+            channel.stats = UnreliableChannelStats::default();
+        }
+        for tracker in self.quality.values_mut() {
+            tracker.reset_counters();
+        }
+    }
+
     pub fn update(&mut self) {
-        self.client_identity_update();
+        let now = self.now();
+        self.update_at(now);
+    }
+
+    // Advances the instance's clock by dt and runs update against it, for deterministic/lockstep simulations and tests.
+    // The first step replaces the clock with a MockClock starting at start_time, don't mix with update() on the same instance.
+    pub fn step(&mut self, dt: Duration) {
+        let clock = match &self.stepped_clock {
+            Some(clock) => clock.clone(),
+            None => {
+                let clock = Arc::new(MockClock::create(self.start_time));
+                self.set_clock(clock.clone());
+                self.stepped_clock = Some(clock.clone());
+                clock
+            }
+        };
+        clock.advance(dt);
+        self.update_at(clock.now());
+    }
+
+    fn update_at(&mut self, now: Instant) {
+        self.check_update_cadence(now);
+        self.client_reconnect_update(now);
+        self.update_connection_states();
+        self.client_identity_update(now);
+        self.client_challenge_update(now);
+        self.client_topic_update(now);
+        self.flush_outgoing_at(now);
+
+        for channel in self.channels.values_mut() {
+            channel.update_at(&self.socket, now);
+        }
+
+        self.update_quality(now);
+        self.rpc_update(now);
+        self.rate_limit_update(now);
+        self.connect_limit_update(now);
+    }
+
+    fn check_update_cadence(&mut self, now: Instant) {
+        let last = self.last_update.replace(now);
+        let interval = self.config.update_interval_millis as u64;
+        let last = match last {
+            Some(last) if interval > 0 => last,
+            _ => return,
+        };
+        let gap = now.saturating_duration_since(last).as_millis() as u64;
+        self.stats.max_update_gap_millis = self.stats.max_update_gap_millis.max(gap);
+        if gap > interval * 2 {
+            self.stats.late_updates += 1;
+            self.stats.skipped_updates += gap / interval - 1;
+        }
+    }
+
+    // Queue a reliable message to go out on the next update/flush_outgoing, higher priority first.  deadline_millis 0
+    // never expires, otherwise the message is dropped if it's still queued that long from now.
+    pub fn enqueue_reliable(&mut self, channel_id: u16, address: NetworkAddress, data: &[u8], priority: u8, deadline_millis: u32) -> u32 {
+        if data.len() == 0 {
+            return SEND_ERROR_LENGTH;
+        }
+        let now = self.now();
+        match self.channel_mut(address, channel_id) {
+            Some(channel) => {
+                let mut deadline: Option<Instant> = None;
+                if deadline_millis > 0 {
+                    deadline = Some(now + Duration::from_millis(deadline_millis as u64));
+                }
+                channel.outgoing.push(data, priority, deadline);
+                return 0;
+            }
+            None => {
+                return SEND_ERROR_CHANNEL;
+            }
+        }
+    }
+
+    // returns the number of queued messages sent
+    pub fn flush_outgoing(&mut self) -> u32 {
+        return self.flush_outgoing_at(self.now());
+    }
+
+    fn flush_outgoing_at(&mut self, now: Instant) -> u32 {
+        if !self.can_send() || !self.socket.is_open() {
+            return 0;
+        }
+
+        let mut sent = 0;
+        for channel in self.channels.values_mut() {
+            if !channel.is_epoch_acked() {
+                continue;
+            }
+            let mut expired = 0;
+            while let Some(message) = channel.outgoing.pop_live(now, &mut expired) {
+                let address = channel.address;
+                let result = Tachyon::send_through_pipeline(channel, &self.socket, address, &[IoSlice::new(&message.data)]);
+                if result.error == SEND_ERROR_WINDOW_FULL {
+                    channel.outgoing.push_back(message);
+                    break;
+                }
+                if result.error == 0 {
+                    sent += 1;
+                }
+            }
+            channel.stats.queue_expired += expired;
+        }
+        return sent;
+    }
+
+    // the pieces of update() individually
+
+    pub fn flush_nacks(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.send_nacks(&self.socket);
+        }
+    }
 
+    pub fn flush_resends(&mut self) {
         for channel in self.channels.values_mut() {
-            channel.update(&self.socket);
+            channel.resend_nacked(&self.socket);
+        }
+    }
+
+    pub fn publish_all(&mut self) {
+        let now = self.now();
+        for channel in self.channels.values_mut() {
+            channel.publish_at(now);
+        }
+    }
+
+    // quality snapshot for a connection, refreshed by update() once per QUALITY_REFRESH_MILLIS
+    pub fn get_connection_quality(&self, address: NetworkAddress) -> Option<ConnectionQuality> {
+        return self.quality.get(&address).map(|tracker| tracker.quality);
+    }
+
+    fn update_quality(&mut self, now: Instant) {
+        for address in self.connections.keys() {
+            let tracker = self.quality.entry(*address).or_insert_with(|| QualityTracker::create(now));
+            if !tracker.should_refresh(now) {
+                continue;
+            }
+
+            let mut stats = ChannelStats::default();
+            for channel_id in self.channel_config.keys() {
+                if let Some(channel) = self.channels.get_mut(&(*address, *channel_id)) {
+                    channel.update_stats();
+                    stats.add_from(&channel.stats);
+                }
+            }
+            let mut unreliable_stats = UnreliableChannelStats::default();
+            if let Some(channel) = self.unreliable_channels.get(address) {
+                unreliable_stats = channel.stats;
+            }
+            tracker.refresh(&stats, &unreliable_stats, now);
+
+            let loss_percent = tracker.quality.loss_percent;
+            for channel_id in self.channel_config.keys() {
+                if let Some(channel) = self.channels.get_mut(&(*address, *channel_id)) {
+                    channel.adapt_nack_redundancy(loss_percent);
+                }
+            }
         }
     }
 
-    fn receive_published_channel_id(&mut self,  receive_buffer: &mut [u8], address: NetworkAddress, channel_id: u8) -> u32 {
+    fn receive_published_channel_id<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T, address: NetworkAddress, channel_id: u16) -> Result<u32, usize> {
         match self.channels.get_mut(&(address, channel_id)) {
             Some(channel) => {
-                let res = channel.receive_published(receive_buffer);
-                return res.0;
+                let res = channel.receive_published_into(target)?;
+                return Ok(res.0);
             }
             None => {
-                return 0;
+                return Ok(0);
             }
         }
     }
 
-    fn receive_published_all_channels(&mut self, receive_buffer: &mut [u8]) -> TachyonReceiveResult {
+    fn receive_published_all_channels<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T) -> TachyonReceiveResult {
         let mut result = TachyonReceiveResult::default();
 
         for channel in self.channels.values_mut() {
-            let res = channel.receive_published(receive_buffer);
-            if res.0 > 0 {
-                result.length = res.0;
-                result.address = res.1;
-                result.channel = channel.id as u16;
-                return result;
+            match channel.receive_published_into(target) {
+                Ok(res) => {
+                    if res.0 > 0 {
+                        result.length = res.0;
+                        result.address = res.1;
+                        result.channel = channel.id as u16;
+                        return result;
+                    }
+                }
+                Err(_) => {
+                    self.stats.receive_too_small += 1;
+                    result.error = RECEIVE_ERROR_MESSAGE_SIZE;
+                    result.address = channel.address;
+                    result.channel = channel.id as u16;
+                    return result;
+                }
             }
         }
         return result;
     }
 
     pub fn receive_loop(&mut self, receive_buffer: &mut [u8]) -> TachyonReceiveResult {
+        let mut result = self.receive_loop_internal(receive_buffer);
+        if result.length > 0 {
+            self.set_receive_connection(&mut result);
+        }
+        return result;
+    }
+
+    // receive_loop into a Vec, grown to fit the message.  Messages longer than max_message_size are dropped with
+    // MessageTooLarge, receive_loop fails the same way for messages that don't fit its buffer.  The Vec is only ever
+    // grown, the message is the first result.length bytes.
+    pub fn receive_into_vec(&mut self, receive_buffer: &mut Vec<u8>, max_message_size: usize) -> Result<TachyonReceiveResult, TachyonReceiveError> {
+        let mut target = GrowableBuffer {
+            buffer: receive_buffer,
+            max_length: max_message_size,
+        };
+        let mut result = self.receive_loop_internal(&mut target);
+        if result.length > 0 {
+            self.set_receive_connection(&mut result);
+        }
+        result.to_result()?;
+        return Ok(result);
+    }
+
+    // The connection's handle, so callers can reply without going through the address again.  When identities are in
+    // use the server also resolves the sender's identity so callers don't have to map addresses themselves.
+    fn set_receive_connection(&self, result: &mut TachyonReceiveResult) {
+        if !result.relayed_by.is_default() {
+            return;
+        }
+        if let Some(conn) = self.connections.get(&result.address) {
+            result.connection = conn.handle;
+            result.user_data = conn.user_data;
+            if self.config.use_identity == 1 && self.socket.is_server {
+                result.identity_id = conn.identity.id;
+                result.session_id = conn.identity.session_id;
+            }
+        }
+    }
+
+    // messages on the rpc channel are handled here, see rpc.rs, and relayed messages too, see relay.rs
+    fn receive_loop_internal<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T) -> TachyonReceiveResult {
+        self.control_counts = ControlCounts::default();
+        loop {
+            let mut result = self.receive_message(target);
+            if result.length > 0 && result.error == 0 && self.relay.channel_id != 0 {
+                if let Some(message) = target.reserve(result.length as usize) {
+                    if !self.process_relay(&mut result, message) {
+                        continue;
+                    }
+                }
+            }
+            if result.length == 0 || result.error > 0 || !self.is_rpc_channel(result.channel) {
+                result.channel_tag = self.get_channel_tag(result.channel);
+                self.report_control();
+                return result;
+            }
+            if let Some(message) = target.reserve(result.length as usize) {
+                self.process_rpc_message(result.address, message);
+            }
+        }
+    }
+
+    fn receive_message<T: ReceiveTarget + ?Sized>(&mut self, target: &mut T) -> TachyonReceiveResult {
         let mut result = TachyonReceiveResult::default();
 
-        for _ in 0..100 {
+        // Control messages (nacks, nones, identity) get their own budget, so a burst of data that publishes nothing yet
+        // (fragments, duplicates) can't hold up reliability control, and a nack storm can't use up the data budget.  Once
+        // the data budget is spent we keep reading to get at control messages queued behind the data, and data read on
+        // the way comes out of the control budget.
+        let mut data_budget = RECEIVE_DATA_BUDGET;
+        let mut control_budget = RECEIVE_CONTROL_BUDGET;
+        while control_budget > 0 {
             let receive_result = self.receive_from_socket();
             match receive_result {
                 ReceiveResult::Reliable {
                     network_address: socket_addr,
                     channel_id,
                 } => {
-                    let published = self.receive_published_channel_id(receive_buffer, socket_addr, channel_id);
+                    if data_budget > 0 {
+                        data_budget -= 1;
+                    } else {
+                        control_budget -= 1;
+                    }
+                    let published = match self.receive_published_channel_id(target, socket_addr, channel_id) {
+                        Ok(published) => published,
+                        Err(_) => {
+                            self.stats.receive_too_small += 1;
+                            result.error = RECEIVE_ERROR_MESSAGE_SIZE;
+                            result.channel = channel_id as u16;
+                            result.address = socket_addr;
+                            return result;
+                        }
+                    };
                     if published > 0 {
                         result.channel = channel_id as u16;
                         result.length = published;
@@ -308,79 +1218,202 @@ impl Tachyon {
                 }
                 ReceiveResult::UnReliable {
                     received_len,
+                    header_len,
                     network_address: socket_addr,
                 } => {
-                    receive_buffer[0..received_len-1].copy_from_slice(&self.socket_receive_buffer[1..received_len]);
-                    result.length = (received_len - 1) as u32;
                     result.address = socket_addr;
+                    if target.keeps_buffers() {
+                        // the slot gets a new buffer in place of the one handed over
+                        if let Some(byte_buffer) = self.receive_ring.take_current(ByteBuffer::create(BYTE_BUFFER_SIZE_DEFAULT)) {
+                            target.keep_buffer(byte_buffer.into_vec(), header_len);
+                            result.length = (received_len - header_len) as u32;
+                            return result;
+                        }
+                    }
+                    match target.reserve(received_len - header_len) {
+                        Some(dest) => {
+                            dest.copy_from_slice(&self.receive_ring.packet()[header_len..received_len]);
+                            result.length = (received_len - header_len) as u32;
+                        }
+                        None => {
+                            self.stats.receive_too_small += 1;
+                            result.error = RECEIVE_ERROR_MESSAGE_SIZE;
+                        }
+                    }
                     return result;
                 }
                 ReceiveResult::Empty => {
                     break;
                 }
-                ReceiveResult::Retry => {}
+                ReceiveResult::Retry => {
+                    if data_budget > 0 {
+                        data_budget -= 1;
+                    } else {
+                        control_budget -= 1;
+                    }
+                }
+                ReceiveResult::Control { message_type } => {
+                    control_budget -= 1;
+                    self.control_counts.record(message_type);
+                }
                 ReceiveResult::Error => {
                     result.error = RECEIVE_ERROR_UNKNOWN;
                     return result;
                 }
-                ReceiveResult::ChannelError => {
+                ReceiveResult::ChannelError { channel_id } => {
                     result.error = RECEIVE_ERROR_CHANNEL;
+                    result.channel = channel_id as u16;
                     return result;
                 }
             }
         }
-        return self.receive_published_all_channels(receive_buffer);
+        return self.receive_published_all_channels(target);
     }
 
 
     fn receive_from_socket(&mut self) -> ReceiveResult {
         let address: NetworkAddress;
-        let received_len: usize;
-        let header: Header;
-
-        let socket_result = self.socket.receive(&mut self.socket_receive_buffer,self.config.drop_packet_chance,self.config.drop_reliable_only == 1);
+        let mut received_len: usize;
+        let mut header: Header;
+
+        let socket_result = if self.socket.can_receive_vectored() {
+            let (slot, overflow) = self.receive_ring.receive_targets();
+            let result = self.socket.receive_vectored(slot, overflow);
+            if let SocketReceiveResult::Success { bytes_received, .. } = result {
+                self.receive_ring.set_received_vectored(bytes_received);
+            }
+            result
+        } else {
+            let result = self.socket.receive(self.receive_ring.overflow_mut());
+            if let SocketReceiveResult::Success { bytes_received, .. } = result {
+                self.receive_ring.set_received(bytes_received);
+            }
+            result
+        };
         match socket_result {
             SocketReceiveResult::Success {bytes_received, network_address} => {
                 received_len = bytes_received;
                 address = network_address;
 
-                header = Header::read(&self.socket_receive_buffer);
+                let rejected_by = self.address_filter.filter(address);
+                if rejected_by != 0 {
+                    self.fire_security_event(SECURITY_ADDRESS_REJECTED, address, rejected_by as u32);
+                    return ReceiveResult::Retry;
+                }
+                if !self.rate_limit_receive(address, received_len) {
+                    return ReceiveResult::Retry;
+                }
+                if !self.peer_allows_receive(address) {
+                    return ReceiveResult::Retry;
+                }
+
+                // a message on a wide channel, from here on it's the datagram behind the wide channel header
+                let mut channel_high: u16 = 0;
+                if self.receive_ring.received().first() == Some(&MESSAGE_TYPE_WIDE_CHANNEL) {
+                    let received = self.receive_ring.received();
+                    if self.config.wide_channels == 0 || received_len < TACHYON_WIDE_CHANNEL_HEADER_SIZE + TACHYON_HEADER_SIZE
+                        || !is_channel_message(received[TACHYON_WIDE_CHANNEL_HEADER_SIZE]) {
+                        return self.receive_malformed(address, MESSAGE_TYPE_WIDE_CHANNEL);
+                    }
+                    channel_high = received[1] as u16;
+                    self.receive_ring.strip_front(TACHYON_WIDE_CHANNEL_HEADER_SIZE);
+                    received_len -= TACHYON_WIDE_CHANNEL_HEADER_SIZE;
+                }
+
+                header = match Header::try_read_type(self.receive_ring.received()) {
+                    Some(header) => header,
+                    None => return ReceiveResult::Retry,
+                };
+                header.channel |= channel_high << 8;
 
                 if self.socket.is_server {
                     if self.config.use_identity == 1 {
                         let connection_header: ConnectionHeader;
 
                         if header.message_type == MESSAGE_TYPE_LINK_IDENTITY {
-                            connection_header = ConnectionHeader::read(&self.socket_receive_buffer);
-                            if self.try_link_identity(address, connection_header.id, connection_header.session_id) {
+                            connection_header = match ConnectionHeader::try_read(self.receive_ring.received()) {
+                                Some(connection_header) => connection_header,
+                                None => return self.receive_malformed(address, header.message_type),
+                            };
+                            let payload = LinkPayloads::read(self.receive_ring.packet(), received_len).to_vec();
+                            let reason = self.link_identity(address, connection_header.id, connection_header.session_id);
+                            if reason == 0 {
+                                self.set_received_link_payload(address, &payload);
                                 self.fire_identity_event(LINK_IDENTITY_EVENT, address, connection_header.id, connection_header.session_id);
+                            } else {
+                                self.send_identity_denied(address, connection_header.id, reason, received_len);
+                                self.fire_security_event(SECURITY_IDENTITY_LINK_FAILED, address, connection_header.id);
                             }
-                            return ReceiveResult::Retry;
+                            return ReceiveResult::Control { message_type: header.message_type };
                         } else if header.message_type == MESSAGE_TYPE_UNLINK_IDENTITY {
-                            connection_header = ConnectionHeader::read(&self.socket_receive_buffer);
+                            connection_header = match ConnectionHeader::try_read(self.receive_ring.received()) {
+                                Some(connection_header) => connection_header,
+                                None => return self.receive_malformed(address, header.message_type),
+                            };
                             if self.try_unlink_identity(address, connection_header.id, connection_header.session_id) {
                                 self.fire_identity_event(UNLINK_IDENTITY_EVENT, address, connection_header.id, connection_header.session_id);
                             }
-                            return ReceiveResult::Retry;
+                            return ReceiveResult::Control { message_type: header.message_type };
                         } else {
                             if !self.validate_and_update_linked_connection(address) {
+                                self.send_not_linked(address, received_len);
                                 return ReceiveResult::Retry;
                             }
                         }
-                    } else {
-                        self.on_receive_connection_update(address);
+                    } else if self.is_challenged() {
+                        if !self.server_challenge_receive(address, received_len) {
+                            return ReceiveResult::Retry;
+                        }
+                    } else if !self.on_receive_connection_update(address) {
+                        return ReceiveResult::Retry;
                     }
                 } else {
+                    self.reconnect_heard();
+                    self.client_connection_heard();
+                    if self.is_challenged() && self.client_challenge_receive(received_len) {
+                        return ReceiveResult::Control { message_type: header.message_type };
+                    }
                     if self.config.use_identity == 1 {
                         if header.message_type == MESSAGE_TYPE_IDENTITY_LINKED {
+                            let payload = LinkPayloads::read(self.receive_ring.packet(), received_len).to_vec();
+                            self.set_received_link_payload(address, &payload);
                             self.identity.set_linked(1);
+                            self.socket.set_scramble_session(address, self.identity.session_id);
+                            self.set_connection_state(address, ConnectionState::Linked);
                             self.fire_identity_event(IDENTITY_LINKED_EVENT, address, 0, 0);
+                            if self.relink_pending {
+                                self.finish_relink();
+                                self.fire_identity_event(IDENTITY_RELINKED_EVENT, address, 0, 0);
+                            }
 
-                            return ReceiveResult::Retry;
+                            return ReceiveResult::Control { message_type: header.message_type };
                         } else if header.message_type == MESSAGE_TYPE_IDENTITY_UNLINKED {
+                            let connection_header = match ConnectionHeader::try_read(self.receive_ring.received()) {
+                                Some(connection_header) => connection_header,
+                                None => return self.receive_malformed(address, header.message_type),
+                            };
+                            if self.identity.is_linked() && connection_header.session_id == UNLINK_REASON_NOT_LINKED as u32 {
+                                self.start_relink();
+                            }
                             self.identity.set_linked(0);
+                            self.socket.set_scramble_session(address, 0);
+                            self.reset_channels(address);
+                            self.set_connection_state(address, ConnectionState::Connecting);
                             self.fire_identity_event(IDENTITY_UNLINKED_EVENT, address, 0, 0);
-                            return ReceiveResult::Retry;
+                            return ReceiveResult::Control { message_type: header.message_type };
+                        } else if header.message_type == MESSAGE_TYPE_IDENTITY_DENIED {
+                            let connection_header = match ConnectionHeader::try_read(self.receive_ring.received()) {
+                                Some(connection_header) => connection_header,
+                                None => return self.receive_malformed(address, header.message_type),
+                            };
+                            if !self.identity.is_linked() && connection_header.id == self.identity.id {
+                                let payload = LinkPayloads::read(self.receive_ring.packet(), received_len).to_vec();
+                                self.set_received_link_payload(address, &payload);
+                                let reason = connection_header.session_id as u8;
+                                self.link_denial = LinkDenial { id: self.identity.id, session_id: self.identity.session_id, reason };
+                                self.fire_identity_event(IDENTITY_DENIED_EVENT, address, self.identity.id, reason as u32);
+                            }
+                            return ReceiveResult::Control { message_type: header.message_type };
                         }
 
                         if !self.identity.is_linked() {
@@ -399,61 +1432,160 @@ impl Tachyon {
                 self.stats.packets_dropped += 1;
                 return ReceiveResult::Retry;
             }
+            SocketReceiveResult::Corrupted => {
+                self.stats.checksum_failed += 1;
+                return ReceiveResult::Retry;
+            }
+            SocketReceiveResult::Rejected { version_mismatch } => {
+                if version_mismatch {
+                    self.stats.version_mismatched += 1;
+                } else {
+                    self.stats.bad_magic += 1;
+                }
+                return ReceiveResult::Retry;
+            }
         }
 
-        if header.message_type == MESSAGE_TYPE_UNRELIABLE {
+        if header.message_type == MESSAGE_TYPE_SUBSCRIBE || header.message_type == MESSAGE_TYPE_UNSUBSCRIBE || header.message_type == MESSAGE_TYPE_TOPIC_ACK {
+            self.topic_receive(address, received_len);
+            return ReceiveResult::Control { message_type: header.message_type };
+        }
+
+        if header.message_type == MESSAGE_TYPE_UNRELIABLE || header.message_type == MESSAGE_TYPE_UNRELIABLE_SEQUENCED {
+            let header_len: usize;
+            let mut sequenced_header: Option<Header> = None;
+            if header.message_type == MESSAGE_TYPE_UNRELIABLE_SEQUENCED {
+                sequenced_header = Header::try_read_unreliable_sequenced(self.receive_ring.received());
+                if sequenced_header.is_none() {
+                    return self.receive_malformed(address, header.message_type);
+                }
+            }
+            let unreliable_channel = self.unreliable_channels.entry(address).or_insert_with(|| UnreliableChannel::create(address));
+            if let Some(sequenced_header) = sequenced_header {
+                if !unreliable_channel.accept_sequence(sequenced_header.sequence) {
+                    return ReceiveResult::Retry;
+                }
+                header_len = TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE;
+            } else {
+                header_len = TACHYON_UNRELIABLE_HEADER_SIZE;
+            }
+            unreliable_channel.on_received(received_len);
+
             self.stats.unreliable_received += 1;
             return ReceiveResult::UnReliable {
                 received_len: received_len,
+                header_len,
                 network_address: address,
             };
         }
 
+        // everything from here on is on a channel and has the whole header
+        if received_len < TACHYON_HEADER_SIZE {
+            return self.receive_malformed(address, header.message_type);
+        }
+
+        if self.config.auto_create_channels != 0 {
+            self.auto_create_channel(address, header.channel);
+        }
+        self.create_channel_on_use(address, header.channel);
         let channel = match self.channels.get_mut(&(address, header.channel)) {
             Some(c) => c,
             None => {
-                return ReceiveResult::ChannelError;
+                return ReceiveResult::ChannelError { channel_id: header.channel };
             }
         };
 
         channel.stats.bytes_received += received_len as u64;
 
-        if header.message_type == MESSAGE_TYPE_NONE {
-            channel.process_none_message(header.sequence, &mut self.socket_receive_buffer, received_len);
+        if header.message_type == MESSAGE_TYPE_CHANNEL_EPOCH || header.message_type == MESSAGE_TYPE_CHANNEL_EPOCH_ACK {
+            channel.process_epoch_message(&self.socket, self.receive_ring.packet(), received_len);
+            return ReceiveResult::Control { message_type: header.message_type };
+        }
+
+        // could be meant for the other end's previous incarnation
+        if !channel.has_remote_epoch() {
             return ReceiveResult::Retry;
         }
 
+        if header.message_type == MESSAGE_TYPE_NONE {
+            channel.process_none_message(header.sequence, self.receive_ring.packet_mut(), received_len);
+            return ReceiveResult::Control { message_type: header.message_type };
+        }
+
         if header.message_type == MESSAGE_TYPE_NACK {
-            channel.process_nack_message(address, &mut self.socket_receive_buffer);
-            return ReceiveResult::Retry;
+            channel.process_nack_message(address, self.receive_ring.packet_mut(), received_len, &self.socket);
+            return ReceiveResult::Control { message_type: header.message_type };
+        }
+
+        if header.message_type == MESSAGE_TYPE_NACK_RANGES {
+            channel.process_nack_ranges_message(address, self.receive_ring.packet_mut(), received_len);
+            return ReceiveResult::Control { message_type: header.message_type };
+        }
+
+        if header.message_type == MESSAGE_TYPE_NACK_FORMAT {
+            channel.process_nack_format_message(header.sequence);
+            return ReceiveResult::Control { message_type: header.message_type };
         }
 
         if header.message_type == MESSAGE_TYPE_FRAGMENT {
-            channel.process_fragment_message(header.sequence, &mut self.socket_receive_buffer, received_len);
+            channel.process_fragment_message(header.sequence, self.receive_ring.packet_mut(), received_len);
+            // counted here, but fragments are data and go against the data budget
+            self.control_counts.record(MESSAGE_TYPE_FRAGMENT);
             return ReceiveResult::Retry;
         }
 
         if header.message_type == MESSAGE_TYPE_RELIABLE || header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
+            // publishing takes the header and timestamp off the front
+            if received_len < header.reliable_size() + channel.timestamp_size() {
+                return self.receive_malformed(address, header.message_type);
+            }
 
             if header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
-                channel.process_single_nack(address, &mut self.socket_receive_buffer);
+                channel.process_single_nack(address, &self.receive_ring.received()[0..received_len]);
             }
 
-            if channel.receiver.receive_packet(header.sequence, &self.socket_receive_buffer, received_len) {
+            // the datagram is kept as is when it's in a ring slot, timestamped channels read it again after
+            let accepted = if self.receive_ring.is_in_slot() && !channel.is_timestamped() {
+                let replacement = channel.receiver.buffer_pool.get_buffer(BYTE_BUFFER_SIZE_DEFAULT);
+                match self.receive_ring.take_current(replacement) {
+                    Some(mut byte_buffer) => {
+                        byte_buffer.length = received_len;
+                        channel.receiver.receive_packet_buffer(header.sequence, byte_buffer)
+                    }
+                    None => channel.receiver.receive_packet(header.sequence, self.receive_ring.packet(), received_len),
+                }
+            } else {
+                channel.receiver.receive_packet(header.sequence, self.receive_ring.packet(), received_len)
+            };
+
+            if accepted {
+                channel.trace(TRACE_RECEIVED, header.sequence);
+                if channel.is_timestamped() {
+                    let header_size = if header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK { TACHYON_NACKED_HEADER_SIZE } else { TACHYON_HEADER_SIZE };
+                    channel.process_timestamp(&self.receive_ring.received()[0..received_len], header_size);
+                }
                 channel.stats.received += 1;
                 return ReceiveResult::Reliable {
                     network_address: address,
                     channel_id: channel.id,
                 };
             } else {
+                channel.trace(TRACE_REJECTED, header.sequence);
                 return ReceiveResult::Retry;
             }
         }
 
+        self.fire_security_event(SECURITY_MALFORMED_PACKET, address, header.message_type as u32);
         return ReceiveResult::Error;
     }
 
-    pub fn send_to_target(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> TachyonSendResult {
+    // a datagram too short for its message type, dropped like a lost one
+    fn receive_malformed(&mut self, address: NetworkAddress, message_type: u8) -> ReceiveResult {
+        self.fire_security_event(SECURITY_MALFORMED_PACKET, address, message_type as u32);
+        return ReceiveResult::Retry;
+    }
+
+    pub fn send_to_target(&mut self, channel: u16, target: SendTarget, data: &[u8], length: usize) -> TachyonSendResult {
         let mut address = target.address;
 
         if target.identity_id > 0 {
@@ -473,17 +1605,41 @@ impl Tachyon {
         }
     }
 
-    pub fn send_unreliable(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+    // like send_to_target, for a handle from a receive result, connection event or get_connection_handle
+    pub fn send_to_handle(&mut self, channel: u16, handle: ConnectionHandle, data: &[u8], length: usize) -> TachyonSendResult {
+        let address = match self.connection_handles.resolve(handle) {
+            Some(address) => address,
+            None => {
+                let mut result = TachyonSendResult::default();
+                result.error = SEND_ERROR_HANDLE;
+                return result;
+            }
+        };
+
+        if channel > 0 {
+            return self.send_reliable(channel, address, data, length);
+        } else {
+            return self.send_unreliable(address, data, length);
+        }
+    }
+
+    pub fn send_unreliable(&mut self, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
         if !self.can_send() {
             let mut result = TachyonSendResult::default();
-            result.error = SEND_ERROR_IDENTITY;
+            result.error = self.send_blocked_error();
             return result;
         }
 
         match &mut self.unreliable_sender {
             Some(sender) => {
-                let result = sender.send(address, data, body_len);
+                let unreliable_channel = self.unreliable_channels.entry(address).or_insert_with(|| UnreliableChannel::create(address));
+                let mut sequence: Option<u16> = None;
+                if self.unreliable_channel_config.is_sequenced() {
+                    sequence = Some(unreliable_channel.next_send_sequence());
+                }
+                let result = sender.send_through(&self.socket, address, sequence, data, body_len);
                 if result.error == 0 {
+                    unreliable_channel.on_sent(result.sent_len as usize);
                     self.stats.unreliable_sent += 1;
                 }
                 return result;
@@ -496,11 +1652,71 @@ impl Tachyon {
         }
     }
 
-    pub fn send_reliable(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+    pub fn send_reliable(&mut self, channel_id: u16, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        if body_len > data.len() {
+            let mut result = TachyonSendResult::default();
+            result.error = SEND_ERROR_LENGTH;
+            return result;
+        }
+        return self.send_vectored(channel_id, address, &[IoSlice::new(&data[0..body_len])]);
+    }
+
+    // Reliable send that stops being resent ttl_millis after it was sent.  A nack for it after that gets a none instead,
+    // the message counts as abandoned in the channel stats and abandoned_callback is called.
+    pub fn send_reliable_ttl(&mut self, channel_id: u16, address: NetworkAddress, data: &[u8], body_len: usize, ttl_millis: u32) -> TachyonSendResult {
+        if let Some(channel) = self.channel_mut(address, channel_id) {
+            channel.send_buffers.ttl = Some(Duration::from_millis(ttl_millis as u64));
+        }
+        let result = self.send_reliable(channel_id, address, data, body_len);
+        if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+            channel.send_buffers.ttl = None;
+        }
+        return result;
+    }
+
+    // send_reliable with SEND_FLAG_x.  Expendable messages take a sequence like any other, a nack for one is answered
+    // with a none so the receiver moves past it.  Counted in ChannelStats.expendable_nacked, abandoned_callback isn't called.
+    pub fn send_reliable_flags(&mut self, channel_id: u16, address: NetworkAddress, data: &[u8], body_len: usize, flags: u32) -> TachyonSendResult {
+        let expendable = flags & SEND_FLAG_EXPENDABLE != 0;
+        if let Some(channel) = self.channel_mut(address, channel_id) {
+            channel.send_buffers.expendable = expendable;
+        }
+        let result = self.send_reliable(channel_id, address, data, body_len);
+        if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+            channel.send_buffers.expendable = false;
+        }
+        return result;
+    }
+
+    pub fn set_abandoned_callback(&mut self, callback: Option<AbandonedCallback>) {
+        self.abandoned_callback = callback;
+        for channel in self.channels.values_mut() {
+            channel.abandoned_callback = callback;
+        }
+    }
+
+    pub fn set_gap_skipped_callback(&mut self, callback: Option<GapSkippedCallback>) {
+        self.gap_skipped_callback = callback;
+        for channel in self.channels.values_mut() {
+            channel.gap_skipped_callback = callback;
+        }
+    }
+
+    // called for anything a peer sends that we drop as malicious or broken, ANOMALY_x in channel.rs
+    pub fn set_anomaly_callback(&mut self, callback: Option<AnomalyCallback>) {
+        self.anomaly_callback = callback;
+        for channel in self.channels.values_mut() {
+            channel.anomaly_callback = callback;
+        }
+    }
+
+    // reliable send of the slices as a single message, so callers can compose header + payload without copying them together first
+    pub fn send_vectored(&mut self, channel_id: u16, address: NetworkAddress, slices: &[IoSlice]) -> TachyonSendResult {
         let mut result = TachyonSendResult::default();
+        let body_len: usize = slices.iter().map(|slice| slice.len()).sum();
 
         if !self.can_send() {
-            result.error = SEND_ERROR_IDENTITY;
+            result.error = self.send_blocked_error();
             return result;
         }
 
@@ -511,25 +1727,70 @@ impl Tachyon {
 
         if channel_id == 0 {
             result.error = SEND_ERROR_CHANNEL;
+            result.header.channel = channel_id;
             return result;
         }
 
-        if !self.socket.socket.is_some() {
+        if !self.socket.is_open() {
             result.error = SEND_ERROR_SOCKET;
             return result;
         }
 
+        self.create_channel_on_use(address, channel_id);
         let channel = match self.channels.get_mut(&(address, channel_id)) {
             Some(c) => c,
             None => {
                 result.error = SEND_ERROR_CHANNEL;
+                result.header.channel = channel_id;
                 return result;
             }
         };
 
-        if Fragmentation::should_fragment(body_len) {
+        if !channel.is_epoch_acked() {
+            result.error = SEND_ERROR_NOT_CONNECTED;
+            result.header.channel = channel_id;
+            return result;
+        }
+
+        return Tachyon::send_through_pipeline(channel, &self.socket, address, slices);
+    }
+
+    fn send_through_pipeline(channel: &mut Channel, socket: &TachyonSocket, address: NetworkAddress, slices: &[IoSlice]) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+        if channel.pipeline.is_empty() {
+            return Tachyon::send_to_channel(channel, socket, address, slices);
+        }
+
+        let mut pipeline = std::mem::take(&mut channel.pipeline);
+        match pipeline.process_send(slices) {
+            Some(data) if data.len() > 0 => {
+                result = Tachyon::send_to_channel(channel, socket, address, &[IoSlice::new(data)]);
+            }
+            _ => {
+                result.error = SEND_ERROR_PIPELINE;
+            }
+        }
+        channel.pipeline = pipeline;
+        return result;
+    }
+
+    fn send_to_channel(channel: &mut Channel, socket: &TachyonSocket, address: NetworkAddress, slices: &[IoSlice]) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+        let body_len: usize = slices.iter().map(|slice| slice.len()).sum();
+        let fragmented = !channel.unfragmented && Fragmentation::should_fragment(body_len);
+
+        if channel.window_full_error {
+            let buffer_count = if fragmented { Fragmentation::fragment_count(body_len) } else { 1 };
+            if !channel.send_buffers.has_room(buffer_count) {
+                result.error = SEND_ERROR_WINDOW_FULL;
+                result.header.channel = channel.id;
+                return result;
+            }
+        }
+
+        if fragmented {
             let mut fragment_bytes_sent = 0;
-            let frag_sequences = channel.frag.create_fragments(&mut channel.send_buffers, channel.id, data, body_len);
+            let frag_sequences = channel.frag.create_fragments_vectored(&mut channel.send_buffers, channel.id, slices);
             if frag_sequences.len() == 0 {
                 result.error = SEND_ERROR_FRAGMENT;
                 return result;
@@ -538,7 +1799,7 @@ impl Tachyon {
             for seq in frag_sequences {
                 match channel.send_buffers.get_send_buffer(seq) {
                     Some(fragment) => {
-                        let sent =self.socket.send_to(address, &fragment.byte_buffer.get(), fragment.byte_buffer.length);
+                        let sent = socket.send_on_channel(channel.id, address, &fragment.byte_buffer.get(), fragment.byte_buffer.length);
                         fragment_bytes_sent += sent;
 
                         channel.stats.bytes_sent += sent as u64;
@@ -549,6 +1810,7 @@ impl Tachyon {
                         return result;
                     }
                 }
+                channel.trace(TRACE_FRAGMENT_SENT, seq);
             }
 
             result.header.message_type = MESSAGE_TYPE_FRAGMENT;
@@ -559,9 +1821,7 @@ impl Tachyon {
             return result;
         }
 
-
-        result = channel.send_reliable(address, data, body_len, &self.socket);
-        return result;
+        return channel.send_reliable_vectored(address, slices, socket);
     }
 }
 
@@ -570,7 +1830,7 @@ mod tests {
 
     use serial_test::serial;
 
-    use crate::tachyon::tachyon_test::TachyonTest;
+    use crate::tachyon_test::TachyonTest;
 
     use super::*;
 
@@ -631,6 +1891,69 @@ mod tests {
         assert_eq!(3497, res.length);
     }
 
+    #[test]
+    #[serial]
+    fn test_send_vectored() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        let header: Vec<u8> = vec![1, 2];
+        let payload: Vec<u8> = vec![3; 30];
+        let sent = test.client.send_vectored(1, NetworkAddress::default(), &[IoSlice::new(&header), IoSlice::new(&payload)]);
+        assert_eq!(0, sent.error);
+        assert_eq!(32 + TACHYON_HEADER_SIZE, sent.sent_len as usize);
+
+        let res = test.server_receive();
+        assert_eq!(32, res.length);
+        assert_eq!(1, test.receive_buffer[0]);
+        assert_eq!(2, test.receive_buffer[1]);
+        assert_eq!(3, test.receive_buffer[31]);
+
+        // fragmented
+        let payload: Vec<u8> = vec![3; 3000];
+        let sent = test.client.send_vectored(1, NetworkAddress::default(), &[IoSlice::new(&header), IoSlice::new(&payload)]);
+        assert_eq!(0, sent.error);
+        let res = test.server_receive();
+        assert_eq!(3002, res.length);
+        assert_eq!(2, test.receive_buffer[1]);
+        assert_eq!(3, test.receive_buffer[3001]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_receive_into_vec() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        let mut receive_buffer: Vec<u8> = Vec::new();
+
+        test.send_buffer[0..3000].fill(7);
+        assert_eq!(0, test.client_send_reliable(1, 3000).error);
+        let res = test.server.receive_into_vec(&mut receive_buffer, 4000).unwrap();
+        assert_eq!(3000, res.length);
+        assert_eq!(3000, receive_buffer.len());
+        assert_eq!(7, receive_buffer[2999]);
+
+        // too large for the max, dropped
+        assert_eq!(0, test.client_send_reliable(1, 3000).error);
+        let error = test.server.receive_into_vec(&mut receive_buffer, 2000).err();
+        assert_eq!(Some(TachyonReceiveError::MessageTooLarge { channel_id: 1 }), error);
+        assert_eq!(0, test.server_receive().length);
+
+        // and doesn't fit a plain buffer either
+        assert_eq!(0, test.client_send_reliable(1, 3000).error);
+        let mut small = vec![0; 100];
+        let res = test.server.receive_loop(&mut small);
+        assert_eq!(RECEIVE_ERROR_MESSAGE_SIZE, res.error);
+        assert_eq!(0, res.length);
+
+        test.client.send_unreliable(NetworkAddress::default(), &test.send_buffer, 200);
+        let res = test.server.receive_loop(&mut small);
+        assert_eq!(RECEIVE_ERROR_MESSAGE_SIZE, res.error);
+        test.client.send_unreliable(NetworkAddress::default(), &test.send_buffer, 50);
+        assert_eq!(50, test.server.receive_loop(&mut small).length);
+        assert_eq!(3, test.server.get_combined_stats().receive_too_small);
+    }
+
     #[test]
     #[serial]
     fn test_unconfigured_channel_fails() {
@@ -646,6 +1969,7 @@ mod tests {
         let res = test.server_receive();
         assert_eq!(0, res.length);
         assert_eq!(RECEIVE_ERROR_CHANNEL, res.error);
+        assert_eq!(3, res.channel);
     }
 
     #[test]
@@ -666,6 +1990,34 @@ mod tests {
         assert_eq!(0, res.error);
     }
 
+    #[test]
+    #[serial]
+    fn test_timestamped_channel() {
+        let mut test = TachyonTest::default();
+        let mut channel_config = ChannelConfig::default_ordered();
+        channel_config.timestamped = 1;
+        test.client.configure_channel(3, channel_config);
+        test.server.configure_channel(3, channel_config);
+        test.connect();
+
+        test.send_buffer[0] = 4;
+        let sent = test.client_send_reliable(3, 2);
+        assert_eq!(2 + TACHYON_HEADER_SIZE + latency::TIMESTAMP_EXTENSION_SIZE, sent.sent_len as usize);
+
+        // timestamp is stripped on receive
+        let res = test.server_receive();
+        assert_eq!(2, res.length);
+        assert_eq!(4, test.receive_buffer[0]);
+
+        test.server_send_reliable(3, 2);
+        let res = test.client_receive();
+        assert_eq!(2, res.length);
+
+        let address = test.remote_client();
+        let channel = test.server.get_channel(address, 3).unwrap();
+        assert!(channel.latency.is_some());
+    }
+
     #[test]
     #[serial]
     fn test_unreliable() {
@@ -696,4 +2048,963 @@ mod tests {
         assert_eq!(5, test.receive_buffer[2]);
         assert_eq!(6, test.receive_buffer[3]);
     }
+
+    #[test]
+    #[serial]
+    fn test_unreliable_sequenced() {
+        let mut test = TachyonTest::default();
+        test.client.configure_unreliable_channel(UnreliableChannelConfig { sequenced: 1 });
+        test.connect();
+
+        test.send_buffer[0] = 3;
+        let sent = test.client_send_unreliable(4);
+        assert_eq!(0, sent.error);
+        assert_eq!(4 + TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE, sent.sent_len as usize);
+        test.client_send_unreliable(4);
+
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(3, test.receive_buffer[0]);
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+
+        let address = test.remote_client();
+        let channel = test.server.get_unreliable_channel(address).unwrap();
+        assert_eq!(2, channel.stats.received);
+        assert_eq!(2, channel.last_received_sequence);
+
+        let stats = test.client.get_combined_stats();
+        assert_eq!(2, stats.unreliable_stats.sent);
+        assert_eq!(2 * sent.sent_len as u64, stats.unreliable_stats.bytes_sent);
+    }
+
+    // appends a trailer byte on send and checks/strips it on receive, dropping anything without it
+    struct TrailerMiddleware;
+
+    impl PacketMiddleware for TrailerMiddleware {
+        fn on_send(&mut self, data: &mut [u8], length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            data[length] = 0xAB;
+            return middleware::PacketAction::Modify(length + 1);
+        }
+
+        fn on_receive(&mut self, data: &mut [u8], length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            if length < 2 || data[length - 1] != 0xAB {
+                return middleware::PacketAction::Drop;
+            }
+            return middleware::PacketAction::Modify(length - 1);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_middleware() {
+        let mut test = TachyonTest::default();
+        test.client.set_middleware(Box::new(TrailerMiddleware));
+        test.server.set_middleware(Box::new(TrailerMiddleware));
+        test.connect();
+
+        test.send_buffer[0] = 7;
+        let sent = test.client_send_unreliable(4);
+        assert_eq!(4 + TACHYON_UNRELIABLE_HEADER_SIZE + 1, sent.sent_len as usize);
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(7, test.receive_buffer[0]);
+
+        test.client_send_reliable(1, 4);
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(1, res.channel);
+
+        // client without the middleware, server drops everything it sends
+        test.client.clear_middleware();
+        test.client_send_unreliable(4);
+        let res = test.server_receive();
+        assert_eq!(0, res.length);
+        assert!(test.server.stats.packets_dropped > 0);
+    }
+
+    struct DropSecondMiddleware {
+        received: u32,
+    }
+
+    impl PacketMiddleware for DropSecondMiddleware {
+        fn on_send(&mut self, _data: &mut [u8], _length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            return middleware::PacketAction::Keep;
+        }
+
+        fn on_receive(&mut self, _data: &mut [u8], _length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            self.received += 1;
+            if self.received == 2 {
+                return middleware::PacketAction::Drop;
+            }
+            return middleware::PacketAction::Keep;
+        }
+    }
+
+    static GAP_SKIPPED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+    unsafe extern "C" fn on_gap_skipped(channel_id: u16, _address: NetworkAddress, first: u16, last: u16) {
+        assert_eq!(3, channel_id);
+        GAP_SKIPPED.store((first as u32) << 16 | last as u32, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    #[serial]
+    fn test_step() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.ordered_gap_timeout = 100;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, config);
+        test.server.set_middleware(Box::new(DropSecondMiddleware { received: 0 }));
+        test.server.set_gap_skipped_callback(Some(on_gap_skipped));
+        test.connect();
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            test.client_send_reliable(3, 4);
+        }
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(0, test.receive_buffer[0]);
+        assert_eq!(0, test.server_receive().length);
+
+        // the gap starts being timed on the first step, client never sees the nack so only the timeout can skip it
+        test.server.step(Duration::from_millis(50));
+        test.server.step(Duration::from_millis(50));
+        assert_eq!(0, test.server_receive().length);
+        test.server.step(Duration::from_millis(60));
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(2, test.receive_buffer[0]);
+        assert_eq!(160, test.server.time_since_start());
+        // the dropped message was sequence 2
+        assert_eq!(2 << 16 | 2, GAP_SKIPPED.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    // stands in for encryption, rejects anything that wasn't xored with the same key
+    struct XorStage {
+        key: u8,
+    }
+
+    impl pipeline::PipelineStage for XorStage {
+        fn on_send(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool {
+            out.push(self.key);
+            out.extend(data.iter().map(|b| b ^ self.key));
+            return true;
+        }
+
+        fn on_receive(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool {
+            if data.len() < 1 || data[0] != self.key {
+                return false;
+            }
+            out.extend(data[1..].iter().map(|b| b ^ self.key));
+            return true;
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_pipeline() {
+        let mut test = TachyonTest::default();
+        let mut unfragmented = ChannelConfig::default_ordered();
+        unfragmented.unfragmented = 1;
+        for tachyon in [&mut test.client, &mut test.server] {
+            tachyon.configure_channel(3, ChannelConfig::default_ordered());
+            tachyon.configure_channel(4, unfragmented);
+        }
+        assert!(test.client.add_channel_stage(3, std::sync::Arc::new(|| Box::new(XorStage { key: 9 }) as Box<dyn pipeline::PipelineStage>)));
+        assert!(test.server.add_channel_stage(3, std::sync::Arc::new(|| Box::new(XorStage { key: 9 }) as Box<dyn pipeline::PipelineStage>)));
+        assert!(!test.server.add_channel_stage(5, std::sync::Arc::new(|| Box::new(XorStage { key: 9 }) as Box<dyn pipeline::PipelineStage>)));
+        test.connect();
+
+        test.send_buffer[0] = 4;
+        test.send_buffer[1] = 5;
+        let sent = test.client_send_reliable(3, 2);
+        assert_eq!(0, sent.error);
+        assert_eq!(TACHYON_HEADER_SIZE + 3, sent.sent_len as usize);
+        let res = test.server_receive();
+        assert_eq!(2, res.length);
+        assert_eq!(3, res.channel);
+        assert_eq!(&[4, 5], &test.receive_buffer[0..2]);
+
+        // large message on the unfragmented channel goes out as one datagram
+        let sent = test.client_send_reliable(4, 3000);
+        assert_eq!(0, sent.error);
+        assert_eq!(TACHYON_HEADER_SIZE + 3000, sent.sent_len as usize);
+        let res = test.server_receive();
+        assert_eq!(3000, res.length);
+        assert_eq!(0, test.client.get_combined_stats().channel_stats.fragments_sent);
+    }
+
+    #[test]
+    #[serial]
+    fn test_window_full() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.window_full_error = 1;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, config);
+        test.connect();
+        test.client.step(Duration::from_millis(0));
+
+        for _ in 0..1023 {
+            assert_eq!(0, test.client_send_reliable(3, 8).error);
+        }
+        // two fragments don't fit in the one buffer left
+        assert_eq!(SEND_ERROR_WINDOW_FULL, test.client_send_reliable(3, 2000).error);
+        assert_eq!(0, test.client_send_reliable(3, 8).error);
+        assert_eq!(SEND_ERROR_WINDOW_FULL, test.client_send_reliable(3, 8).error);
+
+        // oldest buffers can't be nacked anymore once expired
+        test.client.step(Duration::from_millis(5001));
+        assert_eq!(0, test.client_send_reliable(3, 8).error);
+    }
+
+    #[test]
+    fn test_channel_config_validation() {
+        let mut tachyon = Tachyon::create(TachyonConfig::default());
+        let mut config = ChannelConfig::default_ordered();
+        assert_eq!(Err(ChannelConfigError::ReservedId), tachyon.try_configure_channel(2, config));
+        assert!(tachyon.try_configure_channel(3, config).is_ok());
+
+        config.receive_window_size = 0;
+        assert_eq!(Err(ChannelConfigError::WindowTooSmall), tachyon.try_configure_channel(4, config));
+        config.receive_window_size = 70000;
+        assert_eq!(Err(ChannelConfigError::WindowTooLarge), tachyon.try_configure_channel(4, config));
+        config.receive_window_size = RECEIVE_WINDOW_SIZE_DEFAULT;
+        config.send_window_size = 32768;
+        assert_eq!(Err(ChannelConfigError::SendWindowTooLarge), tachyon.try_configure_channel(4, config));
+        config.send_window_size = 0;
+        config.ordered = 2;
+        assert_eq!(Err(ChannelConfigError::InvalidFlag), tachyon.try_configure_channel(4, config));
+        assert!(!tachyon.configure_channel(4, config));
+        assert!(!tachyon.channel_config.contains_key(&4));
+        config.ordered = 0;
+        config.publish_order = 3;
+        assert_eq!(Err(ChannelConfigError::InvalidPublishOrder), tachyon.try_configure_channel(4, config));
+        config.publish_order = 0;
+        config.max_assembled_size = MAX_ASSEMBLED_SIZE_MAX as u32 + 1;
+        assert_eq!(Err(ChannelConfigError::AssembledSizeTooLarge), tachyon.try_configure_channel(4, config));
+        config.max_assembled_size = MAX_ASSEMBLED_SIZE_MAX as u32;
+        assert!(config.validate(4).is_ok());
+
+        // window sizes are local, the digest only changes with settings both ends share
+        let mut other = Tachyon::create(TachyonConfig::default());
+        other.configure_channel(3, ChannelConfig::slim_ordered());
+        assert_eq!(tachyon.channel_config_digest(), other.channel_config_digest());
+        other.configure_channel(3, ChannelConfig::default_unordered());
+        assert_ne!(tachyon.channel_config_digest(), other.channel_config_digest());
+        assert_eq!(Err(ChannelConfigError::Mismatch), ChannelConfig::default_ordered().check_compatible(&ChannelConfig::default_unordered()));
+        other.configure_channel(4, ChannelConfig::default_unordered());
+        assert_ne!(tachyon.channel_config_digest(), other.channel_config_digest());
+
+        assert_eq!(Err(ChannelConfigError::IdOutOfRange), other.try_configure_channel_id(ChannelId(256), ChannelConfig::default_ordered()));
+        assert!(other.try_configure_channel_id(ChannelId(200), ChannelConfig::default_ordered()).is_ok());
+        let ids: Vec<u16> = other.configured_channels().iter().map(|channel| channel.id.0).collect();
+        assert_eq!(vec![1, 2, 3, 4, 200], ids);
+        assert_eq!(1, other.get_channel_config(ChannelId(200)).unwrap().ordered);
+        assert!(other.get_channel_config(ChannelId(256)).is_none());
+
+        // wide ids with wide_channels, the high byte keeps 300 apart from 44 in the digest
+        other.config.wide_channels = 1;
+        let mut low = Tachyon::create(other.config);
+        assert!(other.try_configure_channel_id(ChannelId(300), ChannelConfig::default_ordered()).is_ok());
+        assert!(low.configure_channel(44, ChannelConfig::default_ordered()));
+        assert!(low.configure_channel(3, ChannelConfig::default_unordered()));
+        assert!(low.configure_channel(4, ChannelConfig::default_unordered()));
+        assert!(low.configure_channel(200, ChannelConfig::default_ordered()));
+        assert_ne!(low.channel_config_digest(), other.channel_config_digest());
+        assert_eq!(1, other.get_channel_config(ChannelId(300)).unwrap().ordered);
+    }
+
+    #[test]
+    #[serial]
+    fn test_control_budget() {
+        let mut test = TachyonTest::default();
+        assert!(test.server.bind(test.address));
+
+        // more nacks than the data budget, ahead of a message
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nack: Vec<u8> = vec![MESSAGE_TYPE_NACK, 1, 0, 0, 0, 0, 0, 0];
+        for _ in 0..RECEIVE_DATA_BUDGET + 50 {
+            socket.send_to(&nack, test.address.to_socket_addr()).unwrap();
+        }
+        socket.send_to(&[MESSAGE_TYPE_UNRELIABLE, 7], test.address.to_socket_addr()).unwrap();
+
+        let res = test.server_receive();
+        assert_eq!(1, res.length);
+        assert_eq!(7, test.receive_buffer[0]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_control_behind_data() {
+        let mut test = TachyonTest::default();
+        assert!(test.server.bind(test.address));
+
+        // more reliable messages than the data budget, none of them publish since sequence 1 never comes, then nacks
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        for sequence in 2..RECEIVE_DATA_BUDGET as u16 + 52 {
+            let [low, high] = sequence.to_le_bytes();
+            socket.send_to(&[MESSAGE_TYPE_RELIABLE, 1, low, high, 9], test.address.to_socket_addr()).unwrap();
+        }
+        let nack: Vec<u8> = vec![MESSAGE_TYPE_NACK, 1, 0, 0, 0, 0, 0, 0];
+        for _ in 0..10 {
+            socket.send_to(&nack, test.address.to_socket_addr()).unwrap();
+        }
+
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(10, test.server.control_counts.nacks);
+    }
+
+    #[test]
+    #[serial]
+    fn test_connection_handle() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        let res = test.server_receive();
+        assert_eq!(8, res.length);
+        assert!(res.connection.is_valid());
+        assert_eq!(res.connection, test.server.get_connection_handle(res.address));
+        assert!(test.server.get_connection_by_handle(res.connection).is_some());
+
+        assert_eq!(0, test.server.send_to_handle(1, res.connection, &test.send_buffer, 4).error);
+        assert_eq!(0, test.server.send_to_handle(0, res.connection, &test.send_buffer, 4).error);
+        assert_eq!(4, test.client_receive().length);
+        assert_eq!(4, test.client_receive().length);
+
+        // invalid once the connection is gone, and stays that way when the address connects again
+        test.server.set_identity(1, 1);
+        test.server.try_link_identity(res.address, 1, 1);
+        test.server.set_identity(1, 0);
+        assert!(test.server.get_connection(res.address).is_none());
+        assert_eq!(SEND_ERROR_HANDLE, test.server.send_to_handle(1, res.connection, &test.send_buffer, 4).error);
+        test.server.create_connection(res.address, Identity::default());
+        assert!(test.server.get_connection_by_handle(res.connection).is_none());
+        assert!(test.server.get_connection_handle(res.address).is_valid());
+    }
+
+    #[test]
+    #[serial]
+    fn test_slim_channel() {
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(3, ChannelConfig::slim_ordered());
+        test.server.configure_channel(3, ChannelConfig::slim_ordered());
+        test.connect();
+
+        // nothing allocated for the windows until they are used
+        let channel = test.client.get_channel(NetworkAddress::default(), 3).unwrap();
+        assert!(!channel.receiver.received.is_allocated());
+        assert!(!channel.receiver.buffered.is_allocated());
+        assert!(!channel.send_buffers.buffers.is_allocated());
+
+        for _ in 0..200 {
+            assert_eq!(0, test.client_send_reliable(3, 8).error);
+            assert_eq!(8, test.server_receive().length);
+        }
+        let channel = test.client.get_channel(NetworkAddress::default(), 3).unwrap();
+        assert_eq!(128, channel.send_buffers.buffers.values.len());
+        assert!(!channel.receiver.received.is_allocated());
+    }
+
+    static ANOMALIES: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+    unsafe extern "C" fn on_anomaly(anomaly: u8, channel_id: u16, _address: NetworkAddress, value: u32) {
+        assert_eq!(ANOMALY_FRAGMENT_GROUP_TOO_LARGE, anomaly);
+        assert_eq!(3, channel_id);
+        assert_eq!(3600, value);
+        ANOMALIES.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_assembled_size() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        test.client.configure_channel(3, config);
+        config.max_assembled_size = 2400;
+        test.server.configure_channel(3, config);
+        test.server.set_anomaly_callback(Some(on_anomaly));
+        test.connect();
+
+        assert_eq!(0, test.client_send_reliable(3, 3000).error);
+        assert_eq!(0, test.server_receive().length);
+        let remote = test.remote_client();
+        let channel = test.server.get_channel(remote, 3).unwrap();
+        assert_eq!(1, channel.stats.oversized_groups);
+        assert_eq!(0, channel.frag.received.len());
+        assert_eq!(1, ANOMALIES.load(std::sync::atomic::Ordering::SeqCst));
+
+        // the dropped group doesn't hold up the ordered channel
+        assert_eq!(0, test.client_send_reliable(3, 2400).error);
+        assert_eq!(2400, test.server_receive().length);
+    }
+
+    static ABANDONED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+    unsafe extern "C" fn on_abandoned(channel_id: u16, _address: NetworkAddress, sequence: u16) {
+        assert_eq!(3, channel_id);
+        assert_eq!(2, sequence);
+        ABANDONED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    #[serial]
+    fn test_send_ttl() {
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.set_middleware(Box::new(DropSecondMiddleware { received: 0 }));
+        test.client.set_abandoned_callback(Some(on_abandoned));
+        test.connect();
+        test.client.step(Duration::from_millis(0));
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            assert_eq!(0, test.client.send_reliable_ttl(3, NetworkAddress::default(), &test.send_buffer, 4, 10).error);
+        }
+        assert_eq!(4, test.server_receive().length);
+        assert_eq!(0, test.server_receive().length);
+
+        // the nack arrives after the ttl, the server gets a none and moves on without the lost message
+        test.client.step(Duration::from_millis(20));
+        test.server.step(Duration::from_millis(10));
+        test.client_receive();
+        test.client.step(Duration::from_millis(10));
+
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(2, test.receive_buffer[0]);
+        let stats = test.client.get_combined_stats().channel_stats;
+        assert_eq!(1, stats.abandoned);
+        assert_eq!(1, stats.nones_sent);
+        assert_eq!(0, stats.resent);
+        assert_eq!(1, ABANDONED.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    // drops every copy of one reliable sequence, resends included
+    struct DropSequenceMiddleware {
+        sequence: u16,
+    }
+
+    impl PacketMiddleware for DropSequenceMiddleware {
+        fn on_send(&mut self, _data: &mut [u8], _length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            return middleware::PacketAction::Keep;
+        }
+
+        fn on_receive(&mut self, data: &mut [u8], _length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            let header = Header::read(data);
+            let reliable = header.message_type == MESSAGE_TYPE_RELIABLE || header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK;
+            if reliable && header.sequence == self.sequence {
+                return middleware::PacketAction::Drop;
+            }
+            return middleware::PacketAction::Keep;
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_resends() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.max_resends = 2;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, config);
+        test.server.set_middleware(Box::new(DropSequenceMiddleware { sequence: 2 }));
+        test.connect();
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            assert_eq!(0, test.client_send_reliable(3, 4).error);
+        }
+        assert_eq!(4, test.server_receive().length);
+        assert_eq!(0, test.server_receive().length);
+
+        // two resends are lost too, the third nack gets a none
+        for _ in 0..3 {
+            test.server.update();
+            test.client_receive();
+            test.client.update();
+        }
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(2, test.receive_buffer[0]);
+
+        let stats = test.client.get_combined_stats().channel_stats;
+        assert_eq!(2, stats.resent);
+        assert_eq!(1, stats.resends_exhausted);
+        assert_eq!(0, stats.abandoned);
+        assert_eq!(1, stats.nones_sent);
+    }
+
+    #[test]
+    #[serial]
+    fn test_expendable_send() {
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.set_middleware(Box::new(DropSequenceMiddleware { sequence: 2 }));
+        test.connect();
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            let flags = if i == 1 { SEND_FLAG_EXPENDABLE } else { 0 };
+            assert_eq!(0, test.client.send_reliable_flags(3, NetworkAddress::default(), &test.send_buffer, 4, flags).error);
+        }
+        assert_eq!(4, test.server_receive().length);
+        assert_eq!(0, test.server_receive().length);
+
+        test.server.update();
+        test.client_receive();
+        test.client.update();
+        assert_eq!(4, test.server_receive().length);
+        assert_eq!(2, test.receive_buffer[0]);
+
+        let stats = test.client.get_combined_stats().channel_stats;
+        assert_eq!(0, stats.resent);
+        assert_eq!(1, stats.expendable_nacked);
+        assert_eq!(1, stats.nones_sent);
+    }
+
+    #[test]
+    #[serial]
+    fn test_sequence_trace() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.trace_events = 64;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, config);
+        test.server.set_middleware(Box::new(DropSecondMiddleware { received: 0 }));
+        test.connect();
+
+        for _ in 0..3 {
+            assert_eq!(0, test.client_send_reliable(3, 4).error);
+        }
+        assert_eq!(4, test.server_receive().length);
+        assert_eq!(0, test.server_receive().length);
+        test.server.update();
+        test.client_receive();
+        test.client.update();
+        for _ in 0..3 {
+            test.server_receive();
+        }
+
+        let sent: Vec<u8> = test.client.get_sequence_trace(NetworkAddress::default(), 3, Some(2)).unwrap()
+            .iter().map(|event| event.event).collect();
+        assert_eq!(vec![TRACE_SENT, TRACE_NACK_RECEIVED, TRACE_RESENT], sent);
+
+        let remote = test.remote_client();
+        let received: Vec<u8> = test.server.get_sequence_trace(remote, 3, Some(2)).unwrap()
+            .iter().map(|event| event.event).collect();
+        assert_eq!(vec![TRACE_NACK_SENT, TRACE_RECEIVED, TRACE_PUBLISHED], received);
+        assert!(test.server.get_sequence_trace(remote, 1, None).is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_outgoing_queue() {
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.connect();
+        test.client.step(Duration::from_millis(0));
+
+        let address = NetworkAddress::default();
+        assert_eq!(0, test.client.enqueue_reliable(3, address, &[1], 1, 0));
+        assert_eq!(0, test.client.enqueue_reliable(3, address, &[2], 5, 100));
+        assert_eq!(0, test.client.enqueue_reliable(3, address, &[3], 9, 10));
+        assert_eq!(SEND_ERROR_CHANNEL, test.client.enqueue_reliable(4, address, &[4], 1, 0));
+        assert_eq!(0, test.server_receive().length);
+
+        // the highest priority message is past its deadline by the time update flushes
+        test.client.step(Duration::from_millis(20));
+        assert_eq!(0, test.client.flush_outgoing());
+        assert_eq!(1, test.client.get_combined_stats().channel_stats.queue_expired);
+
+        assert_eq!(1, test.server_receive().length);
+        assert_eq!(2, test.receive_buffer[0]);
+        assert_eq!(1, test.server_receive().length);
+        assert_eq!(1, test.receive_buffer[0]);
+        assert_eq!(0, test.server_receive().length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_protocol_magic() {
+        let mut test = TachyonTest::default();
+        test.server.config.use_protocol_magic = 1;
+        test.client.config.use_protocol_magic = 1;
+        test.connect();
+
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(33, test.client_send_unreliable(32).sent_len);
+        assert_eq!(32, test.server_receive().length);
+        assert_eq!(0, test.server_send_reliable(2, 16).error);
+        assert_eq!(16, test.client_receive().length);
+
+        // stray traffic and other versions never get parsed
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(&[MESSAGE_TYPE_UNRELIABLE, 7], test.address.to_socket_addr()).unwrap();
+        socket.send_to(&[b'T', b'C', b'H', b'Y', PROTOCOL_VERSION + 1, MESSAGE_TYPE_UNRELIABLE, 7], test.address.to_socket_addr()).unwrap();
+        socket.send_to(&[b'T', b'C', b'H', b'Y', PROTOCOL_VERSION, MESSAGE_TYPE_UNRELIABLE, 7], test.address.to_socket_addr()).unwrap();
+        assert_eq!(1, test.server_receive().length);
+        assert_eq!(7, test.receive_buffer[0]);
+        assert_eq!(1, test.server.stats.bad_magic);
+        assert_eq!(1, test.server.stats.version_mismatched);
+    }
+
+    #[test]
+    #[serial]
+    fn test_checksum() {
+        let mut test = TachyonTest::default();
+        for tachyon in [&mut test.server, &mut test.client] {
+            tachyon.config.use_checksum = 1;
+            tachyon.config.use_protocol_magic = 1;
+        }
+        test.connect();
+
+        assert_eq!(0, test.client_send_reliable(1, 64).error);
+        assert_eq!(64, test.server_receive().length);
+        assert_eq!(33, test.client_send_unreliable(32).sent_len);
+        assert_eq!(32, test.server_receive().length);
+        assert_eq!(0, test.server_send_reliable(2, 16).error);
+        assert_eq!(16, test.client_receive().length);
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut datagram: Vec<u8> = vec![0; PROTOCOL_PREFIX_SIZE + CHECKSUM_SIZE + 2];
+        datagram[PROTOCOL_PREFIX_SIZE + CHECKSUM_SIZE] = MESSAGE_TYPE_UNRELIABLE;
+        datagram[PROTOCOL_PREFIX_SIZE + CHECKSUM_SIZE + 1] = 7;
+        tachyon_socket::write_datagram_prefix(&mut datagram, true, true, 2);
+        let mut corrupted = datagram.clone();
+        corrupted[PROTOCOL_PREFIX_SIZE + CHECKSUM_SIZE + 1] = 8;
+        socket.send_to(&corrupted, test.address.to_socket_addr()).unwrap();
+        socket.send_to(&datagram, test.address.to_socket_addr()).unwrap();
+        assert_eq!(1, test.server_receive().length);
+        assert_eq!(7, test.receive_buffer[0]);
+        assert_eq!(1, test.server.stats.checksum_failed);
+    }
+
+    #[test]
+    #[serial]
+    fn test_stats_diff() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        test.client_send_reliable(1, 8);
+        test.client_send_unreliable(8);
+        let first = test.client.get_combined_stats().snapshot();
+        assert_eq!(1, first.channel_stats.sent);
+        assert_eq!(1, first.unreliable_sent);
+
+        test.client_send_reliable(1, 8);
+        test.client_send_reliable(1, 8);
+        let second = test.client.get_combined_stats();
+        let diff = second.diff(&first);
+        assert_eq!(2, diff.channel_stats.sent);
+        assert_eq!(0, diff.unreliable_sent);
+
+        test.client.reset_stats();
+        let reset = test.client.get_combined_stats();
+        assert_eq!(0, reset.channel_stats.sent);
+        assert_eq!(0, reset.unreliable_sent);
+        assert_eq!(0, reset.unreliable_stats.sent);
+        // a reset between snapshots doesn't wrap
+        assert_eq!(0, reset.diff(&second).channel_stats.sent);
+    }
+
+    #[test]
+    fn test_reset_stats_then_update() {
+        let mut test = TachyonTest::default();
+        let _network = test.connect_loopback();
+        test.client.step(Duration::from_millis(0));
+        for _ in 0..20 {
+            test.client_send_reliable(1, 64);
+        }
+        test.client.step(Duration::from_millis(1000));
+        assert!(test.client.get_connection_quality(NetworkAddress::default()).unwrap().send_bytes_per_second > 0);
+
+        // counters below what quality last saw
+        test.client.reset_stats();
+        test.client_send_reliable(1, 64);
+        test.client.step(Duration::from_millis(1000));
+        let quality = test.client.get_connection_quality(NetworkAddress::default()).unwrap();
+        assert!(quality.send_bytes_per_second > 0);
+        assert!(quality.send_bytes_per_second < 1000);
+    }
+
+    #[test]
+    #[serial]
+    fn test_address_stats() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        test.client_send_reliable(1, 8);
+        test.client_send_reliable(2, 8);
+        test.client_send_reliable(2, 8);
+        test.client_send_unreliable(8);
+        for _ in 0..4 {
+            test.server_receive();
+        }
+
+        let remote = test.remote_client();
+        let ids: Vec<u16> = test.server.get_channels_for(remote).iter().map(|channel| channel.id).collect();
+        assert_eq!(vec![1, 2], ids);
+
+        let stats = test.server.get_address_stats(remote).unwrap();
+        assert_eq!(2, stats.channel_count);
+        assert_eq!(3, stats.channel_stats.received);
+        assert_eq!(1, stats.unreliable_stats.received);
+
+        assert!(test.server.get_channels_for(NetworkAddress::localhost(1)).is_empty());
+        assert!(test.server.get_address_stats(NetworkAddress::localhost(1)).is_none());
+    }
+
+    #[test]
+    fn test_late_updates() {
+        let mut config = TachyonConfig::default();
+        config.update_interval_millis = 10;
+        let mut tachyon = Tachyon::create(config);
+        tachyon.step(Duration::from_millis(10));
+        tachyon.step(Duration::from_millis(20));
+        assert_eq!(0, tachyon.get_combined_stats().late_updates);
+
+        tachyon.step(Duration::from_millis(50));
+        let stats = tachyon.get_combined_stats();
+        assert_eq!(1, stats.late_updates);
+        assert_eq!(4, stats.skipped_updates);
+        assert_eq!(50, stats.max_update_gap_millis);
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_tag() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.tag = 0xfeed;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, config);
+        test.connect();
+
+        test.client_send_reliable(3, 8);
+        test.client_send_unreliable(8);
+        let result = test.server_receive();
+        assert_eq!(3, result.channel);
+        assert_eq!(0xfeed, result.channel_tag);
+        assert_eq!(0, test.server_receive().channel_tag);
+
+        let remote = test.remote_client();
+        let channel = test.server.get_channel(remote, 3).unwrap();
+        assert!(channel.to_string().starts_with("channel:3 tag:65261"));
+    }
+    // reliable sends from a client losing bursts on the way out, returns what it dropped and what the server got
+    fn run_outbound_loss(seed: u64) -> (u64, u32) {
+        let mut test = TachyonTest::default();
+        test.client.config.drop_reliable_only = 1;
+        test.client.config.drop_simulation.seed = seed;
+        test.client.config.drop_simulation.outbound_chance = 10;
+        test.client.config.drop_simulation.burst_length = 2;
+        let _network = test.connect_loopback();
+
+        let mut received = 0;
+        for round in 0..40 {
+            // later rounds send one each so the last losses get noticed
+            let count = if round < 20 { 10 } else { 1 };
+            for _ in 0..count {
+                test.client_send_reliable(1, 8);
+            }
+            test.client.update();
+            test.server.update();
+            while test.server_receive().length > 0 {
+                received += 1;
+            }
+            // takes in the server's nacks
+            test.client_receive();
+        }
+        return (test.client.get_combined_stats().outbound_dropped, received);
+    }
+
+    #[test]
+    fn test_drop_simulation() {
+        let (dropped, received) = run_outbound_loss(11);
+        assert!(dropped > 0);
+        assert_eq!(220, received);
+        assert_eq!((dropped, received), run_outbound_loss(11));
+        assert_eq!(220, run_outbound_loss(12).1);
+    }
+
+    #[test]
+    fn test_wide_channels() {
+        let mut test = TachyonTest::default();
+        for tachyon in [&mut test.server, &mut test.client] {
+            tachyon.config.wide_channels = 1;
+            tachyon.config.scramble_key = 0x5eed;
+            assert!(tachyon.configure_channel(300, ChannelConfig::default_ordered()));
+        }
+        // losses on the wide channel get nacked and resent like on any other
+        test.client.config.drop_reliable_only = 1;
+        test.client.config.drop_simulation.outbound_chance = 10;
+        let _network = test.connect_loopback();
+
+        let mut received: Vec<u8> = Vec::new();
+        for round in 0..60 {
+            if round < 20 {
+                test.send_buffer[0] = round as u8;
+                let length = if round % 5 == 0 { 3000 } else { 8 };
+                assert_eq!(0, test.client_send_reliable(300, length).error);
+            } else if round == 20 {
+                test.client.socket.outbound_drops.borrow_mut().chance = 0;
+                test.send_buffer[0] = round as u8;
+                test.client_send_reliable(300, 8);
+            }
+            test.client.update();
+            test.server.update();
+            loop {
+                let res = test.server_receive();
+                if res.length == 0 {
+                    break;
+                }
+                assert_eq!(300, res.channel);
+                received.push(test.receive_buffer[0]);
+            }
+            test.client_receive();
+        }
+        assert!(test.client.get_combined_stats().outbound_dropped > 0);
+        assert_eq!((0..=20).collect::<Vec<u8>>(), received);
+
+        // without wide_channels they're malformed
+        test.server.config.wide_channels = 0;
+        test.server.set_security_event_capacity(10);
+        test.client_send_reliable(300, 8);
+        assert_eq!(0, test.server_receive().length);
+        let event = test.server.take_security_event().unwrap();
+        assert_eq!(SECURITY_MALFORMED_PACKET, event.kind);
+        assert_eq!(MESSAGE_TYPE_WIDE_CHANNEL as u32, event.value);
+    }
+    // the message type on the wire isn't the real one, only what comes back out of middleware says what a datagram is
+    struct FlipTypeMiddleware;
+
+    impl PacketMiddleware for FlipTypeMiddleware {
+        fn on_send(&mut self, data: &mut [u8], length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            data[0] ^= 0x80;
+            return middleware::PacketAction::Modify(length);
+        }
+
+        fn on_receive(&mut self, data: &mut [u8], length: usize, _address: NetworkAddress) -> middleware::PacketAction {
+            data[0] ^= 0x80;
+            return middleware::PacketAction::Modify(length);
+        }
+    }
+
+    #[test]
+    fn test_drop_reliable_only() {
+        let mut test = TachyonTest::default();
+        test.server.config.drop_packet_chance = 50;
+        test.server.config.drop_reliable_only = 1;
+        test.client.set_middleware(Box::new(FlipTypeMiddleware));
+        test.server.set_middleware(Box::new(FlipTypeMiddleware));
+        let _network = test.connect_loopback();
+
+        let mut reliable = 0;
+        let mut unreliable = 0;
+        for round in 0..50 {
+            // the tail needs something after it to be nacked, the last one gets through
+            if round < 40 {
+                test.client_send_reliable(2, 8);
+            } else if round == 40 {
+                test.server.socket.inbound_drops.chance = 0;
+                test.client_send_reliable(2, 8);
+            }
+            if round < 20 {
+                test.client_send_unreliable(8);
+            }
+            test.client.update();
+            test.server.update();
+            loop {
+                let result = test.server_receive();
+                if result.length == 0 {
+                    break;
+                }
+                if result.channel == 0 {
+                    unreliable += 1;
+                } else {
+                    reliable += 1;
+                }
+            }
+            test.client_receive();
+        }
+        assert_eq!(20, unreliable);
+        assert_eq!(41, reliable);
+        assert!(test.server.stats.packets_dropped > 0);
+        let client_channel = test.client.get_channel(NetworkAddress::default(), 2).unwrap();
+        // every drop was of a reliable message, some were nacked again before the resend got in
+        assert!(client_channel.stats.resent >= test.server.stats.packets_dropped);
+    }
+    #[test]
+    fn test_auto_create_channels() {
+        // off, a channel only one side has fails the receive
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(5, ChannelConfig::default_unordered());
+        let _network = test.connect_loopback();
+        test.client_send_reliable(5, 8);
+        let result = test.server_receive();
+        assert_eq!(RECEIVE_ERROR_CHANNEL, result.error);
+        assert_eq!(5, result.channel);
+
+        let mut test = TachyonTest::default();
+        test.server.config.auto_create_channels = AUTO_CREATE_CHANNELS_UNORDERED;
+        test.client.configure_channel(5, ChannelConfig::default_unordered());
+        let _network = test.connect_loopback();
+        test.client_send_reliable(5, 8);
+        let result = test.server_receive();
+        assert_eq!(8, result.length);
+        assert_eq!(5, result.channel);
+        assert_eq!(1, test.server.get_combined_stats().channels_auto_created);
+        assert!(!test.server.get_channel_config(ChannelId(5)).unwrap().is_ordered());
+
+        // from then on it's like any configured channel
+        let remote = test.remote_client();
+        let target = SendTarget { identity_id: 0, address: remote };
+        assert_eq!(0, test.server.send_to_target(5, target, &test.send_buffer, 8).error);
+        assert_eq!(5, test.client_receive().channel);
+        test.client_send_reliable(5, 8);
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(1, test.server.get_combined_stats().channels_auto_created);
+    }
+
+    #[test]
+    fn test_channels_created_on_use() {
+        let mut test = TachyonTest::default();
+        let _network = test.connect_loopback();
+        test.client_send_unreliable(8);
+        assert_eq!(8, test.server_receive().length);
+        let remote = test.remote_client();
+        assert!(test.server.get_channels_for(remote).is_empty());
+        assert_eq!(2, test.server.get_channel_count(remote));
+        assert_eq!(2, test.server.get_address_stats(remote).unwrap().channel_count);
+
+        test.client_send_reliable(2, 8);
+        assert_eq!(8, test.server_receive().length);
+        let ids: Vec<u16> = test.server.get_channels_for(remote).iter().map(|channel| channel.id).collect();
+        assert_eq!(vec![2], ids);
+        assert!(test.server.get_channel(remote, 1).is_some());
+        assert_eq!(2, test.server.get_channels_for(remote).len());
+
+        // only connections get them
+        assert!(test.server.get_channel(NetworkAddress::localhost(1), 1).is_none());
+        assert!(test.server.get_channels_for(NetworkAddress::localhost(1)).is_empty());
+
+        // with epochs they're all there from the start, so the handshake runs before the first send
+        let mut test = TachyonTest::default();
+        test.server.config.use_channel_epochs = 1;
+        test.client.config.use_channel_epochs = 1;
+        let _network = test.connect_loopback();
+        assert_eq!(2, test.client.get_channels_for(test.client_address).len());
+        test.client_send_unreliable(8);
+        assert_eq!(8, test.server_receive().length);
+        let remote = test.remote_client();
+        assert_eq!(2, test.server.get_channels_for(remote).len());
+    }
 }
@@ -0,0 +1,191 @@
+use rustc_hash::FxHashMap;
+
+use super::{
+    header::{LINK_PAYLOAD_MAX, TACHYON_CONNECTION_HEADER_SIZE},
+    network_address::NetworkAddress,
+    Tachyon,
+};
+
+// Identity messages can carry a small application payload after the ConnectionHeader, a length byte and then up to
+// LINK_PAYLOAD_MAX bytes.  Peers that don't know about it ignore the trailing bytes, and a missing payload reads as
+// empty, so either end can be older.
+//
+// The client's payload goes out with its link requests, the server's with the linked or denied reply.  Whatever
+// arrives is kept per address for get_link_payload, set before the identity event fires so callbacks can read it.
+#[derive(Default)]
+pub struct LinkPayloads {
+    // sent by a client with every link request
+    request: Vec<u8>,
+    // sent by a server with the linked reply, by identity id
+    replies: FxHashMap<u32, Vec<u8>>,
+    // sent by a server with the denied reply to link requests that fail
+    denied: Vec<u8>,
+    received: FxHashMap<NetworkAddress, Vec<u8>>,
+}
+
+impl LinkPayloads {
+    pub fn request(&self) -> &[u8] {
+        return &self.request;
+    }
+
+    pub fn reply(&self, id: u32) -> &[u8] {
+        match self.replies.get(&id) {
+            Some(payload) => return payload,
+            None => return &[],
+        }
+    }
+
+    pub fn denied(&self) -> &[u8] {
+        return &self.denied;
+    }
+
+    // bytes an identity message with this payload takes
+    pub fn message_size(payload_len: usize) -> usize {
+        return TACHYON_CONNECTION_HEADER_SIZE + 1 + payload_len;
+    }
+
+    // writes the length and payload after the ConnectionHeader, buffer has to be message_size long
+    pub fn write(buffer: &mut [u8], payload: &[u8]) {
+        let len = payload.len().min(LINK_PAYLOAD_MAX);
+        buffer[TACHYON_CONNECTION_HEADER_SIZE] = len as u8;
+        buffer[TACHYON_CONNECTION_HEADER_SIZE + 1..TACHYON_CONNECTION_HEADER_SIZE + 1 + len].copy_from_slice(&payload[..len]);
+    }
+
+    // empty if the message has none, or claims more than it holds
+    pub fn read(packet: &[u8], received_len: usize) -> &[u8] {
+        if received_len <= TACHYON_CONNECTION_HEADER_SIZE {
+            return &[];
+        }
+        let start = TACHYON_CONNECTION_HEADER_SIZE + 1;
+        let len = packet[TACHYON_CONNECTION_HEADER_SIZE] as usize;
+        if len > LINK_PAYLOAD_MAX || start + len > received_len {
+            return &[];
+        }
+        return &packet[start..start + len];
+    }
+}
+
+impl Tachyon {
+    // false if longer than LINK_PAYLOAD_MAX
+    pub fn set_link_payload(&mut self, payload: &[u8]) -> bool {
+        if payload.len() > LINK_PAYLOAD_MAX {
+            return false;
+        }
+        self.link_payloads.request = payload.to_vec();
+        return true;
+    }
+
+    // what the server sends back when the identity links, empty clears it
+    pub fn set_link_reply_payload(&mut self, id: u32, payload: &[u8]) -> bool {
+        if payload.len() > LINK_PAYLOAD_MAX {
+            return false;
+        }
+        if payload.is_empty() {
+            self.link_payloads.replies.remove(&id);
+        } else {
+            self.link_payloads.replies.insert(id, payload.to_vec());
+        }
+        return true;
+    }
+
+    // Sets what the server sends along with the reason when a link request fails.  A reply is never larger than the
+    // request, so a spoofed request can't be used for amplification.  Clients pad their link requests to fit any payload,
+    // older clients get the reason alone.
+    pub fn set_link_denied_payload(&mut self, payload: &[u8]) -> bool {
+        if payload.len() > LINK_PAYLOAD_MAX {
+            return false;
+        }
+        self.link_payloads.denied = payload.to_vec();
+        return true;
+    }
+
+    // The payload that came with the last link request (server) or reply (client) from the address, a client's server
+    // being NetworkAddress::default().  A server forgets it with the connection.
+    pub fn get_link_payload(&self, address: NetworkAddress) -> Option<&[u8]> {
+        return self.link_payloads.received.get(&address).map(|payload| payload.as_slice());
+    }
+
+    pub fn set_received_link_payload(&mut self, address: NetworkAddress, payload: &[u8]) {
+        if payload.is_empty() {
+            self.link_payloads.received.remove(&address);
+        } else {
+            self.link_payloads.received.insert(address, payload.to_vec());
+        }
+    }
+
+    pub fn remove_received_link_payload(&mut self, address: NetworkAddress) {
+        self.link_payloads.received.remove(&address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{
+        connection::Identity, header::LINK_PAYLOAD_MAX, network_address::NetworkAddress, tachyon_test::TachyonTest,
+        TachyonConfig,
+    };
+
+    use super::LinkPayloads;
+
+    #[test]
+    fn test_read_write() {
+        let mut buffer: Vec<u8> = vec![0; LinkPayloads::message_size(3)];
+        LinkPayloads::write(&mut buffer, &[1, 2, 3]);
+        assert_eq!(&[1, 2, 3], LinkPayloads::read(&buffer, buffer.len()));
+
+        // older peers send no payload, or a zeroed tail
+        assert!(LinkPayloads::read(&buffer, 9).is_empty());
+        assert!(LinkPayloads::read(&[0; 12], 12).is_empty());
+        // a length past the end of the message
+        assert!(LinkPayloads::read(&buffer, buffer.len() - 1).is_empty());
+    }
+
+    fn identity_test(session_id: u32) -> TachyonTest {
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut test = TachyonTest::default();
+        test.server.config = config;
+        test.client.config = config;
+        test.server.set_identity(1, 10);
+        test.client.identity = Identity { id: 1, session_id, linked: 0 };
+        return test;
+    }
+
+    #[test]
+    #[serial]
+    fn test_link_payload() {
+        let mut test = identity_test(10);
+        assert!(!test.client.set_link_payload(&[0; LINK_PAYLOAD_MAX + 1]));
+        assert!(test.client.set_link_payload(b"token"));
+        assert!(test.server.set_link_reply_payload(1, b"region-eu"));
+        test.connect();
+
+        test.client.update();
+        test.server_receive();
+        let remote = test.remote_client();
+        assert_eq!(Some(&b"token"[..]), test.server.get_link_payload(remote));
+
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+        assert_eq!(Some(&b"region-eu"[..]), test.client.get_link_payload(NetworkAddress::default()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_link_denied_payload() {
+        let mut test = identity_test(11);
+        assert!(test.server.set_link_denied_payload(b"bad token"));
+        test.connect();
+
+        test.client.update();
+        test.server_receive();
+        let remote = test.remote_client();
+        assert!(test.server.get_link_payload(remote).is_none());
+
+        test.client_receive();
+        assert!(!test.client.identity.is_linked());
+        assert_eq!(Some(&b"bad token"[..]), test.client.get_link_payload(NetworkAddress::default()));
+    }
+}
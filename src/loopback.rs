@@ -0,0 +1,224 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    network_address::NetworkAddress,
+    tachyon_socket::SocketReceiveResult,
+    transport::{Datagram, Transport},
+    Tachyon,
+};
+
+// clients get ports from here up, like the ephemeral ports a UdpSocket would get
+const CLIENT_PORT_START: u32 = 49152;
+
+#[derive(Default)]
+struct Inboxes {
+    inboxes: FxHashMap<NetworkAddress, VecDeque<Datagram>>,
+    next_client_port: u32,
+}
+
+// Wires Tachyons in the same process to each other without sockets.  Every endpoint has an inbox, a send goes straight
+// into the inbox of the address it's for, so clients and servers run the full protocol with nothing to pump in between.
+// Meant for tests where binding sockets isn't possible, and for running a server and its clients in one process.
+// Datagrams to addresses nothing is bound to are dropped like UDP would.  Clones share the same network.
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    inner: Arc<Mutex<Inboxes>>,
+}
+
+impl LoopbackNetwork {
+    // None if the address is taken
+    pub fn bind(&self, address: NetworkAddress) -> Option<LoopbackTransport> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.inboxes.contains_key(&address) {
+            return None;
+        }
+        inner.inboxes.insert(address, VecDeque::new());
+        return Some(LoopbackTransport { network: self.clone(), address, server_address: None });
+    }
+
+    // a transport for a client of the server at address, from a fresh localhost address of its own
+    pub fn connect(&self, server_address: NetworkAddress) -> LoopbackTransport {
+        let mut inner = self.inner.lock().unwrap();
+        let mut address;
+        loop {
+            address = NetworkAddress::localhost(CLIENT_PORT_START + inner.next_client_port);
+            inner.next_client_port = (inner.next_client_port + 1) % (u16::MAX as u32 + 1 - CLIENT_PORT_START);
+            if !inner.inboxes.contains_key(&address) {
+                break;
+            }
+        }
+        inner.inboxes.insert(address, VecDeque::new());
+        return LoopbackTransport { network: self.clone(), address, server_address: Some(server_address) };
+    }
+
+    // datagrams waiting to be received at the address
+    pub fn pending(&self, address: NetworkAddress) -> usize {
+        match self.inner.lock().unwrap().inboxes.get(&address) {
+            Some(inbox) => return inbox.len(),
+            None => return 0,
+        }
+    }
+
+    // loses the oldest datagram waiting at the address, false if there was none
+    pub fn drop_next(&self, address: NetworkAddress) -> bool {
+        match self.inner.lock().unwrap().inboxes.get_mut(&address) {
+            Some(inbox) => return inbox.pop_front().is_some(),
+            None => return false,
+        }
+    }
+
+    fn deliver(&self, from: NetworkAddress, to: NetworkAddress, data: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(inbox) = inner.inboxes.get_mut(&to) {
+            inbox.push_back(Datagram { address: from, data: data.to_vec() });
+        }
+    }
+}
+
+pub struct LoopbackTransport {
+    network: LoopbackNetwork,
+    address: NetworkAddress,
+    // set for clients, where their sends go
+    server_address: Option<NetworkAddress>,
+}
+
+impl LoopbackTransport {
+    pub fn address(&self) -> NetworkAddress {
+        return self.address;
+    }
+}
+
+// the address is free to bind again once the Tachyon lets go of its transport
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        self.network.inner.lock().unwrap().inboxes.remove(&self.address);
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send_to(&self, address: NetworkAddress, data: &[u8]) -> usize {
+        match self.server_address {
+            Some(server_address) => self.network.deliver(self.address, server_address, data),
+            None => self.network.deliver(self.address, address, data),
+        }
+        return data.len();
+    }
+
+    fn receive(&mut self, data: &mut [u8]) -> SocketReceiveResult {
+        let datagram = match self.network.inner.lock().unwrap().inboxes.get_mut(&self.address) {
+            Some(inbox) => inbox.pop_front(),
+            None => None,
+        };
+        match datagram {
+            Some(datagram) => {
+                if datagram.data.len() > data.len() {
+                    return SocketReceiveResult::Error;
+                }
+                data[0..datagram.data.len()].copy_from_slice(&datagram.data);
+                // clients only ever hear from their server
+                let network_address = if self.server_address.is_some() { NetworkAddress::default() } else { datagram.address };
+                return SocketReceiveResult::Success { bytes_received: datagram.data.len(), network_address };
+            }
+            None => {
+                return SocketReceiveResult::Empty;
+            }
+        }
+    }
+}
+
+impl Tachyon {
+    pub fn bind_loopback(&mut self, network: &LoopbackNetwork, address: NetworkAddress) -> bool {
+        match network.bind(address) {
+            Some(transport) => {
+                self.bind_transport(Box::new(transport));
+                return true;
+            }
+            None => return false,
+        }
+    }
+
+    pub fn connect_loopback(&mut self, network: &LoopbackNetwork, address: NetworkAddress) {
+        self.connect_transport(Box::new(network.connect(address)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        connection::Identity, connection_state::ConnectionState, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon,
+        TachyonConfig,
+    };
+
+    use super::LoopbackNetwork;
+
+    #[test]
+    fn test_loopback() {
+        let mut test = TachyonTest::default();
+        let network = test.connect_loopback();
+
+        test.client_send_reliable(1, 8);
+        let res = test.server_receive();
+        assert_eq!(8, res.length);
+        assert!(NetworkAddress::localhost(49152) == res.address);
+
+        assert_eq!(0, test.server_send_reliable(1, 16).error);
+        assert_eq!(16, test.client_receive().length);
+
+        // lost on the way to the client, its nack gets it resent
+        let client_address = NetworkAddress::localhost(49152);
+        test.server_send_reliable(1, 8);
+        assert!(network.drop_next(client_address));
+        test.server_send_reliable(1, 4);
+        assert_eq!(1, network.pending(client_address));
+        assert_eq!(0, test.client_receive().length);
+        test.client.step(Duration::from_millis(10));
+        test.server_receive();
+        test.server.step(Duration::from_millis(10));
+        assert_eq!(8, test.client_receive().length);
+        assert_eq!(4, test.client_receive().length);
+        assert_eq!(1, test.server.get_combined_stats().channel_stats.nacks_received);
+    }
+
+    #[test]
+    fn test_loopback_link() {
+        let mut test = TachyonTest::default();
+        test.client.config.use_identity = 1;
+        test.client.identity = Identity { id: 1, session_id: 10, linked: 0 };
+        test.server.config.use_identity = 1;
+        test.server.set_identity(1, 10);
+        test.connect_loopback();
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+        assert_eq!(ConnectionState::Linked, test.client.get_connection_state(NetworkAddress::default()));
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(1, test.server_receive().identity_id);
+    }
+
+    #[test]
+    fn test_loopback_bind() {
+        let network = LoopbackNetwork::default();
+        let address = NetworkAddress::localhost(100);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.bind_loopback(&network, address));
+        assert!(!Tachyon::create(TachyonConfig::default()).bind_loopback(&network, address));
+
+        // two clients get their own addresses
+        let first = network.connect(address);
+        let second = network.connect(address);
+        assert!(first.address() != second.address());
+
+        // free again once the transport is gone
+        drop(server);
+        assert!(network.bind(address).is_some());
+    }
+}
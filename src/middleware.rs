@@ -0,0 +1,65 @@
+use super::network_address::NetworkAddress;
+
+// max udp datagram, sends are copied here so middleware can grow them without touching the send buffers used for resends
+const MIDDLEWARE_BUFFER_LEN: usize = 1024 * 64;
+
+pub enum PacketAction {
+    Keep,
+    Drop,
+    // packet was rewritten in place, value is the new length
+    Modify(usize),
+}
+
+// Invoked at the socket boundary for every datagram Tachyon sends or receives, after headers are written and before they are read.
+// data is the whole buffer, the packet being the first length bytes, so a middleware can grow a packet (encryption, framing) as well as shrink it.
+// Unreliable senders used from other threads (UnreliableSender, PoolUnreliableSender) write to the socket directly and bypass middleware.
+pub trait PacketMiddleware: Send {
+    fn on_send(&mut self, data: &mut [u8], length: usize, address: NetworkAddress) -> PacketAction;
+    fn on_receive(&mut self, data: &mut [u8], length: usize, address: NetworkAddress) -> PacketAction;
+}
+
+pub struct MiddlewareState {
+    pub middleware: Box<dyn PacketMiddleware>,
+    pub send_buffer: Vec<u8>,
+}
+
+impl MiddlewareState {
+    pub fn create(middleware: Box<dyn PacketMiddleware>) -> Self {
+        let state = MiddlewareState {
+            middleware,
+            send_buffer: vec![0; MIDDLEWARE_BUFFER_LEN],
+        };
+        return state;
+    }
+
+    // returns the length to send, 0 if the packet should be dropped
+    pub fn process_send(&mut self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        self.send_buffer[0..length].copy_from_slice(&data[0..length]);
+        match self.middleware.on_send(&mut self.send_buffer, length, address) {
+            PacketAction::Keep => {
+                return length;
+            }
+            PacketAction::Drop => {
+                return 0;
+            }
+            PacketAction::Modify(modified_len) => {
+                return std::cmp::min(modified_len, self.send_buffer.len());
+            }
+        }
+    }
+
+    // returns the length received, 0 if the packet should be dropped
+    pub fn process_receive(&mut self, address: NetworkAddress, data: &mut [u8], length: usize) -> usize {
+        match self.middleware.on_receive(data, length, address) {
+            PacketAction::Keep => {
+                return length;
+            }
+            PacketAction::Drop => {
+                return 0;
+            }
+            PacketAction::Modify(modified_len) => {
+                return std::cmp::min(modified_len, data.len());
+            }
+        }
+    }
+}
@@ -1,8 +1,16 @@
-use std::io::Cursor;
+use alloc::vec::Vec;
 
-use varuint::{ReadVarint, WriteVarint};
+use super::{int_buffer::{BufferTooSmall, IntBuffer}, sequence::Sequence};
 
-use super::{int_buffer::IntBuffer, sequence::Sequence};
+// Nack format v2, MESSAGE_TYPE_NACK_RANGES.  Runs of consecutive missing sequences as (start, length), newest first
+// and counting back from start like a Nack.  A varint count, then the first range's start and length, then for each
+// following range how far back it starts from the end of the one before (1 is right after) and its length.  Dense
+// loss comes out at a couple of bytes per run instead of a start and flags per 33 sequences.
+pub const NACK_FORMAT_RANGES: u8 = 2;
+// what a single nack message can ask for, half the sequence space
+const NACK_RANGES_MAX_SEQUENCES: u32 = 32767;
+// sequences run 0..=65534
+const SEQUENCE_SPACE: u32 = u16::MAX as u32;
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -30,18 +38,34 @@ impl Nack {
         return buffer.index as u64;
     }
 
+    // write, or how long data needed to be
+    pub fn try_write(nacks: &[Nack], data: &mut [u8], position: usize) -> Result<usize, BufferTooSmall> {
+        if nacks.len() == 0 {
+            return Ok(0);
+        }
+        BufferTooSmall::check(position + 1 + nacks.len() * 6, data)?;
+        return Ok(Nack::write(nacks, data, position as u64) as usize);
+    }
+
+    // nothing is added when data ends before the nack does
     pub fn read_single(sequences: &mut Vec<u16>, data: &[u8], position: usize) {
         let mut buffer = IntBuffer {
             index: position
         };
 
         let mut nack = Nack::default();
-        nack.start_sequence = buffer.read_u16(data);
+        nack.start_sequence = match buffer.try_read_u16(data) {
+            Some(start_sequence) => start_sequence,
+            None => return,
+        };
         if nack.start_sequence == 0 {
             return;
         }
         
-        nack.flags = buffer.read_u32(data);
+        nack.flags = match buffer.try_read_u32(data) {
+            Some(flags) => flags,
+            None => return,
+        };
         nack.get_nacked(sequences);
     }
 
@@ -60,27 +84,230 @@ impl Nack {
             return 0;
         }
 
-        let mut cursor = Cursor::new(data);
-        cursor.set_position(position);
-        let _ = cursor.write_varint(nacks.len() as u16).unwrap();
+        let mut position = position as usize;
+        Nack::write_varint_u32(nacks.len() as u16 as u32, data, &mut position);
         for nack in nacks {
-            cursor.write_varint(nack.start_sequence).unwrap();
-            cursor.write_varint(nack.flags).unwrap();
+            Nack::write_varint_u32(nack.start_sequence as u32, data, &mut position);
+            Nack::write_varint_u32(nack.flags, data, &mut position);
         }
-        return cursor.position();
+        return position as u64;
     }
 
-    pub fn read_varint(sequences: &mut Vec<u16>, data: &[u8], position: usize) {
-        let mut cursor = Cursor::new(data);
-        cursor.set_position(position as u64);
+    pub fn try_write_varint(nacks: &[Nack], data: &mut [u8], position: usize) -> Result<usize, BufferTooSmall> {
+        if nacks.len() == 0 {
+            return Ok(0);
+        }
+        BufferTooSmall::check(position + Nack::varint_size(nacks), data)?;
+        return Ok(Nack::write_varint(nacks, data, position as u64) as usize);
+    }
 
-        let count = ReadVarint::<u32>::read_varint(&mut cursor).unwrap();
+    // Returns the position after the nacks.  None if they don't decode or data ends first, nothing is added to
+    // sequences from a message like that.
+    pub fn read_varint(sequences: &mut Vec<u16>, data: &[u8], position: usize) -> Option<usize> {
+        let sequences_len = sequences.len();
+        let mut position = position;
+        let count = Nack::checked_varint_u32(data, &mut position)?;
         for _ in 0..count {
             let mut nack = Nack::default();
-            nack.start_sequence = ReadVarint::<u16>::read_varint(&mut cursor).unwrap();
-            nack.flags = ReadVarint::<u32>::read_varint(&mut cursor).unwrap();
+            match (Nack::checked_varint_u16(data, &mut position), Nack::checked_varint_u32(data, &mut position)) {
+                (Some(start_sequence), Some(flags)) => {
+                    nack.start_sequence = start_sequence;
+                    nack.flags = flags;
+                }
+                _ => {
+                    sequences.truncate(sequences_len);
+                    return None;
+                }
+            }
             nack.get_nacked(sequences);
         }
+        return Some(position);
+    }
+
+    // what write_varint writes
+    pub fn varint_size(nacks: &[Nack]) -> usize {
+        let mut size = Nack::varint_size_u32(nacks.len() as u16 as u32);
+        for nack in nacks {
+            size += Nack::varint_size_u32(nack.start_sequence as u32) + Nack::varint_size_u32(nack.flags);
+        }
+        return size;
+    }
+
+    // the nacked sequences as ranges, runs that carry on from one nack into the next are joined
+    pub fn to_ranges(nacks: &[Nack], ranges: &mut Vec<(u16, u16)>) {
+        ranges.clear();
+        for nack in nacks {
+            let mut sequence = nack.start_sequence;
+            for i in -1i32..32 {
+                if i < 0 || nack.get_bits(i) {
+                    match ranges.last_mut() {
+                        Some((start, length)) if Nack::back(*start, *length as u32) == sequence => *length += 1,
+                        _ => ranges.push((sequence, 1)),
+                    }
+                }
+                sequence = Sequence::previous_sequence(sequence);
+            }
+        }
+    }
+
+    // what write_ranges writes
+    pub fn ranges_size(ranges: &[(u16, u16)]) -> usize {
+        let mut size = Nack::varint_size_u32(ranges.len() as u16 as u32);
+        let mut previous: Option<(u16, u16)> = None;
+        for (start, length) in ranges {
+            let first = match previous {
+                Some(previous) => Nack::range_skip(previous, *start),
+                None => *start,
+            };
+            size += Nack::varint_size_u32(first as u32) + Nack::varint_size_u32(*length as u32);
+            previous = Some((*start, *length));
+        }
+        return size;
+    }
+
+    pub fn write_ranges(ranges: &[(u16, u16)], data: &mut [u8], position: u64) -> u64 {
+        let mut position = position as usize;
+        Nack::write_varint_u32(ranges.len() as u16 as u32, data, &mut position);
+        let mut previous: Option<(u16, u16)> = None;
+        for (start, length) in ranges {
+            let first = match previous {
+                Some(previous) => Nack::range_skip(previous, *start),
+                None => *start,
+            };
+            Nack::write_varint_u32(first as u32, data, &mut position);
+            Nack::write_varint_u32(*length as u32, data, &mut position);
+            previous = Some((*start, *length));
+        }
+        return position as u64;
+    }
+
+    pub fn try_write_ranges(ranges: &[(u16, u16)], data: &mut [u8], position: usize) -> Result<usize, BufferTooSmall> {
+        BufferTooSmall::check(position + Nack::ranges_size(ranges), data)?;
+        return Ok(Nack::write_ranges(ranges, data, position as u64) as usize);
+    }
+
+    // None if the ranges don't decode or ask for more than a nack could, nothing is taken from a message like that
+    pub fn read_ranges(ranges: &mut Vec<(u16, u16)>, data: &[u8], position: usize) -> Option<usize> {
+        ranges.clear();
+        let mut position = position;
+        let count = Nack::checked_varint_u16(data, &mut position)?;
+        let mut total: u32 = 0;
+        let mut previous: Option<(u16, u16)> = None;
+        for _ in 0..count {
+            let first = Nack::checked_varint_u16(data, &mut position)?;
+            let length = Nack::checked_varint_u16(data, &mut position)?;
+            total += length as u32;
+            if length == 0 || total > NACK_RANGES_MAX_SEQUENCES || first as u32 >= SEQUENCE_SPACE {
+                return None;
+            }
+            let start = match previous {
+                Some((start, previous_length)) => {
+                    if first == 0 {
+                        return None;
+                    }
+                    Nack::back(start, previous_length as u32 + first as u32 - 1)
+                }
+                None => first,
+            };
+            ranges.push((start, length));
+            previous = Some((start, length));
+        }
+        return Some(position);
+    }
+
+    // The varuint crate's format, 1 to 5 bytes by the first byte.  Panics past the end of data like the slice writes in
+    // write do.
+    pub fn write_varint_u32(value: u32, data: &mut [u8], position: &mut usize) {
+        let length = Nack::varint_size_u32(value);
+        let bytes = &mut data[*position..*position + length];
+        match length {
+            1 => bytes[0] = value as u8,
+            2 => {
+                bytes[0] = ((value - 240) / 256 + 241) as u8;
+                bytes[1] = ((value - 240) % 256) as u8;
+            }
+            3 => {
+                bytes[0] = 248;
+                bytes[1] = ((value - 2032) / 256) as u8;
+                bytes[2] = ((value - 2032) % 256) as u8;
+            }
+            4 => {
+                bytes[0] = 249;
+                bytes[1..4].copy_from_slice(&value.to_le_bytes()[0..3]);
+            }
+            _ => {
+                bytes[0] = 250;
+                bytes[1..5].copy_from_slice(&value.to_ne_bytes());
+            }
+        }
+        *position += length;
+    }
+
+    pub fn varint_size_u32(value: u32) -> usize {
+        return match value {
+            0..=240 => 1,
+            241..=2031 => 2,
+            2032..=67567 => 3,
+            67568..=16_777_215 => 4,
+            _ => 5,
+        };
+    }
+
+    // these return None on bytes no writer produces, for input that isn't trusted
+    pub fn checked_varint_u16(data: &[u8], position: &mut usize) -> Option<u16> {
+        let mut end = *position;
+        let value = Nack::checked_varint_u32(data, &mut end)?;
+        if value > u16::MAX as u32 {
+            return None;
+        }
+        *position = end;
+        return Some(value as u16);
+    }
+
+    pub fn checked_varint_u32(data: &[u8], position: &mut usize) -> Option<u32> {
+        let first = *data.get(*position)?;
+        let length = match first {
+            0..=240 => 1,
+            241..=247 => 2,
+            248 => 3,
+            249 => 4,
+            250 => 5,
+            _ => return None,
+        };
+        let bytes = data.get(*position..*position + length)?;
+        let value: u64 = match length {
+            1 => first as u64,
+            2 => 240 + 256 * (first as u64 - 241) + bytes[1] as u64,
+            3 => 2032 + 256 * bytes[1] as u64 + bytes[2] as u64,
+            4 => bytes[1] as u64 | (bytes[2] as u64) << 8 | (bytes[3] as u64) << 16,
+            _ => u32::from_ne_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as u64,
+        };
+        if value > u32::MAX as u64 {
+            return None;
+        }
+        *position += length;
+        return Some(value as u32);
+    }
+
+    pub fn get_range_nacked(ranges: &[(u16, u16)], sequences: &mut Vec<u16>) {
+        for (start, length) in ranges {
+            let mut sequence = *start;
+            for _ in 0..*length {
+                sequences.push(sequence);
+                sequence = Sequence::previous_sequence(sequence);
+            }
+        }
+    }
+
+    // how far back from the end of the previous range the next one starts, 1 for right after it
+    fn range_skip(previous: (u16, u16), start: u16) -> u16 {
+        let distance = (previous.0 as u32 + SEQUENCE_SPACE - start as u32) % SEQUENCE_SPACE;
+        return (distance + 1).saturating_sub(previous.1 as u32) as u16;
+    }
+
+    // the sequence steps before sequence
+    fn back(sequence: u16, steps: u32) -> u16 {
+        return ((sequence as u32 + SEQUENCE_SPACE - steps % SEQUENCE_SPACE) % SEQUENCE_SPACE) as u16;
     }
 
     pub fn read(sequences: &mut Vec<u16>, data: &[u8], position: u64) {
@@ -158,7 +385,10 @@ impl Nack {
 
 #[cfg(test)]
 mod tests {
-    use crate::tachyon::sequence::Sequence;
+    use rand::{prelude::StdRng, Rng, SeedableRng};
+    use varuint::{VarintSizeHint, WriteVarint};
+
+    use crate::sequence::Sequence;
 
     use super::Nack;
 
@@ -231,4 +461,143 @@ mod tests {
         Nack::read_varint(&mut sequences_out, &data[..], 0);
         assert_eq!(66, sequences_out.len());
     }
+
+    #[test]
+    fn test_read_truncated() {
+        let nacks = [create_full_nack(1), create_full_nack(34)];
+        let mut data: Vec<u8> = vec![0; 64];
+        let length = Nack::write_varint(&nacks, &mut data, 4) as usize;
+
+        let mut sequences_out: Vec<u16> = Vec::new();
+        assert_eq!(Some(length), Nack::read_varint(&mut sequences_out, &data[0..length], 4));
+        assert_eq!(66, sequences_out.len());
+        sequences_out.clear();
+        for cut in 4..length {
+            assert_eq!(None, Nack::read_varint(&mut sequences_out, &data[0..cut], 4));
+            assert!(sequences_out.is_empty());
+        }
+
+        Nack::write_single(&nacks[1], &mut data, 4);
+        Nack::read_single(&mut sequences_out, &data[0..9], 4);
+        assert!(sequences_out.is_empty());
+        Nack::read_single(&mut sequences_out, &data[0..10], 4);
+        assert_eq!(33, sequences_out.len());
+    }
+
+    #[test]
+    fn test_try_write() {
+        let nacks = [create_full_nack(1), create_full_nack(34)];
+        let mut data: Vec<u8> = vec![0; 4];
+        let required = 4 + Nack::varint_size(&nacks);
+        assert_eq!(required, Nack::try_write_varint(&nacks, &mut data, 4).unwrap_err().required);
+        assert_eq!(4 + 1 + 2 * 6, Nack::try_write(&nacks, &mut data, 4).unwrap_err().required);
+
+        data.resize(required, 0);
+        assert_eq!(Ok(required), Nack::try_write_varint(&nacks, &mut data, 4));
+        let mut sequences_out: Vec<u16> = Vec::new();
+        Nack::read_varint(&mut sequences_out, &data, 4);
+        assert_eq!(66, sequences_out.len());
+
+        let ranges = [(34, 66)];
+        let mut data: Vec<u8> = Vec::new();
+        let required = Nack::try_write_ranges(&ranges, &mut data, 4).unwrap_err().required;
+        assert_eq!(4 + Nack::ranges_size(&ranges), required);
+        data.resize(required, 0);
+        assert_eq!(Ok(required), Nack::try_write_ranges(&ranges, &mut data, 4));
+        assert_eq!(Ok(0), Nack::try_write_varint(&[], &mut data, 4));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let nacks = [create_full_nack(34), create_full_nack(1)];
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        Nack::to_ranges(&nacks, &mut ranges);
+        // 34 down to 0 and on past the wrap
+        assert_eq!(vec![(34, 66)], ranges);
+        assert!(Nack::ranges_size(&ranges) < Nack::varint_size(&nacks));
+
+        let mut nack = create_full_nack(100);
+        nack.set_bits(4, false);
+        nack.set_bits(5, false);
+        Nack::to_ranges(&[nack], &mut ranges);
+        assert_eq!(vec![(100, 5), (93, 26)], ranges);
+
+        let mut data: Vec<u8> = vec![0; 64];
+        let length = Nack::write_ranges(&ranges, &mut data, 4) as usize;
+        assert_eq!(length - 4, Nack::ranges_size(&ranges));
+        let mut read: Vec<(u16, u16)> = Vec::new();
+        assert_eq!(Some(length), Nack::read_ranges(&mut read, &data, 4));
+        assert_eq!(ranges, read);
+
+        // the checked readers read what varuint writes, and write_varint_u32 writes the same bytes
+        let values: [u32; 11] = [0, 240, 241, 2031, 2032, 67567, 67568, 16_777_215, 16_777_216, 65534, u32::MAX];
+        for value in values {
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            cursor.write_varint(value).unwrap();
+            let mut position = 0;
+            assert_eq!(Some(value), Nack::checked_varint_u32(&data, &mut position));
+            assert_eq!(value.varint_size(), position);
+            assert_eq!(value.varint_size(), Nack::varint_size_u32(value));
+
+            let mut written: Vec<u8> = vec![0; 8];
+            let mut written_position = 0;
+            Nack::write_varint_u32(value, &mut written, &mut written_position);
+            assert_eq!(&data[0..position], &written[0..written_position]);
+        }
+        assert!(Nack::checked_varint_u16(&[250, 0, 0, 1, 0], &mut 0).is_none());
+        assert!(Nack::checked_varint_u32(&[255], &mut 0).is_none());
+
+        // a skip of 0 or a zero length range doesn't come from write_ranges
+        assert!(Nack::read_ranges(&mut read, &[2, 10, 1, 0, 1], 0).is_none());
+        assert!(Nack::read_ranges(&mut read, &[1, 10, 0], 0).is_none());
+        assert!(Nack::read_ranges(&mut read, &[2, 10, 1], 0).is_none());
+    }
+
+    // random loss in random windows, some across the wrap, reads back as the same sequences as the varint format
+    #[test]
+    fn test_ranges_fuzz() {
+        let mut rng = StdRng::seed_from_u64(4160);
+        let mut data: Vec<u8> = vec![0; 2048];
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        let mut read: Vec<(u16, u16)> = Vec::new();
+        for _ in 0..2000 {
+            let loss: f64 = rng.gen_range(0.0..1.0);
+            let mut nacks: Vec<Nack> = Vec::new();
+            let mut sequence: u16 = rng.gen_range(0..65535);
+            for _ in 0..rng.gen_range(1..16) {
+                let mut nack = Nack::default();
+                nack.start_sequence = sequence;
+                for i in 0..32 {
+                    nack.set_bits(i, rng.gen_bool(loss));
+                }
+                nacks.push(nack);
+                for _ in 0..33 {
+                    sequence = Sequence::previous_sequence(sequence);
+                }
+            }
+
+            let mut expected: Vec<u16> = Vec::new();
+            for nack in &nacks {
+                nack.get_nacked(&mut expected);
+            }
+
+            Nack::to_ranges(&nacks, &mut ranges);
+            let length = Nack::write_ranges(&ranges, &mut data, 0) as usize;
+            assert_eq!(length, Nack::ranges_size(&ranges));
+            assert_eq!(Some(length), Nack::read_ranges(&mut read, &data, 0));
+            let mut sequences: Vec<u16> = Vec::new();
+            Nack::get_range_nacked(&read, &mut sequences);
+            assert_eq!(expected, sequences);
+        }
+
+        // garbage never panics or asks for too much
+        for _ in 0..2000 {
+            let length = rng.gen_range(0..64);
+            rng.fill(&mut data[0..length]);
+            if Nack::read_ranges(&mut read, &data[0..length], 0).is_some() {
+                let total: u32 = read.iter().map(|(_, length)| *length as u32).sum();
+                assert!(total <= 32767);
+            }
+        }
+    }
 }
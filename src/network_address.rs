@@ -1,7 +1,6 @@
-use std::{
-    hash::{Hash, Hasher},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-};
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 #[derive(Eq, Default, Clone, Copy)]
 #[repr(C)]
@@ -13,8 +12,8 @@ pub struct NetworkAddress {
     pub port: u32,
 }
 
-impl std::fmt::Display for NetworkAddress {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for NetworkAddress {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "{0}.{1}.{2}.{3}:{4}\n",
@@ -85,6 +84,7 @@ impl NetworkAddress {
         return address;
     }
 
+    #[cfg(feature = "std")]
     pub fn from_socket_addr(address: SocketAddr) -> NetworkAddress {
         if let IpAddr::V4(ipv4) = address.ip() {
             let parts = ipv4.octets();
@@ -101,6 +101,7 @@ impl NetworkAddress {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn to_socket_addr(&self) -> SocketAddr {
         let ip = Ipv4Addr::new(self.a as u8, self.b as u8, self.c as u8, self.d as u8);
         return SocketAddr::new(IpAddr::V4(ip), self.port as u16);
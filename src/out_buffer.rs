@@ -0,0 +1,142 @@
+use super::{int_buffer::IntBuffer, network_address::NetworkAddress};
+
+// v1 is the original LengthPrefixed framing: length u32, channel u16, address, data. It has no header.
+pub const OUT_BUFFER_VERSION_1: u8 = 1;
+// v2 starts with the version byte, then per message:
+// length u32, channel u16, address (12), identity_id u32, receive_type u8, server_id u16, data
+pub const OUT_BUFFER_VERSION_2: u8 = 2;
+
+pub const OUT_BUFFER_V2_HEADER_SIZE: usize = 1;
+pub const OUT_BUFFER_V2_MESSAGE_HEADER_SIZE: usize = 25;
+
+pub const RECEIVE_TYPE_UNRELIABLE: u8 = 0;
+pub const RECEIVE_TYPE_RELIABLE: u8 = 1;
+
+pub struct OutBufferMessage<'a> {
+    pub channel: u16,
+    pub address: NetworkAddress,
+    pub identity_id: u32,
+    pub receive_type: u8,
+    pub server_id: u16,
+    pub data: &'a [u8],
+}
+
+pub struct OutBufferWriter {
+    pub writer: IntBuffer,
+}
+
+impl OutBufferWriter {
+    pub fn create(dst: &mut [u8]) -> Self {
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u8(OUT_BUFFER_VERSION_2, dst);
+        return OutBufferWriter { writer };
+    }
+
+    // false if the message doesn't fit, nothing is written in that case
+    pub fn write(&mut self, message: &OutBufferMessage, dst: &mut [u8]) -> bool {
+        if self.writer.index + OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + message.data.len() > dst.len() {
+            return false;
+        }
+        self.writer.write_u32(message.data.len() as u32, dst);
+        self.writer.write_u16(message.channel, dst);
+        self.writer.write_address(message.address, dst);
+        self.writer.write_u32(message.identity_id, dst);
+        self.writer.write_u8(message.receive_type, dst);
+        self.writer.write_u16(message.server_id, dst);
+        dst[self.writer.index..self.writer.index + message.data.len()].copy_from_slice(message.data);
+        self.writer.index += message.data.len();
+        return true;
+    }
+}
+
+// Reads a v2 out buffer, data being the bytes_written of it
+pub struct OutBufferReader<'a> {
+    data: &'a [u8],
+    reader: IntBuffer,
+}
+
+impl<'a> OutBufferReader<'a> {
+    pub fn create(data: &'a [u8]) -> Option<Self> {
+        if data.len() < OUT_BUFFER_V2_HEADER_SIZE || data[0] != OUT_BUFFER_VERSION_2 {
+            return None;
+        }
+        let reader = OutBufferReader {
+            data,
+            reader: IntBuffer { index: OUT_BUFFER_V2_HEADER_SIZE },
+        };
+        return Some(reader);
+    }
+
+    pub fn next(&mut self) -> Option<OutBufferMessage<'a>> {
+        if self.reader.index + OUT_BUFFER_V2_MESSAGE_HEADER_SIZE > self.data.len() {
+            return None;
+        }
+        let length = self.reader.read_u32(self.data) as usize;
+        let channel = self.reader.read_u16(self.data);
+        let address = self.reader.read_address(self.data);
+        let identity_id = self.reader.read_u32(self.data);
+        let receive_type = self.reader.read_u8(self.data);
+        let server_id = self.reader.read_u16(self.data);
+        if self.reader.index + length > self.data.len() {
+            return None;
+        }
+
+        let message = OutBufferMessage {
+            channel,
+            address,
+            identity_id,
+            receive_type,
+            server_id,
+            data: &self.data[self.reader.index..self.reader.index + length],
+        };
+        self.reader.index += length;
+        return Some(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network_address::NetworkAddress;
+
+    use super::*;
+
+    #[test]
+    fn test_write_read() {
+        let mut buffer: Vec<u8> = vec![0; 128];
+        let mut writer = OutBufferWriter::create(&mut buffer);
+        let message = OutBufferMessage {
+            channel: 3,
+            address: NetworkAddress::localhost(4000),
+            identity_id: 77,
+            receive_type: RECEIVE_TYPE_RELIABLE,
+            server_id: 9,
+            data: &[1, 2, 3],
+        };
+        assert!(writer.write(&message, &mut buffer));
+        assert!(writer.write(&message, &mut buffer));
+        assert_eq!(1 + 2 * (OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + 3), writer.writer.index);
+
+        let written = writer.writer.index;
+        let mut reader = OutBufferReader::create(&buffer[0..written]).unwrap();
+        for _ in 0..2 {
+            let read = reader.next().unwrap();
+            assert_eq!(3, read.channel);
+            assert!(NetworkAddress::localhost(4000) == read.address);
+            assert_eq!(77, read.identity_id);
+            assert_eq!(RECEIVE_TYPE_RELIABLE, read.receive_type);
+            assert_eq!(9, read.server_id);
+            assert_eq!(&[1, 2, 3], read.data);
+        }
+        assert!(reader.next().is_none());
+
+        // doesn't fit
+        let large: Vec<u8> = vec![0; 100];
+        let mut message = message;
+        message.data = &large;
+        assert!(!writer.write(&message, &mut buffer));
+        assert_eq!(written, writer.writer.index);
+
+        // v1 buffers have no version byte
+        assert!(OutBufferReader::create(&[1, 0, 0]).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use std::{cmp::Ordering, collections::BinaryHeap, time::Instant};
+
+pub struct QueuedMessage {
+    pub priority: u8,
+    // None never expires
+    pub deadline: Option<Instant>,
+    order: u64,
+    pub data: Vec<u8>,
+}
+
+impl PartialEq for QueuedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        return self.priority == other.priority && self.order == other.order;
+    }
+}
+
+impl Eq for QueuedMessage {}
+
+impl PartialOrd for QueuedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+// higher priority first, oldest first within a priority
+impl Ord for QueuedMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.priority.cmp(&other.priority).then_with(|| other.order.cmp(&self.order));
+    }
+}
+
+// Optional per channel queue of outgoing reliable messages, flushed in priority order on update.  Messages still queued
+// past their deadline are dropped instead of sent, late game state is useless to the other side.
+#[derive(Default)]
+pub struct OutgoingQueue {
+    heap: BinaryHeap<QueuedMessage>,
+    next_order: u64,
+}
+
+impl OutgoingQueue {
+    pub fn push(&mut self, data: &[u8], priority: u8, deadline: Option<Instant>) {
+        self.next_order += 1;
+        let message = QueuedMessage {
+            priority,
+            deadline,
+            order: self.next_order,
+            data: data.to_vec(),
+        };
+        self.heap.push(message);
+    }
+
+    // for a message that was popped but couldn't be sent yet, it keeps its place
+    pub fn push_back(&mut self, message: QueuedMessage) {
+        self.heap.push(message);
+    }
+
+    // the next message still within its deadline, expired counts the ones dropped on the way
+    pub fn pop_live(&mut self, now: Instant, expired: &mut u64) -> Option<QueuedMessage> {
+        while let Some(message) = self.heap.pop() {
+            match message.deadline {
+                Some(deadline) if now > deadline => {
+                    *expired += 1;
+                }
+                _ => {
+                    return Some(message);
+                }
+            }
+        }
+        return None;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.heap.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.heap.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::OutgoingQueue;
+
+    #[test]
+    fn test_priority_deadline() {
+        let mut queue = OutgoingQueue::default();
+        let now = Instant::now();
+        queue.push(&[1], 1, None);
+        queue.push(&[2], 5, Some(now + Duration::from_millis(10)));
+        queue.push(&[3], 1, None);
+        queue.push(&[4], 9, Some(now - Duration::from_millis(1)));
+        queue.push(&[5], 5, None);
+
+        let mut expired = 0;
+        let mut order: Vec<u8> = Vec::new();
+        while let Some(message) = queue.pop_live(now, &mut expired) {
+            order.push(message.data[0]);
+        }
+        assert_eq!(vec![2, 5, 1, 3], order);
+        assert_eq!(1, expired);
+
+        queue.push(&[6], 1, None);
+        let message = queue.pop_live(now, &mut expired).unwrap();
+        queue.push(&[7], 1, None);
+        queue.push_back(message);
+        assert_eq!(6, queue.pop_live(now, &mut expired).unwrap().data[0]);
+        assert_eq!(1, queue.len());
+    }
+}
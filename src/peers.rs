@@ -0,0 +1,75 @@
+use super::{connection::Identity, network_address::NetworkAddress, security::SECURITY_NOT_PEER, Tachyon};
+
+// Known peers for fixed topologies like server meshes.  A peer gets its connection and channels when it's added, so
+// it can be sent to before it's heard from.  With TachyonConfig.peers_only a server drops everything from addresses
+// that aren't peers, before identities, challenges or connections see it.
+impl Tachyon {
+    // false if the address already was a peer
+    pub fn add_peer(&mut self, address: NetworkAddress) -> bool {
+        if !self.peers.insert(address) {
+            return false;
+        }
+        if !self.connections.contains_key(&address) {
+            self.create_connection(address, Identity::default());
+        }
+        self.create_configured_channels(address);
+        return true;
+    }
+
+    // the peer's connection goes with it, false if it wasn't a peer
+    pub fn remove_peer(&mut self, address: NetworkAddress) -> bool {
+        if !self.peers.remove(&address) {
+            return false;
+        }
+        self.remove_connection(address);
+        return true;
+    }
+
+    pub fn is_peer(&self, address: NetworkAddress) -> bool {
+        return self.peers.contains(&address);
+    }
+
+    // false if peers_only drops datagrams from the address
+    pub(crate) fn peer_allows_receive(&mut self, address: NetworkAddress) -> bool {
+        if self.config.peers_only == 0 || !self.socket.is_server || self.peers.contains(&address) {
+            return true;
+        }
+        self.stats.unconnected_dropped += 1;
+        self.fire_security_event(SECURITY_NOT_PEER, address, 0);
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTest};
+
+    #[test]
+    fn test_peers_only() {
+        let mut test = TachyonTest::default();
+        test.server.config.peers_only = 1;
+        let _network = test.connect_loopback();
+        test.client_send_unreliable(8);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(1, test.server.stats.unconnected_dropped);
+        assert!(test.server.connections.is_empty());
+
+        // a peer has its channels from the start and can be sent to first
+        let peer = NetworkAddress::localhost(49152);
+        assert!(test.server.add_peer(peer));
+        assert!(!test.server.add_peer(peer));
+        assert!(test.server.is_peer(peer));
+        assert_eq!(2, test.server.get_channels_for(peer).len());
+        assert_eq!(0, test.server_send_reliable(1, 8).error);
+        assert_eq!(8, test.client_receive().length);
+        test.client_send_reliable(1, 8);
+        assert_eq!(8, test.server_receive().length);
+
+        assert!(test.server.remove_peer(peer));
+        assert!(!test.server.remove_peer(peer));
+        assert!(test.server.connections.is_empty());
+        test.client_send_unreliable(8);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(2, test.server.stats.unconnected_dropped);
+    }
+}
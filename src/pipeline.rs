@@ -0,0 +1,111 @@
+use std::{io::IoSlice, sync::Arc};
+
+// A message level processing stage on a reliable channel (compression, encryption, checksums...).
+// Send stages run in the order they were added, on the whole message before fragmentation and reliability.
+// Receive stages run in reverse order on the published message after reassembly.
+pub trait PipelineStage: Send {
+    // write the transformed message to out, false drops the message
+    fn on_send(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool;
+    fn on_receive(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool;
+}
+
+// stages keep per connection state (nonces, dictionaries), so every channel instance gets its own
+pub type PipelineStageFactory = Arc<dyn Fn() -> Box<dyn PipelineStage> + Send + Sync>;
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn PipelineStage>>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl Pipeline {
+    pub fn create(factories: &[PipelineStageFactory]) -> Self {
+        let mut pipeline = Pipeline::default();
+        for factory in factories {
+            pipeline.stages.push(factory());
+        }
+        return pipeline;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.stages.len() == 0;
+    }
+
+    pub fn process_send(&mut self, slices: &[IoSlice]) -> Option<&[u8]> {
+        self.input.clear();
+        for slice in slices {
+            self.input.extend_from_slice(slice);
+        }
+
+        for stage in self.stages.iter_mut() {
+            self.output.clear();
+            if !stage.on_send(&self.input, &mut self.output) {
+                return None;
+            }
+            std::mem::swap(&mut self.input, &mut self.output);
+        }
+        return Some(&self.input);
+    }
+
+    pub fn process_receive(&mut self, data: &[u8]) -> Option<&[u8]> {
+        self.input.clear();
+        self.input.extend_from_slice(data);
+
+        for stage in self.stages.iter_mut().rev() {
+            self.output.clear();
+            if !stage.on_receive(&self.input, &mut self.output) {
+                return None;
+            }
+            std::mem::swap(&mut self.input, &mut self.output);
+        }
+        return Some(&self.input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::IoSlice, sync::Arc};
+
+    use super::{Pipeline, PipelineStage, PipelineStageFactory};
+
+    // appends a byte on send, checks and strips it on receive
+    struct TagStage {
+        tag: u8,
+    }
+
+    impl PipelineStage for TagStage {
+        fn on_send(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool {
+            out.extend_from_slice(data);
+            out.push(self.tag);
+            return true;
+        }
+
+        fn on_receive(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool {
+            if data.last() != Some(&self.tag) {
+                return false;
+            }
+            out.extend_from_slice(&data[0..data.len() - 1]);
+            return true;
+        }
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let factories: Vec<PipelineStageFactory> = vec![
+            Arc::new(|| Box::new(TagStage { tag: 1 }) as Box<dyn PipelineStage>),
+            Arc::new(|| Box::new(TagStage { tag: 2 }) as Box<dyn PipelineStage>),
+        ];
+        let mut pipeline = Pipeline::create(&factories);
+        assert!(!pipeline.is_empty());
+
+        let sent = pipeline.process_send(&[IoSlice::new(&[5, 6]), IoSlice::new(&[7])]).unwrap().to_vec();
+        assert_eq!(vec![5, 6, 7, 1, 2], sent);
+
+        let received = pipeline.process_receive(&sent).unwrap();
+        assert_eq!(&[5, 6, 7], received);
+
+        // stages unwind in reverse, so the wrong order fails
+        assert!(pipeline.process_receive(&[5, 6, 7, 2, 1]).is_none());
+    }
+}
@@ -1,14 +1,15 @@
 use std::{
     collections::VecDeque,
-    sync::{Arc}
+    sync::{atomic::{AtomicI32, AtomicU64, Ordering}, Arc, Mutex},
+    time::{Duration, Instant}
 };
 
 use crossbeam::queue::ArrayQueue;
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use rayon::{Scope, ThreadPool, ThreadPoolBuilder};
 use rustc_hash::FxHashMap;
-use synchronoise::CountdownEvent;
 
-use super::{network_address::NetworkAddress, Tachyon, TachyonConfig, int_buffer::LengthPrefixed, connection::Connection, TachyonSendResult};
+use super::{address_filter::AddressFilter, completion::Completion, gateway::Gateway, connection::Connection, connection_map::{ConnectionChanges, ConnectionMaps}, network_address::NetworkAddress, topic::TopicSendResult, Tachyon, TachyonConfig, int_buffer::LengthPrefixed, TachyonSendResult};
+use super::out_buffer::{OutBufferMessage, OutBufferWriter, OUT_BUFFER_V2_HEADER_SIZE, OUT_BUFFER_V2_MESSAGE_HEADER_SIZE, OUT_BUFFER_VERSION_1, OUT_BUFFER_VERSION_2, RECEIVE_TYPE_RELIABLE, RECEIVE_TYPE_UNRELIABLE};
 
 
 #[derive(Clone, Copy)]
@@ -33,47 +34,129 @@ pub struct OutBufferCounts {
     pub count: u32
 }
 
+// with the bytes feature published messages are slices of the buffers they were received into, see receive_bytes.rs
+#[cfg(feature = "bytes")]
+pub type MessageData = bytes::Bytes;
+#[cfg(not(feature = "bytes"))]
+pub type MessageData = Vec<u8>;
+
+// a received message along with where it came from, so consumers can reply without looking up connection maps
+pub struct PublishedMessage {
+    pub server_id: u16,
+    pub channel: u16,
+    pub address: NetworkAddress,
+    pub identity_id: u32,
+    pub data: MessageData,
+    pub session_id: u32,
+}
+
+// PublishedMessage for ffi, the data is copied into a caller buffer at offset
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct PublishedMessageInfo {
+    pub server_id: u16,
+    pub channel: u16,
+    pub address: NetworkAddress,
+    pub identity_id: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+// a server a receive worker has had longer than expected
+#[derive(Clone, Copy, Debug)]
+pub struct StuckReceive {
+    pub server_id: u16,
+    pub elapsed: Duration,
+}
+
+// what finish_receive_timeout got done.  When not complete, servers and messages are from the workers that finished
+// and stuck has the ones still running.  Their servers and messages come back with a later finish_receive.
+pub struct ReceiveProgress {
+    pub servers: u32,
+    pub messages: i32,
+    pub complete: bool,
+    pub stuck: Vec<StuckReceive>,
+}
+
 pub struct OutBuffer {
     pub data: Vec<u8>,
     pub bytes_written: u32,
     pub count: u32
 }
 
+// Receive and out buffer counts and how often a server went unreceived because none was free.  A server that misses
+// is received on the next call, a growing miss count means the pool needs more buffers, see Pool::grow_buffers.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct PoolBufferStats {
+    pub receive_buffers: u32,
+    pub out_buffers: u32,
+    pub receive_buffer_misses: u64,
+    pub out_buffer_misses: u64,
+}
+
+#[derive(Default)]
+pub struct BufferMisses {
+    pub receive: AtomicU64,
+    pub out: AtomicU64,
+}
+
 pub struct Pool {
     pub next_id: u16,
     pub max_servers: u8,
     pub receive_buffer_len: u32,
     pub servers: FxHashMap<u16, Tachyon>,
-    pub receive_queue: Arc<ArrayQueue<VecDeque<Vec<u8>>>>,
+    pub receive_queue: Arc<ArrayQueue<VecDeque<PublishedMessage>>>,
     pub receive_buffers: Arc<ArrayQueue<Vec<u8>>>,
     pub out_buffers: Arc<ArrayQueue<OutBuffer>>,
-    pub published: VecDeque<Vec<u8>>,
+    pub out_buffer_len: u32,
+    // bumped from the receive workers
+    pub buffer_misses: Arc<BufferMisses>,
+    // framing used by receive_blocking_out_buffer, OUT_BUFFER_VERSION_1 or OUT_BUFFER_VERSION_2
+    pub out_buffer_version: u8,
+    pub published: VecDeque<PublishedMessage>,
     pub servers_in_use: Arc<ArrayQueue<Tachyon>>,
-    pub counter: Option<Arc<CountdownEvent>>,
-    pub connections_by_identity: FxHashMap<u32, Connection>,
-    pub connections_by_address: FxHashMap<NetworkAddress, Connection>
-    
+    pub counter: Option<Arc<Completion>>,
+    // servers receive workers are on and since when
+    pub receiving: Arc<Mutex<FxHashMap<u16, Instant>>>,
+    // published by the servers, applied to connection_maps by build_connection_maps
+    pub connection_changes: ConnectionChanges,
+    connection_maps: Arc<ConnectionMaps>,
+    // receives run here when set, otherwise on the global rayon pool
+    pub thread_pool: Option<Arc<ThreadPool>>,
+    // shared by every server, changes apply to servers that are receiving too
+    pub address_filter: AddressFilter,
+    // servers share one port, see create_servers_on_port
+    pub shared_port: bool,
+    // routes received messages by identity instead of publishing them, see gateway.rs
+    pub gateway: Gateway
 }
 
 impl Pool {
+    // a receive and an out buffer per server
     pub fn create(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32) -> Self {
+        return Pool::create_with_buffers(max_servers, receive_buffer_len, out_buffer_len, max_servers as u32, max_servers as u32);
+    }
 
-        let receive_buffers: ArrayQueue<Vec<u8>> = ArrayQueue::new(max_servers as usize);
-        let out_buffers: ArrayQueue<OutBuffer> = ArrayQueue::new(max_servers as usize);
-        let queue: ArrayQueue<VecDeque<Vec<u8>>> = ArrayQueue::new(max_servers as usize);
+    // Receives run concurrently on as many servers as there are receive buffers (out buffers for
+    // receive_blocking_out_buffer), so fewer than max_servers trades concurrency for memory.  There's at least one of each.
+    pub fn create_with_buffers(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32, receive_buffer_count: u32, out_buffer_count: u32) -> Self {
+        let receive_buffer_count = receive_buffer_count.max(1);
+        let out_buffer_count = out_buffer_count.max(1);
+        let queue: ArrayQueue<VecDeque<PublishedMessage>> = ArrayQueue::new(receive_buffer_count as usize);
+        let receive_buffers: ArrayQueue<Vec<u8>> = ArrayQueue::new(receive_buffer_count as usize);
+        let out_buffers: ArrayQueue<OutBuffer> = ArrayQueue::new(out_buffer_count as usize);
 
-        for _ in 0..max_servers {
+        for _ in 0..receive_buffer_count {
             queue.push(VecDeque::new()).unwrap_or(());
             receive_buffers.push(vec![0; receive_buffer_len as usize]).unwrap_or(());
-
-            let buffer = OutBuffer {
-                data: vec![0; out_buffer_len as usize],
-                bytes_written: 0,
-                count: 0
-            };
-            out_buffers.push(buffer).unwrap_or(());
         }
-        
+        for _ in 0..out_buffer_count {
+            out_buffers.push(Pool::create_out_buffer(out_buffer_len)).unwrap_or(());
+        }
+
         let in_use: ArrayQueue<Tachyon> = ArrayQueue::new(max_servers as usize);
 
         let pool = Pool {
@@ -84,65 +167,235 @@ impl Pool {
             receive_queue: Arc::new(queue),
             receive_buffers: Arc::new(receive_buffers),
             out_buffers: Arc::new(out_buffers),
+            out_buffer_len,
+            buffer_misses: Arc::new(BufferMisses::default()),
+            out_buffer_version: OUT_BUFFER_VERSION_1,
             published: VecDeque::new(),
             servers_in_use: Arc::new(in_use),
             counter: None,
-            connections_by_identity: FxHashMap::default(),
-            connections_by_address: FxHashMap::default()
+            receiving: Arc::new(Mutex::new(FxHashMap::default())),
+            connection_changes: ConnectionChanges::default(),
+            connection_maps: Arc::new(ConnectionMaps::default()),
+            thread_pool: None,
+            address_filter: AddressFilter::default(),
+            shared_port: false,
+            gateway: Gateway::default()
         };
         return pool;
     }
 
-    pub fn create_server(&mut self, config: TachyonConfig, address: NetworkAddress, id: u16) -> bool {
+    fn create_out_buffer(out_buffer_len: u32) -> OutBuffer {
+        return OutBuffer {
+            data: vec![0; out_buffer_len as usize],
+            bytes_written: 0,
+            count: 0
+        };
+    }
 
-        if self.servers.len() > self.max_servers.into() {
+    // Adds buffers so there are at least receive_buffer_count and out_buffer_count, existing ones are kept along with
+    // anything in them.  False while a receive started with receive() hasn't been finished, its workers hold buffers.
+    pub fn grow_buffers(&mut self, receive_buffer_count: u32, out_buffer_count: u32) -> bool {
+        if self.counter.is_some() {
             return false;
         }
-        if self.servers.contains_key(&id) {
-            return false;
+
+        let receive_count = (receive_buffer_count as usize).max(self.receive_buffers.len());
+        if receive_count > self.receive_buffers.len() {
+            let queue: ArrayQueue<VecDeque<PublishedMessage>> = ArrayQueue::new(receive_count);
+            let receive_buffers: ArrayQueue<Vec<u8>> = ArrayQueue::new(receive_count);
+            while let Some(receive_queue) = self.receive_queue.pop() {
+                queue.push(receive_queue).unwrap_or(());
+            }
+            while let Some(receive_buffer) = self.receive_buffers.pop() {
+                receive_buffers.push(receive_buffer).unwrap_or(());
+            }
+            while !queue.is_full() {
+                queue.push(VecDeque::new()).unwrap_or(());
+            }
+            while !receive_buffers.is_full() {
+                receive_buffers.push(vec![0; self.receive_buffer_len as usize]).unwrap_or(());
+            }
+            self.receive_queue = Arc::new(queue);
+            self.receive_buffers = Arc::new(receive_buffers);
+        }
+
+        let out_count = (out_buffer_count as usize).max(self.out_buffers.len());
+        if out_count > self.out_buffers.len() {
+            let out_buffers: ArrayQueue<OutBuffer> = ArrayQueue::new(out_count);
+            while let Some(out_buffer) = self.out_buffers.pop() {
+                out_buffers.push(out_buffer).unwrap_or(());
+            }
+            while !out_buffers.is_full() {
+                out_buffers.push(Pool::create_out_buffer(self.out_buffer_len)).unwrap_or(());
+            }
+            self.out_buffers = Arc::new(out_buffers);
         }
+        return true;
+    }
+
+    pub fn buffer_stats(&self) -> PoolBufferStats {
+        return PoolBufferStats {
+            receive_buffers: self.receive_buffers.capacity() as u32,
+            out_buffers: self.out_buffers.capacity() as u32,
+            receive_buffer_misses: self.buffer_misses.receive.load(Ordering::Relaxed),
+            out_buffer_misses: self.buffer_misses.out.load(Ordering::Relaxed),
+        };
+    }
+
+    // receives run on thread_pool instead of the global rayon pool, so they don't compete with the application's own use of it
+    pub fn create_with_thread_pool(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32, thread_pool: Arc<ThreadPool>) -> Self {
+        let mut pool = Pool::create(max_servers, receive_buffer_len, out_buffer_len);
+        pool.thread_pool = Some(thread_pool);
+        return pool;
+    }
+
+    // create_with_thread_pool with a pool of its own, None if the threads couldn't be started
+    pub fn create_with_threads(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32, thread_count: usize) -> Option<Self> {
+        let builder = ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .thread_name(|index| format!("tachyon-pool-{}", index));
+        match builder.build() {
+            Ok(thread_pool) => return Some(Pool::create_with_thread_pool(max_servers, receive_buffer_len, out_buffer_len, Arc::new(thread_pool))),
+            Err(_) => return None,
+        }
+    }
+
+    fn scope<'scope, OP>(thread_pool: &Option<Arc<ThreadPool>>, op: OP)
+    where
+        OP: FnOnce(&Scope<'scope>) + Send,
+    {
+        match thread_pool {
+            Some(thread_pool) => thread_pool.scope(op),
+            None => rayon::scope(op),
+        }
+    }
 
+    pub fn create_server(&mut self, config: TachyonConfig, address: NetworkAddress, id: u16) -> bool {
+        if !self.can_add_server(id) {
+            return false;
+        }
         let mut tachyon = Tachyon::create(config);
-        match tachyon.bind(address) {
-            true => {
-                tachyon.id = id;
-                self.servers.insert(id, tachyon);
+        if !tachyon.bind(address) {
+            return false;
+        }
+        return self.add_server(tachyon, id);
+    }
 
-                return true;
+    // a server that's already bound, over a transport or a LoopbackNetwork rather than a socket
+    pub fn add_server(&mut self, mut tachyon: Tachyon, id: u16) -> bool {
+        if !self.can_add_server(id) {
+            return false;
+        }
+        tachyon.id = id;
+        tachyon.address_filter = self.address_filter.clone();
+        tachyon.connection_changes = Some(self.connection_changes.clone());
+        self.servers.insert(id, tachyon);
+        return true;
+    }
+
+    fn can_add_server(&self, id: u16) -> bool {
+        return self.servers.len() < self.max_servers.into() && !self.servers.contains_key(&id);
+    }
+
+    // Binds up to count servers on base_address from start_port up, skipping ports that fail to bind, until max_servers.
+    // Ids are the next ones not in use after the last id this handed out.
+    pub fn create_servers_in_range(&mut self, config: TachyonConfig, base_address: NetworkAddress, start_port: u32, count: u32) -> Vec<PoolServerRef> {
+        let mut created: Vec<PoolServerRef> = Vec::new();
+        let mut port = start_port;
+        while created.len() < count as usize && self.servers.len() < self.max_servers as usize && port <= u16::MAX as u32 {
+            let id = self.next_free_id();
+            let mut address = base_address;
+            address.port = port;
+            if self.create_server(config, address, id) {
+                self.next_id = id;
+                created.push(PoolServerRef { address, id });
             }
-            false => {
-                return false;
+            port += 1;
+        }
+        return created;
+    }
+
+    // Binds up to count servers all on address with TachyonConfig.reuse_port, Linux only.  The kernel picks the server
+    // for each client by its address, so the pool can't direct clients and PoolServerRef addresses are all the same.
+    // When the kernel moves a client, after servers are added or removed, build_connection_maps drops the stale
+    // connection from the server it left.  Identity servers need the identities set on every server.
+    pub fn create_servers_on_port(&mut self, mut config: TachyonConfig, address: NetworkAddress, count: u32) -> Vec<PoolServerRef> {
+        config.reuse_port = 1;
+        let mut created: Vec<PoolServerRef> = Vec::new();
+        while created.len() < count as usize && self.servers.len() < self.max_servers as usize {
+            let id = self.next_free_id();
+            if !self.create_server(config, address, id) {
+                break;
             }
+            self.next_id = id;
+            created.push(PoolServerRef { address, id });
         }
+        if created.len() > 0 {
+            self.shared_port = true;
+        }
+        return created;
+    }
+
+    fn next_free_id(&self) -> u16 {
+        let mut id = self.next_id.wrapping_add(1).max(1);
+        while self.servers.contains_key(&id) {
+            id = id.wrapping_add(1).max(1);
+        }
+        return id;
     }
 
     pub fn set_identity(&mut self, server_id: u16, id: u32, session_id: u32, on_self: u32) {
         if let Some(tachyon) = self.get_server(server_id) {
             if on_self == 1 {
-                tachyon.identity.id = id;
-                tachyon.identity.session_id = session_id;
+                tachyon.set_client_identity(id, session_id);
             } else {
                 tachyon.set_identity(id, session_id);
             }
         }
     }
 
-    pub fn build_connection_maps(&mut self) {
-        self.connections_by_address.clear();
-        self.connections_by_identity.clear();
-
+    // update() on every server not out receiving, then build_connection_maps.  Once a frame like Tachyon::update.
+    pub fn update_servers(&mut self) {
         for server in self.servers.values_mut() {
-            for conn in server.connections.values() {
-                self.connections_by_address.insert(conn.address, *conn);
-                if conn.identity.id > 0 {
-                    self.connections_by_identity.insert(conn.identity.id, *conn);
+            server.update();
+        }
+        self.build_connection_maps();
+    }
+
+    // Applies the connection changes the servers published since the last call, including ones made while receiving on
+    // other threads.  Only copies the maps when a snapshot taken earlier is still held.
+    pub fn build_connection_maps(&mut self) -> usize {
+        if self.connection_changes.len() == 0 {
+            return 0;
+        }
+        let maps = Arc::make_mut(&mut self.connection_maps);
+        let mut moved: Vec<Connection> = Vec::new();
+        let mut count = 0;
+        while let Some(change) = self.connection_changes.pop() {
+            if let Some(replaced) = maps.apply(change) {
+                moved.push(replaced);
+            }
+            count += 1;
+        }
+
+        // a server that's out receiving keeps its stale connection until it times out
+        if self.shared_port {
+            for conn in moved {
+                if let Some(server) = self.servers.get_mut(&conn.tachyon_id) {
+                    server.release_moved_connection(conn.address);
                 }
             }
         }
+        return count;
+    }
+
+    // read only view for routing sends, as of the last build_connection_maps
+    pub fn connection_snapshot(&self) -> Arc<ConnectionMaps> {
+        return self.connection_maps.clone();
     }
 
     pub fn get_server_having_connection(&self, address: NetworkAddress) -> u16 {
-        if let Some(conn) = self.connections_by_address.get(&address) {
+        if let Some(conn) = self.connection_maps.get_by_address(address) {
             return conn.tachyon_id;
         } else {
             return 0;
@@ -150,7 +403,7 @@ impl Pool {
     }
 
     pub fn get_server_having_identity(&self, identity_id: u32) -> u16 {
-        if let Some(conn) = self.connections_by_identity.get(&identity_id) {
+        if let Some(conn) = self.connection_maps.get_by_identity(identity_id) {
             return conn.tachyon_id;
         } else {
             return 0;
@@ -162,7 +415,7 @@ impl Pool {
         let mut low = 10000;
         for (_id,server) in &self.servers {
             let conn_count = server.connections.len();
-            if conn_count < low && server.socket.socket.is_some() {
+            if conn_count < low && server.socket.is_open() {
                low = conn_count;
                best = Some(PoolServerRef {address: server.socket.address, id: server.id});
             }
@@ -175,7 +428,20 @@ impl Pool {
         return self.servers.get_mut(&id);
     }
 
-    pub fn send_to_target(&mut self,channel_id: u8, target: SendTarget, data: &mut [u8], length: i32) -> TachyonSendResult {
+    // publish_to_topic on every server, subscribers are per server
+    pub fn publish_to_topic(&mut self, channel_id: u16, topic: u32, data: &[u8], length: usize) -> TopicSendResult {
+        let mut result = TopicSendResult::default();
+        for server in self.servers.values_mut() {
+            result.add_from(&server.publish_to_topic(channel_id, topic, data, length));
+        }
+        return result;
+    }
+
+    pub fn topic_subscriber_count(&self, topic: u32) -> usize {
+        return self.servers.values().map(|server| server.topics.subscriber_count(topic)).sum();
+    }
+
+    pub fn send_to_target(&mut self,channel_id: u16, target: SendTarget, data: &[u8], length: i32) -> TachyonSendResult {
         if target.identity_id > 0 {
             return self.send_to_identity(channel_id,target.identity_id, data, length);
         } else {
@@ -183,8 +449,19 @@ impl Pool {
         }
     }
 
-    fn send_to_identity(&mut self, channel_id: u8, id: u32, data: &mut [u8], length: i32) -> TachyonSendResult {
-        if let Some(conn) = self.connections_by_identity.get(&id) {
+    // the server connected to the target and the target's address there
+    pub(crate) fn target_server(&mut self, target: SendTarget) -> Option<(NetworkAddress, &mut Tachyon)> {
+        let conn = if target.identity_id > 0 {
+            self.connection_maps.get_by_identity(target.identity_id)?
+        } else {
+            self.connection_maps.get_by_address(target.address)?
+        };
+        let server = self.servers.get_mut(&conn.tachyon_id)?;
+        return Some((conn.address, server));
+    }
+
+    fn send_to_identity(&mut self, channel_id: u16, id: u32, data: &[u8], length: i32) -> TachyonSendResult {
+        if let Some(conn) = self.connection_maps.get_by_identity(id) {
             if let Some(server) = self.servers.get_mut(&conn.tachyon_id) {
                 if channel_id == 0 {
                     return server.send_unreliable(conn.address, data, length as usize);
@@ -196,8 +473,8 @@ impl Pool {
         return TachyonSendResult::default();
     }
 
-    fn send_to_address(&mut self,channel_id: u8, address: NetworkAddress, data: &mut [u8], length: i32) -> TachyonSendResult {
-        if let Some(conn) = self.connections_by_address.get(&address) {
+    fn send_to_address(&mut self,channel_id: u16, address: NetworkAddress, data: &[u8], length: i32) -> TachyonSendResult {
+        if let Some(conn) = self.connection_maps.get_by_address(address) {
             if let Some(sender) = self.servers.get_mut(&conn.tachyon_id) {
                 if channel_id == 0 {
                     return sender.send_unreliable(address, data, length as usize);
@@ -209,16 +486,87 @@ impl Pool {
         return TachyonSendResult::default();
     }
 
-    pub fn take_published(&mut self) -> Option<Vec<u8>> {
+    pub fn take_published(&mut self) -> Option<PublishedMessage> {
         return self.published.pop_front();
     }
 
+    // up to max published messages in the order they were received
+    pub fn drain_published(&mut self, max: usize) -> Vec<PublishedMessage> {
+        let count = std::cmp::min(max, self.published.len());
+        return self.published.drain(..count).collect();
+    }
+
+    // length of the next published message
+    pub fn peek_published_len(&self) -> Option<usize> {
+        return self.published.front().map(|message| message.data.len());
+    }
+
+    // Takes published messages into infos, copying their data back to back into data.  Stops when either is full or the
+    // next message doesn't fit, it stays queued.  Returns how many were taken.
+    pub fn take_published_batch(&mut self, infos: &mut [PublishedMessageInfo], data: &mut [u8]) -> usize {
+        let mut count = 0;
+        let mut offset = 0;
+        while count < infos.len() {
+            let message = match self.published.front() {
+                Some(message) => message,
+                None => break,
+            };
+            let length = message.data.len();
+            if offset + length > data.len() {
+                break;
+            }
+            data[offset..offset + length].copy_from_slice(&message.data[..]);
+            infos[count] = PublishedMessageInfo {
+                server_id: message.server_id,
+                channel: message.channel,
+                address: message.address,
+                identity_id: message.identity_id,
+                offset: offset as u32,
+                length: length as u32,
+            };
+            self.published.pop_front();
+            offset += length;
+            count += 1;
+        }
+        return count;
+    }
+
+    // Writes published messages into dst in the v2 out buffer format, see out_buffer.rs, as many as fit.  The rest
+    // stay queued, so dst should have room for at least receive_buffer_len plus a message header.
+    pub fn take_published_into(&mut self, dst: &mut [u8]) -> OutBufferCounts {
+        let mut result = OutBufferCounts::default();
+        if dst.len() < OUT_BUFFER_V2_HEADER_SIZE {
+            return result;
+        }
+        let mut writer = OutBufferWriter::create(dst);
+        while let Some(published) = self.published.front() {
+            let receive_type = if published.channel == 0 { RECEIVE_TYPE_UNRELIABLE } else { RECEIVE_TYPE_RELIABLE };
+            let message = OutBufferMessage {
+                channel: published.channel,
+                address: published.address,
+                identity_id: published.identity_id,
+                receive_type,
+                server_id: published.server_id,
+                data: &published.data[..],
+            };
+            if !writer.write(&message, dst) {
+                break;
+            }
+            self.published.pop_front();
+            result.count += 1;
+        }
+        result.bytes_written = writer.writer.index as u32;
+        return result;
+    }
+
     fn move_received_to_published(&mut self) -> i32 {
         let mut count = 0;
         for _ in 0..self.receive_queue.len() {
             if let Some(mut receive_queue) = self.receive_queue.pop() {
                 for value in receive_queue.drain(..) {
-                    self.published.push_back(value);
+                    if self.route_received(&value) {
+                        self.published.push_back(value);
+                    }
                     count += 1;
                 }
                 self.receive_queue.push(receive_queue).unwrap_or_default();
@@ -227,14 +575,40 @@ impl Pool {
         return count;
     }
 
-    fn receive_server(server: &mut Tachyon, receive_queue: &mut VecDeque<Vec<u8>>, receive_buffer: &mut Vec<u8>) {
+    #[cfg(feature = "bytes")]
+    fn receive_server(server: &mut Tachyon, receive_queue: &mut VecDeque<PublishedMessage>, _receive_buffer: &mut Vec<u8>) {
+        for _ in 0..100000 {
+            let (res, data) = server.receive_bytes();
+            if res.length == 0 || res.error > 0 {
+                break;
+            }
+            let message = PublishedMessage {
+                server_id: server.id,
+                channel: res.channel,
+                address: res.address,
+                identity_id: res.identity_id,
+                data,
+                session_id: res.session_id,
+            };
+            receive_queue.push_back(message);
+        }
+    }
+
+    #[cfg(not(feature = "bytes"))]
+    fn receive_server(server: &mut Tachyon, receive_queue: &mut VecDeque<PublishedMessage>, receive_buffer: &mut Vec<u8>) {
         for _ in 0..100000 {
             let res = server.receive_loop(receive_buffer);
             if res.length == 0 || res.error > 0 {
                 break;
             } else {
-                let mut message: Vec<u8> = vec![0; res.length as usize];
-                message.copy_from_slice(&receive_buffer[0..res.length as usize]);
+                let message = PublishedMessage {
+                    server_id: server.id,
+                    channel: res.channel,
+                    address: res.address,
+                    identity_id: res.identity_id,
+                    data: receive_buffer[0..res.length as usize].to_vec(),
+                    session_id: res.session_id,
+                };
                 receive_queue.push_back(message);
 
             }
@@ -248,7 +622,7 @@ impl Pool {
             return false;
         }
 
-        let counter = Arc::new(CountdownEvent::new(server_count));
+        let counter = Arc::new(Completion::create(server_count));
 
         let in_use = self.servers_in_use.clone();
         for s in self.servers.drain() {
@@ -261,83 +635,212 @@ impl Pool {
             let receive_queue_clone = self.receive_queue.clone();
             let receive_buffers_clone = self.receive_buffers.clone();
             let signal = counter.clone();
+            let receiving = self.receiving.clone();
+            let buffer_misses = self.buffer_misses.clone();
 
-            rayon::spawn(move || {
+            let job = move || {
                 match in_use.pop() {
                     Some(mut server) => {
-                        if let Some(mut receive_queue) = receive_queue_clone.pop() {
-                            if let Some(mut receive_buffer) = receive_buffers_clone.pop() {
+                        receiving.lock().unwrap_or_else(|e| e.into_inner()).insert(server.id, Instant::now());
+                        match (receive_queue_clone.pop(), receive_buffers_clone.pop()) {
+                            (Some(mut receive_queue), Some(mut receive_buffer)) => {
                                 Pool::receive_server(&mut server, &mut receive_queue, &mut receive_buffer);
                                 receive_buffers_clone.push(receive_buffer).unwrap_or_default();
+                                receive_queue_clone.push(receive_queue).unwrap_or_default();
+                            }
+                            (receive_queue, receive_buffer) => {
+                                buffer_misses.receive.fetch_add(1, Ordering::Relaxed);
+                                Pool::return_receive_buffers(&receive_queue_clone, &receive_buffers_clone, receive_queue, receive_buffer);
                             }
-                            receive_queue_clone.push(receive_queue).unwrap_or_default();
                         }
+                        receiving.lock().unwrap_or_else(|e| e.into_inner()).remove(&server.id);
                         in_use.push(server).unwrap_or(());
                     }
                     None => {}
                 }
-                signal.decrement().unwrap();
-            });
+                signal.complete();
+            };
+            match &self.thread_pool {
+                Some(thread_pool) => thread_pool.spawn(job),
+                None => rayon::spawn(job),
+            }
         }
         self.counter = Some(counter);
         return true;
     }
 
     pub fn finish_receive(&mut self) -> (u32, i32) {
-        let mut server_count = 0;
-        let mut message_count = 0;
-
         match &self.counter {
             Some(counter) => {
                 counter.wait();
-                message_count += self.move_received_to_published();
-
-                for _ in 0..self.servers_in_use.len() {
-                    if let Some(server) = self.servers_in_use.pop() {
-                        self.servers.insert(server.id, server);
-                        server_count += 1;
-                    }
-                }
                 self.counter = None;
+                return self.collect_received();
+            }
+            None => {
+                return (0, 0);
+            }
+        }
+    }
+
+    // finish_receive if the workers are all done, None without waiting if they aren't
+    pub fn try_finish_receive(&mut self) -> Option<(u32, i32)> {
+        if let Some(counter) = &self.counter {
+            if !counter.try_wait() {
+                return None;
+            }
+        }
+        return Some(self.finish_receive());
+    }
+
+    // Waits at most timeout for the workers.  Whatever the finished ones received is published either way, so a wedged
+    // socket can't hold up the rest.
+    pub fn finish_receive_timeout(&mut self, timeout: Duration) -> ReceiveProgress {
+        let complete = match &self.counter {
+            Some(counter) => counter.wait_timeout(timeout),
+            None => true,
+        };
+        if complete {
+            self.counter = None;
+        }
+
+        let (servers, messages) = self.collect_received();
+        let stuck = if complete { Vec::new() } else { self.stuck_receives() };
+        return ReceiveProgress { servers, messages, complete, stuck };
+    }
+
+    pub fn stuck_receives(&self) -> Vec<StuckReceive> {
+        let now = Instant::now();
+        let receiving = self.receiving.lock().unwrap_or_else(|e| e.into_inner());
+        return receiving.iter().map(|(server_id, started)| StuckReceive { server_id: *server_id, elapsed: now.duration_since(*started) }).collect();
+    }
+
+    // publishes the queued messages and takes back the servers no worker has
+    fn collect_received(&mut self) -> (u32, i32) {
+        let mut server_count = 0;
+        let message_count = self.move_received_to_published();
+
+        for _ in 0..self.servers_in_use.len() {
+            if let Some(server) = self.servers_in_use.pop() {
+                self.servers.insert(server.id, server);
+                server_count += 1;
             }
-            None => {}
         }
         return (server_count, message_count);
     }
 
     // receive blocking, also heap allocates into the queue
     pub fn receive_blocking(&mut self) {
-        self.servers.par_iter_mut().for_each(|(_key, server)| {
-            let receive_queue_clone = self.receive_queue.clone();
-            let receive_buffers_clone = self.receive_buffers.clone();
-
-            if let Some(mut receive_queue) = receive_queue_clone.pop() {
-                if let Some(mut receive_buffer) = receive_buffers_clone.pop() {
-                    Pool::receive_server(server, &mut receive_queue, &mut receive_buffer);
-                    receive_buffers_clone.push(receive_buffer).unwrap_or_default();
-                }
-                receive_queue_clone.push(receive_queue).unwrap_or_default();
+        let receive_queue = &self.receive_queue;
+        let receive_buffers = &self.receive_buffers;
+        let buffer_misses = &self.buffer_misses;
+        let servers = &mut self.servers;
+        Pool::scope(&self.thread_pool, |scope| {
+            for server in servers.values_mut() {
+                scope.spawn(move |_| {
+                    match (receive_queue.pop(), receive_buffers.pop()) {
+                        (Some(mut queue), Some(mut receive_buffer)) => {
+                            Pool::receive_server(server, &mut queue, &mut receive_buffer);
+                            receive_buffers.push(receive_buffer).unwrap_or_default();
+                            receive_queue.push(queue).unwrap_or_default();
+                        }
+                        (queue, receive_buffer) => {
+                            buffer_misses.receive.fetch_add(1, Ordering::Relaxed);
+                            Pool::return_receive_buffers(receive_queue, receive_buffers, queue, receive_buffer);
+                        }
+                    }
+                });
             }
         });
         self.move_received_to_published();
     }
 
+    // puts back whichever half of a queue and buffer pair a worker got when it couldn't get both
+    fn return_receive_buffers(receive_queue: &ArrayQueue<VecDeque<PublishedMessage>>, receive_buffers: &ArrayQueue<Vec<u8>>,
+        queue: Option<VecDeque<PublishedMessage>>, receive_buffer: Option<Vec<u8>>) {
+        if let Some(queue) = queue {
+            receive_queue.push(queue).unwrap_or_default();
+        }
+        if let Some(receive_buffer) = receive_buffer {
+            receive_buffers.push(receive_buffer).unwrap_or_default();
+        }
+    }
 
-    // blocking receive with more complex api.  messages are copied to a single out buffer with length and ip address prefixed.
-    pub fn receive_blocking_out_buffer(&mut self) {
-        self.servers.par_iter_mut().for_each(|(_key, server)| {
-            let receive_buffers_clone = self.receive_buffers.clone();
-            let out_buffers_clone = self.out_buffers.clone();
+    // Blocking receive that hands each message to callback as it's received, from the worker thread receiving that
+    // server.  Nothing is queued or copied, the slice is only good for the call.  Returns the number of messages.
+    pub fn receive_dispatch<F>(&mut self, callback: F) -> i32
+    where
+        F: Fn(u16, u16, NetworkAddress, &[u8]) + Sync,
+    {
+        let count = AtomicI32::new(0);
+        let receive_buffers = &self.receive_buffers;
+        let buffer_misses = &self.buffer_misses;
+        let servers = &mut self.servers;
+        let callback = &callback;
+        let counter = &count;
+        Pool::scope(&self.thread_pool, |scope| {
+            for server in servers.values_mut() {
+                scope.spawn(move |_| {
+                    if let Some(mut receive_buffer) = receive_buffers.pop() {
+                        for _ in 0..100000 {
+                            let res = server.receive_loop(&mut receive_buffer);
+                            if res.length == 0 || res.error > 0 {
+                                break;
+                            }
+                            callback(server.id, res.channel, res.address, &receive_buffer[0..res.length as usize]);
+                            counter.fetch_add(1, Ordering::Relaxed);
+                        }
+                        receive_buffers.push(receive_buffer).unwrap_or_default();
+                    } else {
+                        buffer_misses.receive.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        return count.load(Ordering::Relaxed);
+    }
 
-            if let Some(mut out_buffer) = out_buffers_clone.pop() {
-                out_buffer.bytes_written = 0;
-                out_buffer.count = 0;
 
-                if let Some(mut receive_buffer) = receive_buffers_clone.pop() {
-                    Pool::receive_server_into_out_buffer(server, &mut out_buffer, &mut receive_buffer);
-                    receive_buffers_clone.push(receive_buffer).unwrap_or_default();
-                }
-                out_buffers_clone.push(out_buffer).unwrap_or_default();
+    pub fn set_out_buffer_version(&mut self, version: u8) -> bool {
+        if version != OUT_BUFFER_VERSION_1 && version != OUT_BUFFER_VERSION_2 {
+            return false;
+        }
+        // v2 only receives while a full receive buffer still fits, it would never receive anything otherwise
+        if version == OUT_BUFFER_VERSION_2 && (self.out_buffer_len as usize) < OUT_BUFFER_V2_HEADER_SIZE + OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + self.receive_buffer_len as usize {
+            return false;
+        }
+        self.out_buffer_version = version;
+        return true;
+    }
+
+    // blocking receive with more complex api.  messages are copied to a single out buffer with length and ip address prefixed.
+    pub fn receive_blocking_out_buffer(&mut self) {
+        let version = self.out_buffer_version;
+        let receive_buffers = &self.receive_buffers;
+        let out_buffers = &self.out_buffers;
+        let buffer_misses = &self.buffer_misses;
+        let servers = &mut self.servers;
+        Pool::scope(&self.thread_pool, |scope| {
+            for (key, server) in servers.iter_mut() {
+                scope.spawn(move |_| {
+                    if let Some(mut out_buffer) = out_buffers.pop() {
+                        out_buffer.bytes_written = 0;
+                        out_buffer.count = 0;
+
+                        if let Some(mut receive_buffer) = receive_buffers.pop() {
+                            if version == OUT_BUFFER_VERSION_2 {
+                                Pool::receive_server_into_out_buffer_v2(*key, server, &mut out_buffer, &mut receive_buffer);
+                            } else {
+                                Pool::receive_server_into_out_buffer(server, &mut out_buffer, &mut receive_buffer);
+                            }
+                            receive_buffers.push(receive_buffer).unwrap_or_default();
+                        } else {
+                            buffer_misses.receive.fetch_add(1, Ordering::Relaxed);
+                        }
+                        out_buffers.push(out_buffer).unwrap_or_default();
+                    } else {
+                        buffer_misses.out.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
             }
         });
     }
@@ -356,6 +859,33 @@ impl Pool {
         }
     }
 
+    fn receive_server_into_out_buffer_v2(server_id: u16, server: &mut Tachyon, out_buffer: &mut OutBuffer, receive_buffer: &mut Vec<u8>) {
+        let mut writer = OutBufferWriter::create(&mut out_buffer.data);
+        for _ in 0..100000 {
+            // stop while a full receive buffer still fits, anything left stays queued for the next call
+            if writer.writer.index + OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + receive_buffer.len() > out_buffer.data.len() {
+                break;
+            }
+            let res = server.receive_loop(receive_buffer);
+            if res.length == 0 || res.error > 0 {
+                break;
+            }
+
+            let receive_type = if res.channel == 0 { RECEIVE_TYPE_UNRELIABLE } else { RECEIVE_TYPE_RELIABLE };
+            let message = OutBufferMessage {
+                channel: res.channel,
+                address: res.address,
+                identity_id: res.identity_id,
+                receive_type,
+                server_id,
+                data: &receive_buffer[0..res.length as usize],
+            };
+            writer.write(&message, &mut out_buffer.data);
+            out_buffer.count += 1;
+        }
+        out_buffer.bytes_written = writer.writer.index as u32;
+    }
+
     pub fn get_next_out_buffer(&mut self, receive_buffer: &mut [u8]) -> OutBufferCounts {
         let mut result = OutBufferCounts::default();
 
@@ -388,16 +918,23 @@ impl Pool {
 mod tests {
     use serial_test::serial;
 
-    use crate::tachyon::{
+    use crate::{
+        address_filter::AddressRange,
+        connection::Identity,
         network_address::NetworkAddress,
         tachyon_test::{TachyonTestClient},
-        TachyonConfig, int_buffer::{IntBuffer, LengthPrefixed}
+        TachyonConfig, int_buffer::{IntBuffer, LengthPrefixed},
+        out_buffer::{OutBufferReader, OUT_BUFFER_V2_MESSAGE_HEADER_SIZE, OUT_BUFFER_VERSION_1, OUT_BUFFER_VERSION_2, RECEIVE_TYPE_RELIABLE, RECEIVE_TYPE_UNRELIABLE}
     };
     use std::{
-        time::Instant,
+        net::UdpSocket,
+        sync::{atomic::{AtomicUsize, Ordering}, Arc},
+        time::{Duration, Instant},
     };
 
-    use super::Pool;
+    use crate::completion::Completion;
+
+    use super::{Pool, PublishedMessageInfo};
 
     #[test]
     #[serial]
@@ -535,6 +1072,13 @@ mod tests {
         assert_eq!(count * 3, res.1);
         assert_eq!(count * 3, pool.published.len() as i32);
 
+        let message = pool.take_published().unwrap();
+        assert!(message.server_id >= 1 && message.server_id <= 3);
+        assert_eq!(1, message.channel);
+        assert_eq!(msg_len, message.data.len());
+        assert!(!message.address.is_default());
+        assert!(pool.get_server(message.server_id).unwrap().get_connection(message.address).is_some());
+
         // nothing to finish
         let res = pool.finish_receive();
         assert_eq!(0, res.0);
@@ -545,5 +1089,370 @@ mod tests {
 
     }
 
-    
+    #[test]
+    #[serial]
+    fn test_receive_dispatch() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+
+        let mut client1 = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        let mut client2 = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client1.connect();
+        client2.connect();
+        for _ in 0..10 {
+            client1.client_send_reliable(1, 64);
+            client2.client_send_unreliable(32);
+        }
+
+        let received = AtomicUsize::new(0);
+        let bytes = AtomicUsize::new(0);
+        let count = pool.receive_dispatch(|server_id, channel, address, data| {
+            if server_id == 1 {
+                assert_eq!(1, channel);
+                assert_eq!(64, data.len());
+            } else {
+                assert_eq!(0, channel);
+                assert_eq!(32, data.len());
+            }
+            assert!(!address.is_default());
+            received.fetch_add(1, Ordering::Relaxed);
+            bytes.fetch_add(data.len(), Ordering::Relaxed);
+        });
+        assert_eq!(20, count);
+        assert_eq!(20, received.load(Ordering::Relaxed));
+        assert_eq!(960, bytes.load(Ordering::Relaxed));
+        assert_eq!(0, pool.published.len());
+        assert_eq!(pool.max_servers as usize, pool.receive_buffers.len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_create_servers_in_range() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        let in_use = UdpSocket::bind("127.0.0.1:8002").unwrap();
+
+        let created = pool.create_servers_in_range(config, NetworkAddress::localhost(0), 8001, 3);
+        let ports: Vec<u32> = created.iter().map(|server| server.address.port).collect();
+        let ids: Vec<u16> = created.iter().map(|server| server.id).collect();
+        assert_eq!(vec![8001, 8003, 8004], ports);
+        assert_eq!(vec![1, 2, 3], ids);
+
+        // only room for one more
+        let created = pool.create_servers_in_range(config, NetworkAddress::localhost(0), 8005, 3);
+        assert_eq!(1, created.len());
+        assert_eq!(4, created[0].id);
+        assert_eq!(4, pool.servers.len());
+        drop(in_use);
+    }
+
+    #[test]
+    #[serial]
+    fn test_create_servers_on_port() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        let created = pool.create_servers_on_port(config, NetworkAddress::localhost(8001), 2);
+        assert_eq!(2, created.len());
+        assert!(pool.shared_port);
+        assert!(created[0].address == created[1].address);
+
+        let mut clients: Vec<TachyonTestClient> = (0..8).map(|_| TachyonTestClient::create(NetworkAddress::localhost(8001))).collect();
+        for client in clients.iter_mut() {
+            client.connect();
+            client.client_send_reliable(1, 32);
+        }
+        pool.receive_blocking();
+        assert_eq!(8, pool.published.len());
+        pool.build_connection_maps();
+        assert_eq!(8, pool.connection_snapshot().len());
+
+        // the kernel moved a client to the other server, the one it left lets go of it
+        let address = pool.published[0].address;
+        let from = pool.get_server_having_connection(address);
+        let to = if from == created[0].id { created[1].id } else { created[0].id };
+        pool.get_server(to).unwrap().create_connection(address, Identity::default());
+        pool.build_connection_maps();
+        assert_eq!(to, pool.get_server_having_connection(address));
+        assert!(pool.get_server(from).unwrap().get_connection(address).is_none());
+        pool.build_connection_maps();
+        assert_eq!(to, pool.get_server_having_connection(address));
+    }
+
+    #[test]
+    #[serial]
+    fn test_grow_buffers() {
+        let mut pool = Pool::create_with_buffers(4, 64 * 1024, 1024, 1, 1);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+        assert_eq!(1, pool.buffer_stats().receive_buffers);
+
+        let mut client1 = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        let mut client2 = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client1.connect();
+        client2.connect();
+        client1.client_send_reliable(1, 64);
+        client2.client_send_reliable(1, 64);
+
+        // nothing free, both servers miss and keep their messages
+        let held = pool.receive_buffers.pop().unwrap();
+        pool.receive_blocking();
+        assert_eq!(2, pool.buffer_stats().receive_buffer_misses);
+        assert_eq!(0, pool.published.len());
+        pool.receive_buffers.push(held).unwrap();
+
+        assert!(pool.grow_buffers(2, 3));
+        let stats = pool.buffer_stats();
+        assert_eq!((2, 3), (stats.receive_buffers, stats.out_buffers));
+        assert_eq!(2, pool.receive_queue.len());
+
+        pool.receive_blocking();
+        assert_eq!(2, pool.published.len());
+        assert_eq!(2, pool.buffer_stats().receive_buffer_misses);
+
+        // never shrinks
+        assert!(pool.grow_buffers(1, 1));
+        assert_eq!(2, pool.buffer_stats().receive_buffers);
+    }
+
+    #[test]
+    #[serial]
+    fn test_dedicated_threads() {
+        let mut pool = Pool::create_with_threads(4, 64 * 1024, 1024, 2).unwrap();
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client.connect();
+        client.client_send_reliable(1, 64);
+        let count = pool.receive_dispatch(|_server_id, _channel, _address, _data| {
+            assert!(std::thread::current().name().unwrap().starts_with("tachyon-pool-"));
+        });
+        assert_eq!(1, count);
+
+        client.client_send_reliable(1, 64);
+        assert!(pool.receive());
+        assert_eq!((1, 1), pool.finish_receive());
+    }
+
+    #[test]
+    #[serial]
+    fn test_finish_receive_timeout() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client.connect();
+        client.client_send_reliable(1, 64);
+
+        assert!(pool.receive());
+        let progress = pool.finish_receive_timeout(Duration::from_secs(5));
+        assert!(progress.complete);
+        assert_eq!(1, progress.servers);
+        assert_eq!(1, progress.messages);
+        assert!(pool.try_finish_receive().is_some());
+
+        // a worker that never finishes
+        pool.counter = Some(Arc::new(Completion::create(1)));
+        pool.receiving.lock().unwrap().insert(2, Instant::now());
+        assert!(pool.try_finish_receive().is_none());
+        let progress = pool.finish_receive_timeout(Duration::from_millis(10));
+        assert!(!progress.complete);
+        assert_eq!(1, progress.stuck.len());
+        assert_eq!(2, progress.stuck[0].server_id);
+        assert!(pool.counter.is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn test_publish_to_topic() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+
+        let mut client1 = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        let mut client2 = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client1.connect();
+        client2.connect();
+        client1.client.subscribe(3);
+        client2.client.subscribe(3);
+        pool.receive_blocking();
+        assert_eq!(2, pool.topic_subscriber_count(3));
+
+        let result = pool.publish_to_topic(1, 3, &[1; 8], 8);
+        assert_eq!(2, result.sent);
+        // acks are handled on the way
+        assert_eq!(8, client1.client_receive().length);
+        assert_eq!(8, client2.client_receive().length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_drain_published() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client.connect();
+        for _ in 0..5 {
+            client.client_send_reliable(1, 32);
+        }
+        client.client_send_unreliable(16);
+        pool.receive_blocking();
+        assert_eq!(6, pool.published.len());
+
+        let drained = pool.drain_published(2);
+        assert_eq!(2, drained.len());
+        assert_eq!(1, drained[0].channel);
+        assert_eq!(4, pool.published.len());
+
+        // room for two reliable messages, the rest stays queued
+        let mut buffer: Vec<u8> = vec![0; 1 + 2 * (OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + 32) + 10];
+        let counts = pool.take_published_into(&mut buffer);
+        assert_eq!(2, counts.count);
+        assert_eq!(2, pool.published.len());
+        let mut reader = OutBufferReader::create(&buffer[0..counts.bytes_written as usize]).unwrap();
+        let message = reader.next().unwrap();
+        assert_eq!(RECEIVE_TYPE_RELIABLE, message.receive_type);
+        assert_eq!(1, message.server_id);
+        assert_eq!(32, message.data.len());
+        assert!(reader.next().is_some());
+        assert!(reader.next().is_none());
+
+        let mut buffer: Vec<u8> = vec![0; 1024];
+        assert_eq!(2, pool.take_published_into(&mut buffer).count);
+        assert_eq!(0, pool.drain_published(10).len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_take_published_batch() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client.connect();
+        for _ in 0..3 {
+            client.client_send_reliable(1, 32);
+        }
+        client.client_send_unreliable(16);
+        pool.receive_blocking();
+
+        let mut infos = [PublishedMessageInfo::default(); 8];
+        let mut data: Vec<u8> = vec![0; 70];
+        assert_eq!(2, pool.take_published_batch(&mut infos, &mut data));
+        assert_eq!(32, infos[1].offset);
+        assert_eq!(32, infos[1].length);
+        assert_eq!(1, infos[1].server_id);
+        assert_eq!(1, infos[1].channel);
+        assert_eq!(Some(32), pool.peek_published_len());
+
+        let mut data: Vec<u8> = vec![0; 1024];
+        assert_eq!(2, pool.take_published_batch(&mut infos, &mut data));
+        assert_eq!(0, infos[1].channel);
+        assert_eq!(16, infos[1].length);
+        assert_eq!(None, pool.peek_published_len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_connection_maps() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+        pool.set_identity(2, 5, 50, 0);
+
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client.client.config.use_identity = 1;
+        client.client.identity = Identity { id: 5, session_id: 50, linked: 0 };
+        client.connect();
+        client.client.update();
+
+        // linked while the server was out receiving, it's in the maps once the change is applied
+        pool.receive_blocking();
+        assert_eq!(0, pool.connection_snapshot().len());
+        assert_eq!(1, pool.build_connection_maps());
+        assert_eq!(2, pool.get_server_having_identity(5));
+        let snapshot = pool.connection_snapshot();
+        let address = snapshot.get_by_identity(5).unwrap().address;
+        assert_eq!(2, pool.get_server_having_connection(address));
+
+        // held snapshots don't change
+        pool.set_identity(2, 5, 0, 0);
+        assert_eq!(1, pool.build_connection_maps());
+        assert_eq!(0, pool.get_server_having_identity(5));
+        assert_eq!(0, pool.connection_snapshot().len());
+        assert_eq!(1, snapshot.len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_address_filter() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+        pool.address_filter.deny(AddressRange::parse("127.0.0.0/8").unwrap());
+
+        let mut client1 = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        let mut client2 = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client1.connect();
+        client2.connect();
+        client1.client_send_unreliable(32);
+        client2.client_send_unreliable(32);
+        pool.receive_blocking();
+        assert_eq!(0, pool.published.len());
+        assert_eq!(2, pool.address_filter.stats().denied);
+
+        // servers created later share it too
+        pool.create_server(config, NetworkAddress::localhost(8003), 3);
+        assert!(!pool.get_server(3).unwrap().address_filter.is_allowed(NetworkAddress::localhost(9000)));
+    }
+
+    #[test]
+    #[serial]
+    fn test_blocking_receive_v2() {
+        let mut pool = Pool::create(4, 64 * 1024, 1024 * 1024);
+        assert!(!pool.set_out_buffer_version(3));
+        assert!(pool.set_out_buffer_version(OUT_BUFFER_VERSION_2));
+
+        // an out buffer that can't hold a full receive buffer is refused
+        let mut small = Pool::create(4, 64 * 1024, 1024);
+        assert!(!small.set_out_buffer_version(OUT_BUFFER_VERSION_2));
+        assert_eq!(OUT_BUFFER_VERSION_1, small.out_buffer_version);
+
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001),7);
+
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client.connect();
+        client.client_send_reliable(1, 64);
+        client.client_send_unreliable(32);
+
+        pool.receive_blocking_out_buffer();
+        let mut receive_buffer: Vec<u8> = vec![0;1024 * 1024];
+        let res = pool.get_next_out_buffer(&mut receive_buffer);
+        assert_eq!(2, res.count);
+        assert_eq!(1 + 2 * OUT_BUFFER_V2_MESSAGE_HEADER_SIZE + 96, res.bytes_written as usize);
+
+        let mut reader = OutBufferReader::create(&receive_buffer[0..res.bytes_written as usize]).unwrap();
+        let message = reader.next().unwrap();
+        assert_eq!(1, message.channel);
+        assert_eq!(RECEIVE_TYPE_RELIABLE, message.receive_type);
+        assert_eq!(7, message.server_id);
+        assert_eq!(64, message.data.len());
+        assert!(!message.address.is_default());
+
+        let message = reader.next().unwrap();
+        assert_eq!(0, message.channel);
+        assert_eq!(RECEIVE_TYPE_UNRELIABLE, message.receive_type);
+        assert_eq!(32, message.data.len());
+        assert!(reader.next().is_none());
+    }
 }
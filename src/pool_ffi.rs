@@ -1,6 +1,6 @@
 
-use crate::tachyon::*;
-use super::{pool::{Pool, PoolServerRef, OutBufferCounts, SendTarget}, ffi::copy_send_result};
+use crate::*;
+use super::{address_filter::{AddressFilterStats, AddressRange}, security::SecurityEventCallback, pool::{Pool, PoolBufferStats, PoolServerRef, OutBufferCounts, PublishedMessageInfo, SendTarget}, ffi::copy_send_result, topic::TopicSendResult};
 
 #[no_mangle]
 pub extern "C" fn pool_create(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32) -> *mut Pool {
@@ -9,6 +9,26 @@ pub extern "C" fn pool_create(max_servers: u8, receive_buffer_len: u32, out_buff
     return Box::into_raw(b);
 }
 
+// receive and out buffer counts independent of max_servers, see Pool::create_with_buffers
+#[no_mangle]
+pub extern "C" fn pool_create_with_buffers(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32, receive_buffer_count: u32, out_buffer_count: u32) -> *mut Pool {
+    let pool = Pool::create_with_buffers(max_servers, receive_buffer_len, out_buffer_len, receive_buffer_count, out_buffer_count);
+    let b = Box::new(pool);
+    return Box::into_raw(b);
+}
+
+// receives run on thread_count threads of the pool's own instead of the global rayon pool, null if they couldn't be started
+#[no_mangle]
+pub extern "C" fn pool_create_with_threads(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32, thread_count: u32) -> *mut Pool {
+    match Pool::create_with_threads(max_servers, receive_buffer_len, out_buffer_len, thread_count as usize) {
+        Some(pool) => {
+            let b = Box::new(pool);
+            return Box::into_raw(b);
+        }
+        None => return std::ptr::null_mut(),
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn pool_destroy(pool: *mut Pool) {
     if !pool.is_null() {
@@ -27,13 +47,39 @@ pub extern "C" fn pool_create_server(pool_ptr: *mut Pool, config_ptr: *const Tac
     }
 }
 
+// Fills refs with up to count servers bound from start_port up, returns how many were bound.  refs has to hold count.
+#[no_mangle]
+pub extern "C" fn pool_create_servers_in_range(pool_ptr: *mut Pool, config_ptr: *const TachyonConfig, naddress: *const NetworkAddress, start_port: u32, count: u32, refs_ptr: *mut PoolServerRef) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let config: TachyonConfig = unsafe { std::ptr::read(config_ptr as *const _) };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let created = pool.create_servers_in_range(config, address, start_port, count);
+    if created.len() > 0 {
+        let dst = unsafe { std::slice::from_raw_parts_mut(refs_ptr, created.len()) };
+        dst.copy_from_slice(&created);
+    }
+    return created.len() as i32;
+}
+// Fills refs with up to count servers sharing the address's port, returns how many were bound.  Linux only.
+#[no_mangle]
+pub extern "C" fn pool_create_servers_on_port(pool_ptr: *mut Pool, config_ptr: *const TachyonConfig, naddress: *const NetworkAddress, count: u32, refs_ptr: *mut PoolServerRef) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let config: TachyonConfig = unsafe { std::ptr::read(config_ptr as *const _) };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let created = pool.create_servers_on_port(config, address, count);
+    if created.len() > 0 {
+        let dst = unsafe { std::slice::from_raw_parts_mut(refs_ptr, created.len()) };
+        dst.copy_from_slice(&created);
+    }
+    return created.len() as i32;
+}
 
 #[no_mangle]
 pub extern "C" fn pool_configure_channel(pool_ptr: *mut Pool, server_id: u16, channel_id: u8, config_ptr: *const ChannelConfig) -> i32 {
     let pool = unsafe { &mut *pool_ptr };
     if let Some(tachyon) = pool.get_server(server_id) {
         let channel_config = unsafe { &*config_ptr };
-        let res = tachyon.configure_channel(channel_id, *channel_config);
+        let res = tachyon.configure_channel(channel_id as u16, *channel_config);
         if res {
             return 1;
         } else {
@@ -81,10 +127,7 @@ pub extern "C" fn pool_set_identity(pool_ptr: *mut Pool, server_id: u16, id: u32
 #[no_mangle]
 pub extern "C" fn pool_update_servers(pool_ptr: *mut Pool) {
     let pool = unsafe { &mut *pool_ptr };
-    for server in pool.servers.values_mut() {
-        server.update();
-    }
-    pool.build_connection_maps();
+    pool.update_servers();
 }
 
 #[no_mangle]
@@ -109,6 +152,50 @@ pub extern "C" fn pool_receive_blocking(pool_ptr: *mut Pool) {
     pool.receive_blocking_out_buffer();
 }
 
+// data is only valid for the call, which comes from a pool worker thread
+pub type PoolDispatchCallback = unsafe extern "C" fn(server_id: u16, channel: u16, address: NetworkAddress, data: *const u8, length: u32);
+
+// returns the number of messages passed to callback
+#[no_mangle]
+pub extern "C" fn pool_receive_dispatch(pool_ptr: *mut Pool, callback: PoolDispatchCallback) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    return pool.receive_dispatch(|server_id, channel, address, data| {
+        unsafe {
+            callback(server_id, channel, address, data.as_ptr(), data.len() as u32);
+        }
+    });
+}
+
+// 1 is the original framing, 2 adds identity id, receive type and server id, see out_buffer.rs.  -1 for 2 when the
+// out buffer is smaller than a receive buffer plus its framing
+#[no_mangle]
+pub extern "C" fn pool_set_out_buffer_version(pool_ptr: *mut Pool, version: u8) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if pool.set_out_buffer_version(version) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+// -1 while a pool_receive hasn't been finished
+#[no_mangle]
+pub extern "C" fn pool_grow_buffers(pool_ptr: *mut Pool, receive_buffer_count: u32, out_buffer_count: u32) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if pool.grow_buffers(receive_buffer_count, out_buffer_count) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_get_buffer_stats(pool_ptr: *mut Pool, ret: *mut PoolBufferStats) {
+    let pool = unsafe { &mut *pool_ptr };
+    let stats = pool.buffer_stats();
+    unsafe { *ret = stats };
+}
+
 #[no_mangle]
 pub extern "C" fn pool_get_next_out_buffer(pool_ptr: *mut Pool, receive_buffer_ptr: *mut u8, result: *mut OutBufferCounts) {
     let pool = unsafe { &mut *pool_ptr };
@@ -119,6 +206,49 @@ pub extern "C" fn pool_get_next_out_buffer(pool_ptr: *mut Pool, receive_buffer_p
     }
 }
 
+// The next published message, its data copied to the start of data.  1 if one was taken, 0 if none are queued.  -1 if
+// it's longer than length, it stays queued and info.length is set to what it needs.
+#[no_mangle]
+pub extern "C" fn pool_take_published(pool_ptr: *mut Pool, data_ptr: *mut u8, length: u32, info: *mut PublishedMessageInfo) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let data = unsafe { std::slice::from_raw_parts_mut(data_ptr, length as usize) };
+    let infos = unsafe { std::slice::from_raw_parts_mut(info, 1) };
+    if pool.take_published_batch(infos, data) == 1 {
+        return 1;
+    }
+    match pool.peek_published_len() {
+        Some(needed) => {
+            infos[0] = PublishedMessageInfo::default();
+            infos[0].length = needed as u32;
+            return -1;
+        }
+        None => {
+            return 0;
+        }
+    }
+}
+
+// Up to info_count published messages into infos, their data back to back in data at each info's offset.  Returns how
+// many, messages that don't fit stay queued.
+#[no_mangle]
+pub extern "C" fn pool_drain_published(pool_ptr: *mut Pool, infos_ptr: *mut PublishedMessageInfo, info_count: u32, data_ptr: *mut u8, length: u32) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let infos = unsafe { std::slice::from_raw_parts_mut(infos_ptr, info_count as usize) };
+    let data = unsafe { std::slice::from_raw_parts_mut(data_ptr, length as usize) };
+    return pool.take_published_batch(infos, data) as i32;
+}
+
+// published messages written into buffer in the v2 out buffer format, as many as fit in length
+#[no_mangle]
+pub extern "C" fn pool_take_published_into(pool_ptr: *mut Pool, buffer_ptr: *mut u8, length: u32, result: *mut OutBufferCounts) {
+    let pool = unsafe { &mut *pool_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(buffer_ptr, length as usize) };
+    let res = pool.take_published_into(slice);
+    unsafe {
+        (*result) = res;
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn pool_receive(pool_ptr: *mut Pool) -> i32 {
     let pool = unsafe { &mut *pool_ptr };
@@ -136,15 +266,68 @@ pub extern "C" fn pool_finish_receive(pool_ptr: *mut Pool) -> i32 {
     return result.1;
 }
 
+// finish_receive waiting at most timeout_millis, complete is set to 0 if workers were still running
+#[no_mangle]
+pub extern "C" fn pool_finish_receive_timeout(pool_ptr: *mut Pool, timeout_millis: u32, complete: *mut i32) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let progress = pool.finish_receive_timeout(std::time::Duration::from_millis(timeout_millis as u64));
+    if !complete.is_null() {
+        unsafe { *complete = progress.complete as i32 };
+    }
+    return progress.messages;
+}
+
 #[no_mangle]
 pub extern "C" fn pool_send_to(pool_ptr: *mut Pool, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
     let pool = unsafe { &mut *pool_ptr };
     
     let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
-    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
 
-    let result = pool.send_to_target(channel, target, slice, length);
+    let result = pool.send_to_target(channel as u16, target, slice, length);
     copy_send_result(result, ret);
 }
 
+#[no_mangle]
+pub extern "C" fn pool_publish_to_topic(pool_ptr: *mut Pool, channel: u8, topic: u32, data: *mut u8, length: i32, ret: *mut TopicSendResult) {
+    let pool = unsafe { &mut *pool_ptr };
+    let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    let result = pool.publish_to_topic(channel as u16, topic, slice, length as usize);
+    unsafe { *ret = result };
+}
 
+#[no_mangle]
+pub extern "C" fn pool_address_filter_add(pool_ptr: *mut Pool, list: u8, naddress: *const NetworkAddress, prefix: u8) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match AddressRange::create(address, prefix) {
+        Some(range) if pool.address_filter.add(list, range) => return 1,
+        _ => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_address_filter_remove(pool_ptr: *mut Pool, list: u8, naddress: *const NetworkAddress, prefix: u8) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    match AddressRange::create(address, prefix) {
+        Some(range) if pool.address_filter.remove(list, range) => return 1,
+        _ => return -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_get_address_filter_stats(pool_ptr: *mut Pool, ret: *mut AddressFilterStats) {
+    let pool = unsafe { &mut *pool_ptr };
+    let stats = pool.address_filter.stats();
+    unsafe { *ret = stats };
+}
+
+// called from the receive threads
+#[no_mangle]
+pub extern "C" fn pool_set_security_event_callback(pool_ptr: *mut Pool, callback: Option<SecurityEventCallback>) {
+    let pool = unsafe { &mut *pool_ptr };
+    for server in pool.servers.values_mut() {
+        server.set_security_event_callback(callback);
+    }
+}
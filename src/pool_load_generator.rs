@@ -0,0 +1,393 @@
+use std::time::{Duration, Instant};
+
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use super::{
+    int_buffer::IntBuffer,
+    latency::LatencyTracker,
+    loopback::LoopbackNetwork,
+    network_address::NetworkAddress,
+    pool::Pool,
+    Tachyon, TachyonConfig,
+};
+
+// client index u32, sequence u32, micros since the generator started u64
+pub const LOAD_HEADER_SIZE: usize = 16;
+const LATENCY_SAMPLE_LIMIT: usize = 100000;
+// how long run keeps receiving after the last send so reliable messages in flight can arrive
+const SETTLE_MILLIS: u64 = 200;
+// the same for run_steps, in steps without sends
+const SETTLE_STEPS: u32 = 100;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct LoadGeneratorConfig {
+    pub client_count: u32,
+    // per client
+    pub messages_per_second: u32,
+    // raised to LOAD_HEADER_SIZE if smaller
+    pub payload_size: u32,
+    // 0 sends unreliable
+    pub channel_id: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct LoadGeneratorStats {
+    pub sent: u64,
+    pub send_errors: u64,
+    pub delivered: u64,
+    // sent but not delivered by the end of the run
+    pub lost: u64,
+    pub bytes_delivered: u64,
+    // one way latency in micros, clients and server share a clock since they run in the same process
+    pub latency_p50: u64,
+    pub latency_p95: u64,
+    pub latency_p99: u64,
+}
+
+impl std::fmt::Display for LoadGeneratorStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "sent:{} send_errors:{} delivered:{} lost:{} kb_delivered:{} latency_p50:{}us latency_p95:{}us latency_p99:{}us\n",
+            self.sent,
+            self.send_errors,
+            self.delivered,
+            self.lost,
+            self.bytes_delivered / 1024,
+            self.latency_p50,
+            self.latency_p95,
+            self.latency_p99
+        )
+    }
+}
+
+struct LoadClient {
+    index: u32,
+    tachyon: Tachyon,
+    sequence: u32,
+    sent: u64,
+    send_errors: u64,
+    // fractional messages owed from previous ticks
+    owed: f64,
+    last_tick: Instant,
+    send_buffer: Vec<u8>,
+    receive_buffer: Vec<u8>,
+}
+
+impl LoadClient {
+    fn tick(&mut self, config: &LoadGeneratorConfig, start_time: Instant, now: Instant, sending: bool) {
+        if sending {
+            self.owed += now.duration_since(self.last_tick).as_secs_f64() * config.messages_per_second as f64;
+            while self.owed >= 1.0 {
+                self.owed -= 1.0;
+                self.send(config, start_time);
+            }
+        }
+        self.last_tick = now;
+        self.receive_and_update();
+    }
+
+    fn step(&mut self, config: &LoadGeneratorConfig, start_time: Instant, sends: u32) {
+        for _ in 0..sends {
+            self.send(config, start_time);
+        }
+        self.receive_and_update();
+    }
+
+    fn receive_and_update(&mut self) {
+        // clients only get nacks and server messages, draining keeps resends flowing
+        for _ in 0..100 {
+            let res = self.tachyon.receive_loop(&mut self.receive_buffer);
+            if res.length == 0 || res.error > 0 {
+                break;
+            }
+        }
+        self.tachyon.update();
+    }
+
+    fn send(&mut self, config: &LoadGeneratorConfig, start_time: Instant) {
+        self.sequence += 1;
+        let micros = start_time.elapsed().as_micros() as u64;
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u32(self.index, &mut self.send_buffer);
+        writer.write_u32(self.sequence, &mut self.send_buffer);
+        writer.write_u32(micros as u32, &mut self.send_buffer);
+        writer.write_u32((micros >> 32) as u32, &mut self.send_buffer);
+
+        let length = self.send_buffer.len();
+        let result;
+        if config.channel_id == 0 {
+            result = self.tachyon.send_unreliable(NetworkAddress::default(), &self.send_buffer, length);
+        } else {
+            result = self.tachyon.send_reliable(config.channel_id as u16, NetworkAddress::default(), &self.send_buffer, length);
+        }
+
+        if result.error == 0 {
+            self.sent += 1;
+        } else {
+            self.send_errors += 1;
+        }
+    }
+}
+
+// Spins up in process clients against a Pool or Tachyon server for capacity planning.  Clients are driven in parallel
+// on the rayon thread pool while the caller's thread does the server side receives.
+//
+// The run_x_steps variants send a fixed number of messages per client per step instead of a rate, one client after the
+// other.  Over a LoopbackNetwork that makes a run the same every time, for tests that want exact counts.
+pub struct LoadGenerator {
+    pub config: LoadGeneratorConfig,
+    clients: Vec<LoadClient>,
+    start_time: Instant,
+    delivered: u64,
+    bytes_delivered: u64,
+    latencies: Vec<u32>,
+}
+
+impl LoadGenerator {
+    pub fn create(config: LoadGeneratorConfig, tachyon_config: TachyonConfig, address: NetworkAddress) -> Self {
+        return LoadGenerator::create_with(config, tachyon_config, |tachyon| {
+            tachyon.connect(address);
+        });
+    }
+
+    // clients connected to the server at address on network
+    pub fn create_loopback(config: LoadGeneratorConfig, tachyon_config: TachyonConfig, network: &LoopbackNetwork, address: NetworkAddress) -> Self {
+        return LoadGenerator::create_with(config, tachyon_config, |tachyon| {
+            tachyon.connect_loopback(network, address);
+        });
+    }
+
+    fn create_with(config: LoadGeneratorConfig, tachyon_config: TachyonConfig, connect: impl Fn(&mut Tachyon)) -> Self {
+        let payload_size = std::cmp::max(config.payload_size as usize, LOAD_HEADER_SIZE);
+        let now = Instant::now();
+
+        let mut clients: Vec<LoadClient> = Vec::with_capacity(config.client_count as usize);
+        for index in 0..config.client_count {
+            let mut tachyon = Tachyon::create(tachyon_config);
+            connect(&mut tachyon);
+            let client = LoadClient {
+                index,
+                tachyon,
+                sequence: 0,
+                sent: 0,
+                send_errors: 0,
+                owed: 0.0,
+                last_tick: now,
+                send_buffer: vec![0; payload_size],
+                receive_buffer: vec![0; 4096],
+            };
+            clients.push(client);
+        }
+
+        let generator = LoadGenerator {
+            config,
+            clients,
+            start_time: now,
+            delivered: 0,
+            bytes_delivered: 0,
+            latencies: Vec::new(),
+        };
+        return generator;
+    }
+
+    // sends whatever each client owes for the time since the last tick
+    pub fn tick(&mut self, sending: bool) {
+        let config = self.config;
+        let start_time = self.start_time;
+        let now = Instant::now();
+        self.clients.par_iter_mut().for_each(|client| {
+            client.tick(&config, start_time, now, sending);
+        });
+    }
+
+    // every client sends sends messages, then takes in what the server sent it
+    pub fn step(&mut self, sends: u32) {
+        let config = self.config;
+        let start_time = self.start_time;
+        for client in &mut self.clients {
+            client.step(&config, start_time, sends);
+        }
+    }
+
+    // feed every message the server receives through here, anything that isn't ours is ignored
+    pub fn record_received(&mut self, data: &[u8]) -> bool {
+        if data.len() < LOAD_HEADER_SIZE {
+            return false;
+        }
+        let mut reader = IntBuffer { index: 0 };
+        let index = reader.read_u32(data);
+        let _sequence = reader.read_u32(data);
+        let micros = reader.read_u32(data) as u64 | (reader.read_u32(data) as u64) << 32;
+        if index >= self.config.client_count {
+            return false;
+        }
+
+        self.delivered += 1;
+        self.bytes_delivered += data.len() as u64;
+        if self.latencies.len() < LATENCY_SAMPLE_LIMIT {
+            let latency = (self.start_time.elapsed().as_micros() as u64).saturating_sub(micros);
+            self.latencies.push(std::cmp::min(latency, u32::MAX as u64) as u32);
+        }
+        return true;
+    }
+
+    fn sent(&self) -> u64 {
+        return self.clients.iter().map(|client| client.sent).sum();
+    }
+
+    // sorts the latency samples, call once a run is done rather than while it's going
+    pub fn get_stats(&self) -> LoadGeneratorStats {
+        let mut stats = LoadGeneratorStats::default();
+        for client in &self.clients {
+            stats.sent += client.sent;
+            stats.send_errors += client.send_errors;
+        }
+        stats.delivered = self.delivered;
+        stats.lost = stats.sent.saturating_sub(self.delivered);
+        stats.bytes_delivered = self.bytes_delivered;
+
+        if self.latencies.len() > 0 {
+            let mut sorted = self.latencies.clone();
+            sorted.sort_unstable();
+            stats.latency_p50 = LatencyTracker::percentile(&sorted, 50) as u64;
+            stats.latency_p95 = LatencyTracker::percentile(&sorted, 95) as u64;
+            stats.latency_p99 = LatencyTracker::percentile(&sorted, 99) as u64;
+        }
+        return stats;
+    }
+
+    pub fn run_pool(&mut self, pool: &mut Pool, duration: Duration) -> LoadGeneratorStats {
+        return self.run(duration, |generator| generator.receive_pool(pool));
+    }
+
+    pub fn run_pool_steps(&mut self, pool: &mut Pool, steps: u32, sends: u32) -> LoadGeneratorStats {
+        return self.run_steps(steps, sends, |generator| generator.receive_pool(pool));
+    }
+
+    pub fn run_server(&mut self, server: &mut Tachyon, duration: Duration) -> LoadGeneratorStats {
+        let mut receive_buffer: Vec<u8> = vec![0; 1024 * 64];
+        return self.run(duration, |generator| generator.receive_server(server, &mut receive_buffer));
+    }
+
+    pub fn run_server_steps(&mut self, server: &mut Tachyon, steps: u32, sends: u32) -> LoadGeneratorStats {
+        let mut receive_buffer: Vec<u8> = vec![0; 1024 * 64];
+        return self.run_steps(steps, sends, |generator| generator.receive_server(server, &mut receive_buffer));
+    }
+
+    fn receive_pool(&mut self, pool: &mut Pool) {
+        pool.receive_blocking();
+        while let Some(message) = pool.take_published() {
+            self.record_received(&message.data);
+        }
+        for server in pool.servers.values_mut() {
+            server.update();
+        }
+    }
+
+    fn receive_server(&mut self, server: &mut Tachyon, receive_buffer: &mut [u8]) {
+        for _ in 0..100000 {
+            let res = server.receive_loop(receive_buffer);
+            if res.length == 0 || res.error > 0 {
+                break;
+            }
+            self.record_received(&receive_buffer[0..res.length as usize]);
+        }
+        server.update();
+    }
+
+    fn run(&mut self, duration: Duration, mut receive: impl FnMut(&mut LoadGenerator)) -> LoadGeneratorStats {
+        let started = Instant::now();
+        while started.elapsed() < duration {
+            self.tick(true);
+            receive(self);
+        }
+
+        let settle_started = Instant::now();
+        while settle_started.elapsed() < Duration::from_millis(SETTLE_MILLIS) {
+            self.tick(false);
+            receive(self);
+            if self.delivered >= self.sent() {
+                break;
+            }
+        }
+        return self.get_stats();
+    }
+
+    fn run_steps(&mut self, steps: u32, sends: u32, mut receive: impl FnMut(&mut LoadGenerator)) -> LoadGeneratorStats {
+        for _ in 0..steps {
+            self.step(sends);
+            receive(self);
+        }
+
+        for _ in 0..SETTLE_STEPS {
+            if self.delivered >= self.sent() {
+                break;
+            }
+            self.step(0);
+            receive(self);
+        }
+        return self.get_stats();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::{loopback::LoopbackNetwork, network_address::NetworkAddress, pool::Pool, Tachyon, TachyonConfig};
+
+    use super::{LoadGenerator, LoadGeneratorConfig};
+
+    #[test]
+    fn test_load_pool() {
+        let network = LoopbackNetwork::default();
+        let address = NetworkAddress::localhost(8101);
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        assert!(server.bind_loopback(&network, address));
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024);
+        assert!(pool.add_server(server, 1));
+
+        let load_config = LoadGeneratorConfig {
+            client_count: 8,
+            messages_per_second: 0,
+            payload_size: 64,
+            channel_id: 1,
+        };
+        let mut generator = LoadGenerator::create_loopback(load_config, config, &network, address);
+        let stats = generator.run_pool_steps(&mut pool, 20, 5);
+        assert_eq!(8 * 20 * 5, stats.sent);
+        assert_eq!(0, stats.send_errors);
+        assert_eq!(stats.sent, stats.delivered);
+        assert_eq!(0, stats.lost);
+        assert_eq!(stats.delivered * 64, stats.bytes_delivered);
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_server() {
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        assert!(server.bind(NetworkAddress::localhost(8102)));
+
+        let load_config = LoadGeneratorConfig {
+            client_count: 2,
+            messages_per_second: 1000,
+            payload_size: 8,
+            channel_id: 0,
+        };
+        let mut generator = LoadGenerator::create(load_config, config, NetworkAddress::localhost(8102));
+        let stats = generator.run_server(&mut server, Duration::from_millis(50));
+        assert!(stats.delivered > 0);
+        assert!(stats.delivered <= stats.sent);
+        // payload raised to fit the load header
+        assert_eq!(stats.delivered * 16, stats.bytes_delivered);
+    }
+}
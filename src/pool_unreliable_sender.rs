@@ -1,25 +1,26 @@
+use std::sync::Arc;
+
 use rustc_hash::FxHashMap;
 
 use super::{
-    pool::{Pool, SendTarget}, TachyonSendResult, network_address::NetworkAddress, connection::Connection, unreliable_sender::UnreliableSender, ffi::copy_send_result
+    pool::{Pool, SendTarget}, TachyonSendResult, network_address::NetworkAddress, connection_map::ConnectionMaps, unreliable_sender::UnreliableSender, ffi::copy_send_result
 };
 
 pub struct PoolUnreliableSender {
-    pub identity_to_conn_map: FxHashMap<u32, Connection>,
-    pub address_to_conn_map: FxHashMap<NetworkAddress, Connection>,
+    // the pool's connection snapshot as of the last build
+    pub connections: Arc<ConnectionMaps>,
     pub senders: FxHashMap<u16, UnreliableSender>
 }
 
 impl PoolUnreliableSender {
     pub fn create() -> Self {
         PoolUnreliableSender {
-            identity_to_conn_map: FxHashMap::default(),
-            address_to_conn_map: FxHashMap::default(),
+            connections: Arc::new(ConnectionMaps::default()),
             senders: FxHashMap::default()
         }
     }
 
-    pub fn send_to_target(&mut self, target: SendTarget, data: &mut [u8], length: i32) -> TachyonSendResult {
+    pub fn send_to_target(&mut self, target: SendTarget, data: &[u8], length: i32) -> TachyonSendResult {
         if target.identity_id > 0 {
             return self.send_to_identity(target.identity_id, data, length);
         } else {
@@ -27,8 +28,8 @@ impl PoolUnreliableSender {
         }
     }
 
-    fn send_to_identity(&mut self, id: u32, data: &mut [u8], length: i32) -> TachyonSendResult {
-        if let Some(conn) = self.identity_to_conn_map.get(&id) {
+    fn send_to_identity(&mut self, id: u32, data: &[u8], length: i32) -> TachyonSendResult {
+        if let Some(conn) = self.connections.get_by_identity(id) {
             if let Some(sender) = self.senders.get_mut(&conn.tachyon_id) {
                 return sender.send(conn.address, data, length as usize);
             }
@@ -36,8 +37,8 @@ impl PoolUnreliableSender {
         return TachyonSendResult::default();
     }
 
-    fn send_to_address(&mut self, address: NetworkAddress, data: &mut [u8], length: i32) -> TachyonSendResult {
-        if let Some(conn) = self.address_to_conn_map.get(&address) {
+    fn send_to_address(&mut self, address: NetworkAddress, data: &[u8], length: i32) -> TachyonSendResult {
+        if let Some(conn) = self.connections.get_by_address(address) {
             if let Some(sender) = self.senders.get_mut(&conn.tachyon_id) {
                 return sender.send(address, data, length as usize);
             }
@@ -46,8 +47,6 @@ impl PoolUnreliableSender {
     }
 
     pub fn build(&mut self, pool: &mut Pool) {
-        self.address_to_conn_map.clear();
-        self.identity_to_conn_map.clear();
         self.senders.clear();
         
         for server in pool.servers.values() {
@@ -56,13 +55,9 @@ impl PoolUnreliableSender {
                     self.senders.insert(server.id, sender);
                 }
             }
-            for conn in server.connections.values() {
-                self.address_to_conn_map.insert(conn.address, *conn);
-                if conn.identity.id > 0 {
-                    self.identity_to_conn_map.insert(conn.identity.id, *conn);
-                }
-            }
         }
+        pool.build_connection_maps();
+        self.connections = pool.connection_snapshot();
     }
 }
 
@@ -94,7 +89,7 @@ pub extern "C" fn pool_unreliable_sender_build(pool_ptr: *mut Pool, sender_ptr:
 pub extern "C" fn pool_unreliable_sender_send(sender_ptr: *mut PoolUnreliableSender, target_ptr: *const SendTarget,  data_ptr: *mut u8, length: i32, ret: *mut TachyonSendResult) {
     let sender = unsafe { &mut *sender_ptr };
     let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
-    let data = unsafe { std::slice::from_raw_parts_mut(data_ptr, length as usize) };
+    let data = unsafe { std::slice::from_raw_parts(data_ptr, length as usize) };
 
     let result =  sender.send_to_target(target, data, length);
     copy_send_result(result, ret);
@@ -0,0 +1,185 @@
+use std::time::Instant;
+
+use super::{channel::ChannelStats, unreliable_channel::UnreliableChannelStats};
+
+// quality is recomputed from stat deltas over this window, short windows make loss and bandwidth too noisy to show
+pub const QUALITY_REFRESH_MILLIS: u128 = 1000;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct ConnectionQuality {
+    // resends plus sequences the receiver gave up on, as a percentage of all reliable messages sent and received in the last window
+    pub loss_percent: f32,
+    // smoothed rtt and rtt variation in millis, only available when a timestamped channel is configured
+    pub rtt: u32,
+    pub jitter: u32,
+    pub send_bytes_per_second: u64,
+    pub receive_bytes_per_second: u64,
+}
+
+impl std::fmt::Display for ConnectionQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "loss:{:.1}% rtt:{} jitter:{} kb_sent_per_second:{} kb_received_per_second:{}\n",
+            self.loss_percent,
+            self.rtt,
+            self.jitter,
+            self.send_bytes_per_second / 1024,
+            self.receive_bytes_per_second / 1024
+        )
+    }
+}
+
+pub struct QualityTracker {
+    pub quality: ConnectionQuality,
+    last_refresh: Instant,
+    last_stats: ChannelStats,
+    last_unreliable_stats: UnreliableChannelStats,
+    smoothed_rtt: f32,
+    rtt_variation: f32,
+    has_rtt: bool,
+}
+
+impl QualityTracker {
+    pub fn create(now: Instant) -> Self {
+        let tracker = QualityTracker {
+            quality: ConnectionQuality::default(),
+            last_refresh: now,
+            last_stats: ChannelStats::default(),
+            last_unreliable_stats: UnreliableChannelStats::default(),
+            smoothed_rtt: 0.0,
+            rtt_variation: 0.0,
+            has_rtt: false,
+        };
+        return tracker;
+    }
+
+    pub fn should_refresh(&self, now: Instant) -> bool {
+        return now.duration_since(self.last_refresh).as_millis() >= QUALITY_REFRESH_MILLIS;
+    }
+
+    // the counters were reset, the next refresh measures from 0.  The last quality and rtt smoothing are kept.
+    pub fn reset_counters(&mut self) {
+        self.last_stats = ChannelStats::default();
+        self.last_unreliable_stats = UnreliableChannelStats::default();
+    }
+
+    // stats are the connection's totals across all of its channels
+    pub fn refresh(&mut self, stats: &ChannelStats, unreliable_stats: &UnreliableChannelStats, now: Instant) {
+        let elapsed = now.duration_since(self.last_refresh).as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        // saturating, the counters go back to 0 when stats are reset
+        let lost = stats.resent.saturating_sub(self.last_stats.resent) + stats.skipped_sequences.saturating_sub(self.last_stats.skipped_sequences);
+        let total = stats.sent.saturating_sub(self.last_stats.sent) + stats.received.saturating_sub(self.last_stats.received);
+        if total > 0 {
+            self.quality.loss_percent = f32::min(lost as f32 / total as f32 * 100.0, 100.0);
+        } else {
+            self.quality.loss_percent = 0.0;
+        }
+
+        // same smoothing as tcp's srtt/rttvar
+        if stats.rtt > 0 {
+            let rtt = stats.rtt as f32;
+            if self.has_rtt {
+                self.rtt_variation = 0.75 * self.rtt_variation + 0.25 * (self.smoothed_rtt - rtt).abs();
+                self.smoothed_rtt = 0.875 * self.smoothed_rtt + 0.125 * rtt;
+            } else {
+                self.smoothed_rtt = rtt;
+                self.rtt_variation = rtt / 2.0;
+                self.has_rtt = true;
+            }
+            self.quality.rtt = self.smoothed_rtt.round() as u32;
+            self.quality.jitter = self.rtt_variation.round() as u32;
+        }
+
+        let bytes_sent = stats.bytes_sent.saturating_sub(self.last_stats.bytes_sent)
+            + unreliable_stats.bytes_sent.saturating_sub(self.last_unreliable_stats.bytes_sent);
+        let bytes_received = stats.bytes_received.saturating_sub(self.last_stats.bytes_received)
+            + unreliable_stats.bytes_received.saturating_sub(self.last_unreliable_stats.bytes_received);
+        self.quality.send_bytes_per_second = (bytes_sent as f64 / elapsed) as u64;
+        self.quality.receive_bytes_per_second = (bytes_received as f64 / elapsed) as u64;
+
+        self.last_stats = *stats;
+        self.last_unreliable_stats = *unreliable_stats;
+        self.last_refresh = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{channel::ChannelStats, unreliable_channel::UnreliableChannelStats};
+
+    use super::QualityTracker;
+
+    #[test]
+    fn test_refresh() {
+        let start = Instant::now();
+        let mut tracker = QualityTracker::create(start);
+        assert!(!tracker.should_refresh(start));
+
+        let mut stats = ChannelStats::default();
+        let mut unreliable_stats = UnreliableChannelStats::default();
+        stats.sent = 80;
+        stats.received = 20;
+        stats.resent = 4;
+        stats.skipped_sequences = 1;
+        stats.bytes_sent = 4000;
+        stats.bytes_received = 1000;
+        unreliable_stats.bytes_sent = 1000;
+        stats.rtt = 40;
+
+        let now = start + Duration::from_secs(2);
+        assert!(tracker.should_refresh(now));
+        tracker.refresh(&stats, &unreliable_stats, now);
+        assert_eq!(5.0, tracker.quality.loss_percent);
+        assert_eq!(2500, tracker.quality.send_bytes_per_second);
+        assert_eq!(500, tracker.quality.receive_bytes_per_second);
+        assert_eq!(40, tracker.quality.rtt);
+        assert_eq!(20, tracker.quality.jitter);
+
+        // nothing new, loss and bandwidth drop to zero, rtt keeps smoothing
+        stats.rtt = 80;
+        tracker.refresh(&stats, &unreliable_stats, now + Duration::from_secs(1));
+        assert_eq!(0.0, tracker.quality.loss_percent);
+        assert_eq!(0, tracker.quality.send_bytes_per_second);
+        assert_eq!(45, tracker.quality.rtt);
+        assert_eq!(25, tracker.quality.jitter);
+    }
+
+    #[test]
+    fn test_refresh_after_counters_drop() {
+        let start = Instant::now();
+        let mut tracker = QualityTracker::create(start);
+        let mut stats = ChannelStats::default();
+        let mut unreliable_stats = UnreliableChannelStats::default();
+        stats.sent = 100;
+        stats.resent = 10;
+        stats.bytes_sent = 5000;
+        unreliable_stats.bytes_received = 1000;
+        tracker.refresh(&stats, &unreliable_stats, start + Duration::from_secs(1));
+
+        // reset, then a little traffic that's less than before
+        let mut stats = ChannelStats::default();
+        stats.sent = 10;
+        stats.bytes_sent = 500;
+        tracker.refresh(&stats, &UnreliableChannelStats::default(), start + Duration::from_secs(2));
+        assert_eq!(0.0, tracker.quality.loss_percent);
+        assert_eq!(0, tracker.quality.send_bytes_per_second);
+        assert_eq!(0, tracker.quality.receive_bytes_per_second);
+
+        // and from there on as usual
+        stats.sent = 20;
+        stats.resent = 1;
+        stats.bytes_sent = 1500;
+        tracker.refresh(&stats, &UnreliableChannelStats::default(), start + Duration::from_secs(3));
+        assert_eq!(10.0, tracker.quality.loss_percent);
+        assert_eq!(1000, tracker.quality.send_bytes_per_second);
+    }
+}
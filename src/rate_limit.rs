@@ -0,0 +1,231 @@
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    connection_impl::{CONNECTION_MUTED_EVENT, CONNECTION_UNMUTED_EVENT},
+    network_address::NetworkAddress,
+    security::{SECURITY_MUTED, SECURITY_RATE_LIMITED},
+    Tachyon,
+};
+
+// addresses not heard from for this long are forgotten, muted ones are kept until the mute is over
+const RATE_IDLE_MILLIS: u128 = 10000;
+const DROP_WINDOW_MILLIS: u128 = 1000;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct RateLimitConfig {
+    // 0 for no limit.  Both allow bursts of up to one second's worth.
+    pub packets_per_second: u32,
+    pub bytes_per_second: u32,
+    // an address that has this many datagrams dropped within a second is muted for mute_millis, 0 never mutes
+    pub mute_after_drops: u32,
+    pub mute_millis: u32,
+}
+
+impl RateLimitConfig {
+    pub fn is_enabled(&self) -> bool {
+        return self.packets_per_second > 0 || self.bytes_per_second > 0;
+    }
+}
+
+struct AddressRate {
+    packets: f64,
+    bytes: f64,
+    last_received: Instant,
+    window_start: Instant,
+    window_drops: u32,
+    muted_until: Option<Instant>,
+}
+
+impl AddressRate {
+    fn create(config: &RateLimitConfig, now: Instant) -> Self {
+        let rate = AddressRate {
+            packets: config.packets_per_second as f64,
+            bytes: config.bytes_per_second as f64,
+            last_received: now,
+            window_start: now,
+            window_drops: 0,
+            muted_until: None,
+        };
+        return rate;
+    }
+}
+
+// Token buckets of every address we receive from, checked before anything else looks at a datagram.  Keyed by address
+// and not connection so reconnecting doesn't reset them.
+#[derive(Default)]
+pub struct RateLimits {
+    rates: FxHashMap<NetworkAddress, AddressRate>,
+}
+
+impl RateLimits {
+    pub fn len(&self) -> usize {
+        return self.rates.len();
+    }
+}
+
+impl Tachyon {
+    pub fn is_muted(&self, address: NetworkAddress) -> bool {
+        let now = self.now();
+        match self.rate_limits.rates.get(&address) {
+            Some(rate) => return rate.muted_until.map_or(false, |until| now < until),
+            None => return false,
+        }
+    }
+
+    // false if the address wasn't muted
+    pub fn unmute(&mut self, address: NetworkAddress) -> bool {
+        let was_muted = self.is_muted(address);
+        if let Some(rate) = self.rate_limits.rates.get_mut(&address) {
+            rate.muted_until = None;
+            rate.window_drops = 0;
+        }
+        if was_muted {
+            self.fire_connection_event(CONNECTION_UNMUTED_EVENT, address);
+        }
+        return was_muted;
+    }
+
+    // false if the datagram goes over the address's limit or the address is muted, it's dropped then
+    pub fn rate_limit_receive(&mut self, address: NetworkAddress, received_len: usize) -> bool {
+        let config = self.config.rate_limit;
+        if !config.is_enabled() {
+            return true;
+        }
+
+        let now = self.now();
+        let rate = self.rate_limits.rates.entry(address).or_insert_with(|| AddressRate::create(&config, now));
+        let mut unmuted = false;
+        if let Some(until) = rate.muted_until {
+            if now < until {
+                self.stats.rate_limited += 1;
+                return false;
+            }
+            rate.muted_until = None;
+            rate.window_drops = 0;
+            unmuted = true;
+        }
+
+        let elapsed = now.saturating_duration_since(rate.last_received).as_secs_f64();
+        rate.last_received = now;
+        if config.packets_per_second > 0 {
+            rate.packets = (rate.packets + elapsed * config.packets_per_second as f64).min(config.packets_per_second as f64);
+        }
+        if config.bytes_per_second > 0 {
+            rate.bytes = (rate.bytes + elapsed * config.bytes_per_second as f64).min(config.bytes_per_second as f64);
+        }
+
+        let allowed = (config.packets_per_second == 0 || rate.packets >= 1.0)
+            && (config.bytes_per_second == 0 || rate.bytes >= received_len as f64);
+        let mut muted = false;
+        let mut first_drop = false;
+        if allowed {
+            rate.packets -= 1.0;
+            rate.bytes -= received_len as f64;
+        } else {
+            if now.saturating_duration_since(rate.window_start).as_millis() >= DROP_WINDOW_MILLIS {
+                rate.window_start = now;
+                rate.window_drops = 0;
+            }
+            rate.window_drops += 1;
+            first_drop = rate.window_drops == 1;
+            if config.mute_after_drops > 0 && rate.window_drops >= config.mute_after_drops {
+                rate.muted_until = Some(now + Duration::from_millis(config.mute_millis as u64));
+                rate.window_drops = 0;
+                muted = true;
+            }
+            self.stats.rate_limited += 1;
+        }
+
+        if unmuted {
+            self.fire_connection_event(CONNECTION_UNMUTED_EVENT, address);
+        }
+        if first_drop {
+            self.fire_security_event(SECURITY_RATE_LIMITED, address, received_len as u32);
+        }
+        if muted {
+            self.stats.mutes += 1;
+            self.fire_connection_event(CONNECTION_MUTED_EVENT, address);
+            self.fire_security_event(SECURITY_MUTED, address, config.mute_millis);
+        }
+        return allowed;
+    }
+
+    // ends mutes that are over and forgets idle addresses, run from update
+    pub fn rate_limit_update(&mut self, now: Instant) {
+        if self.rate_limits.rates.is_empty() {
+            return;
+        }
+
+        let mut unmuted: Vec<NetworkAddress> = Vec::new();
+        for (address, rate) in self.rate_limits.rates.iter_mut() {
+            if let Some(until) = rate.muted_until {
+                if now >= until {
+                    rate.muted_until = None;
+                    rate.window_drops = 0;
+                    unmuted.push(*address);
+                }
+            }
+        }
+        self.rate_limits.rates.retain(|_, rate| {
+            rate.muted_until.is_some() || now.saturating_duration_since(rate.last_received).as_millis() < RATE_IDLE_MILLIS
+        });
+
+        for address in unmuted {
+            self.fire_connection_event(CONNECTION_UNMUTED_EVENT, address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::tachyon_test::TachyonTest;
+
+    #[test]
+    #[serial]
+    fn test_rate_limit() {
+        let mut test = TachyonTest::default();
+        test.server.config.rate_limit.packets_per_second = 5;
+        test.server.config.rate_limit.mute_after_drops = 3;
+        test.server.config.rate_limit.mute_millis = 1000;
+        test.server.step(Duration::from_millis(0));
+        test.connect();
+
+        for _ in 0..8 {
+            assert_eq!(0, test.client_send_unreliable(32).error);
+        }
+        let mut received = 0;
+        for _ in 0..8 {
+            if test.server_receive().length > 0 {
+                received += 1;
+            }
+        }
+        assert_eq!(5, received);
+        assert_eq!(3, test.server.stats.rate_limited);
+        assert_eq!(1, test.server.stats.mutes);
+        let remote = test.remote_client();
+        assert!(test.server.is_muted(remote));
+
+        // muted drops everything, even once the bucket refilled
+        test.server.step(Duration::from_millis(500));
+        test.client_send_unreliable(32);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(4, test.server.stats.rate_limited);
+
+        test.server.step(Duration::from_millis(600));
+        assert!(!test.server.is_muted(remote));
+        test.client_send_unreliable(32);
+        assert_eq!(32, test.server_receive().length);
+
+        // idle addresses are forgotten
+        test.server.step(Duration::from_millis(20000));
+        assert_eq!(0, test.server.rate_limits.len());
+    }
+}
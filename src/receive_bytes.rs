@@ -0,0 +1,137 @@
+use bytes::Bytes;
+
+use super::{receive_result::TachyonReceiveResult, receive_target::ReceiveTarget, Tachyon};
+
+// A receive target that keeps the buffer a message arrived in, so it can be handed out as Bytes without copying.
+// Reliable messages come in the channel's receive buffer, the pool allocates a replacement.  Unreliable datagrams that
+// landed in a receive ring slot come in the slot's buffer, the slot gets a new one.  Assembled fragments come in the Vec
+// they were assembled into.  The Bytes is a slice of that buffer past the header, and the whole buffer stays allocated
+// until the last Bytes pointing into it is dropped.  Messages changed by pipeline stages, and unreliable datagrams
+// received outside the ring slots (larger than a slot, or over a transport without vectored receives) are copied into
+// a Vec of their own.
+pub struct BytesTarget {
+    data: Vec<u8>,
+    start: usize,
+}
+
+impl BytesTarget {
+    pub fn default() -> Self {
+        let target = BytesTarget {
+            data: Vec::new(),
+            start: 0,
+        };
+        return target;
+    }
+
+    pub fn into_bytes(self, length: usize) -> Bytes {
+        if length == 0 {
+            return Bytes::new();
+        }
+        return Bytes::from(self.data).slice(self.start..self.start + length);
+    }
+}
+
+impl ReceiveTarget for BytesTarget {
+    fn reserve(&mut self, length: usize) -> Option<&mut [u8]> {
+        // past start, so rpc and relay read a kept message where it is
+        let end = self.start + length;
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        return Some(&mut self.data[self.start..end]);
+    }
+
+    fn keeps_buffers(&self) -> bool {
+        return true;
+    }
+
+    fn keep_buffer(&mut self, data: Vec<u8>, start: usize) {
+        self.data = data;
+        self.start = start;
+    }
+}
+
+impl Tachyon {
+    // receive_loop, with the message handed out as Bytes backed by the buffer it was received into, see BytesTarget.
+    // Empty if there was nothing to receive.
+    pub fn receive_bytes(&mut self) -> (TachyonReceiveResult, Bytes) {
+        let mut target = BytesTarget::default();
+        let mut result = self.receive_loop_internal(&mut target);
+        if result.length > 0 {
+            self.set_receive_connection(&mut result);
+        }
+        let data = target.into_bytes(result.length as usize);
+        return (result, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::receive_target::ReceiveTarget;
+    use crate::tachyon_test::TachyonTest;
+
+    use super::BytesTarget;
+
+    #[test]
+    fn test_keep_buffer() {
+        let mut data = vec![0; 64];
+        data[10..14].copy_from_slice(&[1, 2, 3, 4]);
+        let address = data.as_ptr() as usize;
+
+        let mut target = BytesTarget::default();
+        target.keep_buffer(data, 10);
+        assert_eq!(&[1, 2, 3, 4], target.reserve(4).unwrap());
+        let bytes = target.into_bytes(4);
+        // the same memory, not a copy
+        assert_eq!(address + 10, bytes.as_ptr() as usize);
+        assert_eq!(vec![1, 2, 3, 4], bytes.to_vec());
+
+        // copied in when there's nothing to keep
+        let mut target = BytesTarget::default();
+        target.reserve(3).unwrap().copy_from_slice(&[7, 8, 9]);
+        assert_eq!(vec![7, 8, 9], target.into_bytes(3).to_vec());
+    }
+
+    #[test]
+    #[serial]
+    fn test_receive_bytes() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        test.send_buffer[0] = 5;
+        test.client_send_reliable(1, 8);
+        test.send_buffer[0] = 6;
+        test.client_send_reliable(1, 8);
+
+        let (res, first) = test.server.receive_bytes();
+        assert_eq!(8, res.length);
+        let (_, second) = test.server.receive_bytes();
+        assert_eq!(8, first.len());
+        assert_eq!(5, first[0]);
+        assert_eq!(6, second[0]);
+        // two messages, two buffers
+        assert_ne!(first.as_ptr() as usize + 8, second.as_ptr() as usize);
+
+        test.send_buffer[0] = 7;
+        test.client_send_unreliable(32);
+        let (res, unreliable) = test.server.receive_bytes();
+        assert_eq!(32, res.length);
+        assert_eq!(7, unreliable[0]);
+        assert_eq!(32, unreliable.len());
+
+        test.send_buffer[0] = 8;
+        test.client_send_reliable(1, 3000);
+        let (res, fragmented) = test.server.receive_bytes();
+        assert_eq!(3000, res.length);
+        assert_eq!(8, fragmented[0]);
+        assert_eq!(3000, fragmented.len());
+
+        let (res, empty) = test.server.receive_bytes();
+        assert_eq!(0, res.length);
+        assert!(empty.is_empty());
+        // the first messages are still there after their buffers were replaced
+        assert_eq!(5, first[0]);
+    }
+}
@@ -1,19 +1,30 @@
-use super::network_address::NetworkAddress;
+use super::{connection::ConnectionHandle, network_address::NetworkAddress};
 
 pub const RECEIVE_ERROR_UNKNOWN: u32 = 1;
 pub const RECEIVE_ERROR_CHANNEL: u32 = 2;
+pub const RECEIVE_ERROR_HANDLE: u32 = 3;
+// the message didn't fit the receive buffer and was dropped
+pub const RECEIVE_ERROR_MESSAGE_SIZE: u32 = 4;
 
 pub enum ReceiveResult {
     Reliable {
         network_address: NetworkAddress,
-        channel_id: u8,
+        channel_id: u16,
     },
     Error,
     Empty,
     Retry,
-    ChannelError,
+    // handled nack/none/identity message, counted against the control budget
+    Control {
+        message_type: u8,
+    },
+    // no channel with the datagram's id for its address
+    ChannelError {
+        channel_id: u16,
+    },
     UnReliable {
         received_len: usize,
+        header_len: usize,
         network_address: NetworkAddress,
     },
 }
@@ -26,6 +37,16 @@ pub struct TachyonReceiveResult {
     pub address: NetworkAddress,
     pub length: u32,
     pub error: u32,
+    pub identity_id: u32,
+    pub session_id: u32,
+    pub connection: ConnectionHandle,
+    // ChannelConfig.tag of the channel the message came in on
+    pub channel_tag: u64,
+    // Connection.user_data of the sender
+    pub user_data: u64,
+    // the server that relayed the message, see relay.rs.  Default for messages that weren't relayed, address and
+    // identity are the original sender's for ones that were.
+    pub relayed_by: NetworkAddress,
 }
 
 impl TachyonReceiveResult {
@@ -35,6 +56,12 @@ impl TachyonReceiveResult {
             address: NetworkAddress::default(),
             length: 0,
             error: 0,
+            identity_id: 0,
+            session_id: 0,
+            connection: ConnectionHandle::default(),
+            channel_tag: 0,
+            user_data: 0,
+            relayed_by: NetworkAddress::default(),
         };
         return result;
     }
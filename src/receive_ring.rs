@@ -0,0 +1,159 @@
+use super::byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT};
+
+pub const RECEIVE_RING_SLOTS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PacketLocation {
+    Slot(usize),
+    Overflow,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PacketRef {
+    pub location: PacketLocation,
+    pub length: usize,
+}
+
+// Where datagrams are received into.  A ring of pool sized slots, each datagram lands in the next slot with anything
+// past the slot size going to the overflow buffer in the same vectored receive.  A datagram that fits its slot can be
+// handed over whole, the reliable path swaps the slot's buffer for an empty one from the channel's pool instead of
+// copying the datagram into it.  Larger datagrams are joined up in the overflow buffer, which is also where everything
+// is received when the socket can't do vectored receives (middleware, custom transports).
+pub struct ReceiveRing {
+    slots: Vec<ByteBuffer>,
+    head: usize,
+    overflow: Vec<u8>,
+    pub current: PacketRef,
+}
+
+impl ReceiveRing {
+    pub fn create(slot_count: usize, overflow_len: usize) -> Self {
+        let mut pool = ByteBufferPool::default();
+        let slots: Vec<ByteBuffer> = (0..slot_count.max(1)).map(|_| pool.get_buffer(BYTE_BUFFER_SIZE_DEFAULT)).collect();
+        let ring = ReceiveRing {
+            slots,
+            head: 0,
+            overflow: vec![0; overflow_len + BYTE_BUFFER_SIZE_DEFAULT],
+            current: PacketRef { location: PacketLocation::Overflow, length: 0 },
+        };
+        return ring;
+    }
+
+    // the slot and overflow buffer for the next vectored receive
+    pub fn receive_targets(&mut self) -> (&mut [u8], &mut [u8]) {
+        self.head = (self.head + 1) % self.slots.len();
+        let overflow_len = self.overflow.len() - BYTE_BUFFER_SIZE_DEFAULT;
+        return (&mut self.slots[self.head].get_mut()[0..BYTE_BUFFER_SIZE_DEFAULT], &mut self.overflow[0..overflow_len]);
+    }
+
+    pub fn set_received_vectored(&mut self, length: usize) {
+        if length <= BYTE_BUFFER_SIZE_DEFAULT {
+            self.slots[self.head].length = length;
+            self.current = PacketRef { location: PacketLocation::Slot(self.head), length };
+            return;
+        }
+
+        // move the tail up and put the slot's part in front of it
+        let tail_len = length - BYTE_BUFFER_SIZE_DEFAULT;
+        self.overflow.copy_within(0..tail_len, BYTE_BUFFER_SIZE_DEFAULT);
+        self.overflow[0..BYTE_BUFFER_SIZE_DEFAULT].copy_from_slice(&self.slots[self.head].get()[0..BYTE_BUFFER_SIZE_DEFAULT]);
+        self.current = PacketRef { location: PacketLocation::Overflow, length };
+    }
+
+    // for receives that don't go through receive_targets
+    pub fn overflow_mut(&mut self) -> &mut [u8] {
+        return &mut self.overflow;
+    }
+
+    pub fn set_received(&mut self, length: usize) {
+        self.current = PacketRef { location: PacketLocation::Overflow, length };
+    }
+
+    pub fn is_in_slot(&self) -> bool {
+        return matches!(self.current.location, PacketLocation::Slot(_));
+    }
+
+    pub fn packet(&self) -> &[u8] {
+        match self.current.location {
+            PacketLocation::Slot(index) => return self.slots[index].get(),
+            PacketLocation::Overflow => return &self.overflow,
+        }
+    }
+
+    // the current datagram cut to its length, what parsers should read from
+    pub fn received(&self) -> &[u8] {
+        return &self.packet()[0..self.current.length];
+    }
+
+    pub fn packet_mut(&mut self) -> &mut [u8] {
+        match self.current.location {
+            PacketLocation::Slot(index) => return self.slots[index].get_mut(),
+            PacketLocation::Overflow => return &mut self.overflow,
+        }
+    }
+
+    // drops the first count bytes of the current datagram, the rest moves up to the front of the buffer it's in
+    pub fn strip_front(&mut self, count: usize) {
+        let length = self.current.length;
+        self.packet_mut().copy_within(count..length, 0);
+        self.current.length = length - count;
+        if let PacketLocation::Slot(index) = self.current.location {
+            self.slots[index].length = self.current.length;
+        }
+    }
+
+    // Takes the current datagram's buffer, leaving replacement in its slot.  None if the datagram isn't in a slot or
+    // replacement is too small to be one, replacement is dropped then.
+    pub fn take_current(&mut self, replacement: ByteBuffer) -> Option<ByteBuffer> {
+        let index = match self.current.location {
+            PacketLocation::Slot(index) => index,
+            PacketLocation::Overflow => return None,
+        };
+        if replacement.get().len() < BYTE_BUFFER_SIZE_DEFAULT {
+            return None;
+        }
+        let packet = std::mem::replace(&mut self.slots[index], replacement);
+        self.current = PacketRef { location: PacketLocation::Overflow, length: 0 };
+        return Some(packet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::byte_buffer_pool::{ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT};
+
+    use super::{PacketLocation, ReceiveRing};
+
+    #[test]
+    fn test_ring() {
+        let mut ring = ReceiveRing::create(2, 4096);
+        let (slot, _) = ring.receive_targets();
+        slot[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        ring.set_received_vectored(4);
+        assert_eq!(PacketLocation::Slot(1), ring.current.location);
+        assert_eq!(&[1, 2, 3, 4], &ring.packet()[0..4]);
+        ring.strip_front(1);
+        assert_eq!(&[2, 3, 4], ring.received());
+
+        let mut pool = ByteBufferPool::default();
+        let packet = ring.take_current(pool.get_buffer(BYTE_BUFFER_SIZE_DEFAULT)).unwrap();
+        assert_eq!(3, packet.length);
+        assert_eq!(&[2, 3, 4], &packet.get()[0..3]);
+        assert!(ring.take_current(pool.get_buffer(BYTE_BUFFER_SIZE_DEFAULT)).is_none());
+
+        // spills over and gets joined up
+        let (slot, overflow) = ring.receive_targets();
+        slot.fill(5);
+        overflow[0..10].fill(6);
+        ring.set_received_vectored(BYTE_BUFFER_SIZE_DEFAULT + 10);
+        assert_eq!(PacketLocation::Overflow, ring.current.location);
+        assert_eq!(5, ring.packet()[BYTE_BUFFER_SIZE_DEFAULT - 1]);
+        assert_eq!(6, ring.packet()[BYTE_BUFFER_SIZE_DEFAULT + 9]);
+        assert!(ring.take_current(pool.get_buffer(BYTE_BUFFER_SIZE_DEFAULT)).is_none());
+
+        // wraps around
+        ring.receive_targets();
+        ring.set_received_vectored(1);
+        assert_eq!(PacketLocation::Slot(1), ring.current.location);
+    }
+}
@@ -0,0 +1,57 @@
+// Where a received message is copied to.  A plain slice refuses messages that don't fit instead of panicking,
+// GrowableBuffer resizes its Vec up to a max.  Messages that don't fit are dropped and the receive fails with
+// RECEIVE_ERROR_MESSAGE_SIZE.
+pub trait ReceiveTarget {
+    // the first length bytes, to copy a message of that length into.  None if it doesn't fit.
+    fn reserve(&mut self, length: usize) -> Option<&mut [u8]>;
+
+    // Targets that can hold on to the buffer a message arrived in get the buffer instead of a copy, where they can.  The
+    // message is data[start..start + length], and reserve(length) has to return it from then on.
+    fn keeps_buffers(&self) -> bool {
+        return false;
+    }
+
+    fn keep_buffer(&mut self, _data: Vec<u8>, _start: usize) {}
+}
+
+impl ReceiveTarget for [u8] {
+    fn reserve(&mut self, length: usize) -> Option<&mut [u8]> {
+        return self.get_mut(0..length);
+    }
+}
+
+pub struct GrowableBuffer<'a> {
+    pub buffer: &'a mut Vec<u8>,
+    pub max_length: usize,
+}
+
+impl<'a> ReceiveTarget for GrowableBuffer<'a> {
+    fn reserve(&mut self, length: usize) -> Option<&mut [u8]> {
+        if length > self.max_length {
+            return None;
+        }
+        if self.buffer.len() < length {
+            self.buffer.resize(length, 0);
+        }
+        return Some(&mut self.buffer[0..length]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrowableBuffer, ReceiveTarget};
+
+    #[test]
+    fn test_reserve() {
+        let mut fixed = [0u8; 4];
+        assert_eq!(4, fixed.reserve(4).unwrap().len());
+        assert!(fixed.reserve(5).is_none());
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut growable = GrowableBuffer { buffer: &mut buffer, max_length: 16 };
+        assert_eq!(10, growable.reserve(10).unwrap().len());
+        assert_eq!(4, growable.reserve(4).unwrap().len());
+        assert!(growable.reserve(17).is_none());
+        assert_eq!(10, buffer.len());
+    }
+}
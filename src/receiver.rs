@@ -1,61 +1,182 @@
 
-use std::collections::VecDeque;
+use alloc::{collections::VecDeque, vec::Vec};
 
-use super::{nack::Nack, sequence::*, sequence_buffer::SequenceBuffer, channel::RECEIVE_WINDOW_SIZE_DEFAULT, byte_buffer_pool::{ByteBuffer, ByteBufferPool}};
+use super::{nack::Nack, sequence::*, sequence_buffer::SequenceBuffer, byte_buffer_pool::{ByteBuffer, ByteBufferPool}, time::Instant};
 
-const RECEIVE_BUFFER_SIZE: u16 = 1024;
+pub const RECEIVE_WINDOW_SIZE_DEFAULT: u32 = 512;
 
+// ChannelConfig.publish_order values
+pub const PUBLISH_ORDER_ARRIVAL: u32 = 0;
+pub const PUBLISH_ORDER_OLDEST_FIRST: u32 = 1;
+pub const PUBLISH_ORDER_NEWEST_FIRST: u32 = 2;
 
-pub struct Receiver {
-    pub is_ordered: bool,
-    pub receive_window_size: u32,
-    pub last_sequence: u16,
-    pub current_sequence: u16,
-    pub buffered: SequenceBuffer<ByteBuffer>,
-    pub published: VecDeque<ByteBuffer>,
-    pub received: SequenceBuffer<bool>,
-    pub resend_list: Vec<u16>,
-    pub nack_list: Vec<Nack>,
-    pub nack_queue: VecDeque<Nack>,
-    pub skipped_sequences: u64,
-    pub buffer_pool: ByteBufferPool
+// The order an unordered receiver hands out the messages waiting in published.  Arrival is first received first out.
+// OldestFirst and NewestFirst go by sequence among whatever hasn't been taken yet, so a message that arrives late still
+// goes ahead of (or behind) newer ones waiting with it.  Ordered receivers are always by sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublishOrder {
+    Arrival,
+    OldestFirst,
+    NewestFirst,
 }
 
-impl Receiver {
-    pub fn create(is_ordered: bool, receive_window_size: u32) -> Self {
-        let mut buffered: SequenceBuffer<ByteBuffer> = SequenceBuffer {
-            values: Vec::new(),
-            partition_by: RECEIVE_BUFFER_SIZE,
-        };
-        for i in 0..RECEIVE_BUFFER_SIZE {
-            buffered.values.insert(i as usize, None);
+impl PublishOrder {
+    pub fn from_config(publish_order: u32) -> Option<Self> {
+        match publish_order {
+            PUBLISH_ORDER_ARRIVAL => return Some(PublishOrder::Arrival),
+            PUBLISH_ORDER_OLDEST_FIRST => return Some(PublishOrder::OldestFirst),
+            PUBLISH_ORDER_NEWEST_FIRST => return Some(PublishOrder::NewestFirst),
+            _ => return None,
         }
+    }
+}
 
-        let received: SequenceBuffer<bool> = SequenceBuffer {
-            values: vec![None; RECEIVE_BUFFER_SIZE as usize],
-            partition_by: RECEIVE_BUFFER_SIZE,
-        };
+// what a Receiver is created with, ChannelConfig::receive_window_config for a channel's
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiveWindowConfig {
+    pub is_ordered: bool,
+    pub window_size: u32,
+    // millis an ordered receiver will hold messages behind a missing sequence before skipping it, 0 waits forever
+    pub ordered_gap_timeout: u32,
+    pub publish_order: PublishOrder,
+}
+
+impl ReceiveWindowConfig {
+    pub fn ordered(window_size: u32) -> Self {
+        return ReceiveWindowConfig { is_ordered: true, window_size, ordered_gap_timeout: 0, publish_order: PublishOrder::Arrival };
+    }
+
+    pub fn unordered(window_size: u32) -> Self {
+        return ReceiveWindowConfig { is_ordered: false, window_size, ordered_gap_timeout: 0, publish_order: PublishOrder::Arrival };
+    }
+}
+
+pub struct Receiver {
+    config: ReceiveWindowConfig,
+    last_sequence: u16,
+    current_sequence: u16,
+    pub(crate) buffered: SequenceBuffer<ByteBuffer>,
+    pub(crate) published: VecDeque<ByteBuffer>,
+    // sequence of each entry in published
+    published_sequences: VecDeque<u16>,
+    pub(crate) received: SequenceBuffer<bool>,
+    resend_list: Vec<u16>,
+    nack_list: Vec<Nack>,
+    nack_queue: VecDeque<Nack>,
+    skipped_sequences: u64,
+    pub buffer_pool: ByteBufferPool,
+    ordered_gaps_skipped: u64,
+    gap_sequence: Option<u16>,
+    // set with gap_sequence
+    gap_started: Option<Instant>
+}
+
+impl Receiver {
+    pub fn create(config: ReceiveWindowConfig) -> Self {
+        // nacks only reach back receive_window_size, slots past twice that are never looked at
+        let buffer_size = Receiver::buffer_size_for(config.window_size);
+        let buffered: SequenceBuffer<ByteBuffer> = SequenceBuffer::create(buffer_size);
+        let received: SequenceBuffer<bool> = SequenceBuffer::create(buffer_size);
 
         let receiver = Receiver {
-            is_ordered,
-            receive_window_size,
+            config,
             last_sequence: 0,
             current_sequence: 0,
             buffered,
             published: VecDeque::new(),
+            published_sequences: VecDeque::new(),
             received,
             resend_list: Vec::new(),
             nack_list: Vec::new(),
             skipped_sequences: 0,
             nack_queue: VecDeque::new(),
-            buffer_pool: ByteBufferPool::default()
+            buffer_pool: ByteBufferPool::default(),
+            ordered_gaps_skipped: 0,
+            gap_sequence: None,
+            gap_started: None
         };
 
         return receiver;
     }
 
+    pub fn buffer_size_for(receive_window_size: u32) -> u16 {
+        let wanted = (receive_window_size.max(8) * 2).next_power_of_two();
+        return wanted.min(SEQUENCE_WINDOW_MAX) as u16;
+    }
+
     pub fn default(is_ordered: bool) -> Self {
-        return Receiver::create(is_ordered, RECEIVE_WINDOW_SIZE_DEFAULT);
+        let config = ReceiveWindowConfig {
+            is_ordered,
+            window_size: RECEIVE_WINDOW_SIZE_DEFAULT,
+            ordered_gap_timeout: 0,
+            publish_order: PublishOrder::Arrival,
+        };
+        return Receiver::create(config);
+    }
+
+    pub fn config(&self) -> ReceiveWindowConfig {
+        return self.config;
+    }
+
+    pub fn is_ordered(&self) -> bool {
+        return self.config.is_ordered;
+    }
+
+    pub fn set_ordered_gap_timeout(&mut self, ordered_gap_timeout: u32) {
+        self.config.ordered_gap_timeout = ordered_gap_timeout;
+    }
+
+    // the oldest sequence not yet received (ordered) or still inside the window (unordered)
+    pub fn current_sequence(&self) -> u16 {
+        return self.current_sequence;
+    }
+
+    // the newest sequence received
+    pub fn last_sequence(&self) -> u16 {
+        return self.last_sequence;
+    }
+
+    pub fn skipped_sequences(&self) -> u64 {
+        return self.skipped_sequences;
+    }
+
+    pub fn ordered_gaps_skipped(&self) -> u64 {
+        return self.ordered_gaps_skipped;
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.skipped_sequences = 0;
+        self.ordered_gaps_skipped = 0;
+    }
+
+    // what the last create_nacks came up with
+    pub fn nack_list(&self) -> &[Nack] {
+        return &self.nack_list;
+    }
+
+    // sequences between current and last we're missing, as of the last set_resend_list
+    pub fn resend_list(&self) -> &[u16] {
+        return &self.resend_list;
+    }
+
+    // The next queued nack to go out with a reliable message, each goes out at most redundancy times.  Queued nacks
+    // are cycled through, one per call.
+    pub fn next_redundant_nack(&mut self, redundancy: u32) -> Option<Nack> {
+        let mut nack = self.nack_queue.pop_front()?;
+        let mut result: Option<Nack> = None;
+        if nack.sent_count < redundancy {
+            nack.sent_count += 1;
+            result = Some(nack);
+        }
+        self.nack_queue.push_back(nack);
+        return result;
+    }
+
+    // checkpoint restore
+    #[cfg(feature = "std")]
+    pub(crate) fn restore_sequences(&mut self, current_sequence: u16, last_sequence: u16) {
+        self.current_sequence = current_sequence;
+        self.last_sequence = last_sequence;
     }
 
     pub fn calculate_current_in_window(current: u16, last: u16) -> u16 {
@@ -65,7 +186,7 @@ impl Receiver {
 
         let mut start: i32 = (last as i32 - RECEIVE_WINDOW_SIZE_DEFAULT as i32) as i32;
         if start < 0 {
-            start = std::u16::MAX as i32 + start;
+            start = u16::MAX as i32 + start;
         }
 
         if Sequence::is_greater_then(start as u16, current) {
@@ -81,7 +202,7 @@ impl Receiver {
 
         let mut start: i32 = (last as i32 - receive_window_size as i32) as i32;
         if start < 0 {
-            start = std::u16::MAX as i32 + start;
+            start = u16::MAX as i32 + start;
         }
 
         if Sequence::is_greater_then(start as u16, current) {
@@ -96,9 +217,33 @@ impl Receiver {
     }
     
     pub fn take_published(&mut self) -> Option<ByteBuffer> {
+        self.published_sequences.pop_front();
         return self.published.pop_front();
     }
 
+    // sequence of the message take_published returns next
+    pub fn peek_published_sequence(&self) -> Option<u16> {
+        return self.published_sequences.front().copied();
+    }
+
+    fn push_published(&mut self, sequence: u16, byte_buffer: ByteBuffer) {
+        let index = match self.config.publish_order {
+            PublishOrder::Arrival => None,
+            PublishOrder::OldestFirst => self.published_sequences.iter().position(|s| Sequence::is_greater_then(*s, sequence)),
+            PublishOrder::NewestFirst => self.published_sequences.iter().position(|s| Sequence::is_less_than(*s, sequence)),
+        };
+        let index = index.unwrap_or(self.published.len());
+        self.published.insert(index, byte_buffer);
+        self.published_sequences.insert(index, sequence);
+    }
+
+    // checkpoint restore, restored messages count as older than anything received after
+    #[cfg(feature = "std")]
+    pub(crate) fn restore_published(&mut self, byte_buffer: ByteBuffer) {
+        self.published.push_back(byte_buffer);
+        self.published_sequences.push_back(self.current_sequence);
+    }
+
     fn is_buffered(&self, sequence: u16) -> bool {
         return self.buffered.is_some(sequence);
     }
@@ -122,8 +267,32 @@ impl Receiver {
     // in big steps for example or we would leave a bunch of entries < current still marked as received.
 
     pub fn receive_packet(&mut self, sequence: u16, data: &[u8], length: usize) -> bool {
+        if !self.accept_sequence(sequence) {
+            return false;
+        }
+        self.set_buffered(sequence, data, length);
+        self.set_received(sequence);
+        self.publish();
+        return true;
+    }
+
+    // receive_packet for a datagram already in a byte buffer, which is kept as is instead of copied.  Rejected buffers
+    // go back to the pool.
+    pub fn receive_packet_buffer(&mut self, sequence: u16, byte_buffer: ByteBuffer) -> bool {
+        if !self.accept_sequence(sequence) {
+            self.buffer_pool.return_buffer(byte_buffer);
+            return false;
+        }
+        self.buffered.insert(sequence, byte_buffer);
+        self.set_received(sequence);
+        self.publish();
+        return true;
+    }
+
+    // moves the window along for sequence, false if it's outside the window or already received
+    fn accept_sequence(&mut self, sequence: u16) -> bool {
         // if the difference between current/last is greater then the window, increment current.
-        if Receiver::should_increment_current(self.current_sequence, self.last_sequence, self.receive_window_size) {
+        if Receiver::should_increment_current(self.current_sequence, self.last_sequence, self.config.window_size) {
             self.received.take(self.current_sequence);
             self.current_sequence = Sequence::next_sequence(self.current_sequence);
             self.skipped_sequences += 1;
@@ -145,19 +314,57 @@ impl Receiver {
         }
 
         // resends can be higher then current and already received.
-        if self.is_received(sequence) {
-            return false;
-        } else {
-            self.set_buffered(sequence, data, length);
-            self.set_received(sequence);
+        return !self.is_received(sequence);
+    }
+
+    // If the sequence after current has been missing for longer than ordered_gap_timeout, give up on it and mark it received
+    // so publish can move past it.  This keeps a single lost packet from stalling an ordered channel forever when the
+    // sender is gone and nacks can't recover it.  The first and last sequence skipped, if any were.
+    fn skip_expired_gap(&mut self, now: Instant) -> Option<(u16, u16)> {
+        let next = Sequence::next_sequence(self.current_sequence);
+        if self.current_sequence == self.last_sequence || self.is_received(next) {
+            self.gap_sequence = None;
+            return None;
         }
 
-        self.publish();
+        if self.gap_sequence != Some(next) {
+            self.gap_sequence = Some(next);
+            self.gap_started = Some(now);
+            return None;
+        }
 
-        return true;
+        let gap_started = self.gap_started.unwrap_or(now);
+        if now.duration_since(gap_started).as_millis() > self.config.ordered_gap_timeout as u128 {
+            // skip the whole run of missing sequences, not just the first one
+            let mut seq = next;
+            loop {
+                self.current_sequence = seq;
+                self.ordered_gaps_skipped += 1;
+                seq = Sequence::next_sequence(seq);
+                if self.current_sequence == self.last_sequence || self.is_received(seq) {
+                    break;
+                }
+            }
+            self.set_received(self.current_sequence);
+            self.gap_sequence = None;
+            return Some((next, self.current_sequence));
+        }
+        return None;
+    }
+
+    // publish plus gap expiry, run from update so gap timing follows the caller's clock.  The range of sequences skipped
+    // if a gap expired.
+    pub fn publish_at(&mut self, now: Instant) -> Option<(u16, u16)> {
+        let mut skipped = None;
+        if self.config.is_ordered && self.config.ordered_gap_timeout > 0 {
+            skipped = self.skip_expired_gap(now);
+        }
+        self.publish();
+        return skipped;
     }
 
     pub fn publish(&mut self) {
+
         // walk from current to last and move buffered into published
         // increment current sequence until we hit a missing sequence.
         // on missing, ordered channel breaks out it's done.
@@ -168,7 +375,7 @@ impl Receiver {
         let mut step_sequence = true;
         let mut seq = start;
 
-        for _ in 0..self.receive_window_size {
+        for _ in 0..self.config.window_size {
             if self.is_received(seq) {
                 if self.current_sequence == seq {
                     self.received.remove(seq);
@@ -180,13 +387,13 @@ impl Receiver {
                 if self.is_buffered(seq) {
                     match self.buffered.take(seq) {
                         Some(byte_buffer) => {
-                            self.published.push_back(byte_buffer);
+                            self.push_published(seq, byte_buffer);
                         }
                         None => {}
                     }
                 }
             } else {
-                if self.is_ordered {
+                if self.config.is_ordered {
                     break;
                 } else {
                     step_sequence = false;
@@ -211,7 +418,7 @@ impl Receiver {
 
         let mut seq = start;
 
-        for _ in 0..self.receive_window_size {
+        for _ in 0..self.config.window_size {
             if !self.is_received(seq) {
                 self.resend_list.push(seq);
             }
@@ -233,7 +440,7 @@ impl Receiver {
             return nacked_count;
         }
      
-        let count = self.receive_window_size / 32;
+        let count = self.config.window_size / 32;
 
         for _ in 0..count {
 
@@ -283,7 +490,9 @@ impl Receiver {
 #[cfg(test)]
 mod tests {
 
-    use crate::tachyon::{receiver::*};
+    use std::time::Duration;
+
+    use crate::{receiver::*};
 
     pub fn is_nacked(receiver: &Receiver, sequence: u16) -> bool {
         for nack in &receiver.nack_list {
@@ -364,6 +573,44 @@ mod tests {
         assert_eq!(2, channel.current_sequence);
     }
 
+    #[test]
+    fn test_ordered_gap_timeout() {
+        let mut channel = Receiver::default(true);
+        channel.set_ordered_gap_timeout(10);
+        let data: Vec<u8> = vec![0; 1024];
+
+        assert!(channel.receive_packet(1, &data[..], 32));
+        assert!(channel.receive_packet(3, &data[..], 32));
+        assert!(channel.receive_packet(4, &data[..], 32));
+        assert_eq!(1, channel.published.len());
+        // gaps are only timed from publish_at
+        assert_eq!(None, channel.gap_sequence);
+
+        let now = Instant::now();
+        channel.publish_at(now);
+        assert_eq!(Some(2), channel.gap_sequence);
+
+        // still within the timeout
+        assert_eq!(None, channel.publish_at(now + Duration::from_millis(5)));
+        assert_eq!(1, channel.published.len());
+
+        assert_eq!(Some((2, 2)), channel.publish_at(now + Duration::from_millis(20)));
+        assert_eq!(3, channel.published.len());
+        assert_eq!(4, channel.current_sequence);
+        assert_eq!(1, channel.ordered_gaps_skipped);
+
+        // late arrival of the skipped sequence is rejected
+        assert!(!channel.receive_packet(2, &data[..], 32));
+
+        // runs of missing sequences are skipped together
+        assert!(channel.receive_packet(8, &data[..], 32));
+        channel.publish_at(now + Duration::from_millis(20));
+        assert_eq!(Some((5, 7)), channel.publish_at(now + Duration::from_millis(40)));
+        assert_eq!(4, channel.published.len());
+        assert_eq!(8, channel.current_sequence);
+        assert_eq!(4, channel.ordered_gaps_skipped);
+    }
+
     #[test]
     fn test_reset_receive_window() {
         assert_eq!(65530, Receiver::calculate_current_in_window(65530, 100));
@@ -403,6 +650,31 @@ mod tests {
         assert!((channel.take_published().is_some()));
     }
 
+    fn published_order(order: PublishOrder) -> Vec<u8> {
+        let mut config = ReceiveWindowConfig::unordered(RECEIVE_WINDOW_SIZE_DEFAULT);
+        config.publish_order = order;
+        let mut channel = Receiver::create(config);
+        channel.current_sequence = 65531;
+        channel.last_sequence = 65531;
+        // sequences wrap from 65534 to 0
+        for sequence in [65534u16, 1, 65533, 0] {
+            let data: Vec<u8> = vec![sequence as u8; 4];
+            assert!(channel.receive_packet(sequence, &data[..], 4));
+        }
+        let mut order: Vec<u8> = Vec::new();
+        while let Some(byte_buffer) = channel.take_published() {
+            order.push(byte_buffer.get()[0]);
+        }
+        return order;
+    }
+
+    #[test]
+    fn test_publish_order() {
+        assert_eq!(vec![254, 1, 253, 0], published_order(PublishOrder::Arrival));
+        assert_eq!(vec![253, 254, 0, 1], published_order(PublishOrder::OldestFirst));
+        assert_eq!(vec![1, 0, 254, 253], published_order(PublishOrder::NewestFirst));
+    }
+
     #[test]
     fn wrapping_out_of_order() {
         let mut channel = Receiver::default(true);
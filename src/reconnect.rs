@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    connection_impl::{CONNECTION_RECONNECTED_EVENT, CONNECTION_RECONNECTING_EVENT, CONNECTION_RECONNECT_FAILED_EVENT},
+    network_address::NetworkAddress,
+    Tachyon,
+};
+
+const BACKOFF_MILLIS_DEFAULT: u32 = 500;
+const BACKOFF_MAX_MILLIS_DEFAULT: u32 = 10000;
+
+// Client side, off unless silence_millis is set.  A client that hears nothing from the server for silence_millis, or
+// stays unlinked that long with identities, closes its socket and connects again from a fresh one, linking its identity
+// again after.  Attempts back off from backoff_millis, doubling up to backoff_max_millis.  The server has to send
+// something more often than silence_millis, application level heartbeats if nothing else.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct ReconnectConfig {
+    pub silence_millis: u32,
+    // 0 for the defaults
+    pub backoff_millis: u32,
+    pub backoff_max_millis: u32,
+    // attempts before giving up with CONNECTION_RECONNECT_FAILED_EVENT, 0 keeps trying
+    pub max_attempts: u32,
+}
+
+impl ReconnectConfig {
+    pub fn is_enabled(&self) -> bool {
+        return self.silence_millis > 0;
+    }
+
+    // wait after the given attempt, counting from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let initial = if self.backoff_millis == 0 { BACKOFF_MILLIS_DEFAULT } else { self.backoff_millis } as u64;
+        let max = if self.backoff_max_millis == 0 { BACKOFF_MAX_MILLIS_DEFAULT } else { self.backoff_max_millis } as u64;
+        let doublings = attempt.saturating_sub(1).min(32);
+        return Duration::from_millis(initial.saturating_mul(1 << doublings).min(max.max(initial)));
+    }
+}
+
+#[derive(Default)]
+pub struct Reconnect {
+    // where the client connected, None once it gives up
+    pub server_address: Option<NetworkAddress>,
+    pub last_heard: Option<Instant>,
+    // when the client was last seen linked, while its identity isn't
+    pub unlinked_since: Option<Instant>,
+    // set while reconnecting, when the next attempt is due
+    pub next_attempt: Option<Instant>,
+    pub attempts: u32,
+    pub heard_since_attempt: bool,
+}
+
+impl Tachyon {
+    pub fn is_reconnecting(&self) -> bool {
+        return self.reconnect.next_attempt.is_some();
+    }
+
+    // client, every datagram from the server
+    pub fn reconnect_heard(&mut self) {
+        if self.config.reconnect.is_enabled() {
+            self.reconnect.last_heard = Some(self.now());
+            self.reconnect.heard_since_attempt = true;
+        }
+    }
+
+    pub fn client_reconnect_update(&mut self, now: Instant) {
+        if !self.config.reconnect.is_enabled() || self.socket.is_server || self.socket.transport.is_some() {
+            return;
+        }
+        let address = match self.reconnect.server_address {
+            Some(address) => address,
+            None => return,
+        };
+
+        match self.reconnect.next_attempt {
+            None => {
+                if self.reconnect_needed(now) {
+                    self.reconnect.attempts = 0;
+                    self.fire_connection_event(CONNECTION_RECONNECTING_EVENT, NetworkAddress::default());
+                    self.reconnect_attempt(address, now);
+                }
+            }
+            Some(next_attempt) => {
+                if self.reconnect.heard_since_attempt && self.can_send() {
+                    self.reconnect.next_attempt = None;
+                    self.reconnect.last_heard = Some(now);
+                    self.reconnect.unlinked_since = None;
+                    self.stats.reconnects += 1;
+                    self.fire_connection_event(CONNECTION_RECONNECTED_EVENT, NetworkAddress::default());
+                } else if now >= next_attempt {
+                    let max_attempts = self.config.reconnect.max_attempts;
+                    if max_attempts > 0 && self.reconnect.attempts >= max_attempts {
+                        self.reconnect.next_attempt = None;
+                        self.reconnect.server_address = None;
+                        self.fire_connection_event(CONNECTION_RECONNECT_FAILED_EVENT, NetworkAddress::default());
+                        return;
+                    }
+                    self.reconnect_attempt(address, now);
+                }
+            }
+        }
+    }
+
+    fn reconnect_needed(&mut self, now: Instant) -> bool {
+        let silence = Duration::from_millis(self.config.reconnect.silence_millis as u64);
+        let last_heard = *self.reconnect.last_heard.get_or_insert(now);
+        if now.saturating_duration_since(last_heard) > silence {
+            return true;
+        }
+
+        // a denied client isn't going to get anywhere by reconnecting
+        let unlinked = self.config.use_identity == 1 && self.identity.is_valid() && !self.identity.is_linked() && !self.link_denial.blocks(&self.identity);
+        if !unlinked {
+            self.reconnect.unlinked_since = None;
+            return false;
+        }
+        let unlinked_since = *self.reconnect.unlinked_since.get_or_insert(now);
+        return now.saturating_duration_since(unlinked_since) > silence;
+    }
+
+    // a fresh socket, connection and channels, like the first connect
+    fn reconnect_attempt(&mut self, address: NetworkAddress, now: Instant) {
+        self.reconnect.attempts += 1;
+        self.reconnect.heard_since_attempt = false;
+        self.reconnect.next_attempt = Some(now + self.config.reconnect.backoff(self.reconnect.attempts));
+
+        self.socket.socket = None;
+        self.remove_connection(NetworkAddress::default());
+        self.identity.set_linked(0);
+        self.relink_pending = false;
+        self.challenge.accepted = false;
+        self.link_on_next_update();
+        // a failed connect is tried again at the next attempt
+        let _ = self.try_connect(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::tachyon_test::TachyonTest;
+
+    use super::ReconnectConfig;
+
+    #[test]
+    fn test_backoff() {
+        let config = ReconnectConfig { silence_millis: 1000, backoff_millis: 100, backoff_max_millis: 700, max_attempts: 0 };
+        assert_eq!(100, config.backoff(1).as_millis());
+        assert_eq!(400, config.backoff(3).as_millis());
+        assert_eq!(700, config.backoff(4).as_millis());
+        assert_eq!(700, config.backoff(100).as_millis());
+        assert_eq!(500, ReconnectConfig::default().backoff(1).as_millis());
+    }
+
+    #[test]
+    #[serial]
+    fn test_reconnect() {
+        let mut test = TachyonTest::default();
+        test.client.config.reconnect = ReconnectConfig { silence_millis: 1000, backoff_millis: 100, backoff_max_millis: 400, max_attempts: 0 };
+        test.connect();
+        test.client_send_reliable(1, 8);
+        test.server_receive();
+        let first = test.remote_client();
+
+        // quiet server
+        test.client.step(Duration::from_millis(600));
+        test.client.step(Duration::from_millis(600));
+        assert!(test.client.is_reconnecting());
+        assert_eq!(1, test.client.reconnect.attempts);
+
+        // no answer, the next attempt waits out the backoff
+        test.client.step(Duration::from_millis(50));
+        assert_eq!(1, test.client.reconnect.attempts);
+        test.client.step(Duration::from_millis(60));
+        assert_eq!(2, test.client.reconnect.attempts);
+
+        // the server hears from the new socket and answers
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(8, test.server_receive().length);
+        let address = test.server.get_connections(10).iter().map(|conn| conn.address).find(|address| *address != first).unwrap();
+        test.server.send_reliable(1, address, &test.send_buffer, 8);
+        assert_eq!(8, test.client_receive().length);
+        test.client.step(Duration::from_millis(10));
+        assert!(!test.client.is_reconnecting());
+        assert_eq!(1, test.client.stats.reconnects);
+    }
+
+    #[test]
+    #[serial]
+    fn test_reconnect_gives_up() {
+        let mut test = TachyonTest::default();
+        test.client.config.reconnect = ReconnectConfig { silence_millis: 100, backoff_millis: 100, backoff_max_millis: 100, max_attempts: 2 };
+        test.connect();
+        for _ in 0..10 {
+            test.client.step(Duration::from_millis(60));
+        }
+        assert!(!test.client.is_reconnecting());
+        assert_eq!(2, test.client.reconnect.attempts);
+        assert!(test.client.reconnect.server_address.is_none());
+    }
+}
@@ -0,0 +1,305 @@
+use std::io::IoSlice;
+
+use rustc_hash::FxHashMap;
+
+use super::{int_buffer::IntBuffer, network_address::NetworkAddress, receive_result::TachyonReceiveResult, Tachyon, TachyonSendResult, SEND_ERROR_CHANNEL};
+
+// a message from the identity, forwarded by the server it's linked to
+pub const RELAY_KIND_FROM: u8 = 1;
+// a message for the identity, the server it's linked to sends it on
+pub const RELAY_KIND_TO: u8 = 2;
+pub const RELAY_HEADER_SIZE: usize = 22;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct RelayStats {
+    // messages from routed identities sent on to their server
+    pub forwarded: u64,
+    // RELAY_KIND_FROM messages returned from receive
+    pub received: u64,
+    // RELAY_KIND_TO messages sent on to a linked identity
+    pub delivered: u64,
+    // RELAY_KIND_TO messages for identities not linked here, or that failed to send
+    pub undeliverable: u64,
+    pub malformed: u64,
+    // relay channel messages from addresses that aren't peers, dropped since anyone could forge the header
+    pub rejected: u64,
+}
+
+// What travels in front of every message on the relay channel.  For RELAY_KIND_FROM the identity and address are the
+// original sender's, for RELAY_KIND_TO address isn't used.
+#[derive(Clone, Copy, Default)]
+pub struct RelayHeader {
+    pub kind: u8,
+    // the channel the message came in on, or goes out on
+    pub channel: u8,
+    pub identity_id: u32,
+    pub session_id: u32,
+    pub address: NetworkAddress,
+}
+
+impl RelayHeader {
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        let mut reader = IntBuffer { index: 0 };
+        let header = RelayHeader {
+            kind: reader.try_read_u8(buffer)?,
+            channel: reader.try_read_u8(buffer)?,
+            identity_id: reader.try_read_u32(buffer)?,
+            session_id: reader.try_read_u32(buffer)?,
+            address: reader.try_read_address(buffer)?,
+        };
+        return Some(header);
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.kind, buffer);
+        writer.write_u8(self.channel, buffer);
+        writer.write_u32(self.identity_id, buffer);
+        writer.write_u32(self.session_id, buffer);
+        writer.write_address(self.address, buffer);
+    }
+}
+
+// Forwards messages between servers over a reliable channel, enabled with Tachyon::enable_relay on every server
+// involved.  Messages from an identity with a route are sent on to the route's server instead of being returned from
+// receive, and come out of that server's receive with the original sender in the result and relayed_by set.  Servers
+// have to be peers of each other, see Tachyon::add_peer, relay channel messages from anything else are dropped.
+#[derive(Default)]
+pub struct Relay {
+    // 0 while disabled
+    pub channel_id: u8,
+    routes: FxHashMap<u32, NetworkAddress>,
+    pub stats: RelayStats,
+}
+
+impl Tachyon {
+    // false for the unreliable channel or a channel that isn't configured
+    pub fn enable_relay(&mut self, channel_id: u8) -> bool {
+        if channel_id == 0 || !self.channel_config.contains_key(&(channel_id as u16)) {
+            return false;
+        }
+        self.relay.channel_id = channel_id;
+        return true;
+    }
+
+    // messages from the identity go to the server at address from now on
+    pub fn set_relay_route(&mut self, identity_id: u32, address: NetworkAddress) {
+        self.relay.routes.insert(identity_id, address);
+    }
+
+    // false if the identity had no route
+    pub fn remove_relay_route(&mut self, identity_id: u32) -> bool {
+        return self.relay.routes.remove(&identity_id).is_some();
+    }
+
+    // Sends data to an identity linked to the server at address, on channel_id there.  The result is of the send to
+    // that server.
+    pub fn send_relayed(&mut self, address: NetworkAddress, identity_id: u32, channel_id: u8, data: &[u8]) -> TachyonSendResult {
+        let header = RelayHeader { kind: RELAY_KIND_TO, channel: channel_id, identity_id, session_id: 0, address: NetworkAddress::default() };
+        return self.send_relay_header(address, header, data);
+    }
+
+    // header then data on the relay channel, SEND_ERROR_CHANNEL while relay is disabled
+    pub(crate) fn send_relay_header(&mut self, address: NetworkAddress, header: RelayHeader, data: &[u8]) -> TachyonSendResult {
+        if self.relay.channel_id == 0 {
+            let mut result = TachyonSendResult::default();
+            result.error = SEND_ERROR_CHANNEL;
+            return result;
+        }
+        let mut header_data = [0; RELAY_HEADER_SIZE];
+        header.write(&mut header_data);
+        return self.send_vectored(self.relay.channel_id as u16, address, &[IoSlice::new(&header_data), IoSlice::new(data)]);
+    }
+
+    // A received message, before it's returned.  False if the relay took it, the result is rewritten for relayed
+    // messages that are returned.
+    pub(crate) fn process_relay(&mut self, result: &mut TachyonReceiveResult, message: &mut [u8]) -> bool {
+        if result.channel == self.relay.channel_id as u16 {
+            if !self.is_peer(result.address) {
+                self.relay.stats.rejected += 1;
+                return false;
+            }
+            let header = match RelayHeader::try_read(message) {
+                Some(header) => header,
+                None => {
+                    self.relay.stats.malformed += 1;
+                    return false;
+                }
+            };
+            let length = message.len() - RELAY_HEADER_SIZE;
+            match header.kind {
+                RELAY_KIND_FROM => {
+                    message.copy_within(RELAY_HEADER_SIZE.., 0);
+                    result.relayed_by = result.address;
+                    result.address = header.address;
+                    result.channel = header.channel as u16;
+                    result.identity_id = header.identity_id;
+                    result.session_id = header.session_id;
+                    result.length = length as u32;
+                    self.relay.stats.received += 1;
+                    return true;
+                }
+                RELAY_KIND_TO => {
+                    let address = match self.identity_to_address_map.get(&header.identity_id) {
+                        Some(address) => *address,
+                        None => {
+                            self.relay.stats.undeliverable += 1;
+                            return false;
+                        }
+                    };
+                    let sent = self.send_vectored(header.channel as u16, address, &[IoSlice::new(&message[RELAY_HEADER_SIZE..])]);
+                    if sent.error == 0 {
+                        self.relay.stats.delivered += 1;
+                    } else {
+                        self.relay.stats.undeliverable += 1;
+                    }
+                    return false;
+                }
+                _ => {
+                    self.relay.stats.malformed += 1;
+                    return false;
+                }
+            }
+        }
+
+        if self.relay.routes.is_empty() {
+            return true;
+        }
+        let identity = match self.connections.get(&result.address) {
+            Some(conn) => conn.identity,
+            None => return true,
+        };
+        let route = match self.relay.routes.get(&identity.id) {
+            Some(route) if identity.id != 0 => *route,
+            _ => return true,
+        };
+        let header = RelayHeader {
+            kind: RELAY_KIND_FROM,
+            channel: result.channel as u8,
+            identity_id: identity.id,
+            session_id: identity.session_id,
+            address: result.address,
+        };
+        let sent = self.send_relay_header(route, header, message);
+        if sent.error == 0 {
+            self.relay.stats.forwarded += 1;
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{
+        channel::ChannelConfig, connection::Identity, loopback::LoopbackNetwork, network_address::NetworkAddress, pool::SendTarget,
+        tachyon_test::TachyonTest, Tachyon, TachyonConfig,
+    };
+
+    use super::{RelayHeader, RELAY_HEADER_SIZE, RELAY_KIND_FROM, RELAY_KIND_TO};
+
+    fn create_server(network: &LoopbackNetwork, address: NetworkAddress) -> Tachyon {
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut server = Tachyon::create(config);
+        server.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(server.enable_relay(3));
+        assert!(server.bind_loopback(network, address));
+        return server;
+    }
+
+    #[test]
+    fn test_relay_header() {
+        let header = RelayHeader { kind: RELAY_KIND_FROM, channel: 2, identity_id: 5, session_id: 50, address: NetworkAddress::localhost(9000) };
+        let mut data = [0; RELAY_HEADER_SIZE];
+        header.write(&mut data);
+        let read = RelayHeader::try_read(&data).unwrap();
+        assert_eq!((RELAY_KIND_FROM, 2, 5, 50), (read.kind, read.channel, read.identity_id, read.session_id));
+        assert!(read.address == header.address);
+        assert!(RelayHeader::try_read(&data[0..RELAY_HEADER_SIZE - 1]).is_none());
+    }
+
+    #[test]
+    fn test_relay() {
+        let network = LoopbackNetwork::default();
+        let front_address = NetworkAddress::localhost(8300);
+        let back_address = NetworkAddress::localhost(8301);
+        let mut front = create_server(&network, front_address);
+        let mut back = create_server(&network, back_address);
+        assert!(front.add_peer(back_address));
+        assert!(back.add_peer(front_address));
+        front.set_identity(5, 50);
+
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut client = Tachyon::create(config);
+        client.identity = Identity { id: 5, session_id: 50, linked: 0 };
+        client.connect_loopback(&network, front_address);
+        client.update();
+        let mut buffer: Vec<u8> = vec![0; 1024];
+        front.receive_loop(&mut buffer);
+        client.receive_loop(&mut buffer);
+        assert!(client.identity.is_linked());
+        let client_address = NetworkAddress::localhost(49152);
+
+        // routed, the front server doesn't see it
+        front.set_relay_route(5, back_address);
+        let target = SendTarget { identity_id: 0, address: NetworkAddress::default() };
+        buffer[0..3].copy_from_slice(&[7, 8, 9]);
+        assert_eq!(0, client.send_to_target(1, target, &buffer, 3).error);
+        assert_eq!(0, front.receive_loop(&mut buffer).length);
+        assert_eq!(1, front.relay.stats.forwarded);
+
+        let result = back.receive_loop(&mut buffer);
+        assert_eq!(3, result.length);
+        assert_eq!(&[7, 8, 9], &buffer[0..3]);
+        assert_eq!(1, result.channel);
+        assert_eq!((5, 50), (result.identity_id, result.session_id));
+        assert!(client_address == result.address);
+        assert!(front_address == result.relayed_by);
+
+        // and back through the front server
+        assert_eq!(0, back.send_relayed(front_address, 5, 1, &[4, 5]).error);
+        assert_eq!(0, front.receive_loop(&mut buffer).length);
+        assert_eq!(1, front.relay.stats.delivered);
+        let result = client.receive_loop(&mut buffer);
+        assert_eq!(2, result.length);
+        assert_eq!(&[4, 5], &buffer[0..2]);
+
+        // nobody linked as 6 on the front server
+        back.send_relayed(front_address, 6, 1, &[4, 5]);
+        front.receive_loop(&mut buffer);
+        assert_eq!(1, front.relay.stats.undeliverable);
+
+        assert!(front.remove_relay_route(5));
+        client.send_to_target(1, target, &buffer, 3);
+        let result = front.receive_loop(&mut buffer);
+        assert_eq!(3, result.length);
+        assert!(result.relayed_by.is_default());
+    }
+
+    #[test]
+    #[serial]
+    fn test_relay_from_non_peer() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(test.server.enable_relay(3));
+        test.connect();
+
+        // a client posing as a server, forging the sender and pushing to an identity
+        for kind in [RELAY_KIND_FROM, RELAY_KIND_TO] {
+            let header = RelayHeader { kind, channel: 1, identity_id: 5, session_id: 50, address: NetworkAddress::localhost(9000) };
+            header.write(&mut test.send_buffer);
+            assert_eq!(0, test.client_send_reliable(3, RELAY_HEADER_SIZE + 4).error);
+            assert_eq!(0, test.server_receive().length);
+        }
+        assert_eq!(2, test.server.relay.stats.rejected);
+        assert_eq!(0, test.server.relay.stats.received);
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    io::IoSlice,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{error::TachyonSendError, int_buffer::IntBuffer, network_address::NetworkAddress, Tachyon};
+
+pub const RPC_KIND_REQUEST: u8 = 1;
+pub const RPC_KIND_RESPONSE: u8 = 2;
+pub const RPC_HEADER_SIZE: usize = 7;
+
+// response status, the handler's response follows RPC_STATUS_OK only
+pub const RPC_STATUS_OK: u16 = 0;
+pub const RPC_STATUS_NO_HANDLER: u16 = 1;
+pub const RPC_STATUS_FAILED: u16 = 2;
+
+// requests nobody polled are forgotten this long after their deadline, answered or not
+const RPC_EXPIRED_KEEP_MILLIS: u64 = 10000;
+
+// Handles a request, writing the response to the Vec.  False fails the request with RPC_STATUS_FAILED.
+pub type RequestHandler = Box<dyn FnMut(NetworkAddress, &[u8], &mut Vec<u8>) -> bool + Send>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct RequestHandle {
+    pub id: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RequestStatus {
+    Pending,
+    // the response is in the Vec passed to poll_response
+    Ready { length: usize },
+    // the other end answered with an RPC_STATUS_x other than ok
+    Failed { status: u16 },
+    TimedOut,
+    // never issued, already polled to completion or forgotten
+    Unknown,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct RpcStats {
+    pub requests_sent: u64,
+    pub requests_received: u64,
+    pub responses_sent: u64,
+    pub responses_received: u64,
+    pub timed_out: u64,
+    // responses to requests that had timed out or were never sent, or from an address the request didn't go to
+    pub unmatched_responses: u64,
+    // responses that came in but were never polled before the request was forgotten
+    pub unpolled_responses: u64,
+}
+
+// the request id header extension at the start of every message on the rpc channel
+#[derive(Clone, Copy, Default)]
+pub struct RpcHeader {
+    pub kind: u8,
+    // request type for requests, RPC_STATUS_x for responses
+    pub value: u16,
+    pub request_id: u32,
+}
+
+impl RpcHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = RpcHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.kind = reader.read_u8(buffer);
+        header.value = reader.read_u16(buffer);
+        header.request_id = reader.read_u32(buffer);
+
+        return header;
+    }
+
+    // None when buffer is shorter than the header
+    pub fn try_read(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < RPC_HEADER_SIZE {
+            return None;
+        }
+        return Some(RpcHeader::read(buffer));
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.kind, buffer);
+        writer.write_u16(self.value, buffer);
+        writer.write_u32(self.request_id, buffer);
+    }
+}
+
+struct PendingRequest {
+    // where the request went, a response from anywhere else isn't its response
+    address: NetworkAddress,
+    deadline: Instant,
+    response: Option<(u16, Vec<u8>)>,
+}
+
+// Request/response over a reliable channel, enabled with Tachyon::enable_rpc.  Every message on that channel starts
+// with an RpcHeader and is handled here instead of being returned from receive.
+#[derive(Default)]
+pub struct Rpc {
+    // 0 while disabled
+    pub channel_id: u8,
+    handlers: FxHashMap<u16, RequestHandler>,
+    pending: FxHashMap<u32, PendingRequest>,
+    next_id: u32,
+    pub stats: RpcStats,
+}
+
+impl Tachyon {
+    // false for the unreliable channel or a channel that isn't configured
+    pub fn enable_rpc(&mut self, channel_id: u8) -> bool {
+        if channel_id == 0 || !self.channel_config.contains_key(&(channel_id as u16)) {
+            return false;
+        }
+        self.rpc.channel_id = channel_id;
+        return true;
+    }
+
+    pub fn register_request_handler(&mut self, request_type: u16, handler: RequestHandler) {
+        self.rpc.handlers.insert(request_type, handler);
+    }
+
+    // Sends a request, poll_response with the handle for the response.  The request times out timeout_millis from now.
+    pub fn send_request(&mut self, address: NetworkAddress, request_type: u16, data: &[u8], timeout_millis: u32) -> Result<RequestHandle, TachyonSendError> {
+        if self.rpc.channel_id == 0 {
+            return Err(TachyonSendError::Channel { channel_id: 0 });
+        }
+
+        self.rpc.next_id = self.rpc.next_id.wrapping_add(1).max(1);
+        let request_id = self.rpc.next_id;
+        let header = RpcHeader { kind: RPC_KIND_REQUEST, value: request_type, request_id };
+        let mut header_data = [0; RPC_HEADER_SIZE];
+        header.write(&mut header_data);
+
+        let result = self.send_vectored(self.rpc.channel_id as u16, address, &[IoSlice::new(&header_data), IoSlice::new(data)]);
+        result.to_result()?;
+
+        let deadline = self.now() + Duration::from_millis(timeout_millis as u64);
+        self.rpc.pending.insert(request_id, PendingRequest { address, deadline, response: None });
+        self.rpc.stats.requests_sent += 1;
+        return Ok(RequestHandle { id: request_id });
+    }
+
+    // Ready and Failed complete the request, later polls for it get Unknown
+    pub fn poll_response(&mut self, handle: RequestHandle, response: &mut Vec<u8>) -> RequestStatus {
+        let now = self.now();
+        let pending = match self.rpc.pending.get_mut(&handle.id) {
+            Some(pending) => pending,
+            None => return RequestStatus::Unknown,
+        };
+
+        if let Some((status, data)) = pending.response.take() {
+            self.rpc.pending.remove(&handle.id);
+            if status != RPC_STATUS_OK {
+                return RequestStatus::Failed { status };
+            }
+            response.clear();
+            response.extend_from_slice(&data);
+            return RequestStatus::Ready { length: data.len() };
+        }
+
+        if now > pending.deadline {
+            self.rpc.pending.remove(&handle.id);
+            self.rpc.stats.timed_out += 1;
+            return RequestStatus::TimedOut;
+        }
+        return RequestStatus::Pending;
+    }
+
+    pub fn is_rpc_channel(&self, channel_id: u16) -> bool {
+        return self.rpc.channel_id != 0 && channel_id == self.rpc.channel_id as u16;
+    }
+
+    // a message received on the rpc channel
+    pub fn process_rpc_message(&mut self, address: NetworkAddress, message: &[u8]) {
+        let header = match RpcHeader::try_read(message) {
+            Some(header) => header,
+            None => return,
+        };
+        let body = &message[RPC_HEADER_SIZE..];
+
+        match header.kind {
+            RPC_KIND_REQUEST => {
+                self.rpc.stats.requests_received += 1;
+                let mut response: Vec<u8> = Vec::new();
+                let status = match self.rpc.handlers.get_mut(&header.value) {
+                    Some(handler) => {
+                        if handler(address, body, &mut response) {
+                            RPC_STATUS_OK
+                        } else {
+                            RPC_STATUS_FAILED
+                        }
+                    }
+                    None => RPC_STATUS_NO_HANDLER,
+                };
+                if status != RPC_STATUS_OK {
+                    response.clear();
+                }
+
+                let header = RpcHeader { kind: RPC_KIND_RESPONSE, value: status, request_id: header.request_id };
+                let mut header_data = [0; RPC_HEADER_SIZE];
+                header.write(&mut header_data);
+                let result = self.send_vectored(self.rpc.channel_id as u16, address, &[IoSlice::new(&header_data), IoSlice::new(&response)]);
+                if result.error == 0 {
+                    self.rpc.stats.responses_sent += 1;
+                }
+            }
+            RPC_KIND_RESPONSE => {
+                self.rpc.stats.responses_received += 1;
+                match self.rpc.pending.get_mut(&header.request_id) {
+                    Some(pending) if pending.response.is_none() && pending.address == address => {
+                        pending.response = Some((header.value, body.to_vec()));
+                    }
+                    _ => {
+                        self.rpc.stats.unmatched_responses += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // forgets requests nobody polled, run from update
+    pub fn rpc_update(&mut self, now: Instant) {
+        let keep = Duration::from_millis(RPC_EXPIRED_KEEP_MILLIS);
+        let stats = &mut self.rpc.stats;
+        self.rpc.pending.retain(|_, pending| {
+            if now <= pending.deadline + keep {
+                return true;
+            }
+            if pending.response.is_some() {
+                stats.unpolled_responses += 1;
+            } else {
+                stats.timed_out += 1;
+            }
+            return false;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::{
+        channel::ChannelConfig, loopback::LoopbackNetwork, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon,
+        TachyonConfig,
+    };
+
+    use super::{RequestStatus, RpcHeader, RPC_HEADER_SIZE, RPC_KIND_RESPONSE, RPC_STATUS_FAILED, RPC_STATUS_NO_HANDLER, RPC_STATUS_OK};
+
+    #[test]
+    #[serial]
+    fn test_request_response() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(!test.client.enable_rpc(4));
+        assert!(test.server.enable_rpc(3));
+        assert!(test.client.enable_rpc(3));
+        test.connect();
+
+        test.server.register_request_handler(1, Box::new(|_address, request, response| {
+            response.extend(request.iter().map(|b| b + 1));
+            return true;
+        }));
+        test.server.register_request_handler(2, Box::new(|_address, _request, _response| {
+            return false;
+        }));
+
+        let echo = test.client.send_request(test.client_address, 1, &[1, 2, 3], 1000).unwrap();
+        let failing = test.client.send_request(test.client_address, 2, &[1], 1000).unwrap();
+        let unhandled = test.client.send_request(test.client_address, 9, &[1], 1000).unwrap();
+
+        // requests are handled inside receive, nothing is returned for them
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(3, test.server.rpc.stats.responses_sent);
+
+        let mut response: Vec<u8> = Vec::new();
+        assert_eq!(RequestStatus::Pending, test.client.poll_response(echo, &mut response));
+        // a message on another channel still comes through
+        assert_eq!(0, test.server_send_reliable(1, 8).error);
+        assert_eq!(8, test.client_receive().length);
+        test.client_receive();
+
+        assert_eq!(RequestStatus::Ready { length: 3 }, test.client.poll_response(echo, &mut response));
+        assert_eq!(vec![2, 3, 4], response);
+        assert_eq!(RequestStatus::Unknown, test.client.poll_response(echo, &mut response));
+        assert_eq!(RequestStatus::Failed { status: RPC_STATUS_FAILED }, test.client.poll_response(failing, &mut response));
+        assert_eq!(RequestStatus::Failed { status: RPC_STATUS_NO_HANDLER }, test.client.poll_response(unhandled, &mut response));
+    }
+
+    #[test]
+    #[serial]
+    fn test_request_timeout() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.enable_rpc(3);
+        test.connect();
+
+        let mut response: Vec<u8> = Vec::new();
+        test.client.step(Duration::from_millis(0));
+        let handle = test.client.send_request(test.client_address, 1, &[1], 100).unwrap();
+        test.client.step(Duration::from_millis(50));
+        assert_eq!(RequestStatus::Pending, test.client.poll_response(handle, &mut response));
+        test.client.step(Duration::from_millis(100));
+        assert_eq!(RequestStatus::TimedOut, test.client.poll_response(handle, &mut response));
+        assert_eq!(1, test.client.rpc.stats.timed_out);
+
+        // unpolled ones are forgotten eventually
+        let handle = test.client.send_request(test.client_address, 1, &[1], 100).unwrap();
+        test.client.step(Duration::from_millis(20000));
+        assert_eq!(RequestStatus::Unknown, test.client.poll_response(handle, &mut response));
+        assert_eq!(2, test.client.rpc.stats.timed_out);
+    }
+
+    #[test]
+    #[serial]
+    fn test_unpolled_response() {
+        let mut test = TachyonTest::default();
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.enable_rpc(3);
+        test.client.enable_rpc(3);
+        test.connect();
+        test.server.register_request_handler(1, Box::new(|_address, _request, response| {
+            response.push(1);
+            return true;
+        }));
+
+        test.client.step(Duration::from_millis(0));
+        let handle = test.client.send_request(test.client_address, 1, &[1], 100).unwrap();
+        test.server_receive();
+        test.client_receive();
+        assert_eq!(1, test.client.rpc.stats.responses_received);
+
+        // answered but never polled, forgotten the same as an unanswered one
+        test.client.step(Duration::from_millis(5000));
+        assert_eq!(1, test.client.rpc.pending.len());
+        test.client.step(Duration::from_millis(20000));
+        assert!(test.client.rpc.pending.is_empty());
+        let mut response: Vec<u8> = Vec::new();
+        assert_eq!(RequestStatus::Unknown, test.client.poll_response(handle, &mut response));
+        assert_eq!(1, test.client.rpc.stats.unpolled_responses);
+        assert_eq!(0, test.client.rpc.stats.timed_out);
+    }
+
+    #[test]
+    fn test_response_from_other_address() {
+        let network = LoopbackNetwork::default();
+        let address = NetworkAddress::localhost(8103);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.bind_loopback(&network, address));
+        let mut clients: Vec<Tachyon> = (0..2).map(|_| Tachyon::create(TachyonConfig::default())).collect();
+        for tachyon in clients.iter_mut().chain([&mut server]) {
+            tachyon.configure_channel(3, ChannelConfig::default_ordered());
+            assert!(tachyon.enable_rpc(3));
+        }
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let mut addresses: Vec<NetworkAddress> = Vec::new();
+        for client in &mut clients {
+            client.connect_loopback(&network, address);
+            client.send_reliable(1, NetworkAddress::default(), &[1], 1);
+            addresses.push(server.receive_loop(&mut receive_buffer).address);
+        }
+        clients[0].register_request_handler(1, Box::new(|_address, _request, response| {
+            response.push(7);
+            return true;
+        }));
+
+        let handle = server.send_request(addresses[0], 1, &[1], 1000).unwrap();
+        // the other client answers first with the request's id
+        let header = RpcHeader { kind: RPC_KIND_RESPONSE, value: RPC_STATUS_OK, request_id: handle.id };
+        let mut forged = [0; RPC_HEADER_SIZE + 1];
+        header.write(&mut forged);
+        clients[1].send_reliable(3, NetworkAddress::default(), &forged, forged.len());
+        server.receive_loop(&mut receive_buffer);
+        assert_eq!(1, server.rpc.stats.unmatched_responses);
+        let mut response: Vec<u8> = Vec::new();
+        assert_eq!(RequestStatus::Pending, server.poll_response(handle, &mut response));
+
+        clients[0].receive_loop(&mut receive_buffer);
+        server.receive_loop(&mut receive_buffer);
+        assert_eq!(RequestStatus::Ready { length: 1 }, server.poll_response(handle, &mut response));
+        assert_eq!(vec![7], response);
+        assert_eq!(1, server.rpc.stats.unmatched_responses);
+    }
+}
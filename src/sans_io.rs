@@ -0,0 +1,237 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    clock::MockClock,
+    connection_map::{ConnectionChange, ConnectionChanges},
+    network_address::NetworkAddress,
+    transport::{Datagram, DatagramQueue},
+    Tachyon, TachyonConfig, TachyonSendResult,
+};
+
+// how often to call handle_timeout when TachyonConfig.update_interval_millis isn't set
+const DEFAULT_TIMEOUT_MILLIS: u64 = 10;
+
+pub enum Event {
+    Received {
+        channel: u16,
+        address: NetworkAddress,
+        identity_id: u32,
+        data: Vec<u8>,
+    },
+    Connected { address: NetworkAddress },
+    Disconnected { address: NetworkAddress },
+}
+
+// The protocol as a state machine that never touches a socket or reads the time.  Datagrams go in through
+// handle_datagram and come out of poll_transmit, received messages and connection changes come out of poll_event, and
+// the caller says what time it is with every call.  Headers, channels, the receiver, nacks and fragmentation are the
+// same as over UDP, this is what drivers for WASM hosts, custom sockets or deterministic tests build on.
+pub struct TachyonCore {
+    tachyon: Tachyon,
+    queue: DatagramQueue,
+    clock: Arc<MockClock>,
+    changes: ConnectionChanges,
+    events: VecDeque<Event>,
+    receive_buffer: Vec<u8>,
+}
+
+impl TachyonCore {
+    pub fn server(config: TachyonConfig, now: Instant) -> Self {
+        let mut core = TachyonCore::create(config, now);
+        core.tachyon.bind_transport(Box::new(core.queue.clone()));
+        return core;
+    }
+
+    // the server is NetworkAddress::default(), for datagrams in and out, and a Connected event for it is the first event
+    pub fn client(config: TachyonConfig, now: Instant) -> Self {
+        let mut core = TachyonCore::create(config, now);
+        core.tachyon.connect_transport(Box::new(core.queue.clone()));
+        return core;
+    }
+
+    fn create(config: TachyonConfig, now: Instant) -> Self {
+        let clock = Arc::new(MockClock::create(now));
+        let changes = ConnectionChanges::default();
+        let mut tachyon = Tachyon::create(config);
+        tachyon.start_time = now;
+        tachyon.set_clock(clock.clone());
+        tachyon.connection_changes = Some(changes.clone());
+
+        let core = TachyonCore {
+            tachyon,
+            queue: DatagramQueue::default(),
+            clock,
+            changes,
+            events: VecDeque::new(),
+            receive_buffer: vec![0; 1024 * 1024],
+        };
+        return core;
+    }
+
+    // the instance underneath, for configuration, identities and stats
+    pub fn tachyon(&mut self) -> &mut Tachyon {
+        return &mut self.tachyon;
+    }
+
+    // a datagram from address, anything it completes is in poll_event after
+    pub fn handle_datagram(&mut self, now: Instant, address: NetworkAddress, data: &[u8]) {
+        self.set_time(now);
+        self.queue.push_incoming(address, data);
+        loop {
+            let res = self.tachyon.receive_loop(&mut self.receive_buffer);
+            // a connection comes out before the first message from it
+            self.take_connection_changes();
+            if res.length == 0 || res.error > 0 {
+                break;
+            }
+            self.events.push_back(Event::Received {
+                channel: res.channel,
+                address: res.address,
+                identity_id: res.identity_id,
+                data: self.receive_buffer[0..res.length as usize].to_vec(),
+            });
+        }
+    }
+
+    // nacks, resends and timeouts, call it by poll_timeout
+    pub fn handle_timeout(&mut self, now: Instant) {
+        self.set_time(now);
+        self.tachyon.update();
+        self.take_connection_changes();
+    }
+
+    pub fn poll_timeout(&self) -> Instant {
+        let millis = match self.tachyon.config.update_interval_millis {
+            0 => DEFAULT_TIMEOUT_MILLIS,
+            interval => interval as u64,
+        };
+        return self.tachyon.now() + Duration::from_millis(millis);
+    }
+
+    // the next datagram to put on the wire
+    pub fn poll_transmit(&mut self) -> Option<Datagram> {
+        return self.queue.pop_outgoing();
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        // changes made straight on the instance too
+        self.take_connection_changes();
+        return self.events.pop_front();
+    }
+
+    pub fn send_reliable(&mut self, now: Instant, channel_id: u16, address: NetworkAddress, data: &[u8]) -> TachyonSendResult {
+        self.set_time(now);
+        return self.tachyon.send_reliable(channel_id, address, data, data.len());
+    }
+
+    pub fn send_unreliable(&mut self, now: Instant, address: NetworkAddress, data: &[u8]) -> TachyonSendResult {
+        self.set_time(now);
+        return self.tachyon.send_unreliable(address, data, data.len());
+    }
+
+    // time only goes forward, an earlier now is taken as no time passed
+    fn set_time(&mut self, now: Instant) {
+        let current = self.tachyon.now();
+        if now > current {
+            self.clock.advance(now - current);
+        }
+    }
+
+    fn take_connection_changes(&mut self) {
+        while let Some(change) = self.changes.pop() {
+            let event = match change {
+                ConnectionChange::Added(conn) => Event::Connected { address: conn.address },
+                ConnectionChange::Removed(conn) => Event::Disconnected { address: conn.address },
+            };
+            self.events.push_back(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::network_address::NetworkAddress;
+    use crate::TachyonConfig;
+
+    use super::{Event, TachyonCore};
+
+    // everything each side has to send goes to the other, dropping the nth client datagram if asked
+    fn pump(client: &mut TachyonCore, server: &mut TachyonCore, now: Instant, client_address: NetworkAddress, drop_at: Option<usize>) {
+        let mut moved = 0;
+        while let Some(datagram) = client.poll_transmit() {
+            if drop_at != Some(moved) {
+                server.handle_datagram(now, client_address, &datagram.data);
+            }
+            moved += 1;
+        }
+        while let Some(datagram) = server.poll_transmit() {
+            assert!(client_address == datagram.address);
+            client.handle_datagram(now, NetworkAddress::default(), &datagram.data);
+        }
+    }
+
+    fn received(core: &mut TachyonCore) -> Vec<u8> {
+        let mut firsts: Vec<u8> = Vec::new();
+        while let Some(event) = core.poll_event() {
+            if let Event::Received { data, .. } = event {
+                firsts.push(data[0]);
+            }
+        }
+        return firsts;
+    }
+
+    #[test]
+    fn test_core() {
+        let config = TachyonConfig::default();
+        let mut now = Instant::now();
+        let mut server = TachyonCore::server(config, now);
+        let mut client = TachyonCore::client(config, now);
+        let client_address = NetworkAddress::localhost(9999);
+        // a client is connected to the server from the start
+        assert!(matches!(client.poll_event(), Some(Event::Connected { .. })));
+
+        // the second of three ordered messages is lost, the server's nack gets it resent
+        for i in 0..3 {
+            assert_eq!(0, client.send_reliable(now, 1, NetworkAddress::default(), &[i; 32]).error);
+        }
+        pump(&mut client, &mut server, now, client_address, Some(1));
+        match server.poll_event() {
+            Some(Event::Connected { address }) => assert!(client_address == address),
+            _ => panic!("no connected event"),
+        }
+        assert_eq!(vec![0], received(&mut server));
+
+        now += Duration::from_millis(10);
+        assert!(server.poll_timeout() <= now);
+        server.handle_timeout(now);
+        pump(&mut client, &mut server, now, client_address, None);
+        client.handle_timeout(now);
+        pump(&mut client, &mut server, now, client_address, None);
+        assert_eq!(vec![1, 2], received(&mut server));
+
+        // server to client, unreliable
+        assert_eq!(0, server.send_unreliable(now, client_address, &[7; 8]).error);
+        let datagram = server.poll_transmit().unwrap();
+        assert!(server.poll_transmit().is_none());
+        client.handle_datagram(now, NetworkAddress::default(), &datagram.data);
+        match client.poll_event() {
+            Some(Event::Received { channel, data, .. }) => {
+                assert_eq!(0, channel);
+                assert_eq!(vec![7; 8], data);
+            }
+            _ => panic!("nothing received"),
+        }
+
+        server.tachyon().remove_connection(client_address);
+        match server.poll_event() {
+            Some(Event::Disconnected { address }) => assert!(client_address == address),
+            _ => panic!("no disconnected event"),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    header::{
+        channel_message_start, MESSAGE_TYPE_FRAGMENT, MESSAGE_TYPE_NACK, MESSAGE_TYPE_NACK_FORMAT, MESSAGE_TYPE_NACK_RANGES, MESSAGE_TYPE_NONE, MESSAGE_TYPE_RELIABLE,
+        MESSAGE_TYPE_RELIABLE_WITH_NACK,
+        TACHYON_HEADER_SIZE,
+    },
+    network_address::NetworkAddress,
+};
+
+// Keyed scrambling of the channel and sequence of reliable channel messages, so packets can't be injected by someone
+// who can't see the traffic.  Not encryption, the mask is the same for every packet on a connection.
+//
+// The mask comes from TachyonConfig.scramble_key and the session of the connection's identity once it's linked, until
+// then from the key alone.  XOR, so the same call scrambles and unscrambles.
+pub struct Scrambler {
+    key: u64,
+    default_mask: [u8; 3],
+    masks: FxHashMap<NetworkAddress, [u8; 3]>,
+    // send_to takes &self, sends are scrambled into here
+    pub buffer: RefCell<Vec<u8>>,
+}
+
+impl Scrambler {
+    pub fn create(key: u64) -> Self {
+        let scrambler = Scrambler {
+            key,
+            default_mask: Scrambler::mask(key, 0),
+            masks: FxHashMap::default(),
+            buffer: RefCell::new(vec![0; 1024 * 64]),
+        };
+        return scrambler;
+    }
+
+    pub fn set_session(&mut self, address: NetworkAddress, session_id: u32) {
+        if session_id == 0 {
+            self.masks.remove(&address);
+        } else {
+            self.masks.insert(address, Scrambler::mask(self.key, session_id));
+        }
+    }
+
+    // the message behind a wide channel header is scrambled like it would be without one
+    pub fn applies(data: &[u8], length: usize) -> bool {
+        let start = channel_message_start(&data[0..length]);
+        if length - start < TACHYON_HEADER_SIZE {
+            return false;
+        }
+        match data[start] {
+            MESSAGE_TYPE_RELIABLE | MESSAGE_TYPE_RELIABLE_WITH_NACK | MESSAGE_TYPE_FRAGMENT | MESSAGE_TYPE_NONE | MESSAGE_TYPE_NACK
+            | MESSAGE_TYPE_NACK_RANGES | MESSAGE_TYPE_NACK_FORMAT => return true,
+            _ => return false,
+        }
+    }
+
+    pub fn apply(&self, address: NetworkAddress, data: &mut [u8], length: usize) {
+        if !Scrambler::applies(data, length) {
+            return;
+        }
+        let start = channel_message_start(&data[0..length]);
+        let mask = self.masks.get(&address).unwrap_or(&self.default_mask);
+        data[start + 1] ^= mask[0];
+        data[start + 2] ^= mask[1];
+        data[start + 3] ^= mask[2];
+    }
+
+    // splitmix64 of the key and session
+    fn mask(key: u64, session_id: u32) -> [u8; 3] {
+        let mut z = key ^ (session_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z = z ^ (z >> 31);
+        return [z as u8, (z >> 8) as u8, (z >> 16) as u8];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{header::{MESSAGE_TYPE_RELIABLE, MESSAGE_TYPE_WIDE_CHANNEL}, network_address::NetworkAddress, tachyon_test::TachyonTest, TachyonConfig};
+
+    use super::Scrambler;
+
+    #[test]
+    fn test_scramble() {
+        let mut scrambler = Scrambler::create(1234);
+        let address = NetworkAddress::localhost(100);
+        let packet: [u8; 6] = [MESSAGE_TYPE_RELIABLE, 1, 2, 3, 4, 5];
+
+        let mut data = packet;
+        scrambler.apply(address, &mut data, 6);
+        assert_ne!(packet, data);
+        assert_eq!(packet[4..], data[4..]);
+        let key_only = data;
+        scrambler.apply(address, &mut data, 6);
+        assert_eq!(packet, data);
+
+        // a session gives the connection its own mask
+        scrambler.set_session(address, 77);
+        scrambler.apply(address, &mut data, 6);
+        assert_ne!(key_only, data);
+
+        // behind a wide channel header, the header isn't touched
+        let mut wide: [u8; 8] = [MESSAGE_TYPE_WIDE_CHANNEL, 1, MESSAGE_TYPE_RELIABLE, 1, 2, 3, 4, 5];
+        scrambler.apply(address, &mut wide, 8);
+        assert_eq!([MESSAGE_TYPE_WIDE_CHANNEL, 1], wide[0..2]);
+        assert_eq!(data[0..6], wide[2..8]);
+
+        // unreliable isn't touched
+        let mut unreliable: [u8; 4] = [0, 1, 2, 3];
+        scrambler.apply(address, &mut unreliable, 4);
+        assert_eq!([0, 1, 2, 3], unreliable);
+    }
+
+    #[test]
+    #[serial]
+    fn test_scrambled_connection() {
+        let mut config = TachyonConfig::default();
+        config.scramble_key = 0x5eed;
+        let mut test = TachyonTest::default();
+        test.server.config = config;
+        test.client.config = config;
+        test.connect();
+
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(0, test.server_send_reliable(1, 8).error);
+        assert_eq!(8, test.client_receive().length);
+
+        // a reliable message built without the key lands on the wrong channel and sequence
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(&[MESSAGE_TYPE_RELIABLE, 1, 1, 0, 9], test.address.to_socket_addr()).unwrap();
+        assert_eq!(0, test.server_receive().length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_scrambled_identity() {
+        let mut config = TachyonConfig::default();
+        config.scramble_key = 0x5eed;
+        config.use_identity = 1;
+        let mut test = TachyonTest::default();
+        test.server.config = config;
+        test.client.config = config;
+        test.server.set_identity(1, 10);
+        test.client.identity.id = 1;
+        test.client.identity.session_id = 10;
+        test.connect();
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.identity.is_linked());
+
+        // both ends are on the session's mask now
+        assert_eq!(0, test.client_send_reliable(1, 8).error);
+        assert_eq!(8, test.server_receive().length);
+        assert_eq!(0, test.server_send_reliable(1, 8).error);
+        assert_eq!(8, test.client_receive().length);
+    }
+}
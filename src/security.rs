@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use super::{network_address::NetworkAddress, Tachyon};
+
+// a server created a connection for an address it had no connection for
+pub const SECURITY_NEW_ADDRESS: u8 = 1;
+// value is the identity id
+pub const SECURITY_IDENTITY_LINK_FAILED: u8 = 2;
+// value is the message type
+pub const SECURITY_MALFORMED_PACKET: u8 = 3;
+// the first drop of an address within a second, value is the datagram length
+pub const SECURITY_RATE_LIMITED: u8 = 4;
+pub const SECURITY_MUTED: u8 = 5;
+// value is the ADDRESS_LIST_x that rejected it
+pub const SECURITY_ADDRESS_REJECTED: u8 = 6;
+pub const SECURITY_CHALLENGE_FAILED: u8 = 7;
+// the first connection TachyonConfig.connect_limit turned away within a second, value is the prefix bits when the
+// prefix limit did and 0 for the global one
+pub const SECURITY_CONNECT_THROTTLED: u8 = 8;
+// a server with TachyonConfig.peers_only got a datagram from an address that isn't a peer
+pub const SECURITY_NOT_PEER: u8 = 9;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct SecurityEvent {
+    pub kind: u8,
+    pub address: NetworkAddress,
+    pub value: u32,
+    // Tachyon::time_since_start when it happened
+    pub time: u64,
+}
+
+pub type SecurityEventCallback = unsafe extern "C" fn(event: SecurityEvent);
+
+// Events go to the callback when one is set, otherwise to the queue if it has a capacity.  A full queue drops new
+// events and counts them, so a flood can't grow it.
+#[derive(Default)]
+pub struct SecurityEvents {
+    pub callback: Option<SecurityEventCallback>,
+    queue: VecDeque<SecurityEvent>,
+    capacity: usize,
+    pub dropped: u64,
+}
+
+impl SecurityEvents {
+    pub fn is_enabled(&self) -> bool {
+        return self.callback.is_some() || self.capacity > 0;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.queue.len();
+    }
+}
+
+impl Tachyon {
+    pub fn set_security_event_callback(&mut self, callback: Option<SecurityEventCallback>) {
+        self.security_events.callback = callback;
+    }
+
+    // queue up to capacity events for take_security_event, 0 stops queuing and clears the queue
+    pub fn set_security_event_capacity(&mut self, capacity: usize) {
+        self.security_events.capacity = capacity;
+        self.security_events.queue.truncate(capacity);
+    }
+
+    pub fn take_security_event(&mut self) -> Option<SecurityEvent> {
+        return self.security_events.queue.pop_front();
+    }
+
+    pub fn fire_security_event(&mut self, kind: u8, address: NetworkAddress, value: u32) {
+        if !self.security_events.is_enabled() {
+            return;
+        }
+        let event = SecurityEvent { kind, address, value, time: self.time_since_start() };
+
+        if let Some(callback) = self.security_events.callback {
+            unsafe {
+                callback(event);
+            }
+            return;
+        }
+        if self.security_events.queue.len() >= self.security_events.capacity {
+            self.security_events.dropped += 1;
+            return;
+        }
+        self.security_events.queue.push_back(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{
+        address_filter::{AddressRange, ADDRESS_LIST_DENY},
+        header::{MESSAGE_TYPE_NACK, MESSAGE_TYPE_RELIABLE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_UNRELIABLE_SEQUENCED},
+        tachyon_test::TachyonTest,
+        TachyonConfig,
+    };
+
+    use super::{SECURITY_ADDRESS_REJECTED, SECURITY_IDENTITY_LINK_FAILED, SECURITY_MALFORMED_PACKET, SECURITY_NEW_ADDRESS};
+
+    #[test]
+    #[serial]
+    fn test_security_events() {
+        let mut test = TachyonTest::default();
+        test.server.set_security_event_capacity(2);
+        test.connect();
+
+        test.client_send_unreliable(32);
+        test.server_receive();
+        let event = test.server.take_security_event().unwrap();
+        assert_eq!(SECURITY_NEW_ADDRESS, event.kind);
+        assert!(test.remote_client() == event.address);
+        assert!(test.server.take_security_event().is_none());
+
+        // a full queue drops new ones
+        test.server.address_filter.deny(AddressRange::single(test.address));
+        for _ in 0..3 {
+            test.client_send_unreliable(32);
+        }
+        test.server_receive();
+        assert_eq!(2, test.server.security_events.len());
+        assert_eq!(1, test.server.security_events.dropped);
+        let event = test.server.take_security_event().unwrap();
+        assert_eq!(SECURITY_ADDRESS_REJECTED, event.kind);
+        assert_eq!(ADDRESS_LIST_DENY as u32, event.value);
+    }
+
+    #[test]
+    #[serial]
+    fn test_identity_link_failed() {
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut test = TachyonTest::default();
+        test.server.config = config;
+        test.client.config = config;
+        test.server.set_security_event_capacity(10);
+        test.server.set_identity(1, 10);
+        test.client.identity.id = 1;
+        test.client.identity.session_id = 11;
+        test.connect();
+
+        test.client.update();
+        test.server_receive();
+        let event = test.server.take_security_event().unwrap();
+        assert_eq!(SECURITY_IDENTITY_LINK_FAILED, event.kind);
+        assert_eq!(1, event.value);
+    }
+    #[test]
+    #[serial]
+    fn test_truncated_datagrams() {
+        let mut test = TachyonTest::default();
+        test.server.set_security_event_capacity(10);
+        assert!(test.server.bind(test.address));
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let datagrams: [&[u8]; 5] = [
+            &[MESSAGE_TYPE_RELIABLE, 1],
+            &[MESSAGE_TYPE_RELIABLE_WITH_NACK, 1, 1, 0, 9],
+            &[MESSAGE_TYPE_UNRELIABLE_SEQUENCED, 1],
+            // the nack count's varint runs past the end
+            &[MESSAGE_TYPE_NACK, 1, 0, 0, 250, 1],
+            &[MESSAGE_TYPE_UNRELIABLE, 7],
+        ];
+        for datagram in datagrams {
+            socket.send_to(datagram, test.address.to_socket_addr()).unwrap();
+        }
+
+        let res = test.server_receive();
+        assert_eq!(1, res.length);
+        assert_eq!(7, test.receive_buffer[0]);
+
+        assert_eq!(SECURITY_NEW_ADDRESS, test.server.take_security_event().unwrap().kind);
+        for message_type in [MESSAGE_TYPE_RELIABLE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_UNRELIABLE_SEQUENCED] {
+            let event = test.server.take_security_event().unwrap();
+            assert_eq!(SECURITY_MALFORMED_PACKET, event.kind);
+            assert_eq!(message_type as u32, event.value);
+        }
+        assert!(test.server.take_security_event().is_none());
+    }
+}
@@ -1,62 +1,114 @@
-use std::time::Instant;
+use alloc::{sync::Arc, vec::Vec};
 
-use super::{sequence::Sequence, sequence_buffer::SequenceBuffer, byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT}};
+use super::{clock::{default_clock, Clock}, time::{Duration, Instant}, sequence::{Sequence, SEQUENCE_WINDOW_MAX}, sequence_buffer::SequenceBuffer, byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT}};
 
-const SEND_BUFFER_SIZE: u16 = 1024;
+pub const SEND_BUFFER_SIZE: u16 = 1024;
+// ChannelConfig.send_window_size can go up to this for links with a lot in flight
+pub const SEND_WINDOW_SIZE_MAX: u32 = SEQUENCE_WINDOW_MAX;
 const EXPIRE: u128 = 5000;
 
 pub struct SendBuffer {
     pub sequence: u16,
     pub byte_buffer: ByteBuffer,
     pub created_at: Instant,
+    // past this it's abandoned instead of resent
+    pub expires_at: Option<Instant>,
+    // times it's been resent for a nack
+    pub resends: u32,
+    // never resent, a nack gets a none
+    pub expendable: bool,
 }
+impl SendBuffer {
+    // expendable buffers count too, nothing is going to resend them
+    pub fn is_abandoned(&self, now: Instant) -> bool {
+        if self.expendable {
+            return true;
+        }
+        match self.expires_at {
+            Some(expires_at) => return now > expires_at,
+            None => return false,
+        }
+    }
+}
+
 pub struct SendBufferManager {
     pub current_sequence: u16,
     pub buffers: SequenceBuffer<SendBuffer>,
-    pub buffer_pool: ByteBufferPool
+    pub buffer_pool: ByteBufferPool,
+    pub clock: Arc<dyn Clock>,
+    // applied to buffers created while set
+    pub ttl: Option<Duration>,
+    pub expendable: bool
 }
 
 impl SendBufferManager {
     pub fn default() -> Self {
-        let mut buffers: SequenceBuffer<SendBuffer> = SequenceBuffer {
-            values: Vec::new(),
-            partition_by: SEND_BUFFER_SIZE,
-        };
-        for _ in 0..SEND_BUFFER_SIZE {
-            buffers.values.push(None);
-        }
+        return SendBufferManager::create(SEND_BUFFER_SIZE, SEND_BUFFER_SIZE as usize);
+    }
 
+    // window_size is rounded up to a power of two so sequence wraparound stays on the same slots.  Slots are allocated
+    // on the first send.
+    pub fn create(window_size: u16, pool_size: usize) -> Self {
+        let window_size = window_size.clamp(1, SEND_WINDOW_SIZE_MAX as u16).next_power_of_two();
         let sender = SendBufferManager {
             current_sequence: 0,
-            buffers,
-            buffer_pool: ByteBufferPool::create(BYTE_BUFFER_SIZE_DEFAULT,SEND_BUFFER_SIZE as usize)
+            buffers: SequenceBuffer::create(window_size),
+            buffer_pool: ByteBufferPool::create(BYTE_BUFFER_SIZE_DEFAULT, pool_size),
+            clock: default_clock(),
+            ttl: None,
+            expendable: false
         };
         return sender;
     }
 
+    // None if the slot has since been reused by a newer sequence, small windows wrap around quickly
     pub fn get_send_buffer(&mut self, sequence: u16) -> Option<&mut SendBuffer> {
         match self.buffers.get_mut(sequence) {
-            Some(send_buffer) => {
+            Some(send_buffer) if send_buffer.sequence == sequence => {
                 return Some(send_buffer);
             }
-            None => {
+            _ => {
                 return None;
             }
         }
     }
 
+    // false if any of the next count sequences would overwrite a buffer that hasn't expired yet.  There are no acks,
+    // so any buffer younger than the expire time might still get nacked.
+    pub fn has_room(&self, count: u16) -> bool {
+        let now = self.clock.now();
+        let mut sequence = self.current_sequence;
+        for _ in 0..count {
+            sequence = Sequence::next_sequence(sequence);
+            if let Some(buffer) = self.buffers.get(sequence) {
+                if now.saturating_duration_since(buffer.created_at).as_millis() <= EXPIRE && !buffer.is_abandoned(now) {
+                    return false;
+                }
+            }
+        }
+        return true;
+    }
+
     pub fn expire(&mut self) {
         let mut expired: Vec<u16> = Vec::new();
+        let now = self.clock.now();
 
         for value in &self.buffers.values {
             if let Some(buffer) = value {
-                if buffer.created_at.elapsed().as_millis() > EXPIRE {
+                if now.duration_since(buffer.created_at).as_millis() > EXPIRE {
                     expired.push(buffer.sequence);
                 }
             }
         }
         for sequence in expired {
-            self.buffers.remove(sequence);
+            self.remove(sequence);
+        }
+    }
+
+    // the byte buffer goes back to the pool
+    pub fn remove(&mut self, sequence: u16) {
+        if let Some(send_buffer) = self.buffers.take(sequence) {
+            self.buffer_pool.return_buffer(send_buffer.byte_buffer);
         }
     }
 
@@ -66,10 +118,14 @@ impl SendBufferManager {
 
         let byte_buffer = ByteBuffer::create(length);
        
+        let now = self.clock.now();
         let buffer = SendBuffer {
             sequence: self.current_sequence,
             byte_buffer,
-            created_at: Instant::now(),
+            created_at: now,
+            expires_at: self.ttl.map(|ttl| now + ttl),
+            resends: 0,
+            expendable: self.expendable,
         };
 
         self.buffers.insert(self.current_sequence, buffer);
@@ -94,16 +150,24 @@ impl SendBufferManager {
                 self.buffer_pool.return_buffer(send_buffer.byte_buffer);
                 send_buffer.byte_buffer = self.buffer_pool.get_buffer(length);
             }
+            let now = self.clock.now();
             send_buffer.sequence = self.current_sequence;
-            send_buffer.created_at = Instant::now();
+            send_buffer.created_at = now;
+            send_buffer.expires_at = self.ttl.map(|ttl| now + ttl);
+            send_buffer.resends = 0;
+            send_buffer.expendable = self.expendable;
             return self.buffers.insert(self.current_sequence, send_buffer);
         }
 
         let byte_buffer = self.buffer_pool.get_buffer(length);
+        let now = self.clock.now();
         let send_buffer = SendBuffer {
             sequence: self.current_sequence,
             byte_buffer,
-            created_at: Instant::now(),
+            created_at: now,
+            expires_at: self.ttl.map(|ttl| now + ttl),
+            resends: 0,
+            expendable: self.expendable,
         };
         return self.buffers.insert(self.current_sequence, send_buffer);
         
@@ -112,10 +176,9 @@ impl SendBufferManager {
 
 #[cfg(test)]
 mod tests {
-    use std::time::{Duration, Instant};
+    use std::{sync::Arc, time::{Duration, Instant}};
 
-
-    use crate::tachyon::byte_buffer_pool::BYTE_BUFFER_SIZE_DEFAULT;
+    use crate::{byte_buffer_pool::BYTE_BUFFER_SIZE_DEFAULT, clock::MockClock};
 
     use super::SendBufferManager;
 
@@ -164,7 +227,52 @@ mod tests {
         buffer.created_at = now;
 
         assert!(buffers.buffers.is_some(sequence));
+        let pooled = buffers.buffer_pool.len();
         buffers.expire();
         assert!(!buffers.buffers.is_some(sequence));
+        assert_eq!(pooled + 1, buffers.buffer_pool.len());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut buffers = SendBufferManager::default();
+        let sequence = buffers.create_send_buffer(32).unwrap().sequence;
+        let pooled = buffers.buffer_pool.len();
+        buffers.remove(sequence);
+        assert!(!buffers.buffers.is_some(sequence));
+        assert_eq!(pooled + 1, buffers.buffer_pool.len());
+        buffers.remove(sequence);
+        assert_eq!(pooled + 1, buffers.buffer_pool.len());
+    }
+
+    #[test]
+    fn test_expire_mock_clock() {
+        let clock = Arc::new(MockClock::create(Instant::now()));
+        let mut buffers = SendBufferManager::default();
+        buffers.clock = clock.clone();
+        let sequence = buffers.create_send_buffer(32).unwrap().sequence;
+
+        clock.advance(Duration::from_millis(5000));
+        buffers.expire();
+        assert!(buffers.buffers.is_some(sequence));
+
+        clock.advance(Duration::from_millis(1));
+        buffers.expire();
+        assert!(!buffers.buffers.is_some(sequence));
+    }
+
+    #[test]
+    fn test_small_window() {
+        let mut manager = SendBufferManager::create(100, 16);
+        assert_eq!(128, manager.buffers.partition_by);
+        assert!(!manager.buffers.is_allocated());
+
+        let sequence = manager.create_send_buffer(32).unwrap().sequence;
+        assert_eq!(128, manager.buffers.values.len());
+        manager.current_sequence = sequence + 127;
+        manager.create_send_buffer(32);
+        // wrapped around onto the first slot
+        assert!(manager.get_send_buffer(sequence).is_none());
+        assert!(manager.get_send_buffer(sequence + 128).is_some());
     }
 }
@@ -1,3 +1,8 @@
+// Sequences run 0..=65534 and compare by whichever way round is shorter, so two sequences can only be told apart within
+// half of that.  Windows stay within a quarter, receivers keep slots for twice their window.
+pub const SEQUENCE_WINDOW_MAX: u32 = 16384;
+pub const SEQUENCE_SPACE: u32 = 65535;
+
 pub struct Sequence {}
 
 impl Sequence {
@@ -17,7 +22,7 @@ impl Sequence {
     }
 
     pub fn next_sequence(sequence: u16) -> u16 {
-        if sequence >= std::u16::MAX - 1 {
+        if sequence >= u16::MAX - 1 {
             return 0;
         } else {
             return sequence + 1;
@@ -26,16 +31,45 @@ impl Sequence {
 
     pub fn previous_sequence(sequence: u16) -> u16 {
         if sequence == 0 {
-            return std::u16::MAX - 1;
+            return u16::MAX - 1;
+        } else {
+            return sequence - 1;
+        }
+    }
+
+    // the comparisons above for sequences running 0..space, any space that fits in a u32
+    pub fn is_greater_in(s1: u32, s2: u32, space: u32) -> bool {
+        let ahead = Sequence::ahead_in(s1, s2, space);
+        return ahead > 0 && ahead <= space / 2;
+    }
+
+    // how far apart, whichever way round is shorter
+    pub fn distance_in(s1: u32, s2: u32, space: u32) -> u32 {
+        let ahead = Sequence::ahead_in(s1, s2, space);
+        return ahead.min(space - ahead);
+    }
+
+    pub fn next_in(sequence: u32, space: u32) -> u32 {
+        return (sequence + 1) % space;
+    }
+
+    pub fn previous_in(sequence: u32, space: u32) -> u32 {
+        if sequence == 0 {
+            return space - 1;
         } else {
             return sequence - 1;
         }
     }
+
+    // s1 - s2 going forward around the space
+    pub fn ahead_in(s1: u32, s2: u32, space: u32) -> u32 {
+        return ((s1 as u64 + space as u64 - s2 as u64) % space as u64) as u32;
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tachyon::sequence::Sequence;
+    use crate::sequence::{Sequence, SEQUENCE_SPACE};
 
     #[test]
     fn test_basic() {
@@ -49,4 +83,23 @@ mod tests {
 
         assert!(Sequence::is_greater_then(0, 65534));
     }
+
+    #[test]
+    fn test_space() {
+        // the same answers as the 16 bit versions
+        for (s1, s2) in [(0, 65534), (1, 0), (100, 40000), (40000, 100), (5, 5), (32767, 0)] {
+            assert_eq!(Sequence::is_greater_then(s1, s2), Sequence::is_greater_in(s1 as u32, s2 as u32, SEQUENCE_SPACE));
+        }
+        assert_eq!(0, Sequence::next_in(65534, SEQUENCE_SPACE));
+        assert_eq!(65534, Sequence::previous_in(0, SEQUENCE_SPACE));
+
+        let space = u32::MAX;
+        let last = space - 1;
+        assert_eq!(0, Sequence::next_in(last, space));
+        assert_eq!(last, Sequence::previous_in(0, space));
+        assert!(Sequence::is_greater_in(2, last, space));
+        assert!(!Sequence::is_greater_in(last, 2, space));
+        assert_eq!(3, Sequence::distance_in(2, last, space));
+        assert_eq!(3, Sequence::distance_in(last, 2, space));
+    }
 }
@@ -1,14 +1,36 @@
+use alloc::vec::Vec;
+
 pub struct SequenceBuffer<T> {
     pub values: Vec<Option<T>>,
     pub partition_by: u16,
 }
 
 impl<T> SequenceBuffer<T> {
+    // slots are allocated on first insert, so an idle buffer costs nothing
+    pub fn create(partition_by: u16) -> Self {
+        let buffer = SequenceBuffer {
+            values: Vec::new(),
+            partition_by,
+        };
+        return buffer;
+    }
+
+    pub fn is_allocated(&self) -> bool {
+        return self.values.len() > 0;
+    }
+
+    pub fn allocate(&mut self) {
+        if self.values.len() < self.partition_by as usize {
+            self.values.resize_with(self.partition_by as usize, || None);
+        }
+    }
+
     pub fn sequence_to_index(&self, sequence: u16) -> usize {
         return (sequence % self.partition_by) as usize;
     }
 
     pub fn insert(&mut self, sequence: u16, value: T) -> Option<&mut T> {
+        self.allocate();
         let index = self.sequence_to_index(sequence);
         self.values[index] = Some(value);
         return self.values[index].as_mut();
@@ -16,21 +38,23 @@ impl<T> SequenceBuffer<T> {
 
     pub fn remove(&mut self, sequence: u16) {
         let index = self.sequence_to_index(sequence);
-        self.values[index] = None;
+        self.remove_at_index(index);
     }
 
     pub fn remove_at_index(&mut self, index: usize) {
-        self.values[index] = None;
+        if let Some(value) = self.values.get_mut(index) {
+            *value = None;
+        }
     }
 
     pub fn is_some(&self, sequence: u16) -> bool {
         let index = self.sequence_to_index(sequence);
-        return self.values[index].is_some();
+        return self.values.get(index).map_or(false, |value| value.is_some());
     }
 
     pub fn take(&mut self, sequence: u16) -> Option<T> {
         let index = self.sequence_to_index(sequence);
-        return self.values[index].take();
+        return self.values.get_mut(index).and_then(|value| value.take());
     }
 
     pub fn get(&self, sequence: u16) -> Option<&T> {
@@ -74,7 +98,7 @@ impl<T> SequenceBuffer<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::tachyon::sequence_buffer::SequenceBuffer;
+    use crate::sequence_buffer::SequenceBuffer;
 
     #[test]
     fn basic_test() {
@@ -88,4 +112,19 @@ mod tests {
         let option = buffer.get(1);
         assert!(option.is_some());
     }
+
+    #[test]
+    fn test_lazy() {
+        let mut buffer: SequenceBuffer<u16> = SequenceBuffer::create(64);
+        assert!(!buffer.is_allocated());
+        assert!(!buffer.is_some(3));
+        assert!(buffer.take(3).is_none());
+        buffer.remove(3);
+        assert!(!buffer.is_allocated());
+
+        buffer.insert(67, 67);
+        assert!(buffer.is_allocated());
+        assert_eq!(64, buffer.values.len());
+        assert_eq!(Some(&67), buffer.get(3));
+    }
 }
@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// what happened to a sequence, SequenceEvent.event
+pub const TRACE_SENT: u8 = 1;
+pub const TRACE_FRAGMENT_SENT: u8 = 2;
+pub const TRACE_NACK_RECEIVED: u8 = 3;
+pub const TRACE_RESENT: u8 = 4;
+pub const TRACE_NONE_SENT: u8 = 5;
+pub const TRACE_NACK_SENT: u8 = 6;
+pub const TRACE_RECEIVED: u8 = 7;
+// duplicate or outside the receive window
+pub const TRACE_REJECTED: u8 = 8;
+pub const TRACE_NONE_RECEIVED: u8 = 9;
+pub const TRACE_PUBLISHED: u8 = 10;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct SequenceEvent {
+    // since the channel's first traced event
+    pub millis: u32,
+    pub sequence: u16,
+    pub event: u8,
+}
+
+// The last capacity events of a channel's sequences, on both the send and receive side, for working out where a
+// message that never arrived went missing.  Off unless ChannelConfig.trace_events is set, see
+// Tachyon::get_sequence_trace.
+pub struct SequenceTrace {
+    events: VecDeque<SequenceEvent>,
+    capacity: usize,
+    start: Option<Instant>,
+}
+
+impl SequenceTrace {
+    pub fn create(capacity: usize) -> Self {
+        let trace = SequenceTrace {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            start: None,
+        };
+        return trace;
+    }
+
+    pub fn record(&mut self, event: u8, sequence: u16, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        let start = *self.start.get_or_insert(now);
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(SequenceEvent {
+            millis: now.saturating_duration_since(start).as_millis() as u32,
+            sequence,
+            event,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        return self.events.len();
+    }
+
+    // oldest first
+    pub fn events(&self) -> Vec<SequenceEvent> {
+        return self.events.iter().copied().collect();
+    }
+
+    pub fn events_for(&self, sequence: u16) -> Vec<SequenceEvent> {
+        return self.events.iter().filter(|event| event.sequence == sequence).copied().collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{SequenceTrace, TRACE_NACK_RECEIVED, TRACE_RESENT, TRACE_SENT};
+
+    #[test]
+    fn test_record() {
+        let now = Instant::now();
+        let mut trace = SequenceTrace::create(3);
+        trace.record(TRACE_SENT, 1, now);
+        trace.record(TRACE_SENT, 2, now);
+        trace.record(TRACE_NACK_RECEIVED, 1, now + Duration::from_millis(30));
+        trace.record(TRACE_RESENT, 1, now + Duration::from_millis(40));
+
+        // the oldest fell off
+        assert_eq!(3, trace.len());
+        assert_eq!(2, trace.events()[0].sequence);
+        let events = trace.events_for(1);
+        assert_eq!(2, events.len());
+        assert_eq!(TRACE_RESENT, events[1].event);
+        assert_eq!(40, events[1].millis);
+    }
+}
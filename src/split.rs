@@ -0,0 +1,201 @@
+use crossbeam::channel::{unbounded, Receiver, Sender};
+
+use super::{
+    network_address::NetworkAddress, pool::SendTarget, receive_result::TachyonReceiveResult,
+    unreliable_sender::UnreliableSender, Tachyon, TachyonSendResult,
+};
+
+struct QueuedSend {
+    channel: u16,
+    target: SendTarget,
+    data: Vec<u8>,
+}
+
+// Producer side of a split Tachyon.  Clone one per worker thread.  Reliable sends go on a queue that the ReceiveHalf
+// drains on update, since they need channel state.  Unreliable sends go straight out over a cloned socket like
+// UnreliableSender, or through the queue when there is no udp socket (custom transports).
+pub struct SendHalf {
+    queue: Sender<QueuedSend>,
+    unreliable_sender: Option<UnreliableSender>,
+}
+
+impl Clone for SendHalf {
+    fn clone(&self) -> Self {
+        let unreliable_sender = match &self.unreliable_sender {
+            Some(sender) => sender.try_clone(),
+            None => None,
+        };
+        return SendHalf {
+            queue: self.queue.clone(),
+            unreliable_sender,
+        };
+    }
+}
+
+impl SendHalf {
+    // false if the ReceiveHalf is gone
+    pub fn send_reliable(&self, channel_id: u16, address: NetworkAddress, data: &[u8]) -> bool {
+        let target = SendTarget { identity_id: 0, address };
+        return self.send_to_target(channel_id, target, data);
+    }
+
+    // queued sends are validated when the ReceiveHalf flushes them, failures show up in its queued_send_errors
+    pub fn send_to_target(&self, channel_id: u16, target: SendTarget, data: &[u8]) -> bool {
+        let queued = QueuedSend {
+            channel: channel_id,
+            target,
+            data: data.to_vec(),
+        };
+        return self.queue.send(queued).is_ok();
+    }
+
+    // straight out over the socket when there is one, otherwise queued as a reliable send to the identity on channel 0
+    pub fn send_unreliable_to_identity(&mut self, identity_id: u32, data: &[u8], body_len: usize) -> TachyonSendResult {
+        match &mut self.unreliable_sender {
+            Some(sender) => {
+                return sender.send_to_identity(identity_id, data, body_len);
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                let target = SendTarget { identity_id, address: NetworkAddress::default() };
+                if !self.send_to_target(0, target, &data[0..body_len]) {
+                    result.error = super::SEND_ERROR_SOCKET;
+                }
+                return result;
+            }
+        }
+    }
+
+    pub fn send_unreliable(&mut self, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        match &mut self.unreliable_sender {
+            Some(sender) => {
+                return sender.send(address, data, body_len);
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                if !self.send_reliable(0, address, &data[0..body_len]) {
+                    result.error = super::SEND_ERROR_SOCKET;
+                }
+                return result;
+            }
+        }
+    }
+}
+
+// Consumer side of a split Tachyon, owns the instance and everything that needs the protocol state.  Stays on one thread.
+pub struct ReceiveHalf {
+    pub tachyon: Tachyon,
+    queue: Receiver<QueuedSend>,
+    pub queued_sent: u64,
+    pub queued_send_errors: u64,
+}
+
+impl ReceiveHalf {
+    pub fn receive(&mut self, receive_buffer: &mut [u8]) -> TachyonReceiveResult {
+        return self.tachyon.receive_loop(receive_buffer);
+    }
+
+    // sends everything the SendHalves have queued so far, returns how many were sent
+    pub fn flush_sends(&mut self) -> u32 {
+        let mut count = 0;
+        while let Ok(queued) = self.queue.try_recv() {
+            let result = self.tachyon.send_to_target(queued.channel, queued.target, &queued.data, queued.data.len());
+            if result.error == 0 {
+                self.queued_sent += 1;
+                count += 1;
+            } else {
+                self.queued_send_errors += 1;
+            }
+        }
+        return count;
+    }
+
+    pub fn update(&mut self) {
+        self.flush_sends();
+        self.tachyon.update();
+    }
+}
+
+impl Tachyon {
+    // call after bind/connect.  Unreliable sends from the SendHalf skip middleware, the same as UnreliableSender.
+    pub fn split(self) -> (SendHalf, ReceiveHalf) {
+        let (sender, receiver) = unbounded();
+        let unreliable_sender = self.create_unreliable_sender();
+        let send_half = SendHalf {
+            queue: sender,
+            unreliable_sender,
+        };
+        let receive_half = ReceiveHalf {
+            tachyon: self,
+            queue: receiver,
+            queued_sent: 0,
+            queued_send_errors: 0,
+        };
+        return (send_half, receive_half);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{channel::ChannelConfig, network_address::NetworkAddress, Tachyon, TachyonConfig};
+
+    #[test]
+    #[serial]
+    fn test_split() {
+        let address = NetworkAddress::localhost(8193);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        server.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(server.bind(address));
+
+        let mut client = Tachyon::create(TachyonConfig::default());
+        client.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(client.connect(address));
+        let (send_half, mut receive_half) = client.split();
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let mut send_half = send_half.clone();
+                std::thread::spawn(move || {
+                    let data: Vec<u8> = vec![i; 32];
+                    for _ in 0..10 {
+                        assert!(send_half.send_reliable(3, NetworkAddress::default(), &data));
+                        assert_eq!(0, send_half.send_unreliable(NetworkAddress::default(), &data, 16).error);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // nothing reliable goes out until the receive half flushes
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let mut unreliable = 0;
+        while server.receive_loop(&mut receive_buffer).length > 0 {
+            unreliable += 1;
+        }
+        assert_eq!(40, unreliable);
+
+        assert!(send_half.send_reliable(4, NetworkAddress::default(), &[1]));
+        receive_half.update();
+        assert_eq!(40, receive_half.queued_sent);
+        assert_eq!(1, receive_half.queued_send_errors);
+
+        let mut reliable = 0;
+        loop {
+            let res = server.receive_loop(&mut receive_buffer);
+            if res.length == 0 {
+                break;
+            }
+            assert_eq!(3, res.channel);
+            assert_eq!(32, res.length);
+            reliable += 1;
+        }
+        assert_eq!(40, reliable);
+
+        drop(receive_half);
+        assert!(!send_half.send_reliable(3, NetworkAddress::default(), &[1]));
+    }
+}
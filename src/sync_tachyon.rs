@@ -0,0 +1,253 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHasher;
+
+use super::{
+    network_address::NetworkAddress, receive_result::TachyonReceiveResult, unreliable_sender::UnreliableSender, Tachyon,
+    TachyonSendResult,
+};
+
+// Concurrency model
+//
+// Tachyon is Send but not Sync.  Every method takes &mut self and the reliable send path, receive and update all share
+// channel state, so a Tachyon is owned by one thread at a time.  Pool works this way, whole servers are moved through
+// an ArrayQueue to the rayon task receiving for them.  UnreliableSender/PoolUnreliableSender are the existing way to
+// send unreliable messages from other threads, over a cloned socket.
+//
+// SyncTachyon is for sharing one instance between threads without building that yourself.  It is Send + Sync and
+// splits locking in two:
+// - the Tachyon lock, taken by reliable sends, receive, update and lock() for everything else
+// - a set of unreliable sender shards with their own locks.  Threads pick a shard by thread id, so plain unreliable
+//   sends from workers don't wait on a receive or on each other.
+// Sharded unreliable sends skip middleware and the per address unreliable stats, the same as UnreliableSender.  They
+// are only used on a bound/connected udp socket with identities and sequenced unreliable turned off, otherwise
+// unreliable sends take the Tachyon lock like everything else.
+pub struct SyncTachyon {
+    tachyon: Mutex<Tachyon>,
+    unreliable_senders: Vec<Mutex<UnreliableSender>>,
+}
+
+impl SyncTachyon {
+    // call after bind/connect and channel configuration, shard_count 0 sends everything through the Tachyon lock
+    pub fn create(tachyon: Tachyon, shard_count: usize) -> Self {
+        let mut unreliable_senders: Vec<Mutex<UnreliableSender>> = Vec::new();
+        let shardable = tachyon.config.use_identity == 0 && !tachyon.unreliable_channel_config.is_sequenced();
+        if shardable {
+            for _ in 0..shard_count {
+                match tachyon.create_unreliable_sender() {
+                    Some(sender) => unreliable_senders.push(Mutex::new(sender)),
+                    None => break,
+                }
+            }
+        }
+
+        return SyncTachyon {
+            tachyon: Mutex::new(tachyon),
+            unreliable_senders,
+        };
+    }
+
+    pub fn shard_count(&self) -> usize {
+        return self.unreliable_senders.len();
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Tachyon> {
+        return self.tachyon.lock().unwrap();
+    }
+
+    pub fn into_inner(self) -> Tachyon {
+        return self.tachyon.into_inner().unwrap();
+    }
+
+    pub fn send_reliable(&self, channel_id: u16, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        return self.lock().send_reliable(channel_id, address, data, body_len);
+    }
+
+    pub fn send_unreliable(&self, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        if self.unreliable_senders.len() == 0 {
+            return self.lock().send_unreliable(address, data, body_len);
+        }
+
+        let mut hasher = FxHasher::default();
+        std::thread::current().id().hash(&mut hasher);
+        let start = hasher.finish() as usize % self.unreliable_senders.len();
+
+        // an idle shard if there is one, otherwise wait on our own
+        for i in 0..self.unreliable_senders.len() {
+            let index = (start + i) % self.unreliable_senders.len();
+            if let Ok(mut sender) = self.unreliable_senders[index].try_lock() {
+                return sender.send(address, data, body_len);
+            }
+        }
+        return self.unreliable_senders[start].lock().unwrap().send(address, data, body_len);
+    }
+
+    pub fn receive(&self, receive_buffer: &mut [u8]) -> TachyonReceiveResult {
+        return self.lock().receive_loop(receive_buffer);
+    }
+
+    pub fn update(&self) {
+        self.lock().update();
+    }
+
+    // see UpdateThread
+    pub fn start_update_thread(self: &Arc<Self>, tick: Duration) -> UpdateThread {
+        return UpdateThread::start(self.clone(), tick);
+    }
+}
+
+// For applications without a main loop to call update from.  A thread that takes the Tachyon lock every tick and runs
+// update, until stop() or drop.  Ticks are scheduled from the start time so a slow update doesn't push the rest back,
+// ticks that are already past are dropped rather than run back to back.
+pub struct UpdateThread {
+    stop: Arc<AtomicBool>,
+    ticks: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UpdateThread {
+    pub fn start(tachyon: Arc<SyncTachyon>, tick: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let ticks = Arc::new(AtomicU64::new(0));
+        let thread_stop = stop.clone();
+        let thread_ticks = ticks.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut next = Instant::now();
+            while !thread_stop.load(Ordering::Acquire) {
+                tachyon.update();
+                thread_ticks.fetch_add(1, Ordering::Relaxed);
+
+                next += tick;
+                let now = Instant::now();
+                if next < now {
+                    next = now;
+                    continue;
+                }
+                std::thread::park_timeout(next - now);
+            }
+        });
+
+        return UpdateThread {
+            stop,
+            ticks,
+            handle: Some(handle),
+        };
+    }
+
+    // updates run so far
+    pub fn ticks(&self) -> u64 {
+        return self.ticks.load(Ordering::Relaxed);
+    }
+
+    // waits for an update in progress to finish
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for UpdateThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use serial_test::serial;
+
+    use crate::{channel::ChannelConfig, network_address::NetworkAddress, Tachyon, TachyonConfig};
+
+    use super::{SyncTachyon, UpdateThread};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    #[serial]
+    fn test_sync_tachyon() {
+        assert_send_sync::<SyncTachyon>();
+
+        let address = NetworkAddress::localhost(8192);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        server.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(server.bind(address));
+
+        let mut client = Tachyon::create(TachyonConfig::default());
+        client.configure_channel(3, ChannelConfig::default_ordered());
+        assert!(client.connect(address));
+        let client = Arc::new(SyncTachyon::create(client, 4));
+        assert_eq!(4, client.shard_count());
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let client = client.clone();
+                std::thread::spawn(move || {
+                    let data: Vec<u8> = vec![i; 32];
+                    for _ in 0..25 {
+                        assert_eq!(0, client.send_unreliable(NetworkAddress::default(), &data, 32).error);
+                        assert_eq!(0, client.send_reliable(3, NetworkAddress::default(), &data, 32).error);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let mut unreliable = 0;
+        let mut reliable = 0;
+        for _ in 0..1000 {
+            let res = server.receive_loop(&mut receive_buffer);
+            if res.length == 0 {
+                break;
+            }
+            if res.channel == 0 {
+                unreliable += 1;
+            } else {
+                reliable += 1;
+            }
+        }
+        assert_eq!(100, reliable);
+        assert!(unreliable > 0);
+
+        // identities force everything through the lock
+        let mut config = TachyonConfig::default();
+        config.use_identity = 1;
+        let mut identity_client = Tachyon::create(config);
+        identity_client.connect(address);
+        assert_eq!(0, SyncTachyon::create(identity_client, 4).shard_count());
+    }
+
+    #[test]
+    fn test_update_thread() {
+        let mut config = TachyonConfig::default();
+        config.update_interval_millis = 5;
+        let tachyon = Arc::new(SyncTachyon::create(Tachyon::create(config), 0));
+        let mut thread = tachyon.start_update_thread(Duration::from_millis(1));
+        while thread.ticks() < 5 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        thread.stop();
+        let ticks = thread.ticks();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(ticks, thread.ticks());
+
+        let thread = UpdateThread::start(tachyon.clone(), Duration::from_millis(1));
+        drop(thread);
+    }
+}
@@ -1,22 +1,88 @@
 use std::{
-    io,
+    cell::{Cell, RefCell},
+    fmt, io,
+    mem::MaybeUninit,
     net::{Ipv4Addr, SocketAddrV4, UdpSocket},
 };
 
-use rand::{prelude::StdRng, Rng, SeedableRng};
-use socket2::{Domain, Socket, Type};
+use socket2::{Domain, MaybeUninitSlice, SockRef, Socket, Type};
 
 use super::{
-    header::{MESSAGE_TYPE_RELIABLE},
-    int_buffer::IntBuffer,
-    network_address::NetworkAddress
+    checksum::crc32c,
+    drop_simulation::{DropSimulationConfig, DropSimulator, DROP_SEED_DEFAULT},
+    header::{wide_channel_header, CHECKSUM_SIZE, PROTOCOL_MAGIC, PROTOCOL_PREFIX_SIZE, PROTOCOL_VERSION, TACHYON_WIDE_CHANNEL_HEADER_SIZE},
+    middleware::{MiddlewareState, PacketMiddleware},
+    network_address::NetworkAddress,
+    scramble::Scrambler,
+    transport::Transport
 };
 
-pub enum CreateConnectResult {
-    Success,
-    Error,
+pub const SOCKET_ERROR_ALREADY_OPEN: i32 = 1;
+pub const SOCKET_ERROR_ADDRESS_IN_USE: i32 = 2;
+pub const SOCKET_ERROR_ADDRESS_NOT_AVAILABLE: i32 = 3;
+pub const SOCKET_ERROR_PERMISSION_DENIED: i32 = 4;
+pub const SOCKET_ERROR_CONNECTION_REFUSED: i32 = 5;
+pub const SOCKET_ERROR_WOULD_BLOCK: i32 = 6;
+pub const SOCKET_ERROR_OTHER: i32 = 7;
+
+// What went wrong in a socket call.  Keeps the io::Error's kind and os error code instead of the error itself so it's
+// Copy and can be stashed away as the last error of a send.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TachyonSocketError {
+    // bind or connect on a socket that's already open
+    AlreadyOpen,
+    Io {
+        kind: io::ErrorKind,
+        raw_os_error: Option<i32>,
+    },
+}
+
+impl TachyonSocketError {
+    // SOCKET_ERROR_x, what the ffi returns negated
+    pub fn code(&self) -> i32 {
+        match self {
+            TachyonSocketError::AlreadyOpen => return SOCKET_ERROR_ALREADY_OPEN,
+            TachyonSocketError::Io { kind, .. } => match kind {
+                io::ErrorKind::AddrInUse => return SOCKET_ERROR_ADDRESS_IN_USE,
+                io::ErrorKind::AddrNotAvailable => return SOCKET_ERROR_ADDRESS_NOT_AVAILABLE,
+                io::ErrorKind::PermissionDenied => return SOCKET_ERROR_PERMISSION_DENIED,
+                io::ErrorKind::ConnectionRefused => return SOCKET_ERROR_CONNECTION_REFUSED,
+                io::ErrorKind::WouldBlock => return SOCKET_ERROR_WOULD_BLOCK,
+                _ => return SOCKET_ERROR_OTHER,
+            },
+        }
+    }
+
+    // 0 if there isn't one
+    pub fn raw_os_error(&self) -> i32 {
+        match self {
+            TachyonSocketError::AlreadyOpen => return 0,
+            TachyonSocketError::Io { raw_os_error, .. } => return raw_os_error.unwrap_or(0),
+        }
+    }
 }
 
+impl From<io::Error> for TachyonSocketError {
+    fn from(error: io::Error) -> Self {
+        return TachyonSocketError::Io {
+            kind: error.kind(),
+            raw_os_error: error.raw_os_error(),
+        };
+    }
+}
+
+impl fmt::Display for TachyonSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TachyonSocketError::AlreadyOpen => return f.write_str("socket is already open"),
+            TachyonSocketError::Io { kind, raw_os_error: Some(code) } => return write!(f, "{} (os error {})", kind, code),
+            TachyonSocketError::Io { kind, raw_os_error: None } => return write!(f, "{}", kind),
+        }
+    }
+}
+
+impl std::error::Error for TachyonSocketError {}
+
 pub enum SocketReceiveResult {
     Success {
         bytes_received: usize,
@@ -25,12 +91,64 @@ pub enum SocketReceiveResult {
     Empty,
     Error,
     Dropped,
+    // no protocol magic, or a version other than ours
+    Rejected {
+        version_mismatch: bool,
+    },
+    // the checksum didn't match
+    Corrupted,
+}
+
+// what goes in front of the header with the protocol magic and/or checksum on
+pub fn datagram_prefix_len(magic: bool, checksum: bool) -> usize {
+    let mut length = 0;
+    if magic {
+        length += PROTOCOL_PREFIX_SIZE;
+    }
+    if checksum {
+        length += CHECKSUM_SIZE;
+    }
+    return length;
+}
+
+// the packet is already in buffer after datagram_prefix_len bytes
+pub fn write_datagram_prefix(buffer: &mut [u8], magic: bool, checksum: bool, packet_len: usize) {
+    let mut index = 0;
+    if magic {
+        buffer[0..4].copy_from_slice(&PROTOCOL_MAGIC);
+        buffer[4] = PROTOCOL_VERSION;
+        index += PROTOCOL_PREFIX_SIZE;
+    }
+    if checksum {
+        let start = index + CHECKSUM_SIZE;
+        let crc = crc32c(&buffer[start..start + packet_len]);
+        buffer[index..start].copy_from_slice(&crc.to_le_bytes());
+    }
 }
 pub struct TachyonSocket {
     pub address: NetworkAddress,
     pub is_server: bool,
     pub socket: Option<UdpSocket>,
-    pub rng: StdRng
+    // simulated loss, see drop_simulation.rs.  send_to takes &self like for middleware.
+    pub inbound_drops: DropSimulator,
+    pub outbound_drops: RefCell<DropSimulator>,
+    // send_to takes &self, middleware is only ever touched from the thread owning the socket
+    pub middleware: Option<RefCell<MiddlewareState>>,
+    // replaces the UdpSocket when set
+    pub transport: Option<Box<dyn Transport>>,
+    // the most recent failed send, sends return 0 bytes and leave the reason here
+    pub last_error: Cell<Option<TachyonSocketError>>,
+    // TachyonConfig.use_protocol_magic and use_checksum
+    pub protocol_magic: bool,
+    pub checksum: bool,
+    // set when datagrams carry a prefix, sends are copied here behind it
+    pub prefix_buffer: Option<RefCell<Vec<u8>>>,
+    // TachyonConfig.scramble_key
+    pub scrambler: Option<Scrambler>,
+    // TachyonConfig.reuse_port
+    pub reuse_port: bool,
+    // sends on channels above 255 are copied here behind the wide channel header
+    pub wide_buffer: RefCell<Vec<u8>>,
 }
 
 impl TachyonSocket {
@@ -39,11 +157,79 @@ impl TachyonSocket {
             address: NetworkAddress::default(),
             is_server: false,
             socket: None,
-            rng: SeedableRng::seed_from_u64(32634)
+            inbound_drops: DropSimulator::create(DROP_SEED_DEFAULT),
+            outbound_drops: RefCell::new(DropSimulator::create(DROP_SEED_DEFAULT + 1)),
+            middleware: None,
+            transport: None,
+            last_error: Cell::new(None),
+            protocol_magic: false,
+            checksum: false,
+            prefix_buffer: None,
+            scrambler: None,
+            reuse_port: false,
+            wide_buffer: RefCell::new(Vec::new()),
         };
         return socket;
     }
 
+    pub fn set_protocol_magic(&mut self, enabled: bool) {
+        self.protocol_magic = enabled;
+        self.update_prefix_buffer();
+    }
+
+    pub fn set_checksum(&mut self, enabled: bool) {
+        self.checksum = enabled;
+        self.update_prefix_buffer();
+    }
+
+    // 0 turns it off
+    pub fn set_scramble_key(&mut self, key: u64) {
+        if key == 0 {
+            self.scrambler = None;
+        } else {
+            self.scrambler = Some(Scrambler::create(key));
+        }
+    }
+
+    // session 0 goes back to the key alone
+    pub fn set_scramble_session(&mut self, address: NetworkAddress, session_id: u32) {
+        if let Some(scrambler) = &mut self.scrambler {
+            scrambler.set_session(address, session_id);
+        }
+    }
+
+    fn update_prefix_buffer(&mut self) {
+        let prefix_len = datagram_prefix_len(self.protocol_magic, self.checksum);
+        if prefix_len > 0 {
+            self.prefix_buffer = Some(RefCell::new(vec![0; prefix_len + 1024 * 64]));
+        } else {
+            self.prefix_buffer = None;
+        }
+    }
+
+    pub fn set_middleware(&mut self, middleware: Box<dyn PacketMiddleware>) {
+        self.middleware = Some(RefCell::new(MiddlewareState::create(middleware)));
+    }
+
+    pub fn clear_middleware(&mut self) {
+        self.middleware = None;
+    }
+
+    // a bound/connected UdpSocket or a transport
+    pub fn is_open(&self) -> bool {
+        return self.socket.is_some() || self.transport.is_some();
+    }
+
+    pub fn bind_transport(&mut self, transport: Box<dyn Transport>) {
+        self.transport = Some(transport);
+        self.is_server = true;
+    }
+
+    pub fn connect_transport(&mut self, transport: Box<dyn Transport>) {
+        self.transport = Some(transport);
+        self.is_server = false;
+    }
+
     pub fn clone_socket(&self) -> Option<UdpSocket> {
         match &self.socket {
             Some(sock) => {
@@ -53,84 +239,188 @@ impl TachyonSocket {
         }
     }
 
-    pub fn bind_socket(&mut self, naddress: NetworkAddress) -> CreateConnectResult {
+    pub fn bind_socket(&mut self, naddress: NetworkAddress) -> Result<(), TachyonSocketError> {
         if self.socket.is_some() {
-            return CreateConnectResult::Error;
+            return Err(TachyonSocketError::AlreadyOpen);
         }
 
         let address = naddress.to_socket_addr();
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+        if self.reuse_port {
+            TachyonSocket::set_reuse_port(&socket)?;
+        }
+        socket.bind(&address.into())?;
+        socket.set_recv_buffer_size(8192 * 256)?;
+        socket.set_nonblocking(true)?;
+
         self.address = naddress;
+        self.socket = Some(socket.into());
+        self.is_server = true;
+        return Ok(());
+    }
 
-        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
-        match socket.bind(&address.into()) {
-            Ok(()) => {
-                socket.set_recv_buffer_size(8192 * 256).unwrap();
-                socket.set_nonblocking(true).unwrap();
-                self.socket = Some(socket.into());
-                self.is_server = true;
+    #[cfg(target_os = "linux")]
+    fn set_reuse_port(socket: &Socket) -> Result<(), TachyonSocketError> {
+        socket.set_reuse_port(true)?;
+        return Ok(());
+    }
 
-                return CreateConnectResult::Success;
-            }
-            Err(_) => {
-                return CreateConnectResult::Error;
-            }
-        }
+    // other platforms don't balance across sockets sharing a port
+    #[cfg(not(target_os = "linux"))]
+    fn set_reuse_port(_socket: &Socket) -> Result<(), TachyonSocketError> {
+        return Err(io::Error::from(io::ErrorKind::Unsupported).into());
     }
 
-    pub fn connect_socket(&mut self, naddress: NetworkAddress) -> CreateConnectResult {
+    pub fn connect_socket(&mut self, naddress: NetworkAddress) -> Result<(), TachyonSocketError> {
         if self.socket.is_some() {
-            return CreateConnectResult::Error;
+            return Err(TachyonSocketError::AlreadyOpen);
         }
 
-        self.address = NetworkAddress::default();
         let sock_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
-        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
-
-        match socket.bind(&sock_addr.into()) {
-            Ok(()) => {
-                socket.set_recv_buffer_size(8192 * 256).unwrap();
-                socket.set_nonblocking(true).unwrap();
-                let address = naddress.to_socket_addr();
-                let udp_socket: UdpSocket = socket.into();
-
-                match udp_socket.connect(&address) {
-                    Ok(()) => {
-                        self.socket = Some(udp_socket);
-                        return CreateConnectResult::Success;
-                    }
-                    Err(_) => {
-                        return CreateConnectResult::Error;
-                    }
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+        socket.bind(&sock_addr.into())?;
+        socket.set_recv_buffer_size(8192 * 256)?;
+        socket.set_nonblocking(true)?;
+
+        let udp_socket: UdpSocket = socket.into();
+        udp_socket.connect(naddress.to_socket_addr())?;
+
+        self.address = NetworkAddress::default();
+        self.socket = Some(udp_socket);
+        return Ok(());
+    }
+
+    // the last failed send's error, cleared
+    pub fn take_last_error(&self) -> Option<TachyonSocketError> {
+        return self.last_error.take();
+    }
+
+    // TachyonConfig.drop_packet_chance is the inbound chance.  Starts both directions over from the seed.
+    pub fn set_drop_simulation(&mut self, inbound_chance: u64, reliable_only: bool, config: DropSimulationConfig) {
+        let seed = config.seed();
+        self.inbound_drops = DropSimulator::create(seed);
+        self.inbound_drops.chance = inbound_chance;
+        self.inbound_drops.reliable_only = reliable_only;
+        self.inbound_drops.burst_length = config.burst_length;
+
+        let mut outbound = DropSimulator::create(seed.wrapping_add(1));
+        outbound.chance = config.outbound_chance;
+        outbound.reliable_only = reliable_only;
+        outbound.burst_length = config.burst_length;
+        self.outbound_drops = RefCell::new(outbound);
+    }
+
+    pub fn receive(&mut self, data: &mut [u8]) -> SocketReceiveResult {
+        let (mut bytes_received, address) = match self.receive_datagram(data) {
+            SocketReceiveResult::Success { bytes_received, network_address } => (bytes_received, network_address),
+            other => {
+                return other;
+            }
+        };
+
+        let prefix_len = datagram_prefix_len(self.protocol_magic, self.checksum);
+        if prefix_len > 0 {
+            let mut index = 0;
+            if self.protocol_magic {
+                if bytes_received < PROTOCOL_PREFIX_SIZE || data[0..4] != PROTOCOL_MAGIC {
+                    return SocketReceiveResult::Rejected { version_mismatch: false };
                 }
+                if data[4] != PROTOCOL_VERSION {
+                    return SocketReceiveResult::Rejected { version_mismatch: true };
+                }
+                index += PROTOCOL_PREFIX_SIZE;
             }
-            Err(_) => {
-                return CreateConnectResult::Error;
+            if self.checksum {
+                if bytes_received < prefix_len {
+                    return SocketReceiveResult::Corrupted;
+                }
+                let expected = u32::from_le_bytes([data[index], data[index + 1], data[index + 2], data[index + 3]]);
+                if crc32c(&data[prefix_len..bytes_received]) != expected {
+                    return SocketReceiveResult::Corrupted;
+                }
             }
+            data.copy_within(prefix_len..bytes_received, 0);
+            bytes_received -= prefix_len;
+        }
+
+        let bytes_received = self.intercept_receive(address, data, bytes_received);
+        if bytes_received == 0 {
+            return SocketReceiveResult::Dropped;
+        }
+        // decided on the datagram middleware handed back, the one the protocol is going to parse
+        if self.inbound_drops.should_drop(&data[0..bytes_received]) {
+            return SocketReceiveResult::Dropped;
         }
+        if let Some(scrambler) = &self.scrambler {
+            scrambler.apply(address, data, bytes_received);
+        }
+        return SocketReceiveResult::Success {
+            bytes_received,
+            network_address: address,
+        };
     }
 
-    fn should_drop(&mut self, data: &mut [u8], drop_chance: u64, drop_reliable_only: bool) -> bool {
-        if drop_chance > 0 {
-            let r = self.rng.gen_range(1..100);
-            if r <= drop_chance {
-                let mut can_drop = true;
-                if drop_reliable_only {
-                    let mut reader = IntBuffer { index: 0 };
-                    let message_type = reader.read_u8(data);
-                    if message_type != MESSAGE_TYPE_RELIABLE {
-                        can_drop = false;
-                    }
-                }
+    // middleware, transports and datagram prefixes need the datagram in one buffer
+    pub fn can_receive_vectored(&self) -> bool {
+        return self.socket.is_some() && self.middleware.is_none() && self.transport.is_none() && self.prefix_buffer.is_none();
+    }
 
-                if can_drop {
-                    return true;
-                }
+    // receive that fills first and continues into second, see ReceiveRing.  Only when can_receive_vectored.
+    pub fn receive_vectored(&mut self, first: &mut [u8], second: &mut [u8]) -> SocketReceiveResult {
+        let socket = match &self.socket {
+            Some(v) => v,
+            None => {
+                return SocketReceiveResult::Error;
             }
+        };
+
+        // the kernel only ever writes initialized bytes into these
+        let mut bufs = unsafe {
+            [
+                MaybeUninitSlice::new(&mut *(first as *mut [u8] as *mut [MaybeUninit<u8>])),
+                MaybeUninitSlice::new(&mut *(second as *mut [u8] as *mut [MaybeUninit<u8>])),
+            ]
+        };
+        let (bytes_received, network_address) = match SockRef::from(socket).recv_from_vectored(&mut bufs) {
+            Ok((bytes_received, _, src_addr)) => {
+                let network_address = match src_addr.as_socket() {
+                    Some(addr) if self.is_server => NetworkAddress::from_socket_addr(addr),
+                    _ => NetworkAddress::default(),
+                };
+                (bytes_received, network_address)
+            }
+            Err(_) => {
+                return SocketReceiveResult::Empty;
+            }
+        };
+
+        if self.inbound_drops.should_drop(&first[0..bytes_received.min(first.len())]) {
+            return SocketReceiveResult::Dropped;
         }
-        return false;
+        // the header is always in first
+        if let Some(scrambler) = &self.scrambler {
+            scrambler.apply(network_address, first, bytes_received.min(first.len()));
+        }
+        return SocketReceiveResult::Success {
+            bytes_received,
+            network_address,
+        };
     }
 
-    pub fn receive(&mut self, data: &mut [u8], drop_chance: u64, drop_reliable_only: bool) -> SocketReceiveResult {
+    fn receive_datagram(&mut self, data: &mut [u8]) -> SocketReceiveResult {
+        if let Some(transport) = &mut self.transport {
+            let result = transport.receive(data);
+            if let SocketReceiveResult::Success { bytes_received, .. } = result {
+                if !self.is_server {
+                    return SocketReceiveResult::Success {
+                        bytes_received,
+                        network_address: NetworkAddress::default(),
+                    };
+                }
+            }
+            return result;
+        }
+
         let socket = match &self.socket {
             Some(v) => v,
             None => {
@@ -141,13 +431,9 @@ impl TachyonSocket {
         if self.is_server {
             match socket.recv_from(data) {
                 Ok((bytes_received, src_addr)) => {
-                    if self.should_drop(data, drop_chance, drop_reliable_only) {
-                        return SocketReceiveResult::Dropped;
-                    }
-                    let address = NetworkAddress::from_socket_addr(src_addr);
                     return SocketReceiveResult::Success {
                         bytes_received,
-                        network_address: address,
+                        network_address: NetworkAddress::from_socket_addr(src_addr),
                     };
                 }
                 Err(_) => {
@@ -157,9 +443,6 @@ impl TachyonSocket {
         } else {
             match socket.recv(data) {
                 Ok(size) => {
-                    if self.should_drop(data, drop_chance, drop_reliable_only) {
-                        return SocketReceiveResult::Dropped;
-                    }
                     return SocketReceiveResult::Success {
                         bytes_received: size,
                         network_address: NetworkAddress::default(),
@@ -172,7 +455,97 @@ impl TachyonSocket {
         }
     }
 
+    fn intercept_receive(&self, address: NetworkAddress, data: &mut [u8], length: usize) -> usize {
+        match &self.middleware {
+            Some(middleware) => {
+                return middleware.borrow_mut().process_receive(address, data, length);
+            }
+            None => {
+                return length;
+            }
+        }
+    }
+
     pub fn send_to(&self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        // a dropped datagram was sent as far as the caller can tell
+        if self.outbound_drops.borrow_mut().should_drop(&data[0..length]) {
+            return length;
+        }
+        match &self.scrambler {
+            Some(scrambler) if Scrambler::applies(data, length) => {
+                let mut buffer = scrambler.buffer.borrow_mut();
+                if length > buffer.len() {
+                    return 0;
+                }
+                buffer[0..length].copy_from_slice(&data[0..length]);
+                scrambler.apply(address, &mut buffer, length);
+                return self.send_through_middleware(address, &buffer, length);
+            }
+            _ => {
+                return self.send_through_middleware(address, data, length);
+            }
+        }
+    }
+
+    // send_to for channel messages, behind the wide channel header on channels above 255.  Returns the length sent
+    // without that header.
+    pub fn send_on_channel(&self, channel_id: u16, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        let wide = match wide_channel_header(channel_id) {
+            Some(wide) => wide,
+            None => return self.send_to(address, data, length),
+        };
+        let mut buffer = self.wide_buffer.borrow_mut();
+        let wide_len = TACHYON_WIDE_CHANNEL_HEADER_SIZE + length;
+        if buffer.len() < wide_len {
+            buffer.resize(wide_len, 0);
+        }
+        buffer[0..TACHYON_WIDE_CHANNEL_HEADER_SIZE].copy_from_slice(&wide);
+        buffer[TACHYON_WIDE_CHANNEL_HEADER_SIZE..wide_len].copy_from_slice(&data[0..length]);
+        let sent = self.send_to(address, &buffer, wide_len);
+        return sent.saturating_sub(TACHYON_WIDE_CHANNEL_HEADER_SIZE);
+    }
+
+    fn send_through_middleware(&self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        match &self.middleware {
+            Some(middleware) => {
+                let mut state = middleware.borrow_mut();
+                let modified_len = state.process_send(address, data, length);
+                if modified_len == 0 {
+                    return 0;
+                }
+                return self.send_prefixed(address, &state.send_buffer, modified_len);
+            }
+            None => {
+                return self.send_prefixed(address, data, length);
+            }
+        }
+    }
+
+    // returns the length sent without the prefix, like there wasn't one
+    fn send_prefixed(&self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        match &self.prefix_buffer {
+            Some(buffer) => {
+                let mut buffer = buffer.borrow_mut();
+                let prefix_len = datagram_prefix_len(self.protocol_magic, self.checksum);
+                if prefix_len + length > buffer.len() {
+                    return 0;
+                }
+                buffer[prefix_len..prefix_len + length].copy_from_slice(&data[0..length]);
+                write_datagram_prefix(&mut buffer, self.protocol_magic, self.checksum, length);
+                let sent = self.send_to_socket(address, &buffer, prefix_len + length);
+                return sent.saturating_sub(prefix_len);
+            }
+            None => {
+                return self.send_to_socket(address, data, length);
+            }
+        }
+    }
+
+    fn send_to_socket(&self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        if let Some(transport) = &self.transport {
+            return transport.send_to(address, &data[0..length]);
+        }
+
         match &self.socket {
             Some(socket) => {
                 let slice = &data[0..length];
@@ -188,7 +561,8 @@ impl TachyonSocket {
                     Ok(size) => {
                         return size;
                     }
-                    Err(_) => {
+                    Err(error) => {
+                        self.last_error.set(Some(error.into()));
                         return 0;
                     }
                 }
@@ -199,3 +573,43 @@ impl TachyonSocket {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use serial_test::serial;
+
+    use crate::{ffi::take_socket_error, network_address::NetworkAddress, Tachyon, TachyonConfig};
+
+    use super::{TachyonSocket, TachyonSocketError, SOCKET_ERROR_ADDRESS_IN_USE, SOCKET_ERROR_ALREADY_OPEN};
+
+    #[test]
+    #[serial]
+    fn test_socket_errors() {
+        let address = NetworkAddress::localhost(8101);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.try_bind(address).is_ok());
+        assert_eq!(Err(TachyonSocketError::AlreadyOpen), server.try_bind(address));
+        assert_eq!(SOCKET_ERROR_ALREADY_OPEN, TachyonSocketError::AlreadyOpen.code());
+
+        let mut other = Tachyon::create(TachyonConfig::default());
+        let error = other.try_bind(address).unwrap_err();
+        assert!(matches!(error, TachyonSocketError::Io { kind: io::ErrorKind::AddrInUse, .. }));
+        assert_eq!(SOCKET_ERROR_ADDRESS_IN_USE, error.code());
+        assert!(error.raw_os_error() != 0);
+        assert!(!other.socket.is_open());
+
+        // an unconnected socket with nowhere to send to
+        assert!(server.take_socket_error().is_none());
+        assert_eq!(0, server.socket.send_to(NetworkAddress::default(), &[1; 4], 4));
+        let mut os_error = 0;
+        assert!(take_socket_error(&mut server, &mut os_error) > 0);
+        assert!(os_error != 0);
+        assert!(server.take_socket_error().is_none());
+
+        let mut socket = TachyonSocket::create();
+        assert!(socket.connect_socket(address).is_ok());
+        assert_eq!(Err(TachyonSocketError::AlreadyOpen), socket.connect_socket(address));
+    }
+}
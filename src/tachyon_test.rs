@@ -4,9 +4,10 @@ use std::time::Instant;
 
 use serial_test::serial;
 
-use crate::tachyon::header::*;
-use crate::tachyon::receiver::*;
-use crate::tachyon::*;
+use crate::header::*;
+use crate::loopback::LoopbackNetwork;
+use crate::receiver::*;
+use crate::*;
 
 pub struct TachyonTestClient {
     pub client_address: NetworkAddress,
@@ -36,7 +37,7 @@ impl TachyonTestClient {
         assert!(self.client.connect(self.address), "connect failed");
     }
 
-    pub fn client_send_reliable(&mut self, channel_id: u8, length: usize) -> TachyonSendResult {
+    pub fn client_send_reliable(&mut self, channel_id: u16, length: usize) -> TachyonSendResult {
         let target = SendTarget {address: self.client_address, identity_id: 0};
         return self.client.send_to_target(channel_id, target,&mut self.send_buffer, length);
     }
@@ -89,6 +90,14 @@ impl TachyonTest {
         assert!(self.client.connect(self.address), "connect failed");
     }
 
+    // the same over a LoopbackNetwork, no sockets
+    pub fn connect_loopback(&mut self) -> LoopbackNetwork {
+        let network = LoopbackNetwork::default();
+        assert!(self.server.bind_loopback(&network, self.address), "bind failed");
+        self.client.connect_loopback(&network, self.address);
+        return network;
+    }
+
     pub fn remote_client(&mut self) -> NetworkAddress {
         let list = self.server.get_connections(100);
         if list.len() > 0 {
@@ -98,7 +107,7 @@ impl TachyonTest {
         }
     }
 
-    pub fn server_send_reliable(&mut self, channel_id: u8, length: usize) -> TachyonSendResult {
+    pub fn server_send_reliable(&mut self, channel_id: u16, length: usize) -> TachyonSendResult {
         let address = self.remote_client();
         if address.is_default() {
             return TachyonSendResult::default();
@@ -116,7 +125,7 @@ impl TachyonTest {
         return self.server.send_to_target(0,target, &mut self.send_buffer, length);
     }
 
-    pub fn client_send_reliable(&mut self, channel_id: u8, length: usize) -> TachyonSendResult {
+    pub fn client_send_reliable(&mut self, channel_id: u16, length: usize) -> TachyonSendResult {
         let target = SendTarget {address: self.client_address, identity_id: 0};
         return self.client.send_to_target(channel_id,  target,&mut self.send_buffer,length);
     }
@@ -138,15 +147,15 @@ impl TachyonTest {
 pub fn show_channel_debug(channel: &mut Receiver) {
     print!(
         "current:{0} last:{1}\n",
-        channel.current_sequence, channel.last_sequence
+        channel.current_sequence(), channel.last_sequence()
     );
     channel.set_resend_list();
-    for seq in &channel.resend_list {
+    for seq in channel.resend_list() {
         print!("not received:{0} \n", seq);
     }
 }
 
-fn send_receive(update: bool,send: bool,channel_id: u8, message_type: u8, client: &mut Tachyon, server: &mut Tachyon,  send_buffer: &mut [u8], receive_buffer: &mut [u8],
+fn send_receive(update: bool,send: bool,channel_id: u16, message_type: u8, client: &mut Tachyon, server: &mut Tachyon,  send_buffer: &mut [u8], receive_buffer: &mut [u8],
      send_message_size: usize, client_remote: &mut NetworkAddress) {
     let client_address = NetworkAddress::default();
     //let mut client_remote = NetworkAddress::default();
@@ -263,9 +272,9 @@ fn general_stress() {
     print!(
         "CLIENT {0} current_seq:{1} last_seq:{2}, missing:{3}\n",
         channel.stats,
-        channel.receiver.current_sequence,
-        channel.receiver.last_sequence,
-        channel.receiver.resend_list.len()
+        channel.receiver.current_sequence(),
+        channel.receiver.last_sequence(),
+        channel.receiver.resend_list().len()
     );
 
     match server.get_channel(client_remote, channel_id) {
@@ -276,9 +285,9 @@ fn general_stress() {
             print!(
                 "SERVER {0} current_seq:{1} last_seq:{2} missing:{3}\n",
                 channel.stats,
-                channel.receiver.current_sequence,
-                channel.receiver.last_sequence,
-                channel.receiver.resend_list.len()
+                channel.receiver.current_sequence(),
+                channel.receiver.last_sequence(),
+                channel.receiver.resend_list().len()
             );
         }
         None => {}
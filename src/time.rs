@@ -0,0 +1,79 @@
+// std's Instant and Duration with the std feature.  Without it Instant is how long after a start the application picks,
+// and a Clock is the only thing that makes one.  The protocol core only ever compares and offsets them.
+#[cfg(feature = "std")]
+pub use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "std"))]
+pub use core::time::Duration;
+
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    since_start: Duration,
+}
+
+#[cfg(not(feature = "std"))]
+impl Instant {
+    pub const fn from_start(since_start: Duration) -> Self {
+        return Instant { since_start };
+    }
+
+    pub fn since_start(&self) -> Duration {
+        return self.since_start;
+    }
+
+    // saturates like std's
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        return self.since_start.saturating_sub(earlier.since_start);
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        return self.duration_since(earlier);
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        return self.since_start.checked_sub(earlier.since_start);
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        return self.since_start.checked_add(duration).map(Instant::from_start);
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        return self.since_start.checked_sub(duration).map(Instant::from_start);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        return Instant::from_start(self.since_start + duration);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::ops::AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        self.since_start += duration;
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::ops::Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        return Instant::from_start(self.since_start - duration);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::ops::Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        return self.duration_since(earlier);
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    io::IoSlice,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{
+    header::{TopicHeader, MESSAGE_TYPE_SUBSCRIBE, MESSAGE_TYPE_TOPIC_ACK, MESSAGE_TYPE_UNSUBSCRIBE, TACHYON_TOPIC_HEADER_SIZE},
+    network_address::NetworkAddress,
+    Tachyon,
+};
+
+const TOPIC_SEND_INTERVAL: u128 = 300;
+// subscribe requests past this many topics for one connection are acked as not subscribed
+pub const MAX_TOPICS_PER_CONNECTION: usize = 256;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct TopicSendResult {
+    pub sent: u32,
+    pub failed: u32,
+    pub bytes_sent: u32,
+}
+
+impl TopicSendResult {
+    pub fn add_from(&mut self, other: &TopicSendResult) {
+        self.sent += other.sent;
+        self.failed += other.failed;
+        self.bytes_sent += other.bytes_sent;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Subscription {
+    subscribed: bool,
+    acked: bool,
+}
+
+// Topic subscriptions.  Clients subscribe with Tachyon::subscribe, which sends until the server acks it.  The server
+// keeps the subscribers of each topic and publish_to_topic sends to them only.  A connection's subscriptions go away
+// with the connection.
+pub struct Topics {
+    // server
+    subscribers: FxHashMap<u32, FxHashSet<NetworkAddress>>,
+    subscriptions: FxHashMap<NetworkAddress, FxHashSet<u32>>,
+    // client, what we want the server to have
+    wanted: FxHashMap<u32, Subscription>,
+    last_sent: Instant,
+}
+
+impl Topics {
+    pub fn default() -> Self {
+        let topics = Topics {
+            subscribers: FxHashMap::default(),
+            subscriptions: FxHashMap::default(),
+            wanted: FxHashMap::default(),
+            last_sent: Instant::now() - Duration::new(100, 0),
+        };
+        return topics;
+    }
+
+    pub fn subscriber_count(&self, topic: u32) -> usize {
+        match self.subscribers.get(&topic) {
+            Some(subscribers) => return subscribers.len(),
+            None => return 0,
+        }
+    }
+
+    pub fn is_subscriber(&self, topic: u32, address: NetworkAddress) -> bool {
+        match self.subscribers.get(&topic) {
+            Some(subscribers) => return subscribers.contains(&address),
+            None => return false,
+        }
+    }
+
+    fn add_subscriber(&mut self, topic: u32, address: NetworkAddress) -> bool {
+        let subscriptions = self.subscriptions.entry(address).or_default();
+        if !subscriptions.contains(&topic) && subscriptions.len() >= MAX_TOPICS_PER_CONNECTION {
+            return false;
+        }
+        subscriptions.insert(topic);
+        self.subscribers.entry(topic).or_default().insert(address);
+        return true;
+    }
+
+    fn remove_subscriber(&mut self, topic: u32, address: NetworkAddress) {
+        if let Some(subscriptions) = self.subscriptions.get_mut(&address) {
+            subscriptions.remove(&topic);
+            if subscriptions.is_empty() {
+                self.subscriptions.remove(&address);
+            }
+        }
+        if let Some(subscribers) = self.subscribers.get_mut(&topic) {
+            subscribers.remove(&address);
+            if subscribers.is_empty() {
+                self.subscribers.remove(&topic);
+            }
+        }
+    }
+
+    pub fn remove_address(&mut self, address: NetworkAddress) {
+        if let Some(subscriptions) = self.subscriptions.remove(&address) {
+            for topic in subscriptions {
+                if let Some(subscribers) = self.subscribers.get_mut(&topic) {
+                    subscribers.remove(&address);
+                    if subscribers.is_empty() {
+                        self.subscribers.remove(&topic);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Tachyon {
+    // client, the server has it once is_subscribed returns true
+    pub fn subscribe(&mut self, topic: u32) {
+        self.topics.wanted.insert(topic, Subscription { subscribed: true, acked: false });
+        self.send_topic_message(MESSAGE_TYPE_SUBSCRIBE, topic, true, NetworkAddress::default());
+    }
+
+    pub fn unsubscribe(&mut self, topic: u32) {
+        self.topics.wanted.insert(topic, Subscription { subscribed: false, acked: false });
+        self.send_topic_message(MESSAGE_TYPE_UNSUBSCRIBE, topic, false, NetworkAddress::default());
+    }
+
+    // client, true once the server acked the subscription
+    pub fn is_subscribed(&self, topic: u32) -> bool {
+        match self.topics.wanted.get(&topic) {
+            Some(subscription) => return subscription.subscribed && subscription.acked,
+            None => return false,
+        }
+    }
+
+    // server, a reliable send of the message to every subscriber of the topic
+    pub fn publish_to_topic(&mut self, channel_id: u16, topic: u32, data: &[u8], length: usize) -> TopicSendResult {
+        let mut result = TopicSendResult::default();
+        let subscribers: Vec<NetworkAddress> = match self.topics.subscribers.get(&topic) {
+            Some(subscribers) => subscribers.iter().copied().collect(),
+            None => return result,
+        };
+        if length > data.len() {
+            result.failed = subscribers.len() as u32;
+            return result;
+        }
+
+        let slices = [IoSlice::new(&data[0..length])];
+        for address in subscribers {
+            let send_result = self.send_vectored(channel_id, address, &slices);
+            if send_result.error == 0 {
+                result.sent += 1;
+                result.bytes_sent += send_result.sent_len;
+            } else {
+                result.failed += 1;
+            }
+        }
+        return result;
+    }
+
+    pub fn topic_receive(&mut self, address: NetworkAddress, received_len: usize) {
+        let header = match TopicHeader::try_read(&self.receive_ring.received()[0..received_len]) {
+            Some(header) => header,
+            None => return,
+        };
+        if self.socket.is_server {
+            let subscribed = match header.message_type {
+                MESSAGE_TYPE_SUBSCRIBE => self.topics.add_subscriber(header.topic, address),
+                MESSAGE_TYPE_UNSUBSCRIBE => {
+                    self.topics.remove_subscriber(header.topic, address);
+                    false
+                }
+                _ => return,
+            };
+            self.send_topic_message(MESSAGE_TYPE_TOPIC_ACK, header.topic, subscribed, address);
+        } else if header.message_type == MESSAGE_TYPE_TOPIC_ACK {
+            let subscribed = header.subscribed == 1;
+            if let Some(subscription) = self.topics.wanted.get_mut(&header.topic) {
+                if subscription.subscribed {
+                    // refused acks leave it unacked, we keep asking
+                    subscription.acked = subscribed;
+                } else if !subscribed {
+                    self.topics.wanted.remove(&header.topic);
+                }
+            }
+        }
+    }
+
+    // client, resends subscription changes the server hasn't acked
+    pub fn client_topic_update(&mut self, now: Instant) {
+        if self.socket.is_server || !self.socket.is_open() {
+            return;
+        }
+        if now.duration_since(self.topics.last_sent).as_millis() <= TOPIC_SEND_INTERVAL {
+            return;
+        }
+        self.topics.last_sent = now;
+
+        let unacked: Vec<(u32, bool)> = self.topics.wanted.iter()
+            .filter(|(_, subscription)| !subscription.acked)
+            .map(|(topic, subscription)| (*topic, subscription.subscribed))
+            .collect();
+        for (topic, subscribed) in unacked {
+            let message_type = if subscribed { MESSAGE_TYPE_SUBSCRIBE } else { MESSAGE_TYPE_UNSUBSCRIBE };
+            self.send_topic_message(message_type, topic, subscribed, NetworkAddress::default());
+        }
+    }
+
+    fn send_topic_message(&self, message_type: u8, topic: u32, subscribed: bool, address: NetworkAddress) {
+        if !self.socket.is_open() {
+            return;
+        }
+        let header = TopicHeader { message_type, topic, subscribed: subscribed as u8 };
+        let mut send_buffer: Vec<u8> = vec![0; TACHYON_TOPIC_HEADER_SIZE];
+        header.write(&mut send_buffer);
+        self.socket.send_to(address, &send_buffer, send_buffer.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, tachyon_test::TachyonTestClient, Tachyon, TachyonConfig};
+
+    #[test]
+    #[serial]
+    fn test_topics() {
+        let address = NetworkAddress::localhost(8197);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.bind(address));
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+
+        let mut subscriber = TachyonTestClient::create(address);
+        let mut other = TachyonTestClient::create(address);
+        subscriber.connect();
+        other.connect();
+        assert_eq!(0, other.client_send_reliable(1, 8).error);
+
+        subscriber.client.subscribe(7);
+        assert!(!subscriber.client.is_subscribed(7));
+        assert_eq!(8, server.receive_loop(&mut receive_buffer).length);
+        server.receive_loop(&mut receive_buffer);
+        assert_eq!(1, server.topics.subscriber_count(7));
+        subscriber.client_receive();
+        assert!(subscriber.client.is_subscribed(7));
+
+        let result = server.publish_to_topic(1, 7, &[3; 16], 16);
+        assert_eq!(1, result.sent);
+        assert_eq!(16, subscriber.client_receive().length);
+        assert_eq!(0, other.client_receive().length);
+        assert_eq!(0, server.publish_to_topic(1, 8, &[3; 16], 16).sent);
+
+        subscriber.client.unsubscribe(7);
+        server.receive_loop(&mut receive_buffer);
+        assert_eq!(0, server.topics.subscriber_count(7));
+        subscriber.client_receive();
+        assert!(!subscriber.client.is_subscribed(7));
+        assert_eq!(0, server.publish_to_topic(1, 7, &[3; 16], 16).sent);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use super::{network_address::NetworkAddress, tachyon_socket::SocketReceiveResult};
+
+// Datagram transport under TachyonSocket.  The protocol (headers, channels, receiver, nacks, fragmentation) only ever
+// hands complete datagrams to send_to and pulls complete datagrams from receive, so anything that can move datagrams
+// can carry it.  TachyonSocket uses its own UdpSocket when no transport is set.
+pub trait Transport: Send {
+    // address is default when a client sends to the server it connected to
+    fn send_to(&self, address: NetworkAddress, data: &[u8]) -> usize;
+
+    // Success, Empty or Error, never blocks.  Clients can leave network_address default.
+    fn receive(&mut self, data: &mut [u8]) -> SocketReceiveResult;
+}
+
+pub struct Datagram {
+    pub address: NetworkAddress,
+    pub data: Vec<u8>,
+}
+
+// In memory transport.  Tachyon reads incoming and writes outgoing, the caller moves datagrams in and out through a clone
+// of the queue however it likes: custom sockets, WASM host calls, or straight into another Tachyon for deterministic tests.
+// sans_io::TachyonCore drives one for the caller.
+#[derive(Clone, Default)]
+pub struct DatagramQueue {
+    incoming: Arc<Mutex<VecDeque<Datagram>>>,
+    outgoing: Arc<Mutex<VecDeque<Datagram>>>,
+}
+
+impl DatagramQueue {
+    pub fn push_incoming(&self, address: NetworkAddress, data: &[u8]) {
+        let datagram = Datagram {
+            address,
+            data: data.to_vec(),
+        };
+        self.incoming.lock().unwrap().push_back(datagram);
+    }
+
+    pub fn pop_outgoing(&self) -> Option<Datagram> {
+        return self.outgoing.lock().unwrap().pop_front();
+    }
+
+    pub fn outgoing_len(&self) -> usize {
+        return self.outgoing.lock().unwrap().len();
+    }
+}
+
+impl Transport for DatagramQueue {
+    fn send_to(&self, address: NetworkAddress, data: &[u8]) -> usize {
+        let datagram = Datagram {
+            address,
+            data: data.to_vec(),
+        };
+        self.outgoing.lock().unwrap().push_back(datagram);
+        return data.len();
+    }
+
+    fn receive(&mut self, data: &mut [u8]) -> SocketReceiveResult {
+        match self.incoming.lock().unwrap().pop_front() {
+            Some(datagram) => {
+                if datagram.data.len() > data.len() {
+                    return SocketReceiveResult::Error;
+                }
+                data[0..datagram.data.len()].copy_from_slice(&datagram.data);
+                return SocketReceiveResult::Success {
+                    bytes_received: datagram.data.len(),
+                    network_address: datagram.address,
+                };
+            }
+            None => {
+                return SocketReceiveResult::Empty;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{network_address::NetworkAddress, Tachyon, TachyonConfig};
+
+    use super::DatagramQueue;
+
+    // moves everything each side has sent over to the other, dropping the nth datagram from the client if asked
+    fn pump(client: &DatagramQueue, server: &DatagramQueue, client_address: NetworkAddress, drop_at: Option<usize>) -> usize {
+        let mut moved = 0;
+        while let Some(datagram) = client.pop_outgoing() {
+            if drop_at != Some(moved) {
+                server.push_incoming(client_address, &datagram.data);
+            }
+            moved += 1;
+        }
+        while let Some(datagram) = server.pop_outgoing() {
+            assert!(client_address == datagram.address);
+            client.push_incoming(NetworkAddress::default(), &datagram.data);
+        }
+        return moved;
+    }
+
+    #[test]
+    fn test_datagram_queue() {
+        let config = TachyonConfig::default();
+        let client_queue = DatagramQueue::default();
+        let server_queue = DatagramQueue::default();
+        let client_address = NetworkAddress::localhost(9999);
+
+        let mut server = Tachyon::create(config);
+        server.bind_transport(Box::new(server_queue.clone()));
+        let mut client = Tachyon::create(config);
+        client.connect_transport(Box::new(client_queue.clone()));
+
+        let mut send_buffer: Vec<u8> = vec![0; 1024];
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+
+        // lose the second of three ordered messages, the nack from the server gets it resent
+        for i in 0..3 {
+            send_buffer[0] = i;
+            assert_eq!(0, client.send_reliable(1, NetworkAddress::default(), &send_buffer, 32).error);
+        }
+        assert_eq!(3, pump(&client_queue, &server_queue, client_address, Some(1)));
+
+        let res = server.receive_loop(&mut receive_buffer);
+        assert_eq!(32, res.length);
+        assert_eq!(0, receive_buffer[0]);
+        assert!(client_address == res.address);
+        assert_eq!(0, server.receive_loop(&mut receive_buffer).length);
+
+        server.step(Duration::from_millis(10));
+        pump(&client_queue, &server_queue, client_address, None);
+        client.receive_loop(&mut receive_buffer);
+        client.step(Duration::from_millis(10));
+        pump(&client_queue, &server_queue, client_address, None);
+
+        let res = server.receive_loop(&mut receive_buffer);
+        assert_eq!(32, res.length);
+        assert_eq!(1, receive_buffer[0]);
+        assert_eq!(32, server.receive_loop(&mut receive_buffer).length);
+        assert_eq!(2, receive_buffer[0]);
+        assert_eq!(1, server.get_combined_stats().channel_stats.nacks_sent);
+
+        // server to client, unreliable
+        send_buffer[0] = 7;
+        assert_eq!(0, server.send_unreliable(client_address, &send_buffer, 8).error);
+        pump(&client_queue, &server_queue, client_address, None);
+        let res = client.receive_loop(&mut receive_buffer);
+        assert_eq!(8, res.length);
+        assert_eq!(7, receive_buffer[0]);
+        assert_eq!(0, res.channel);
+    }
+}
@@ -0,0 +1,134 @@
+use super::{network_address::NetworkAddress, sequence::Sequence};
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct UnreliableChannelConfig {
+    // sequenced messages carry a sequence number and anything older than the newest received is dropped
+    pub sequenced: u32
+}
+
+impl UnreliableChannelConfig {
+    pub fn is_sequenced(&self) -> bool {
+        return self.sequenced == 1;
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct UnreliableChannelStats {
+    pub sent: u64,
+    pub received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub out_of_order_dropped: u64,
+}
+
+impl UnreliableChannelStats {
+    pub fn add_from(&mut self, other: &UnreliableChannelStats) {
+        self.sent += other.sent;
+        self.received += other.received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.out_of_order_dropped += other.out_of_order_dropped;
+    }
+
+    pub fn snapshot(&self) -> UnreliableChannelStats {
+        return *self;
+    }
+
+    pub fn diff(&self, earlier: &UnreliableChannelStats) -> UnreliableChannelStats {
+        let stats = UnreliableChannelStats {
+            sent: self.sent.saturating_sub(earlier.sent),
+            received: self.received.saturating_sub(earlier.received),
+            bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            out_of_order_dropped: self.out_of_order_dropped.saturating_sub(earlier.out_of_order_dropped),
+        };
+        return stats;
+    }
+}
+
+impl std::fmt::Display for UnreliableChannelStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "sent:{} received:{} kb_sent:{} kb_received:{} out_of_order_dropped:{}\n",
+            self.sent,
+            self.received,
+            self.bytes_sent / 1024,
+            self.bytes_received / 1024,
+            self.out_of_order_dropped
+        )
+    }
+}
+
+// Channel 0.  Unreliable messages don't go through Channel, this just tracks per address stats and sequencing.
+pub struct UnreliableChannel {
+    pub address: NetworkAddress,
+    pub stats: UnreliableChannelStats,
+    pub send_sequence: u16,
+    pub last_received_sequence: u16,
+    has_received: bool
+}
+
+impl UnreliableChannel {
+    pub fn create(address: NetworkAddress) -> Self {
+        let channel = UnreliableChannel {
+            address,
+            stats: UnreliableChannelStats::default(),
+            send_sequence: 0,
+            last_received_sequence: 0,
+            has_received: false
+        };
+        return channel;
+    }
+
+    pub fn next_send_sequence(&mut self) -> u16 {
+        self.send_sequence = Sequence::next_sequence(self.send_sequence);
+        return self.send_sequence;
+    }
+
+    pub fn on_sent(&mut self, sent_len: usize) {
+        self.stats.sent += 1;
+        self.stats.bytes_sent += sent_len as u64;
+    }
+
+    pub fn on_received(&mut self, received_len: usize) {
+        self.stats.received += 1;
+        self.stats.bytes_received += received_len as u64;
+    }
+
+    // returns false if the sequence is older than the newest one we have seen
+    pub fn accept_sequence(&mut self, sequence: u16) -> bool {
+        if self.has_received && !Sequence::is_greater_then(sequence, self.last_received_sequence) {
+            self.stats.out_of_order_dropped += 1;
+            return false;
+        }
+        self.has_received = true;
+        self.last_received_sequence = sequence;
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network_address::NetworkAddress;
+
+    use super::UnreliableChannel;
+
+    #[test]
+    fn test_accept_sequence() {
+        let mut channel = UnreliableChannel::create(NetworkAddress::default());
+        assert!(channel.accept_sequence(5));
+        assert!(!channel.accept_sequence(5));
+        assert!(!channel.accept_sequence(3));
+        assert!(channel.accept_sequence(6));
+        assert_eq!(2, channel.stats.out_of_order_dropped);
+
+        // wrapping
+        channel.last_received_sequence = 65533;
+        assert!(channel.accept_sequence(1));
+    }
+}
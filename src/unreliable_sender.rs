@@ -1,9 +1,14 @@
 use std::{io, net::UdpSocket};
 
 use super::{
-    header::{Header, MESSAGE_TYPE_UNRELIABLE},
+    identity_addresses::IdentityAddresses,
+    header::{
+        Header, MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_UNRELIABLE_SEQUENCED, TACHYON_UNRELIABLE_HEADER_SIZE,
+        TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE,
+    },
     network_address::NetworkAddress,
-    TachyonSendResult, SEND_ERROR_CHANNEL, SEND_ERROR_LENGTH,
+    tachyon_socket::{datagram_prefix_len, write_datagram_prefix, TachyonSocket, TachyonSocketError},
+    TachyonSendResult, SEND_ERROR_CHANNEL, SEND_ERROR_IDENTITY, SEND_ERROR_LENGTH,
 };
 
 const UNRELIABLE_BUFFER_LEN: usize = 1024 * 16;
@@ -11,7 +16,14 @@ const UNRELIABLE_BUFFER_LEN: usize = 1024 * 16;
 // this is created with a cloned UdpSocket which can then be used from another thread.
 pub struct UnreliableSender {
     pub socket: Option<UdpSocket>,
-    pub send_buffer: Vec<u8>
+    pub send_buffer: Vec<u8>,
+    // why the last send on our own socket failed
+    pub last_error: Option<TachyonSocketError>,
+    // set when created by a Tachyon, for send_to_identity
+    pub identity_addresses: Option<IdentityAddresses>,
+    // our own socket's sends get the prefixes, TachyonConfig.use_protocol_magic and use_checksum
+    pub protocol_prefix: bool,
+    pub checksum: bool,
 }
 
 impl UnreliableSender {
@@ -19,11 +31,71 @@ impl UnreliableSender {
     pub fn create(socket: Option<UdpSocket>) -> Self {
         UnreliableSender {
             socket,
-            send_buffer: vec![0;UNRELIABLE_BUFFER_LEN]
+            send_buffer: vec![0;UNRELIABLE_BUFFER_LEN],
+            last_error: None,
+            identity_addresses: None,
+            protocol_prefix: false,
+            checksum: false,
         }
     }
 
-    pub fn send(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+    pub fn send(&mut self, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_UNRELIABLE;
+        return self.send_with_header(address, header, data, body_len, None);
+    }
+
+    // Sends to the address the identity is linked to, as of the last snapshot the owning Tachyon published.
+    // SEND_ERROR_IDENTITY if it isn't linked or this sender wasn't created by a Tachyon.
+    pub fn send_to_identity(&mut self, identity_id: u32, data: &[u8], body_len: usize) -> TachyonSendResult {
+        let address = self.identity_addresses.as_ref().and_then(|addresses| addresses.get(identity_id));
+        match address {
+            Some(address) => {
+                return self.send(address, data, body_len);
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                result.error = SEND_ERROR_IDENTITY;
+                return result;
+            }
+        }
+    }
+
+    // another sender over a clone of our socket, sharing the identity snapshot
+    pub fn try_clone(&self) -> Option<UnreliableSender> {
+        let socket = self.socket.as_ref()?.try_clone().ok()?;
+        let mut sender = UnreliableSender::create(Some(socket));
+        sender.identity_addresses = self.identity_addresses.clone();
+        sender.protocol_prefix = self.protocol_prefix;
+        sender.checksum = self.checksum;
+        return Some(sender);
+    }
+
+    // sequenced, the receiver drops anything older than the newest sequence it has seen from us
+    pub fn send_sequenced(&mut self, address: NetworkAddress, sequence: u16, data: &[u8], body_len: usize) -> TachyonSendResult {
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_UNRELIABLE_SEQUENCED;
+        header.sequence = sequence;
+        return self.send_with_header(address, header, data, body_len, None);
+    }
+
+    // sends through the owning TachyonSocket instead of our clone so packet middleware sees unreliable messages too.
+    // Only usable from the thread that owns the socket.
+    pub fn send_through(&mut self, socket: &TachyonSocket, address: NetworkAddress, sequence: Option<u16>, data: &[u8], body_len: usize) -> TachyonSendResult {
+        let mut header = Header::default();
+        match sequence {
+            Some(sequence) => {
+                header.message_type = MESSAGE_TYPE_UNRELIABLE_SEQUENCED;
+                header.sequence = sequence;
+            }
+            None => {
+                header.message_type = MESSAGE_TYPE_UNRELIABLE;
+            }
+        }
+        return self.send_with_header(address, header, data, body_len, Some(socket));
+    }
+
+    fn send_with_header(&mut self, address: NetworkAddress, header: Header, data: &[u8], body_len: usize, socket: Option<&TachyonSocket>) -> TachyonSendResult {
         let mut result = TachyonSendResult::default();
         
         if body_len < 1 {
@@ -31,27 +103,42 @@ impl UnreliableSender {
             return result;
         }
 
-        if !self.socket.is_some() {
+        if socket.is_none() && !self.socket.is_some() {
             result.error = SEND_ERROR_CHANNEL;
             return result;
         }
 
-        // copy to send buffer at +1 offset for message_type
-        self.send_buffer[1..body_len+1].copy_from_slice(&data[0..body_len]);
-        let length = body_len + 1;
+        // a TachyonSocket adds the prefixes itself
+        let start = if socket.is_none() { datagram_prefix_len(self.protocol_prefix, self.checksum) } else { 0 };
 
-        let mut header = Header::default();
-        header.message_type = MESSAGE_TYPE_UNRELIABLE;
-        header.write_unreliable(&mut self.send_buffer);
+        let header_len: usize;
+        if header.message_type == MESSAGE_TYPE_UNRELIABLE_SEQUENCED {
+            header_len = TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE;
+            header.write_unreliable_sequenced(&mut self.send_buffer[start..]);
+        } else {
+            header_len = TACHYON_UNRELIABLE_HEADER_SIZE;
+            header.write_unreliable(&mut self.send_buffer[start..]);
+        }
+
+        // copy to send buffer after the header
+        let body_start = start + header_len;
+        self.send_buffer[body_start..body_start + body_len].copy_from_slice(&data[0..body_len]);
+        let length = body_len + header_len;
+        if start > 0 {
+            write_datagram_prefix(&mut self.send_buffer, self.protocol_prefix, self.checksum, length);
+        }
 
-        let sent_len = self.send_to(address, length);
+        let sent_len = match socket {
+            Some(socket) => socket.send_to(address, &self.send_buffer, length),
+            None => self.send_to(address, start + length).saturating_sub(start),
+        };
         result.sent_len = sent_len as u32;
         result.header = header;
 
         return result;
     }
 
-    fn send_to(&self, address: NetworkAddress, length: usize) -> usize {
+    fn send_to(&mut self, address: NetworkAddress, length: usize) -> usize {
         match &self.socket {
             Some(socket) => {
                 let slice = &self.send_buffer[0..length];
@@ -67,7 +154,8 @@ impl UnreliableSender {
                     Ok(size) => {
                         return size;
                     }
-                    Err(_) => {
+                    Err(error) => {
+                        self.last_error = Some(error.into());
                         return 0;
                     }
                 }
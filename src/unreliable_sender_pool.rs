@@ -0,0 +1,223 @@
+use std::net::UdpSocket;
+
+use crossbeam::queue::SegQueue;
+
+use super::{ffi::copy_send_result, identity_addresses::IdentityAddresses, network_address::NetworkAddress, unreliable_sender::UnreliableSender, Tachyon, TachyonSendResult};
+
+// Shared source of UnreliableSenders for sending unreliable from any number of threads without managing a sender per
+// thread yourself.  A send takes an idle sender (its own cloned socket and send buffer), or clones a new one when every
+// sender is busy, and puts it back after.  So there are only ever as many senders as threads sending at the same time.
+pub struct UnreliableSenderPool {
+    socket: UdpSocket,
+    identity_addresses: IdentityAddresses,
+    idle: SegQueue<UnreliableSender>,
+    protocol_prefix: bool,
+    checksum: bool,
+}
+
+impl UnreliableSenderPool {
+    // None if the tachyon isn't bound/connected on a udp socket
+    pub fn create(tachyon: &Tachyon) -> Option<Self> {
+        let socket = tachyon.socket.clone_socket()?;
+        let pool = UnreliableSenderPool {
+            socket,
+            identity_addresses: tachyon.identity_addresses.clone(),
+            idle: SegQueue::new(),
+            protocol_prefix: tachyon.config.use_protocol_magic == 1,
+            checksum: tachyon.config.use_checksum == 1,
+        };
+        return Some(pool);
+    }
+
+    pub fn idle_count(&self) -> usize {
+        return self.idle.len();
+    }
+
+    pub fn take(&self) -> Option<UnreliableSender> {
+        if let Some(sender) = self.idle.pop() {
+            return Some(sender);
+        }
+        let socket = self.socket.try_clone().ok()?;
+        let mut sender = UnreliableSender::create(Some(socket));
+        sender.identity_addresses = Some(self.identity_addresses.clone());
+        sender.protocol_prefix = self.protocol_prefix;
+        sender.checksum = self.checksum;
+        return Some(sender);
+    }
+
+    pub fn give_back(&self, sender: UnreliableSender) {
+        self.idle.push(sender);
+    }
+
+    pub fn send(&self, address: NetworkAddress, data: &[u8], body_len: usize) -> TachyonSendResult {
+        match self.take() {
+            Some(mut sender) => {
+                let result = sender.send(address, data, body_len);
+                self.give_back(sender);
+                return result;
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                result.error = super::SEND_ERROR_SOCKET;
+                return result;
+            }
+        }
+    }
+
+    pub fn send_to_identity(&self, identity_id: u32, data: &[u8], body_len: usize) -> TachyonSendResult {
+        match self.take() {
+            Some(mut sender) => {
+                let result = sender.send_to_identity(identity_id, data, body_len);
+                self.give_back(sender);
+                return result;
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                result.error = super::SEND_ERROR_SOCKET;
+                return result;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn unreliable_sender_pool_create(tachyon_ptr: *mut Tachyon) -> *mut UnreliableSenderPool {
+    let tachyon = unsafe { &*tachyon_ptr };
+    if let Some(pool) = UnreliableSenderPool::create(tachyon) {
+        let b = Box::new(pool);
+        return Box::into_raw(b);
+    }
+    return std::ptr::null_mut();
+}
+
+#[no_mangle]
+pub extern "C" fn unreliable_sender_pool_destroy(pool_ptr: *mut UnreliableSenderPool) {
+    if !pool_ptr.is_null() {
+        let _b = unsafe { Box::from_raw(pool_ptr) };
+    }
+}
+
+// safe to call from multiple threads at once with the same pool
+#[no_mangle]
+pub extern "C" fn unreliable_sender_pool_send(pool_ptr: *mut UnreliableSenderPool, naddress: *const NetworkAddress, data_ptr: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let pool = unsafe { &*pool_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let data = unsafe { std::slice::from_raw_parts(data_ptr, length as usize) };
+    let result = pool.send(address, data, length as usize);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn unreliable_sender_pool_send_to_identity(pool_ptr: *mut UnreliableSenderPool, identity_id: u32, data_ptr: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let pool = unsafe { &*pool_ptr };
+    let data = unsafe { std::slice::from_raw_parts(data_ptr, length as usize) };
+    let result = pool.send_to_identity(identity_id, data, length as usize);
+    copy_send_result(result, ret);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serial_test::serial;
+
+    use crate::{network_address::NetworkAddress, Tachyon, TachyonConfig, SEND_ERROR_IDENTITY};
+
+    use super::UnreliableSenderPool;
+
+    #[test]
+    #[serial]
+    fn test_sender_pool() {
+        let address = NetworkAddress::localhost(8194);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.bind(address));
+        let mut client = Tachyon::create(TachyonConfig::default());
+        assert!(UnreliableSenderPool::create(&client).is_none());
+        assert!(client.connect(address));
+
+        let pool = Arc::new(UnreliableSenderPool::create(&client).unwrap());
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    let data: Vec<u8> = vec![i; 32];
+                    for _ in 0..10 {
+                        assert_eq!(0, pool.send(NetworkAddress::default(), &data, 32).error);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // never more senders than threads
+        assert!(pool.idle_count() >= 1 && pool.idle_count() <= 4);
+
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let mut received = 0;
+        while server.receive_loop(&mut receive_buffer).length > 0 {
+            received += 1;
+        }
+        assert_eq!(40, received);
+    }
+
+    #[test]
+    #[serial]
+    fn test_sender_pool_wire_options() {
+        let address = NetworkAddress::localhost(8196);
+        let mut config = TachyonConfig::default();
+        config.use_protocol_magic = 1;
+        config.use_checksum = 1;
+        let mut server = Tachyon::create(config);
+        assert!(server.bind(address));
+        let mut client = Tachyon::create(config);
+        assert!(client.connect(address));
+
+        // senders the pool makes write what the server expects, or it would drop them
+        let pool = UnreliableSenderPool::create(&client).unwrap();
+        assert_eq!(0, pool.send(NetworkAddress::default(), &[3; 32], 32).error);
+
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let result = server.receive_loop(&mut receive_buffer);
+        assert_eq!(32, result.length);
+        assert_eq!(&[3; 32], &receive_buffer[0..32]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_send_to_identity() {
+        let address = NetworkAddress::localhost(8195);
+        let mut server = Tachyon::create(TachyonConfig::default());
+        assert!(server.bind(address));
+        let mut client_config = TachyonConfig::default();
+        // takes the identity linked message the server sends
+        client_config.use_identity = 1;
+        let mut client = Tachyon::create(client_config);
+        client.identity.id = 1;
+        client.identity.session_id = 10;
+        assert!(client.connect(address));
+        let client_port = client.socket.socket.as_ref().unwrap().local_addr().unwrap().port() as u32;
+
+        let pool = Arc::new(UnreliableSenderPool::create(&server).unwrap());
+        assert_eq!(SEND_ERROR_IDENTITY, pool.send_to_identity(1, &[1; 8], 8).error);
+
+        server.set_identity(1, 10);
+        assert!(server.try_link_identity(NetworkAddress::localhost(client_port), 1, 10));
+        let sender_pool = pool.clone();
+        std::thread::spawn(move || {
+            assert_eq!(0, sender_pool.send_to_identity(1, &[1; 8], 8).error);
+        })
+        .join()
+        .unwrap();
+
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        let mut received = 0;
+        while client.receive_loop(&mut receive_buffer).length > 0 {
+            received += 1;
+        }
+        assert_eq!(1, received);
+
+        assert!(server.try_unlink_identity(NetworkAddress::localhost(client_port), 1, 10));
+        assert_eq!(SEND_ERROR_IDENTITY, pool.send_to_identity(1, &[1; 8], 8).error);
+    }
+}
@@ -0,0 +1,507 @@
+use super::{
+    checksum::crc32c,
+    header::*,
+    link_payload::LinkPayloads,
+    nack::Nack,
+    tachyon_socket::datagram_prefix_len,
+};
+
+// Tachyon datagrams parsed from bytes alone, for dissectors, captures and other tooling that isn't a Tachyon.  The
+// datagram is what middleware and captures see, without the protocol magic/checksum prefix.  Reliable sequences and
+// channels are as sent, if TachyonConfig.scramble_key is set they only make sense unscrambled.
+//
+// A message on a wide channel parses as the datagram behind the wide channel header, with the whole channel id.
+//
+// Payloads are everything after the header.  On timestamped channels that starts with the timestamp extension, the
+// datagram doesn't say whether the channel is timestamped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Datagram<'a> {
+    Unreliable {
+        payload: &'a [u8],
+    },
+    UnreliableSequenced {
+        sequence: u16,
+        payload: &'a [u8],
+    },
+    Reliable {
+        channel: u16,
+        sequence: u16,
+        // (start_sequence, flags) of a nack riding along, MESSAGE_TYPE_RELIABLE_WITH_NACK
+        nack: Option<(u16, u32)>,
+        payload: &'a [u8],
+    },
+    Fragment {
+        channel: u16,
+        sequence: u16,
+        group: u16,
+        start_sequence: u16,
+        count: u16,
+        payload: &'a [u8],
+    },
+    // stands in for a sequence the sender no longer has
+    None {
+        channel: u16,
+        sequence: u16,
+    },
+    Nack {
+        channel: u16,
+        // (start_sequence, flags) pairs, Nack::get_nacked expands them to sequences
+        nacks: Vec<(u16, u32)>,
+    },
+    // nack format v2
+    NackRanges {
+        channel: u16,
+        // (start_sequence, length), each counting back from start_sequence
+        ranges: Vec<(u16, u16)>,
+    },
+    NackFormat {
+        channel: u16,
+        format: u16,
+    },
+    // link/unlink requests and the linked, unlinked and denied replies
+    Identity {
+        message_type: u8,
+        id: u32,
+        session_id: u32,
+        payload: &'a [u8],
+    },
+    Challenge {
+        message_type: u8,
+        cookie: u64,
+    },
+    ChannelEpoch {
+        message_type: u8,
+        channel: u16,
+        epoch: u32,
+        ack_epoch: u32,
+    },
+    Topic {
+        message_type: u8,
+        topic: u32,
+        subscribed: u8,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireError {
+    Empty,
+    UnknownType(u8),
+    // shorter than the header of its type
+    Truncated { message_type: u8, length: usize },
+    // a nack list that doesn't decode
+    Malformed { message_type: u8 },
+    BadMagic,
+    VersionMismatch,
+    ChecksumFailed,
+}
+
+impl std::fmt::Display for WireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WireError::Empty => write!(f, "empty datagram"),
+            WireError::UnknownType(message_type) => write!(f, "unknown message type {}", message_type),
+            WireError::Truncated { message_type, length } => write!(f, "message type {} truncated at {} bytes", message_type, length),
+            WireError::Malformed { message_type } => write!(f, "message type {} is malformed", message_type),
+            WireError::BadMagic => write!(f, "no protocol magic"),
+            WireError::VersionMismatch => write!(f, "other protocol version"),
+            WireError::ChecksumFailed => write!(f, "checksum doesn't match"),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+// the smallest datagram of each type
+pub fn header_size(message_type: u8) -> Option<usize> {
+    match message_type {
+        MESSAGE_TYPE_UNRELIABLE => return Some(TACHYON_UNRELIABLE_HEADER_SIZE),
+        MESSAGE_TYPE_UNRELIABLE_SEQUENCED => return Some(TACHYON_UNRELIABLE_SEQUENCED_HEADER_SIZE),
+        MESSAGE_TYPE_RELIABLE | MESSAGE_TYPE_NONE | MESSAGE_TYPE_NACK | MESSAGE_TYPE_NACK_RANGES | MESSAGE_TYPE_NACK_FORMAT => {
+            return Some(TACHYON_HEADER_SIZE)
+        }
+        MESSAGE_TYPE_RELIABLE_WITH_NACK => return Some(TACHYON_NACKED_HEADER_SIZE),
+        MESSAGE_TYPE_FRAGMENT => return Some(TACHYON_FRAGMENTED_HEADER_SIZE),
+        MESSAGE_TYPE_LINK_IDENTITY | MESSAGE_TYPE_UNLINK_IDENTITY | MESSAGE_TYPE_IDENTITY_LINKED | MESSAGE_TYPE_IDENTITY_UNLINKED
+        | MESSAGE_TYPE_IDENTITY_DENIED => return Some(TACHYON_CONNECTION_HEADER_SIZE),
+        MESSAGE_TYPE_CONNECT_REQUEST | MESSAGE_TYPE_CHALLENGE | MESSAGE_TYPE_CHALLENGE_RESPONSE | MESSAGE_TYPE_CONNECT_ACCEPTED => {
+            return Some(TACHYON_CHALLENGE_HEADER_SIZE)
+        }
+        MESSAGE_TYPE_CHANNEL_EPOCH | MESSAGE_TYPE_CHANNEL_EPOCH_ACK => return Some(TACHYON_CHANNEL_EPOCH_HEADER_SIZE),
+        MESSAGE_TYPE_SUBSCRIBE | MESSAGE_TYPE_UNSUBSCRIBE | MESSAGE_TYPE_TOPIC_ACK => return Some(TACHYON_TOPIC_HEADER_SIZE),
+        _ => return None,
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<Datagram<'_>, WireError> {
+    if data.is_empty() {
+        return Err(WireError::Empty);
+    }
+    let message_type = data[0];
+    if message_type == MESSAGE_TYPE_WIDE_CHANNEL {
+        if data.len() < TACHYON_WIDE_CHANNEL_HEADER_SIZE + 1 {
+            return Err(WireError::Truncated { message_type, length: data.len() });
+        }
+        if !is_channel_message(data[TACHYON_WIDE_CHANNEL_HEADER_SIZE]) {
+            return Err(WireError::Malformed { message_type });
+        }
+        let mut datagram = parse(&data[TACHYON_WIDE_CHANNEL_HEADER_SIZE..])?;
+        datagram.set_channel_high(data[1]);
+        return Ok(datagram);
+    }
+    let size = header_size(message_type).ok_or(WireError::UnknownType(message_type))?;
+    if data.len() < size {
+        return Err(WireError::Truncated { message_type, length: data.len() });
+    }
+
+    match message_type {
+        MESSAGE_TYPE_UNRELIABLE => {
+            return Ok(Datagram::Unreliable { payload: &data[size..] });
+        }
+        MESSAGE_TYPE_UNRELIABLE_SEQUENCED => {
+            let header = Header::read_unreliable_sequenced(data);
+            return Ok(Datagram::UnreliableSequenced { sequence: header.sequence, payload: &data[size..] });
+        }
+        MESSAGE_TYPE_RELIABLE => {
+            let header = Header::read(data);
+            return Ok(Datagram::Reliable { channel: header.channel, sequence: header.sequence, nack: None, payload: &data[size..] });
+        }
+        MESSAGE_TYPE_RELIABLE_WITH_NACK => {
+            let header = Header::read_nacked(data);
+            let nack = Some((header.start_sequence, header.flags));
+            return Ok(Datagram::Reliable { channel: header.channel, sequence: header.sequence, nack, payload: &data[size..] });
+        }
+        MESSAGE_TYPE_FRAGMENT => {
+            let header = Header::read_fragmented(data);
+            return Ok(Datagram::Fragment {
+                channel: header.channel,
+                sequence: header.sequence,
+                group: header.fragment_group,
+                start_sequence: header.fragment_start_sequence,
+                count: header.fragment_count,
+                payload: &data[size..],
+            });
+        }
+        MESSAGE_TYPE_NONE => {
+            let header = Header::read(data);
+            return Ok(Datagram::None { channel: header.channel, sequence: header.sequence });
+        }
+        MESSAGE_TYPE_NACK => {
+            let header = Header::read(data);
+            let nacks = read_nacks(data, size).ok_or(WireError::Malformed { message_type })?;
+            return Ok(Datagram::Nack { channel: header.channel, nacks });
+        }
+        MESSAGE_TYPE_NACK_RANGES => {
+            let header = Header::read(data);
+            let mut ranges: Vec<(u16, u16)> = Vec::new();
+            Nack::read_ranges(&mut ranges, data, size).ok_or(WireError::Malformed { message_type })?;
+            return Ok(Datagram::NackRanges { channel: header.channel, ranges });
+        }
+        MESSAGE_TYPE_NACK_FORMAT => {
+            let header = Header::read(data);
+            return Ok(Datagram::NackFormat { channel: header.channel, format: header.sequence });
+        }
+        MESSAGE_TYPE_CONNECT_REQUEST | MESSAGE_TYPE_CHALLENGE | MESSAGE_TYPE_CHALLENGE_RESPONSE | MESSAGE_TYPE_CONNECT_ACCEPTED => {
+            let header = ChallengeHeader::read(data);
+            return Ok(Datagram::Challenge { message_type, cookie: header.cookie });
+        }
+        MESSAGE_TYPE_CHANNEL_EPOCH | MESSAGE_TYPE_CHANNEL_EPOCH_ACK => {
+            let header = ChannelEpochHeader::read(data);
+            return Ok(Datagram::ChannelEpoch { message_type, channel: header.channel, epoch: header.epoch, ack_epoch: header.ack_epoch });
+        }
+        MESSAGE_TYPE_SUBSCRIBE | MESSAGE_TYPE_UNSUBSCRIBE | MESSAGE_TYPE_TOPIC_ACK => {
+            let header = TopicHeader::read(data);
+            return Ok(Datagram::Topic { message_type, topic: header.topic, subscribed: header.subscribed });
+        }
+        _ => {
+            let header = ConnectionHeader::read(data);
+            return Ok(Datagram::Identity {
+                message_type,
+                id: header.id,
+                session_id: header.session_id,
+                payload: LinkPayloads::read(data, data.len()),
+            });
+        }
+    }
+}
+
+pub fn message_type_name(message_type: u8) -> &'static str {
+    match message_type {
+        MESSAGE_TYPE_UNRELIABLE => return "unreliable",
+        MESSAGE_TYPE_RELIABLE => return "reliable",
+        MESSAGE_TYPE_FRAGMENT => return "fragment",
+        MESSAGE_TYPE_NONE => return "none",
+        MESSAGE_TYPE_NACK => return "nack",
+        MESSAGE_TYPE_RELIABLE_WITH_NACK => return "reliable_with_nack",
+        MESSAGE_TYPE_LINK_IDENTITY => return "link_identity",
+        MESSAGE_TYPE_UNLINK_IDENTITY => return "unlink_identity",
+        MESSAGE_TYPE_IDENTITY_LINKED => return "identity_linked",
+        MESSAGE_TYPE_IDENTITY_UNLINKED => return "identity_unlinked",
+        MESSAGE_TYPE_IDENTITY_DENIED => return "identity_denied",
+        MESSAGE_TYPE_UNRELIABLE_SEQUENCED => return "unreliable_sequenced",
+        MESSAGE_TYPE_CONNECT_REQUEST => return "connect_request",
+        MESSAGE_TYPE_CHALLENGE => return "challenge",
+        MESSAGE_TYPE_CHALLENGE_RESPONSE => return "challenge_response",
+        MESSAGE_TYPE_CONNECT_ACCEPTED => return "connect_accepted",
+        MESSAGE_TYPE_CHANNEL_EPOCH => return "channel_epoch",
+        MESSAGE_TYPE_CHANNEL_EPOCH_ACK => return "channel_epoch_ack",
+        MESSAGE_TYPE_SUBSCRIBE => return "subscribe",
+        MESSAGE_TYPE_UNSUBSCRIBE => return "unsubscribe",
+        MESSAGE_TYPE_TOPIC_ACK => return "topic_ack",
+        MESSAGE_TYPE_NACK_RANGES => return "nack_ranges",
+        MESSAGE_TYPE_NACK_FORMAT => return "nack_format",
+        MESSAGE_TYPE_WIDE_CHANNEL => return "wide_channel",
+        _ => return "unknown",
+    }
+}
+
+impl Datagram<'_> {
+    fn set_channel_high(&mut self, high: u8) {
+        match self {
+            Datagram::Reliable { channel, .. }
+            | Datagram::Fragment { channel, .. }
+            | Datagram::None { channel, .. }
+            | Datagram::Nack { channel, .. }
+            | Datagram::NackRanges { channel, .. }
+            | Datagram::NackFormat { channel, .. }
+            | Datagram::ChannelEpoch { channel, .. } => *channel |= (high as u16) << 8,
+            _ => {}
+        }
+    }
+}
+
+// the nacked sequences, start sequence first
+fn write_nack(f: &mut std::fmt::Formatter, start_sequence: u16, flags: u32) -> std::fmt::Result {
+    let nack = Nack { start_sequence, flags, nacked_count: 0, sent_count: 0 };
+    let mut sequences: Vec<u16> = Vec::new();
+    nack.get_nacked(&mut sequences);
+    write!(f, " nack:{:?}", sequences)
+}
+
+// One line per datagram, the header fields and the payload length
+impl std::fmt::Display for Datagram<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Datagram::Unreliable { payload } => write!(f, "unreliable payload:{}", payload.len()),
+            Datagram::UnreliableSequenced { sequence, payload } => {
+                write!(f, "unreliable_sequenced sequence:{} payload:{}", sequence, payload.len())
+            }
+            Datagram::Reliable { channel, sequence, nack, payload } => {
+                write!(f, "reliable channel:{} sequence:{} payload:{}", channel, sequence, payload.len())?;
+                if let Some((start_sequence, flags)) = nack {
+                    write_nack(f, *start_sequence, *flags)?;
+                }
+                Ok(())
+            }
+            Datagram::Fragment { channel, sequence, group, start_sequence, count, payload } => write!(
+                f,
+                "fragment channel:{} sequence:{} group:{} start:{} count:{} payload:{}",
+                channel,
+                sequence,
+                group,
+                start_sequence,
+                count,
+                payload.len()
+            ),
+            Datagram::None { channel, sequence } => write!(f, "none channel:{} sequence:{}", channel, sequence),
+            Datagram::Nack { channel, nacks } => {
+                write!(f, "nack channel:{}", channel)?;
+                for (start_sequence, flags) in nacks {
+                    write_nack(f, *start_sequence, *flags)?;
+                }
+                Ok(())
+            }
+            Datagram::NackRanges { channel, ranges } => write!(f, "nack_ranges channel:{} ranges:{:?}", channel, ranges),
+            Datagram::NackFormat { channel, format } => write!(f, "nack_format channel:{} format:{}", channel, format),
+            Datagram::Identity { message_type, id, session_id, payload } => write!(
+                f,
+                "{} id:{} session:{} payload:{}",
+                message_type_name(*message_type),
+                id,
+                session_id,
+                payload.len()
+            ),
+            Datagram::Challenge { message_type, cookie } => write!(f, "{} cookie:{:#x}", message_type_name(*message_type), cookie),
+            Datagram::ChannelEpoch { message_type, channel, epoch, ack_epoch } => write!(
+                f,
+                "{} channel:{} epoch:{} ack_epoch:{}",
+                message_type_name(*message_type),
+                channel,
+                epoch,
+                ack_epoch
+            ),
+            Datagram::Topic { message_type, topic, subscribed } => {
+                write!(f, "{} topic:{} subscribed:{}", message_type_name(*message_type), topic, subscribed)
+            }
+        }
+    }
+}
+
+// A readable line for a UDP payload out of a pcap or similar, magic/checksum as the sender had them configured.
+// Anything that doesn't parse is described rather than failing, tooling wants a line per packet.
+pub fn describe(payload: &[u8], protocol_magic: bool, checksum: bool) -> String {
+    let data = match strip_prefix(payload, protocol_magic, checksum) {
+        Ok(data) => data,
+        Err(error) => return format!("invalid length:{} {}", payload.len(), error),
+    };
+    match parse(data) {
+        Ok(datagram) => return datagram.to_string(),
+        Err(error) => return format!("invalid length:{} {}", data.len(), error),
+    }
+}
+
+// Checks and strips the protocol magic and checksum when the sender had TachyonConfig.use_protocol_magic/use_checksum
+// on, for datagrams taken off the network rather than from a capture.
+pub fn strip_prefix(data: &[u8], protocol_magic: bool, checksum: bool) -> Result<&[u8], WireError> {
+    let prefix_len = datagram_prefix_len(protocol_magic, checksum);
+    let mut index = 0;
+    if protocol_magic {
+        if data.len() < PROTOCOL_PREFIX_SIZE || data[0..4] != PROTOCOL_MAGIC {
+            return Err(WireError::BadMagic);
+        }
+        if data[4] != PROTOCOL_VERSION {
+            return Err(WireError::VersionMismatch);
+        }
+        index += PROTOCOL_PREFIX_SIZE;
+    }
+    if checksum {
+        if data.len() < prefix_len {
+            return Err(WireError::ChecksumFailed);
+        }
+        let expected = u32::from_le_bytes([data[index], data[index + 1], data[index + 2], data[index + 3]]);
+        if crc32c(&data[prefix_len..]) != expected {
+            return Err(WireError::ChecksumFailed);
+        }
+    }
+    return Ok(&data[prefix_len..]);
+}
+
+// the nacks as sent, Nack::read_varint expands them to sequences
+fn read_nacks(data: &[u8], position: usize) -> Option<Vec<(u16, u32)>> {
+    let mut position = position;
+    let count = Nack::checked_varint_u32(data, &mut position)?;
+    let mut nacks: Vec<(u16, u32)> = Vec::new();
+    for _ in 0..count {
+        let start_sequence = Nack::checked_varint_u16(data, &mut position)?;
+        let flags = Nack::checked_varint_u32(data, &mut position)?;
+        nacks.push((start_sequence, flags));
+    }
+    return Some(nacks);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        header::*,
+        link_payload::LinkPayloads,
+        nack::Nack,
+        tachyon_socket::{datagram_prefix_len, write_datagram_prefix},
+    };
+
+    use super::{describe, parse, strip_prefix, Datagram, WireError};
+
+    #[test]
+    fn test_parse() {
+        let mut buffer: Vec<u8> = vec![0; 64];
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE;
+        header.channel = 3;
+        header.sequence = 700;
+        header.write(&mut buffer);
+        buffer[4] = 9;
+        match parse(&buffer[0..5]).unwrap() {
+            Datagram::Reliable { channel, sequence, nack, payload } => {
+                assert_eq!((3, 700), (channel, sequence));
+                assert!(nack.is_none());
+                assert_eq!(&[9], payload);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let fragment = Header::create_fragmented(12, 4, 2, 11, 3);
+        fragment.write_fragmented(&mut buffer);
+        match parse(&buffer[0..TACHYON_FRAGMENTED_HEADER_SIZE]).unwrap() {
+            Datagram::Fragment { channel, start_sequence, count, .. } => assert_eq!((4, 11, 3), (channel, start_sequence, count)),
+            other => panic!("{:?}", other),
+        }
+
+        header.message_type = MESSAGE_TYPE_NACK;
+        header.write(&mut buffer);
+        let nacks = [Nack { start_sequence: 40, flags: 5, nacked_count: 0, sent_count: 0 }];
+        let length = Nack::write_varint(&nacks, &mut buffer, TACHYON_HEADER_SIZE as u64) as usize;
+        match parse(&buffer[0..length]).unwrap() {
+            Datagram::Nack { channel, nacks } => {
+                assert_eq!(3, channel);
+                assert_eq!(vec![(40, 5)], nacks);
+            }
+            other => panic!("{:?}", other),
+        }
+        // a nack list cut short
+        assert_eq!(Err(WireError::Malformed { message_type: MESSAGE_TYPE_NACK }), parse(&buffer[0..length - 1]));
+
+        let connection = ConnectionHeader { message_type: MESSAGE_TYPE_IDENTITY_DENIED, id: 7, session_id: 2 };
+        let mut identity: Vec<u8> = vec![0; LinkPayloads::message_size(2)];
+        connection.write(&mut identity);
+        LinkPayloads::write(&mut identity, b"no");
+        match parse(&identity).unwrap() {
+            Datagram::Identity { message_type, id, session_id, payload } => {
+                assert_eq!((MESSAGE_TYPE_IDENTITY_DENIED, 7, 2), (message_type, id, session_id));
+                assert_eq!(b"no", payload);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // a wide channel, the channel's high byte in front
+        fragment.write_fragmented(&mut buffer);
+        let wide: Vec<u8> = [&[MESSAGE_TYPE_WIDE_CHANNEL, 1][..], &buffer[0..TACHYON_FRAGMENTED_HEADER_SIZE]].concat();
+        match parse(&wide).unwrap() {
+            Datagram::Fragment { channel, start_sequence, .. } => assert_eq!((260, 11), (channel, start_sequence)),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(Err(WireError::Malformed { message_type: MESSAGE_TYPE_WIDE_CHANNEL }), parse(&[MESSAGE_TYPE_WIDE_CHANNEL, 1, MESSAGE_TYPE_UNRELIABLE]));
+
+        assert_eq!(Err(WireError::Empty), parse(&[]));
+        assert_eq!(Err(WireError::UnknownType(99)), parse(&[99, 0, 0, 0]));
+        assert_eq!(Err(WireError::Truncated { message_type: MESSAGE_TYPE_FRAGMENT, length: 4 }), parse(&[MESSAGE_TYPE_FRAGMENT, 4, 12, 0]));
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let prefix_len = datagram_prefix_len(true, true);
+        let mut buffer: Vec<u8> = vec![0; prefix_len + 3];
+        buffer[prefix_len..].copy_from_slice(&[MESSAGE_TYPE_UNRELIABLE, 1, 2]);
+        write_datagram_prefix(&mut buffer, true, true, 3);
+
+        let data = strip_prefix(&buffer, true, true).unwrap();
+        assert_eq!(Datagram::Unreliable { payload: &[1, 2] }, parse(data).unwrap());
+
+        buffer[prefix_len + 2] = 3;
+        assert_eq!(Err(WireError::ChecksumFailed), strip_prefix(&buffer, true, true));
+        buffer[0] = b'X';
+        assert_eq!(Err(WireError::BadMagic), strip_prefix(&buffer, true, true));
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut buffer: Vec<u8> = vec![0; 64];
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
+        header.channel = 2;
+        header.sequence = 30;
+        header.start_sequence = 20;
+        header.flags = 0b101;
+        header.write_nacked(&mut buffer);
+        assert_eq!("reliable channel:2 sequence:30 payload:2 nack:[20, 19, 17]", describe(&buffer[0..12], false, false));
+
+        let fragment = Header::create_fragmented(12, 4, 2, 11, 3);
+        fragment.write_fragmented(&mut buffer);
+        assert_eq!("fragment channel:4 sequence:12 group:2 start:11 count:3 payload:0", describe(&buffer[0..10], false, false));
+
+        let mut ranges: Vec<u8> = vec![MESSAGE_TYPE_NACK_RANGES, 3, 0, 0];
+        ranges.resize(64, 0);
+        let length = Nack::write_ranges(&[(40, 5), (30, 2)], &mut ranges, TACHYON_HEADER_SIZE as u64) as usize;
+        assert_eq!("nack_ranges channel:3 ranges:[(40, 5), (30, 2)]", describe(&ranges[0..length], false, false));
+
+        assert_eq!("invalid length:1 unknown message type 99", describe(&[99], false, false));
+        assert_eq!("invalid length:3 no protocol magic", describe(&[0, 1, 2], true, false));
+    }
+}